@@ -0,0 +1,100 @@
+//! Benchmarks for the per-packet paths: encoding, decoding, CRC and framing.
+//!
+//! Run with `cargo bench --bench packet`.
+
+// The module's unit tests are compiled out here, leaving their imports unused
+#[allow(dead_code, unused_imports)]
+#[path = "../src/protocol/mod.rs"]
+mod protocol;
+
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use protocol::commands::*;
+use protocol::crc::crc16_xmodem;
+use protocol::{HuaweiSppPacket, SppFramer};
+
+/// A device info response, the largest packet read during init.
+fn device_info() -> HuaweiSppPacket {
+    let mut pkt = HuaweiSppPacket::new(CMD_DEVICE_INFO);
+    for (param, value) in [
+        (3, "HL1CDR"),
+        (7, "1.0.0.188"),
+        (9, "SN0000000000001"),
+        (15, "CD-R551"),
+        (24, "L-SN0000000000002,R-SN0000000000003"),
+    ] {
+        pkt.set_param(param, value.as_bytes().to_vec());
+    }
+    pkt
+}
+
+fn battery() -> HuaweiSppPacket {
+    let mut pkt = HuaweiSppPacket::new(CMD_BATTERY_NOTIFY);
+    pkt.set_param(1, vec![80]);
+    pkt.set_param(2, vec![80, 85, 60]);
+    pkt.set_param(3, vec![0, 0, 0]);
+    pkt
+}
+
+fn encode(c: &mut Criterion) {
+    let info = device_info();
+    c.bench_function("to_bytes/device_info", |b| b.iter(|| black_box(&info).to_bytes()));
+    let read = HuaweiSppPacket::read_request(CMD_ANC_READ, &[1, 2, 3]);
+    c.bench_function("to_bytes/read_request", |b| b.iter(|| black_box(&read).to_bytes()));
+    c.bench_function("write_request", |b| {
+        b.iter(|| {
+            HuaweiSppPacket::write_request(
+                black_box(CMD_DUAL_CONNECT_EXECUTE),
+                [(1, vec![0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6])],
+            )
+        })
+    });
+}
+
+fn decode(c: &mut Criterion) {
+    // Cloning `Bytes` only bumps a refcount, as when the framer splits a packet off
+    let info = Bytes::from(device_info().to_bytes());
+    c.bench_function("from_shared/device_info", |b| {
+        b.iter(|| HuaweiSppPacket::from_shared(black_box(&info).clone()).unwrap())
+    });
+    let battery = Bytes::from(battery().to_bytes());
+    c.bench_function("from_shared/battery", |b| {
+        b.iter(|| {
+            let pkt = HuaweiSppPacket::from_shared(black_box(&battery).clone()).unwrap();
+            // Handlers look up a few parameters per packet
+            (pkt.find_param(1)[0], pkt.find_param(2).len(), pkt.find_param(3).len())
+        })
+    });
+}
+
+fn crc(c: &mut Criterion) {
+    let data = device_info().to_bytes();
+    c.bench_function("crc16_xmodem/device_info", |b| b.iter(|| crc16_xmodem(black_box(&data))));
+}
+
+fn framing(c: &mut Criterion) {
+    // Init responses as they come off the socket: several per read, some split
+    let mut stream = Vec::new();
+    for _ in 0..4 {
+        stream.extend_from_slice(&device_info().to_bytes());
+        stream.extend_from_slice(&battery().to_bytes());
+    }
+    let mut framer = SppFramer::default();
+    c.bench_function("framer/init_stream", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for chunk in black_box(&stream).chunks(100) {
+                framer.push(chunk);
+                while let Some(pkt) = framer.next_packet() {
+                    pkt.unwrap();
+                    count += 1;
+                }
+            }
+            assert_eq!(count, 8);
+        })
+    });
+}
+
+criterion_group!(benches, encode, decode, crc, framing);
+criterion_main!(benches);
@@ -0,0 +1,396 @@
+//! FreeBuds Pro 3 simulator for development without hardware.
+//!
+//! Listens on a Unix socket (for MyBuds' mock transport, see
+//! `MYBUDS_MOCK_SOCKET`) or on an RFCOMM channel, answers the reads the
+//! Pro 3 handlers send, applies their writes, and pushes battery and ANC
+//! notifications. Lines on stdin simulate on-device changes:
+//!
+//! ```text
+//! battery 80 75 40     left, right, case
+//! charging on|off
+//! anc off|cancellation|awareness
+//! wear on|off
+//! ```
+
+#[allow(dead_code)]
+#[path = "../protocol/mod.rs"]
+mod protocol;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, info, warn};
+
+use protocol::commands::*;
+use protocol::{HuaweiSppPacket, SppFramer};
+
+#[derive(Parser)]
+#[command(name = "mybuds-sim", about = "Emulate a FreeBuds Pro 3 for MyBuds development")]
+struct Cli {
+    /// Listen on this Unix socket (point MYBUDS_MOCK_SOCKET at it)
+    #[arg(long, value_name = "PATH", conflicts_with = "rfcomm")]
+    unix: Option<PathBuf>,
+
+    /// Listen on this RFCOMM channel instead
+    #[arg(long, value_name = "CHANNEL")]
+    rfcomm: Option<u8>,
+
+    /// Seconds between battery drain steps while not charging (0 = off)
+    #[arg(long, default_value_t = 60)]
+    drain: u64,
+}
+
+/// A write and the read register it updates, with the param types that move
+/// between them (unlisted params keep their type).
+type WriteMapping = (CommandId, CommandId, &'static [(u8, u8)]);
+
+const WRITES: &[WriteMapping] = &[
+    (CMD_AUTO_PAUSE_WRITE, CMD_AUTO_PAUSE_READ, &[]),
+    (CMD_SOUND_QUALITY_WRITE, CMD_SOUND_QUALITY_READ, &[(1, 2)]),
+    (CMD_EQUALIZER_WRITE, CMD_EQUALIZER_READ, &[(1, 2)]),
+    (CMD_DUAL_TAP_WRITE, CMD_DUAL_TAP_READ, &[]),
+    (CMD_LONG_TAP_SPLIT_WRITE_BASE, CMD_LONG_TAP_SPLIT_READ_BASE, &[]),
+    (CMD_LONG_TAP_SPLIT_WRITE_ANC, CMD_LONG_TAP_SPLIT_READ_ANC, &[]),
+    (CMD_SWIPE_WRITE, CMD_SWIPE_READ, &[]),
+    (CMD_LOW_LATENCY, CMD_LOW_LATENCY, &[(1, 2)]),
+];
+
+/// Simulated device state: the response to each read command.
+struct Device {
+    registers: BTreeMap<CommandId, BTreeMap<u8, Vec<u8>>>,
+}
+
+impl Device {
+    /// A FreeBuds Pro 3 out of the box.
+    fn pro3() -> Self {
+        let text = |s: &str| s.as_bytes().to_vec();
+        let registers = [
+            (
+                CMD_DEVICE_INFO,
+                vec![
+                    (3, text("HL1CDR")),
+                    (7, text("1.0.0.188")),
+                    (9, text("SIM0000000000001")),
+                    (15, text("CD-R551")),
+                ],
+            ),
+            (
+                CMD_BATTERY_READ,
+                vec![(1, vec![80]), (2, vec![80, 80, 60]), (3, vec![0, 0, 0])],
+            ),
+            (CMD_ANC_READ, vec![(1, vec![0, 1])]),
+            (CMD_AUTO_PAUSE_READ, vec![(1, vec![1])]),
+            (CMD_LOW_LATENCY, vec![(2, vec![0])]),
+            (CMD_SOUND_QUALITY_READ, vec![(2, vec![1])]),
+            (CMD_EQUALIZER_READ, vec![(2, vec![1]), (3, vec![1, 2, 3, 9])]),
+            (
+                CMD_DUAL_TAP_READ,
+                vec![(1, vec![1]), (2, vec![2]), (3, vec![0xFF, 0, 1, 2, 7])],
+            ),
+            (
+                CMD_LONG_TAP_SPLIT_READ_BASE,
+                vec![(1, vec![10]), (2, vec![10]), (4, vec![0])],
+            ),
+            (CMD_LONG_TAP_SPLIT_READ_ANC, vec![(1, vec![2]), (2, vec![2])]),
+            (CMD_SWIPE_READ, vec![(1, vec![0])]),
+        ]
+        .into_iter()
+        .map(|(cmd, params)| (cmd, params.into_iter().collect()))
+        .collect();
+        Self { registers }
+    }
+
+    /// Full contents of a read register as an unsolicited packet.
+    fn register(&self, cmd: CommandId) -> HuaweiSppPacket {
+        let params = self.registers.get(&cmd).into_iter().flatten();
+        HuaweiSppPacket::write_request(cmd, params.map(|(&t, v)| (t, v.clone())))
+    }
+
+    fn set(&mut self, cmd: CommandId, param: u8, value: Vec<u8>) {
+        self.registers.entry(cmd).or_default().insert(param, value);
+    }
+
+    /// Answer a packet from MyBuds: reads get the requested params, writes
+    /// update the register the next read returns.
+    fn handle(&mut self, pkt: &HuaweiSppPacket) -> Option<HuaweiSppPacket> {
+        let is_write = pkt.params().any(|(_, v)| !v.is_empty());
+
+        if is_write && pkt.command_id == CMD_ANC_WRITE {
+            if let [mode, level] = *pkt.find_param(1) {
+                let level = match (mode, level) {
+                    (_, l) if l != 0xFF => l,
+                    (2, _) => 2,
+                    _ => 0,
+                };
+                self.set(CMD_ANC_READ, 1, vec![level, mode]);
+            }
+            return None;
+        }
+
+        if is_write {
+            if let Some((_, read, remap)) = WRITES.iter().find(|(w, ..)| *w == pkt.command_id) {
+                for (param, value) in pkt.params().filter(|(_, v)| !v.is_empty()) {
+                    let target = remap
+                        .iter()
+                        .find(|(from, _)| *from == param)
+                        .map_or(param, |&(_, to)| to);
+                    self.set(*read, target, value.to_vec());
+                }
+                return None;
+            }
+        }
+
+        let Some(params) = self.registers.get(&pkt.command_id) else {
+            debug!("No answer for {}", pkt);
+            return None;
+        };
+        let mut reply = HuaweiSppPacket::new(pkt.command_id);
+        for (param, _) in pkt.params() {
+            if let Some(value) = params.get(&param) {
+                reply.set_param(param, value.clone());
+            }
+        }
+        Some(reply)
+    }
+
+    /// Apply a stdin command, returning the notifications it causes.
+    fn command(&mut self, line: &str) -> Result<Vec<HuaweiSppPacket>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["battery", levels @ ..] if (1..=3).contains(&levels.len()) => {
+                let mut battery = self.register(CMD_BATTERY_READ).find_param(2).to_vec();
+                for (slot, level) in battery.iter_mut().zip(levels) {
+                    *slot = level.parse::<u8>().context("Battery level")?.min(100);
+                }
+                self.set_battery(battery);
+                Ok(vec![self.battery_notification()])
+            }
+            ["charging", state @ ("on" | "off")] => {
+                let flag = u8::from(*state == "on");
+                self.set(CMD_BATTERY_READ, 3, vec![flag; 3]);
+                Ok(vec![self.battery_notification()])
+            }
+            ["anc", mode] => {
+                let (mode, level) = match *mode {
+                    "off" => (0, 0),
+                    "cancellation" => (1, 0),
+                    "awareness" => (2, 2),
+                    _ => bail!("Unknown ANC mode: {}", mode),
+                };
+                self.set(CMD_ANC_READ, 1, vec![level, mode]);
+                Ok(vec![self.register(CMD_ANC_READ)])
+            }
+            ["wear", state @ ("on" | "off")] => {
+                let worn = u8::from(*state == "on");
+                Ok(vec![HuaweiSppPacket::write_request(
+                    CMD_IN_EAR_NOTIFY,
+                    [(8, vec![worn])],
+                )])
+            }
+            _ => bail!("Unknown command: {}", line),
+        }
+    }
+
+    /// Store per-bud levels, deriving the global level from the buds.
+    fn set_battery(&mut self, battery: Vec<u8>) {
+        let global = battery[..2].iter().copied().min().unwrap_or(0);
+        self.set(CMD_BATTERY_READ, 1, vec![global]);
+        self.set(CMD_BATTERY_READ, 2, battery);
+    }
+
+    /// One drain step on each bud unless charging. Returns the notification
+    /// if anything changed.
+    fn drain(&mut self) -> Option<HuaweiSppPacket> {
+        let battery = self.register(CMD_BATTERY_READ);
+        if battery.find_param(3).contains(&1) {
+            return None;
+        }
+        let mut levels = battery.find_param(2).to_vec();
+        if levels[..2].iter().all(|&l| l == 0) {
+            return None;
+        }
+        for level in &mut levels[..2] {
+            *level = level.saturating_sub(1);
+        }
+        self.set_battery(levels);
+        Some(self.battery_notification())
+    }
+
+    fn battery_notification(&self) -> HuaweiSppPacket {
+        let mut pkt = self.register(CMD_BATTERY_READ);
+        pkt.command_id = CMD_BATTERY_NOTIFY;
+        pkt
+    }
+}
+
+/// Serve one MyBuds connection until it closes.
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    device: Arc<Mutex<Device>>,
+    mut notifications: broadcast::Receiver<HuaweiSppPacket>,
+) -> Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buf = [0u8; 1024];
+    let mut framer = SppFramer::default();
+    let mut out = Vec::new();
+    loop {
+        let mut outgoing = Vec::new();
+        tokio::select! {
+            n = reader.read(&mut buf) => {
+                let n = n?;
+                if n == 0 {
+                    return Ok(());
+                }
+                framer.push(&buf[..n]);
+                while let Some(pkt) = framer.next_packet() {
+                    let pkt = pkt?;
+                    debug!("<- {}", pkt);
+                    outgoing.extend(device.lock().await.handle(&pkt));
+                }
+            }
+            pkt = notifications.recv() => match pkt {
+                Ok(pkt) => outgoing.push(pkt),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        }
+        for pkt in outgoing {
+            debug!("-> {}", pkt);
+            out.clear();
+            pkt.write_to(&mut out);
+            writer.write_all(&out).await?;
+        }
+    }
+}
+
+/// Apply stdin commands and broadcast what they change.
+async fn read_stdin(device: Arc<Mutex<Device>>, tx: broadcast::Sender<HuaweiSppPacket>) {
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match device.lock().await.command(&line) {
+            Ok(packets) => {
+                for pkt in packets {
+                    let _ = tx.send(pkt);
+                }
+            }
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "mybuds_sim=info".into()),
+        )
+        .init();
+    let cli = Cli::parse();
+
+    let device = Arc::new(Mutex::new(Device::pro3()));
+    let (tx, _) = broadcast::channel(16);
+    tokio::spawn(read_stdin(device.clone(), tx.clone()));
+
+    if cli.drain > 0 {
+        let (device, tx) = (device.clone(), tx.clone());
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(cli.drain));
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                if let Some(pkt) = device.lock().await.drain() {
+                    let _ = tx.send(pkt);
+                }
+            }
+        });
+    }
+
+    if let Some(channel) = cli.rfcomm {
+        let addr = bluer::rfcomm::SocketAddr::new(bluer::Address::any(), channel);
+        let listener = bluer::rfcomm::Listener::bind(addr)
+            .await
+            .with_context(|| format!("Listening on RFCOMM channel {}", channel))?;
+        info!("Simulating FreeBuds Pro 3 on RFCOMM channel {}", channel);
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            info!("MyBuds connected from {}", peer.addr);
+            if let Err(e) = serve(stream, device.clone(), tx.subscribe()).await {
+                warn!("Connection ended: {:#}", e);
+            }
+            info!("MyBuds disconnected");
+        }
+    }
+
+    let Some(path) = cli.unix else {
+        bail!("Pass --unix PATH or --rfcomm CHANNEL");
+    };
+    // A socket left behind by an earlier run would make bind fail
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)
+        .with_context(|| format!("Listening on {}", path.display()))?;
+    info!(
+        "Simulating FreeBuds Pro 3 on {} (run MyBuds with MYBUDS_MOCK_SOCKET={})",
+        path.display(),
+        path.display()
+    );
+    loop {
+        let (stream, _) = listener.accept().await?;
+        info!("MyBuds connected");
+        if let Err(e) = serve(stream, device.clone(), tx.subscribe()).await {
+            warn!("Connection ended: {:#}", e);
+        }
+        info!("MyBuds disconnected");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(device: &mut Device, cmd: CommandId, params: &[u8]) -> HuaweiSppPacket {
+        device
+            .handle(&HuaweiSppPacket::read_request(cmd, params))
+            .expect("register answers reads")
+    }
+
+    #[test]
+    fn writes_show_up_in_later_reads() {
+        let mut device = Device::pro3();
+
+        let write = HuaweiSppPacket::write_request(CMD_EQUALIZER_WRITE, [(1, vec![9])]);
+        assert!(device.handle(&write).is_none());
+        assert_eq!(read(&mut device, CMD_EQUALIZER_READ, &[2, 3]).find_param(2), [9]);
+
+        let write = HuaweiSppPacket::write_request(CMD_ANC_WRITE, [(1, vec![2, 0xFF])]);
+        device.handle(&write);
+        assert_eq!(read(&mut device, CMD_ANC_READ, &[1, 2]).find_param(1), [2, 2]);
+
+        // Low latency reads and writes share a command
+        let write = HuaweiSppPacket::write_request(CMD_LOW_LATENCY, [(1, vec![1])]);
+        assert!(device.handle(&write).is_none());
+        assert_eq!(read(&mut device, CMD_LOW_LATENCY, &[2]).find_param(2), [1]);
+    }
+
+    #[test]
+    fn stdin_commands_notify() {
+        let mut device = Device::pro3();
+        let pkts = device.command("battery 40 60").unwrap();
+        assert_eq!(pkts[0].command_id, CMD_BATTERY_NOTIFY);
+        assert_eq!(pkts[0].find_param(1), [40]);
+        assert_eq!(pkts[0].find_param(2), [40, 60, 60]);
+
+        device.command("charging on").unwrap();
+        assert!(device.drain().is_none());
+        assert!(device.command("anc loud").is_err());
+    }
+}
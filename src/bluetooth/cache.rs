@@ -0,0 +1,83 @@
+//! Last successfully connected device, so startup can skip the BlueZ scan.
+//!
+//! Stored as TOML at ~/.cache/mybuds/last_device.toml.
+
+use std::path::PathBuf;
+
+use bluer::Address;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::device::models::rules::DeviceRules;
+
+/// Resolved connection details from the last session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BondedDevice {
+    pub address: String,
+    pub name: String,
+    /// Profile name, used to detect a stale cache after a model table change.
+    pub profile: String,
+    /// RFCOMM channel that worked last time (Huawei devices only).
+    pub rfcomm_channel: Option<u8>,
+}
+
+impl BondedDevice {
+    /// Cache file path: ~/.cache/mybuds/last_device.toml
+    pub fn path() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("mybuds")
+            .join("last_device.toml")
+    }
+
+    /// Read the cached device without checking its profile.
+    pub fn read() -> Option<Self> {
+        let contents = std::fs::read_to_string(Self::path()).ok()?;
+        let cached: Self = match toml::from_str(&contents) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Ignoring unreadable device cache: {}", e);
+                return None;
+            }
+        };
+        cached.address()?;
+        Some(cached)
+    }
+
+    /// Load the cached device, ignoring it if it no longer resolves to the
+    /// same profile (model table or device rules changed).
+    pub fn load(rules: &DeviceRules) -> Option<Self> {
+        let cached = Self::read()?;
+        if rules.profile(&cached.name, &cached.address).name != cached.profile {
+            debug!("Cached device profile changed, ignoring cache");
+            return None;
+        }
+        Some(cached)
+    }
+
+    /// Write the cache, logging (not failing) on error.
+    pub fn save(&self) {
+        let path = Self::path();
+        let result = toml::to_string(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, s)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            warn!("Failed to write device cache: {}", e);
+        }
+    }
+
+    /// Forget the cached device so the next startup scans again.
+    pub fn clear() {
+        let _ = std::fs::remove_file(Self::path());
+    }
+
+    pub fn address(&self) -> Option<Address> {
+        self.address.parse().ok()
+    }
+}
@@ -0,0 +1,196 @@
+//! Charging case lid state from AirPods proximity advertisements.
+//!
+//! AirPods advertise a "proximity pairing" message over BLE while the case
+//! is open, which is what makes phones pop up their battery card. The
+//! message goes out from a rotating private address rather than the
+//! classic one we connect to, so it is matched on the model it names
+//! instead: only adverts for the connected AirPods' model count. Another
+//! pair of the same model nearby still looks alike. Huawei buds send
+//! nothing comparable that we know of.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use bluer::{Adapter, AdapterEvent, DiscoveryFilter, DiscoveryTransport, Session};
+use futures::StreamExt;
+
+use crate::device::handler::{replace_group, PropertyStore};
+
+/// Property group the last advertisement is published under.
+pub const CASE_GROUP: &str = "case";
+
+const APPLE_COMPANY_ID: u16 = 0x004C;
+/// Message type of the proximity pairing message.
+const PROXIMITY_PAIRING: u8 = 0x07;
+/// Weaker advertisers are someone else's AirPods across the room.
+const NEARBY_RSSI: i16 = -65;
+
+/// Model bytes of the proximity message by the model numbers the AirPods
+/// report in their info (Apple's A-numbers, one per bud or case).
+const ADVERT_MODELS: &[(&[&str], [u8; 2])] = &[
+    (&["A1523", "A1722"], [0x02, 0x20]),
+    (&["A2031", "A2032"], [0x0F, 0x20]),
+    (&["A2564", "A2565"], [0x13, 0x20]),
+    (&["A2083", "A2084"], [0x0E, 0x20]),
+    (&["A2698", "A2699", "A2931"], [0x14, 0x20]),
+    (&["A3047", "A3048", "A3049"], [0x24, 0x20]),
+    (&["A2096"], [0x0A, 0x20]),
+    (&["A3184"], [0x1F, 0x20]),
+];
+
+/// Model bytes the AirPods with `model` (from `info.device_model`) put in
+/// their proximity message, if the model is known.
+pub fn advert_model(model: &str) -> Option<[u8; 2]> {
+    ADVERT_MODELS
+        .iter()
+        .find(|(numbers, _)| numbers.contains(&model))
+        .map(|&(_, bytes)| bytes)
+}
+
+/// What a proximity pairing message says about the case and buds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proximity {
+    /// Model bytes, see [`advert_model`].
+    pub model: [u8; 2],
+    pub lid_open: bool,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub case: Option<u8>,
+}
+
+impl Proximity {
+    /// Parse Apple's manufacturer data. Levels come in tens of percent,
+    /// with 15 for a bud or case that isn't reporting.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 9 || data[0] != PROXIMITY_PAIRING {
+            return None;
+        }
+        let level = |nibble: u8| (nibble <= 10).then_some(nibble * 10);
+        // The battery nibbles are ordered by which bud is primary
+        let (high, low) = (data[6] >> 4, data[6] & 0x0F);
+        let (left, right) = if data[5] & 0x20 == 0 {
+            (high, low)
+        } else {
+            (low, high)
+        };
+        Some(Self {
+            model: [data[3], data[4]],
+            // Bit 3 of the lid byte is set while the lid is closed
+            lid_open: data[8] & 0x08 == 0,
+            left: level(left),
+            right: level(right),
+            case: level(data[7] & 0x0F),
+        })
+    }
+}
+
+/// Publish `proximity` under [`CASE_GROUP`].
+pub async fn publish(props: &PropertyStore, proximity: &Proximity) {
+    let mut values = HashMap::from([("lid_open".to_string(), proximity.lid_open.to_string())]);
+    for (key, level) in [
+        ("left", proximity.left),
+        ("right", proximity.right),
+        ("case", proximity.case),
+    ] {
+        if let Some(level) = level {
+            values.insert(key.to_string(), level.to_string());
+        }
+    }
+    replace_group(props, CASE_GROUP, values).await;
+}
+
+/// Puts the default discovery filter back when the scan ends, so other
+/// BlueZ clients stop getting every repeated advert. The discovery itself
+/// stops when its event stream is dropped, just before.
+struct FilterReset(Adapter);
+
+impl Drop for FilterReset {
+    fn drop(&mut self) {
+        let adapter = self.0.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = adapter
+                    .set_discovery_filter(DiscoveryFilter::default())
+                    .await;
+            });
+        }
+    }
+}
+
+/// Scan for advertisements of AirPods with the `model` bytes until a nearby
+/// case is opened. Only a closed-to-open change counts, so a case left open
+/// doesn't keep firing. Discovery runs only while this is awaited.
+pub async fn wait_lid_open(model: [u8; 2]) -> Result<Proximity> {
+    let session = Session::new().await?;
+    let adapter = session.default_adapter().await?;
+    // Fails when another discovery is running; its results are still seen
+    let _ = adapter
+        .set_discovery_filter(DiscoveryFilter {
+            transport: DiscoveryTransport::Le,
+            duplicate_data: true,
+            ..Default::default()
+        })
+        .await;
+    let _reset = FilterReset(adapter.clone());
+    let mut events = adapter.discover_devices_with_changes().await?;
+    let mut lid_open = HashMap::new();
+
+    while let Some(event) = events.next().await {
+        let AdapterEvent::DeviceAdded(address) = event else {
+            continue;
+        };
+        let Ok(device) = adapter.device(address) else {
+            continue;
+        };
+        if device.rssi().await.ok().flatten().is_none_or(|r| r < NEARBY_RSSI) {
+            continue;
+        }
+        let Some(proximity) = device
+            .manufacturer_data()
+            .await
+            .ok()
+            .flatten()
+            .and_then(|data| data.get(&APPLE_COMPANY_ID).and_then(|d| Proximity::parse(d)))
+        else {
+            continue;
+        };
+        if proximity.model != model {
+            continue;
+        }
+        if lid_open.insert(address, proximity.lid_open) == Some(false) && proximity.lid_open {
+            return Ok(proximity);
+        }
+    }
+    bail!("Discovery ended")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lid_and_battery() {
+        // Right bud primary, left 80%, right 70%, case 50%, lid open
+        let open = [0x07, 0x19, 0x01, 0x14, 0x20, 0x2B, 0x78, 0x05, 0x31];
+        let proximity = Proximity::parse(&open).unwrap();
+        assert!(proximity.lid_open);
+        assert_eq!(Some(proximity.model), advert_model("A2931"));
+        assert_eq!(
+            (proximity.left, proximity.right, proximity.case),
+            (Some(80), Some(70), Some(50))
+        );
+
+        let mut closed = open;
+        closed[8] = 0x39;
+        closed[7] = 0x0F;
+        let proximity = Proximity::parse(&closed).unwrap();
+        assert!(!proximity.lid_open);
+        assert_eq!(proximity.case, None);
+
+        // Models without known bytes aren't watched at all
+        assert_eq!(advert_model("A0000"), None);
+
+        // Other Apple messages (e.g. nearby info) aren't ours
+        assert_eq!(Proximity::parse(&[0x10, 0x05, 0x01, 0, 0, 0, 0, 0, 0]), None);
+    }
+}
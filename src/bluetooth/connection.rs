@@ -1,13 +1,16 @@
-use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::fd::{FromRawFd, OwnedFd};
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use bluer::Address;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
+use tokio::io::AsyncReadExt;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
-use crate::protocol::HuaweiSppPacket;
+use super::platform::Link;
+use super::socket::{connect_blocking, is_cancelled, write_all_timeout, BtStream};
+use super::stats::TRAFFIC;
+use crate::protocol::{HuaweiSppPacket, SppFramer};
 
 // Bluetooth socket constants (from Linux kernel headers)
 const AF_BLUETOOTH: libc::c_int = 31;
@@ -25,14 +28,19 @@ struct SockaddrRc {
 /// Uses raw blocking sockets for connect (like Python/OpenFreebuds),
 /// then wraps in tokio async I/O for the read/write phase.
 pub struct RfcommConnection {
-    stream: UnixStream,
+    stream: BtStream,
 }
 
 impl RfcommConnection {
     /// Connect to a device via RFCOMM on the given channel.
     /// Uses a blocking connect (matching Python's socket.connect behavior)
     /// to ensure the RFCOMM DLC handshake completes before returning.
-    pub async fn connect(address: Address, channel: u8) -> Result<Self> {
+    /// Aborts early with `Cancelled` when `cancel` fires.
+    pub async fn connect(
+        address: Address,
+        channel: u8,
+        cancel: &CancellationToken,
+    ) -> Result<Self> {
         info!("Connecting to {} on RFCOMM channel {}", address, channel);
 
         // Do the blocking connect in a spawn_blocking context
@@ -41,76 +49,57 @@ impl RfcommConnection {
         //                         sock.connect((address, port))
         let addr_bytes = address.0; // [u8; 6] in big-endian
 
-        let raw_fd = tokio::task::spawn_blocking(move || -> Result<OwnedFd> {
-            unsafe {
-                // Create RFCOMM socket (blocking mode, like Python)
-                let fd = libc::socket(AF_BLUETOOTH, libc::SOCK_STREAM, BTPROTO_RFCOMM);
-                if fd < 0 {
-                    anyhow::bail!(
-                        "Failed to create RFCOMM socket: {}",
-                        std::io::Error::last_os_error()
-                    );
-                }
-
-                // Set 5-second timeout for connect and I/O
-                let timeout = libc::timeval {
-                    tv_sec: 5,
-                    tv_usec: 0,
-                };
-                libc::setsockopt(
-                    fd,
-                    libc::SOL_SOCKET,
-                    libc::SO_SNDTIMEO,
-                    &timeout as *const _ as *const libc::c_void,
-                    std::mem::size_of::<libc::timeval>() as libc::socklen_t,
-                );
-                libc::setsockopt(
-                    fd,
-                    libc::SOL_SOCKET,
-                    libc::SO_RCVTIMEO,
-                    &timeout as *const _ as *const libc::c_void,
-                    std::mem::size_of::<libc::timeval>() as libc::socklen_t,
-                );
-
-                // Build sockaddr_rc with address in little-endian (BlueZ convention)
-                let mut rc_bdaddr = addr_bytes;
-                rc_bdaddr.reverse(); // Big-endian -> little-endian for BlueZ
-                let addr = SockaddrRc {
-                    rc_family: AF_BLUETOOTH as u16,
-                    rc_bdaddr,
-                    rc_channel: channel,
-                };
-
-                // Blocking connect — waits for full RFCOMM DLC handshake
-                let ret = libc::connect(
-                    fd,
-                    &addr as *const _ as *const libc::sockaddr,
-                    std::mem::size_of::<SockaddrRc>() as libc::socklen_t,
+        let sock = unsafe {
+            // Create RFCOMM socket (blocking mode, like Python)
+            let fd = libc::socket(AF_BLUETOOTH, libc::SOCK_STREAM, BTPROTO_RFCOMM);
+            if fd < 0 {
+                anyhow::bail!(
+                    "Failed to create RFCOMM socket: {}",
+                    std::io::Error::last_os_error()
                 );
-                if ret < 0 {
-                    let err = std::io::Error::last_os_error();
-                    libc::close(fd);
-                    anyhow::bail!("RFCOMM connect failed: {}", err);
-                }
-
-                // Set non-blocking for tokio async I/O
-                let flags = libc::fcntl(fd, libc::F_GETFL);
-                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
-
-                Ok(OwnedFd::from_raw_fd(fd))
             }
-        })
-        .await
-        .context("spawn_blocking panicked")?
-        .context("RFCOMM connect")?;
-
-        // Wrap in tokio UnixStream for async I/O
-        // (UnixStream is just an AsyncFd<OwnedFd> wrapper — works for any stream socket)
-        let std_stream =
-            unsafe { std::os::unix::net::UnixStream::from_raw_fd(raw_fd.as_raw_fd()) };
-        // Prevent double-close: forget the OwnedFd since std_stream now owns it
-        std::mem::forget(raw_fd);
-        let stream = UnixStream::from_std(std_stream)?;
+            let sock = OwnedFd::from_raw_fd(fd);
+
+            // Set 5-second timeout for connect and I/O
+            let timeout = libc::timeval {
+                tv_sec: 5,
+                tv_usec: 0,
+            };
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_SNDTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            );
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            );
+            sock
+        };
+
+        // Build sockaddr_rc with address in little-endian (BlueZ convention)
+        let mut rc_bdaddr = addr_bytes;
+        rc_bdaddr.reverse(); // Big-endian -> little-endian for BlueZ
+        let addr = SockaddrRc {
+            rc_family: AF_BLUETOOTH as u16,
+            rc_bdaddr,
+            rc_channel: channel,
+        };
+
+        // Blocking connect — waits for full RFCOMM DLC handshake
+        let raw_fd = match connect_blocking(sock, addr, cancel).await {
+            Ok(fd) => fd,
+            Err(e) if is_cancelled(&e) => return Err(e),
+            Err(e) => return Err(e.context("RFCOMM connect failed")),
+        };
+
+        // Hand the socket to tokio for the async read/write phase
+        let stream = BtStream::new(raw_fd)?;
 
         info!(
             "Connected to {} on RFCOMM channel {} (blocking connect OK)",
@@ -119,16 +108,13 @@ impl RfcommConnection {
         Ok(Self { stream })
     }
 
-    /// Split into read/write tasks. Returns a receiver for incoming packets
-    /// and a sender for outgoing packets.
-    pub fn into_split(
-        self,
-    ) -> (
-        mpsc::Receiver<HuaweiSppPacket>,
-        mpsc::Sender<HuaweiSppPacket>,
-        tokio::task::JoinHandle<()>,
-        tokio::task::JoinHandle<()>,
-    ) {
+    /// Wrap an already connected stream (the mock transport).
+    pub(super) fn from_stream(stream: BtStream) -> Self {
+        Self { stream }
+    }
+
+    /// Split into read/write tasks, returning the packet channels as a [`Link`].
+    pub fn into_split(self) -> Link {
         let (read_half, write_half) = tokio::io::split(self.stream);
         let (incoming_tx, incoming_rx) = mpsc::channel::<HuaweiSppPacket>(64);
         let (outgoing_tx, outgoing_rx) = mpsc::channel::<HuaweiSppPacket>(32);
@@ -136,110 +122,68 @@ impl RfcommConnection {
         let read_task = tokio::spawn(recv_loop(read_half, incoming_tx));
         let write_task = tokio::spawn(send_loop(write_half, outgoing_rx));
 
-        (incoming_rx, outgoing_tx, read_task, write_task)
+        Link {
+            incoming: incoming_rx,
+            outgoing: outgoing_tx,
+            read_task,
+            write_task,
+        }
     }
 }
 
 async fn recv_loop(
-    mut reader: tokio::io::ReadHalf<UnixStream>,
+    mut reader: tokio::io::ReadHalf<BtStream>,
     tx: mpsc::Sender<HuaweiSppPacket>,
 ) {
     let mut buf = [0u8; 1024];
+    let mut framer = SppFramer::default();
 
     loop {
-        // Read header (4 bytes: magic + length(2) + reserved)
-        match reader.read(&mut buf[..4]).await {
+        let n = match reader.read(&mut buf).await {
             Ok(0) => {
                 info!("RFCOMM connection closed (EOF)");
                 return;
             }
-            Ok(n) if n < 4 => {
-                // Try to read remaining header bytes
-                let mut total = n;
-                while total < 4 {
-                    match reader.read(&mut buf[total..4]).await {
-                        Ok(0) => return,
-                        Ok(m) => total += m,
-                        Err(e) => {
-                            error!("RFCOMM read error: {}", e);
-                            return;
-                        }
-                    }
-                }
-            }
-            Ok(_) => {}
+            Ok(n) => n,
             Err(e) => {
                 error!("RFCOMM read error: {}", e);
                 return;
             }
-        }
-
-        // Check magic byte
-        if buf[0] != 0x5A {
-            warn!("Invalid magic byte: 0x{:02X}, skipping", buf[0]);
-            continue;
-        }
-
-        // Parse length
-        let length = u16::from_be_bytes([buf[1], buf[2]]) as usize;
-        if length < 3 || length > 1000 {
-            warn!("Invalid packet length: {}, skipping", length);
-            continue;
-        }
-
-        // Read remaining body + CRC (length - 1 bytes for body after reserved byte, + 2 for CRC)
-        let remaining = length - 1 + 2; // body (without the 0x00 byte already read) + CRC
-        if 4 + remaining > buf.len() {
-            warn!("Packet too large: {}", 4 + remaining);
-            continue;
-        }
-
-        let mut total_read = 0;
-        while total_read < remaining {
-            match reader.read(&mut buf[4 + total_read..4 + remaining]).await {
-                Ok(0) => {
-                    info!("RFCOMM connection closed during read");
-                    return;
-                }
-                Ok(n) => total_read += n,
-                Err(e) => {
-                    error!("RFCOMM read error: {}", e);
-                    return;
-                }
-            }
-        }
-
-        let packet_data = &buf[..4 + remaining];
-        match HuaweiSppPacket::from_bytes(packet_data) {
-            Ok(pkt) => {
-                debug!("RX: {}", pkt);
-                if tx.send(pkt).await.is_err() {
-                    info!("Packet channel closed, stopping recv loop");
-                    return;
+        };
+
+        TRAFFIC.read(n);
+        framer.push(&buf[..n]);
+        while let Some(result) = framer.next_packet() {
+            match result {
+                Ok(pkt) => {
+                    debug!("RX: {}", pkt);
+                    TRAFFIC.received();
+                    if tx.send(pkt).await.is_err() {
+                        info!("Packet channel closed, stopping recv loop");
+                        return;
+                    }
                 }
-            }
-            Err(e) => {
-                warn!("Failed to parse packet: {}", e);
+                Err(e) => warn!("Failed to parse packet: {}", e),
             }
         }
     }
 }
 
 async fn send_loop(
-    mut writer: tokio::io::WriteHalf<UnixStream>,
+    mut writer: tokio::io::WriteHalf<BtStream>,
     mut rx: mpsc::Receiver<HuaweiSppPacket>,
 ) {
+    let mut bytes = Vec::new();
     while let Some(pkt) = rx.recv().await {
-        let bytes = pkt.to_bytes();
+        bytes.clear();
+        pkt.write_to(&mut bytes);
         debug!("TX: {}", pkt);
-        if let Err(e) = writer.write_all(&bytes).await {
+        // Returning drops rx and ends the task, which the manager treats as link-dead
+        if let Err(e) = write_all_timeout(&mut writer, &bytes).await {
             error!("RFCOMM write error: {}", e);
             return;
         }
-        if let Err(e) = writer.flush().await {
-            error!("RFCOMM flush error: {}", e);
-            return;
-        }
+        TRAFFIC.sent(bytes.len());
     }
     info!("Outgoing channel closed, stopping send loop");
 }
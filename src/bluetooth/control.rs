@@ -0,0 +1,40 @@
+//! Control channel into the Bluetooth manager.
+//!
+//! Property requests change the device; controls change which device the
+//! manager talks to. The `connection.switch_device` request of the Devices
+//! pages is checked against the paired devices and sent on as a
+//! [`Control`]. The manager watches the channel while connecting, connected
+//! and waiting to reconnect, so a switch never waits for a connect attempt
+//! to time out.
+
+use bluer::Address;
+use tokio::sync::mpsc;
+
+/// Something to do with the link rather than the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control {
+    /// Disconnect from the current device and connect to this one instead.
+    SwitchDevice { address: Address, name: String },
+}
+
+pub type ControlReceiver = mpsc::UnboundedReceiver<Control>;
+
+/// Sends [`Control`]s to a running Bluetooth manager.
+#[derive(Debug, Clone)]
+pub struct Controller(mpsc::UnboundedSender<Control>);
+
+impl Controller {
+    pub fn new() -> (Self, ControlReceiver) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self(tx), rx)
+    }
+
+    /// Ask the manager to switch to the device with `address`. Does
+    /// nothing once the manager is gone.
+    pub fn switch_device(&self, address: Address, name: impl Into<String>) {
+        let _ = self.0.send(Control::SwitchDevice {
+            address,
+            name: name.into(),
+        });
+    }
+}
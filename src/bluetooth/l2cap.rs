@@ -1,13 +1,21 @@
+use std::collections::HashMap;
 use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use bluer::Address;
 use tokio::io::unix::AsyncFd;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
-use crate::protocol::aap::AapPacket;
+use super::platform::Link;
+use super::stats::TRAFFIC;
+use super::socket::{
+    connect_blocking, is_cancelled, set_nonblocking, MAX_WRITE_STALLS, WRITE_TIMEOUT,
+};
+use crate::protocol::aap::{AapFramer, AapPacket, FramingStats};
 use crate::protocol::HuaweiSppPacket;
 
 const AF_BLUETOOTH: libc::c_int = 31;
@@ -30,74 +38,64 @@ pub struct L2capConnection {
 
 impl L2capConnection {
     /// Connect to a device via L2CAP on the given PSM.
-    pub async fn connect(address: Address, psm: u16) -> Result<Self> {
+    /// Aborts early with `Cancelled` when `cancel` fires.
+    pub async fn connect(address: Address, psm: u16, cancel: &CancellationToken) -> Result<Self> {
         info!("Connecting to {} on L2CAP PSM 0x{:04X}", address, psm);
 
         let addr_bytes = address.0;
 
-        let raw_fd = tokio::task::spawn_blocking(move || -> Result<OwnedFd> {
-            unsafe {
-                let fd = libc::socket(AF_BLUETOOTH, libc::SOCK_SEQPACKET, BTPROTO_L2CAP);
-                if fd < 0 {
-                    anyhow::bail!(
-                        "Failed to create L2CAP socket: {}",
-                        std::io::Error::last_os_error()
-                    );
-                }
-
-                // 10-second timeout for connect and I/O
-                let timeout = libc::timeval {
-                    tv_sec: 10,
-                    tv_usec: 0,
-                };
-                libc::setsockopt(
-                    fd,
-                    libc::SOL_SOCKET,
-                    libc::SO_SNDTIMEO,
-                    &timeout as *const _ as *const libc::c_void,
-                    std::mem::size_of::<libc::timeval>() as libc::socklen_t,
-                );
-                libc::setsockopt(
-                    fd,
-                    libc::SOL_SOCKET,
-                    libc::SO_RCVTIMEO,
-                    &timeout as *const _ as *const libc::c_void,
-                    std::mem::size_of::<libc::timeval>() as libc::socklen_t,
-                );
-
-                // Build sockaddr_l2 with address in little-endian
-                let mut l2_bdaddr = addr_bytes;
-                l2_bdaddr.reverse();
-
-                let addr = SockaddrL2 {
-                    l2_family: AF_BLUETOOTH as u16,
-                    l2_psm: psm.to_le(),
-                    l2_bdaddr,
-                    l2_cid: 0,
-                    l2_bdaddr_type: 0, // BR/EDR
-                };
-
-                let ret = libc::connect(
-                    fd,
-                    &addr as *const _ as *const libc::sockaddr,
-                    std::mem::size_of::<SockaddrL2>() as libc::socklen_t,
+        let sock = unsafe {
+            let fd = libc::socket(AF_BLUETOOTH, libc::SOCK_SEQPACKET, BTPROTO_L2CAP);
+            if fd < 0 {
+                anyhow::bail!(
+                    "Failed to create L2CAP socket: {}",
+                    std::io::Error::last_os_error()
                 );
-                if ret < 0 {
-                    let err = std::io::Error::last_os_error();
-                    libc::close(fd);
-                    anyhow::bail!("L2CAP connect failed: {}", err);
-                }
-
-                // Set non-blocking for tokio
-                let flags = libc::fcntl(fd, libc::F_GETFL);
-                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
-
-                Ok(OwnedFd::from_raw_fd(fd))
             }
-        })
-        .await
-        .context("spawn_blocking panicked")?
-        .context("L2CAP connect")?;
+            let sock = OwnedFd::from_raw_fd(fd);
+
+            // 10-second timeout for connect and I/O
+            let timeout = libc::timeval {
+                tv_sec: 10,
+                tv_usec: 0,
+            };
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_SNDTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            );
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            );
+            sock
+        };
+
+        // Build sockaddr_l2 with address in little-endian
+        let mut l2_bdaddr = addr_bytes;
+        l2_bdaddr.reverse();
+
+        let addr = SockaddrL2 {
+            l2_family: AF_BLUETOOTH as u16,
+            l2_psm: psm.to_le(),
+            l2_bdaddr,
+            l2_cid: 0,
+            l2_bdaddr_type: 0, // BR/EDR
+        };
+
+        let raw_fd = match connect_blocking(sock, addr, cancel).await {
+            Ok(fd) => fd,
+            Err(e) if is_cancelled(&e) => return Err(e),
+            Err(e) => return Err(e.context("L2CAP connect failed")),
+        };
+
+        // Set non-blocking for tokio
+        set_nonblocking(&raw_fd)?;
 
         let async_fd = AsyncFd::new(raw_fd)?;
 
@@ -181,15 +179,8 @@ impl L2capConnection {
         }
     }
 
-    /// Split into read/write tasks. Returns same tuple as RfcommConnection::into_split().
-    pub fn into_split(
-        self,
-    ) -> (
-        mpsc::Receiver<HuaweiSppPacket>,
-        mpsc::Sender<HuaweiSppPacket>,
-        tokio::task::JoinHandle<()>,
-        tokio::task::JoinHandle<()>,
-    ) {
+    /// Split into read/write tasks. Same [`Link`] as `RfcommConnection::into_split()`.
+    pub fn into_split(self) -> Link {
         let (incoming_tx, incoming_rx) = mpsc::channel::<HuaweiSppPacket>(64);
         let (outgoing_tx, outgoing_rx) = mpsc::channel::<HuaweiSppPacket>(32);
 
@@ -199,7 +190,12 @@ impl L2capConnection {
         let read_task = tokio::spawn(aap_recv_loop(read_fd, incoming_tx));
         let write_task = tokio::spawn(aap_send_loop(write_fd, outgoing_rx));
 
-        (incoming_rx, outgoing_tx, read_task, write_task)
+        Link {
+            incoming: incoming_rx,
+            outgoing: outgoing_tx,
+            read_task,
+            write_task,
+        }
     }
 }
 
@@ -253,9 +249,44 @@ async fn send_l2cap(fd: &AsyncFd<OwnedFd>, data: &[u8]) -> std::io::Result<()> {
     }
 }
 
+/// Send one L2CAP packet, retrying a bounded number of times if the socket
+/// stays unwritable. SEQPACKET sends are all-or-nothing, so a retry resends
+/// the whole packet.
+async fn send_l2cap_timeout(fd: &AsyncFd<OwnedFd>, data: &[u8]) -> std::io::Result<()> {
+    for stall in 1..=MAX_WRITE_STALLS {
+        match tokio::time::timeout(WRITE_TIMEOUT, send_l2cap(fd, data)).await {
+            Ok(result) => return result,
+            Err(_) => warn!("L2CAP write stalled ({}/{})", stall, MAX_WRITE_STALLS),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        "L2CAP write stalled",
+    ))
+}
+
+/// Framing oddities since startup, across connections.
+static FRAMING: FramingStats = FramingStats::new();
+
+/// Non-zero framing counters, keyed for the `diagnostics` group.
+pub fn framing_diagnostics() -> HashMap<String, String> {
+    [
+        ("aap_concatenated", &FRAMING.concatenated),
+        ("aap_reassembled", &FRAMING.reassembled),
+        ("aap_skipped_bytes", &FRAMING.skipped_bytes),
+        ("aap_truncated", &FRAMING.truncated),
+    ]
+    .into_iter()
+    .map(|(key, counter)| (key, counter.load(Ordering::Relaxed)))
+    .filter(|&(_, n)| n > 0)
+    .map(|(key, n)| (key.to_string(), n.to_string()))
+    .collect()
+}
+
 /// Read loop: receive AAP packets → convert to HuaweiSppPacket → send to handlers.
 async fn aap_recv_loop(fd: Arc<AsyncFd<OwnedFd>>, tx: mpsc::Sender<HuaweiSppPacket>) {
     let mut buf = [0u8; 2048];
+    let mut framer = AapFramer::new(&FRAMING);
 
     loop {
         match recv_l2cap(&fd, &mut buf).await {
@@ -265,27 +296,22 @@ async fn aap_recv_loop(fd: Arc<AsyncFd<OwnedFd>>, tx: mpsc::Sender<HuaweiSppPack
             }
             Ok(n) => {
                 let data = &buf[..n];
+                TRAFFIC.read(n);
                 debug!(
                     "AAP RX: {} bytes [{:02x?}...]",
                     n,
                     &data[..n.min(12)]
                 );
 
-                if n < 5 {
-                    warn!("AAP packet too short: {} bytes", n);
-                    continue;
-                }
-
-                if let Some(aap) = AapPacket::from_bytes(data) {
+                for aap in framer.push(data) {
                     let handler_pkt = aap.to_handler_packet();
                     debug!("AAP → handler: {}", handler_pkt);
+                    TRAFFIC.received();
 
                     if tx.send(handler_pkt).await.is_err() {
                         info!("Handler channel closed, stopping AAP recv loop");
                         return;
                     }
-                } else {
-                    warn!("Failed to parse AAP packet ({} bytes)", n);
                 }
             }
             Err(e) => {
@@ -299,17 +325,16 @@ async fn aap_recv_loop(fd: Arc<AsyncFd<OwnedFd>>, tx: mpsc::Sender<HuaweiSppPack
 /// Write loop: receive HuaweiSppPacket from handlers → convert to AAP → send over L2CAP.
 async fn aap_send_loop(fd: Arc<AsyncFd<OwnedFd>>, mut rx: mpsc::Receiver<HuaweiSppPacket>) {
     while let Some(pkt) = rx.recv().await {
-        if let Some(bytes) = AapPacket::from_handler_packet(&pkt) {
-            debug!("AAP TX: {} bytes", bytes.len());
-            if let Err(e) = send_l2cap(&fd, &bytes).await {
-                error!("L2CAP write error: {}", e);
-                return;
+        match AapPacket::from_handler_packet(&pkt) {
+            Ok(bytes) => {
+                debug!("AAP TX: {} bytes", bytes.len());
+                if let Err(e) = send_l2cap_timeout(&fd, &bytes).await {
+                    error!("L2CAP write error: {}", e);
+                    return;
+                }
+                TRAFFIC.sent(bytes.len());
             }
-        } else {
-            warn!(
-                "Cannot convert handler packet to AAP: {:02X}{:02X}",
-                pkt.command_id[0], pkt.command_id[1]
-            );
+            Err(e) => warn!("Not sending handler packet {}: {:#}", pkt, e),
         }
     }
     info!("Outgoing channel closed, stopping AAP send loop");
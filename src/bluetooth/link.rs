@@ -0,0 +1,127 @@
+//! Audio link details (codec, signal strength) read from BlueZ.
+//!
+//! None of this goes over the device protocol: the codec comes from the
+//! BlueZ media transport of the connected device and the RSSI from its
+//! Device1 object, both on the system bus.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bluer::Address;
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::ObjectManager;
+use dbus::blocking::Connection;
+
+/// Property group the link details are published under.
+pub const LINK_GROUP: &str = "link";
+
+/// How often the link details are re-read while connected.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+const HFP_UUIDS: &[&str] = &[
+    "0000111e-0000-1000-8000-00805f9b34fb",
+    "0000111f-0000-1000-8000-00805f9b34fb",
+    "00001108-0000-1000-8000-00805f9b34fb",
+    "00001112-0000-1000-8000-00805f9b34fb",
+];
+
+/// Name of a transport's codec, from its BlueZ `Codec` byte and, for
+/// vendor codecs, the vendor and codec IDs at the start of `Configuration`.
+fn codec_name(uuid: &str, codec: u8, config: &[u8]) -> String {
+    if HFP_UUIDS.contains(&uuid) {
+        return match codec {
+            1 => "CVSD".into(),
+            2 => "mSBC".into(),
+            3 => "LC3-SWB".into(),
+            other => format!("HFP codec {}", other),
+        };
+    }
+    match codec {
+        0x00 => "SBC".into(),
+        0x01 => "MP3".into(),
+        0x02 => "AAC".into(),
+        0x04 => "ATRAC".into(),
+        0xff if config.len() >= 6 => {
+            let vendor = u32::from_le_bytes([config[0], config[1], config[2], config[3]]);
+            let id = u16::from_le_bytes([config[4], config[5]]);
+            match (vendor, id) {
+                (0x012d, 0x00aa) => "LDAC".into(),
+                (0x004f, 0x0001) => "aptX".into(),
+                (0x00d7, 0x0024) => "aptX HD".into(),
+                (0x000a, 0x0002) => "aptX LL".into(),
+                (0x053a, _) => "LHDC".into(),
+                _ => format!("vendor {:04x}:{:04x}", vendor, id),
+            }
+        }
+        other => format!("codec {:#04x}", other),
+    }
+}
+
+/// Read the link details of `address`. Blocking; keys that BlueZ doesn't
+/// report (no audio transport yet, no RSSI while connected) are left out.
+pub fn read(address: Address) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let Ok(conn) = Connection::new_system() else {
+        return out;
+    };
+    let Ok(objects) = conn
+        .with_proxy("org.bluez", "/", Duration::from_millis(1000))
+        .get_managed_objects()
+    else {
+        return out;
+    };
+
+    let dev_suffix = format!("/dev_{}", address.to_string().replace(':', "_"));
+    for (path, interfaces) in &objects {
+        let path = path.to_string();
+        if path.ends_with(&dev_suffix) {
+            if let Some(rssi) = interfaces
+                .get("org.bluez.Device1")
+                .and_then(|p| prop_cast::<i16>(p, "RSSI"))
+            {
+                out.insert("rssi".to_string(), rssi.to_string());
+            }
+        } else if path.contains(&format!("{}/", dev_suffix)) {
+            if let Some(transport) = interfaces.get("org.bluez.MediaTransport1") {
+                read_transport(transport, &mut out);
+            }
+        }
+    }
+    out
+}
+
+fn read_transport(transport: &PropMap, out: &mut HashMap<String, String>) {
+    let Some(&codec) = prop_cast::<u8>(transport, "Codec") else {
+        return;
+    };
+    let uuid = prop_cast::<String>(transport, "UUID")
+        .map(|s| s.to_lowercase())
+        .unwrap_or_default();
+    let config = prop_cast::<Vec<u8>>(transport, "Configuration")
+        .cloned()
+        .unwrap_or_default();
+    let active = prop_cast::<String>(transport, "State").is_some_and(|s| s == "active");
+
+    // With both A2DP and HFP up, report the one carrying audio
+    if out.contains_key("codec") && !active {
+        return;
+    }
+    out.insert("codec".to_string(), codec_name(&uuid, codec, &config));
+    out.insert("streaming".to_string(), active.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codec_names() {
+        let a2dp = "0000110b-0000-1000-8000-00805f9b34fb";
+        assert_eq!(codec_name(a2dp, 0x02, &[]), "AAC");
+        assert_eq!(
+            codec_name(a2dp, 0xff, &[0x2d, 0x01, 0, 0, 0xaa, 0x00, 0x3c]),
+            "LDAC"
+        );
+        assert_eq!(codec_name(HFP_UUIDS[0], 2, &[]), "mSBC");
+    }
+}
@@ -0,0 +1,43 @@
+//! Development transport: Huawei SPP over a Unix socket.
+//!
+//! With `MYBUDS_MOCK_SOCKET` set, MyBuds skips BlueZ and talks to the
+//! socket instead, which is what `mybuds-sim` listens on. This is for UI
+//! and handler work without hardware and for end-to-end tests; the
+//! simulated device is a FreeBuds Pro 3.
+
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use bluer::Address;
+use tracing::info;
+
+use super::connection::RfcommConnection;
+use super::socket::BtStream;
+
+/// Environment variable naming the simulator's socket.
+pub const SOCKET_ENV: &str = "MYBUDS_MOCK_SOCKET";
+
+/// Bluetooth name the simulated device goes by, which picks its profile.
+pub const DEVICE_NAME: &str = "HUAWEI FreeBuds Pro 3";
+
+/// Socket to use instead of Bluetooth, if the environment asks for one.
+pub fn socket_path() -> Option<PathBuf> {
+    std::env::var_os(SOCKET_ENV)
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Device the mock transport stands in for.
+pub fn device() -> (Address, String) {
+    (Address::any(), DEVICE_NAME.to_string())
+}
+
+/// Connect to the simulator listening on `path`.
+pub fn connect(path: &Path) -> Result<RfcommConnection> {
+    let stream = UnixStream::connect(path)
+        .with_context(|| format!("Mock device socket {}", path.display()))?;
+    info!("Connected to mock device at {}", path.display());
+    Ok(RfcommConnection::from_stream(BtStream::new(OwnedFd::from(stream))?))
+}
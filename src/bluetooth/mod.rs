@@ -1,17 +1,42 @@
+pub mod cache;
+pub mod case;
 pub mod connection;
+pub mod control;
 pub mod l2cap;
+pub mod link;
+pub mod mock;
+pub mod paired;
+pub mod platform;
 pub mod scanner;
+pub mod sleep_timer;
+pub mod socket;
+pub mod state;
+pub mod stats;
 
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use bluer::Address;
-use tracing::{info, warn};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, info_span, warn, Instrument};
 
-use crate::device::models::{DeviceProfile, Transport};
-use crate::device::DeviceManager;
-use connection::RfcommConnection;
-use l2cap::L2capConnection;
+use crate::config::{AppConfig, DeviceRule};
+use crate::device::handler::{put_properties, replace_group};
+use crate::device::models::rules::DeviceRules;
+use crate::device::models::{self, gates, DeviceProfile, Transport};
+use crate::device::request::{PropertyReceiver, PropertyRequest, SetPropertyError};
+use crate::device::{DeviceManager, PROFILE_KEY, READ_ONLY_KEY};
+use crate::integrations::mpris;
+use crate::sandbox;
+use crate::startup;
+use cache::BondedDevice;
+use control::{Control, ControlReceiver, Controller};
+use platform::{Link, SocketProvider};
+use sleep_timer::SleepTimer;
+use state::ConnectionState;
+use stats::SessionStats;
 
 /// Reset the BT link to clear stale RFCOMM state.
 /// Disconnects and reconnects the device to force BlueZ to clean up.
@@ -34,30 +59,294 @@ async fn reset_bt_link(address: Address) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Disconnect the device at the BlueZ level, dropping its audio too.
+async fn disconnect_device(address: Address) -> anyhow::Result<()> {
+    let session = bluer::Session::new().await?;
+    let adapter = session.default_adapter().await?;
+    adapter.device(address)?.disconnect().await?;
+    Ok(())
+}
+
+/// Builds the profile for a device name, address and reported model code,
+/// see [`BluetoothManager::set_profiles`].
+pub type ProfileSource = Box<dyn Fn(&str, Address, Option<&str>) -> DeviceProfile + Send + Sync>;
+
 /// High-level Bluetooth manager that orchestrates connection and packet routing.
 pub struct BluetoothManager {
     device_manager: DeviceManager,
     address: Address,
+    device_name: String,
+    profile_name: &'static str,
     transport: Transport,
-    prop_rx: Option<tokio::sync::mpsc::Receiver<(String, String, String)>>,
+    /// Cached details for this address from a previous session.
+    cached: Option<BondedDevice>,
+    /// Whether a connection succeeded since startup.
+    connected_once: bool,
+    /// Simulator socket to use instead of Bluetooth (see [`mock`]).
+    mock_socket: Option<std::path::PathBuf>,
+    /// Opens the RFCOMM/L2CAP link on this platform.
+    sockets: Box<dyn SocketProvider>,
+    prop_rx: Option<PropertyReceiver>,
+    /// Fires on shutdown to abort connects and waits.
+    cancel: CancellationToken,
+    /// Child of `cancel` for the current device; fires on a device switch too.
+    session: CancellationToken,
+    props: crate::device::handler::PropertyStore,
+    /// Latest battery group, sent whenever a packet changes it.
+    battery_tx: watch::Sender<HashMap<String, String>>,
+    stats: SessionStats,
+    sleep_timer: Option<SleepTimer>,
+    /// The sleep timer disconnected the device: don't reconnect on our own.
+    asleep: bool,
+    /// Writes are dropped (published as `connection.read_only`).
+    read_only: bool,
+    /// Config rules, for listing supported paired devices.
+    device_rules: Vec<DeviceRule>,
+    /// Refresh interval overrides, kept for the device manager of the next
+    /// device.
+    refresh_overrides: HashMap<String, u64>,
+    /// Profiles for devices switched to; the device rules alone if unset.
+    profiles: Option<ProfileSource>,
+    controller: Controller,
+    controls: Option<ControlReceiver>,
+    /// A control that ended the session, handled before the next connect.
+    pending: Option<Control>,
+    /// The device's model code has been checked for a better profile.
+    model_checked: bool,
+    /// A better profile for the model code, used from the next connect.
+    refined: Option<DeviceProfile>,
+    /// Scan for the case opening nearby while disconnected (AirPods).
+    watch_case: bool,
+    /// Model bytes of the connected AirPods' case adverts, once known.
+    case_model: Option<[u8; 2]>,
+    /// The device in use, as address and name.
+    device_tx: watch::Sender<(Address, String)>,
 }
 
 impl BluetoothManager {
     pub fn new(
         address: Address,
+        device_name: String,
         profile: DeviceProfile,
         props: crate::device::handler::PropertyStore,
-        prop_rx: tokio::sync::mpsc::Receiver<(String, String, String)>,
+        prop_rx: PropertyReceiver,
+        cancel: CancellationToken,
     ) -> Self {
         let transport = profile.transport;
-        let device_manager = DeviceManager::new(profile.handlers, props);
+        let profile_name = profile.name;
+        let mut device_manager = DeviceManager::new(profile.handlers, props.clone());
+        device_manager.set_firmware_gates(gates::for_profile(profile_name));
+        let cached = BondedDevice::read()
+            .filter(|c| c.address() == Some(address) && c.profile == profile_name);
+        let (controller, controls) = Controller::new();
 
         Self {
             device_manager,
             address,
+            device_tx: watch::Sender::new((address, device_name.clone())),
+            device_name,
+            profile_name,
             transport,
+            cached,
+            connected_once: false,
+            mock_socket: mock::socket_path(),
+            sockets: platform::native(),
             prop_rx: Some(prop_rx),
+            session: cancel.child_token(),
+            cancel,
+            props,
+            battery_tx: watch::Sender::new(HashMap::new()),
+            stats: SessionStats::default(),
+            sleep_timer: None,
+            asleep: false,
+            read_only: false,
+            device_rules: Vec::new(),
+            refresh_overrides: HashMap::new(),
+            profiles: None,
+            controller,
+            controls: Some(controls),
+            pending: None,
+            model_checked: false,
+            refined: None,
+            watch_case: false,
+            case_model: None,
+        }
+    }
+
+    /// The device in use, as address and name; changes on a device switch.
+    pub fn device_events(&self) -> watch::Receiver<(Address, String)> {
+        self.device_tx.subscribe()
+    }
+
+    /// Battery changes as they arrive from the device (empty while
+    /// disconnected), for consumers that shouldn't poll the store.
+    pub fn battery_events(&self) -> watch::Receiver<HashMap<String, String>> {
+        self.battery_tx.subscribe()
+    }
+
+    /// Pass the battery group on to `battery_events` receivers if it changed.
+    fn publish_battery(&self, battery: HashMap<String, String>) {
+        self.battery_tx.send_if_modified(|current| {
+            let changed = *current != battery;
+            *current = battery;
+            changed
+        });
+    }
+
+    /// Override handler refresh intervals (seconds by handler id, 0 = off).
+    pub fn set_refresh_intervals(&mut self, overrides: &HashMap<String, u64>) {
+        self.device_manager.set_refresh_intervals(overrides);
+        self.refresh_overrides = overrides.clone();
+    }
+
+    /// Drop property writes instead of sending them to the device.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.device_manager.set_read_only(read_only);
+        self.read_only = read_only;
+    }
+
+    /// Reconnect as soon as the AirPods' case is opened nearby, which takes
+    /// a BLE discovery during every wait.
+    pub fn set_watch_case(&mut self, watch_case: bool) {
+        self.watch_case = watch_case;
+    }
+
+    /// Rules for telling supported devices apart in device lists.
+    pub fn set_device_rules(&mut self, rules: &[DeviceRule]) {
+        self.device_rules = rules.to_vec();
+    }
+
+    /// Build the profile of devices switched to, or refined by model code,
+    /// with `profiles` (config overrides and extra handlers on top of the
+    /// device rules).
+    pub fn set_profiles(
+        &mut self,
+        profiles: impl Fn(&str, Address, Option<&str>) -> DeviceProfile + Send + Sync + 'static,
+    ) {
+        self.profiles = Some(Box::new(profiles));
+    }
+
+    /// Answer a device picker request.
+    async fn device_request(&mut self, req: PropertyRequest) {
+        if req.prop == paired::LIST_PROP {
+            tokio::spawn(paired::publish(self.props.clone(), self.device_rules.clone()));
+            req.respond(Ok(()));
+            return;
+        }
+        let result = self.pick_device(&req.value).await;
+        req.respond(result);
+    }
+
+    /// Switch to the listed device with `address`.
+    async fn pick_device(&self, address: &str) -> Result<(), SetPropertyError> {
+        let Ok(parsed) = address.parse::<Address>() else {
+            return Err(SetPropertyError::InvalidValue {
+                prop: paired::SWITCH_PROP.to_string(),
+                value: address.to_string(),
+                expected: "a Bluetooth address".to_string(),
+            });
+        };
+        if parsed == self.address {
+            return Ok(());
+        }
+        let listed = paired::from_group(self.props.lock().await.get(paired::PAIRED_GROUP));
+        let Some(device) = listed.into_iter().find(|d| d.address == parsed.to_string()) else {
+            return Err(SetPropertyError::Rejected(format!(
+                "{} is not a supported paired device",
+                address
+            )));
+        };
+        self.controller.switch_device(parsed, device.name);
+        Ok(())
+    }
+
+    /// Tear down everything tied to the current device and target the one
+    /// with `address` instead: a new device manager for its profile, an
+    /// empty property store and a fresh session. Saved in the config, so
+    /// the next start uses it too.
+    async fn switch_device(&mut self, address: Address, name: String) {
+        if address == self.address {
+            return;
+        }
+        info!("Switching to {} ({})", name, address);
+        let profile = self.profile_for(&name, address, None);
+        self.address = address;
+        self.device_name = name.clone();
+        self.use_profile(profile).await;
+        self.model_checked = false;
+        self.case_model = None;
+        self.connected_once = false;
+        self.asleep = false;
+        self.sleep_timer = None;
+
+        if let Err(e) = AppConfig::save_device(&address.to_string(), &name) {
+            warn!("Could not save the device choice: {:#}", e);
         }
+        self.device_tx.send_replace((address, name));
+    }
+
+    /// Profile for a device, from `profiles` if set.
+    fn profile_for(&self, name: &str, address: Address, model: Option<&str>) -> DeviceProfile {
+        if let Some(profiles) = &self.profiles {
+            return profiles(name, address, model);
+        }
+        let profile = DeviceRules::compile(&self.device_rules).profile(name, &address.to_string());
+        model
+            .and_then(|code| models::refine(profile.name, code))
+            .unwrap_or(profile)
+    }
+
+    /// Replace the device manager with one for `profile`, dropping the
+    /// current device's properties.
+    async fn use_profile(&mut self, profile: DeviceProfile) {
+        info!(
+            "Device profile: {}, transport: {:?}",
+            profile.name, profile.transport
+        );
+        self.device_manager.clear_props().await;
+        self.publish_battery(HashMap::new());
+        let mut device_manager = DeviceManager::new(profile.handlers, self.props.clone());
+        device_manager.set_firmware_gates(gates::for_profile(profile.name));
+        device_manager.set_refresh_intervals(&self.refresh_overrides);
+        device_manager.set_read_only(self.read_only);
+        self.device_manager = device_manager;
+
+        let address = self.address;
+        self.cached = BondedDevice::read()
+            .filter(|c| c.address() == Some(address) && c.profile == profile.name);
+        self.profile_name = profile.name;
+        self.transport = profile.transport;
+    }
+
+    /// Once the device reports its model code, look for a better profile
+    /// than the one its name picked. Returns whether one was found, to be
+    /// used from the next connect.
+    async fn check_model(&mut self) -> bool {
+        if self.model_checked {
+            return false;
+        }
+        let Some(code) = self
+            .props
+            .lock()
+            .await
+            .get("info")
+            .and_then(|info| info.get("device_model"))
+            .cloned()
+        else {
+            return false;
+        };
+        self.model_checked = true;
+        self.case_model = case::advert_model(&code);
+        let profile = self.profile_for(&self.device_name, self.address, Some(&code));
+        if profile.name == self.profile_name {
+            return false;
+        }
+        info!(
+            "Model {} uses the {} profile rather than {}, reconnecting",
+            code, profile.name, self.profile_name
+        );
+        self.refined = Some(profile);
+        true
     }
 
     /// Run the connection loop: connect, init handlers, route packets.
@@ -66,6 +355,12 @@ impl BluetoothManager {
         // Reset channels so run() can be called again after reconnect
         self.device_manager.reset_channels();
 
+        if let Some(path) = &self.mock_socket {
+            let link = mock::connect(path)?.into_split();
+            return self.run_packet_loop(link).await;
+        }
+
+        debug!("Connecting through {}", self.sockets.name());
         match self.transport {
             Transport::Rfcomm(port) => self.run_rfcomm(port as u8).await,
             Transport::L2cap(psm) => self.run_l2cap(psm).await,
@@ -73,52 +368,77 @@ impl BluetoothManager {
     }
 
     async fn run_rfcomm(&mut self, port: u8) -> Result<()> {
-        // Try the configured channel first, then fallback to the other common one
+        // Try the channel that worked last time, then the configured one,
+        // then fallback to the other common one
         let alt = if port == 16 { 1 } else { 16 };
-        let channels = [port, alt];
+        let mut channels = vec![port, alt];
+        if let Some(ch) = self.cached.as_ref().and_then(|c| c.rfcomm_channel) {
+            channels.retain(|&c| c != ch);
+            channels.insert(0, ch);
+        }
 
         let mut conn_result = None;
         for &ch in &channels {
-            match RfcommConnection::connect(self.address, ch).await {
-                Ok(c) => {
-                    conn_result = Some(c);
+            let connect = self
+                .sockets
+                .connect_rfcomm(self.address, ch, &self.session)
+                .instrument(info_span!("rfcomm_connect", channel = ch));
+            match connect.await {
+                Ok(link) => {
+                    conn_result = Some(link);
+                    self.remember_device(Some(ch));
                     break;
                 }
+                Err(e) if socket::is_cancelled(&e) => return Err(e),
                 Err(e) => {
                     warn!("RFCOMM channel {} failed: {}", ch, e);
                 }
             }
         }
 
-        let conn = match conn_result {
-            Some(c) => c,
+        let link = match conn_result {
+            Some(link) => link,
             None => anyhow::bail!("No RFCOMM channel worked (tried {:?})", &channels),
         };
 
-        let (incoming_rx, outgoing_tx, read_task, write_task) = conn.into_split();
-        self.run_packet_loop(incoming_rx, outgoing_tx, read_task, write_task)
-            .await
+        self.run_packet_loop(link).await
     }
 
     async fn run_l2cap(&mut self, psm: u16) -> Result<()> {
-        let conn = L2capConnection::connect(self.address, psm).await?;
+        // Connects and runs the AAP initialization (handshake + feature flags + notifications)
+        let link = self
+            .sockets
+            .connect_l2cap(self.address, psm, &self.session)
+            .instrument(info_span!("l2cap_connect", psm))
+            .await?;
+        self.remember_device(None);
 
-        // Perform AAP protocol initialization (handshake + feature flags + notifications)
-        conn.initialize().await?;
+        self.run_packet_loop(link).await
+    }
 
-        let (incoming_rx, outgoing_tx, read_task, write_task) = conn.into_split();
-        self.run_packet_loop(incoming_rx, outgoing_tx, read_task, write_task)
-            .await
+    /// Record a successful connection in the bonded-device cache.
+    fn remember_device(&mut self, rfcomm_channel: Option<u8>) {
+        self.connected_once = true;
+        let device = BondedDevice {
+            address: self.address.to_string(),
+            name: self.device_name.clone(),
+            profile: self.profile_name.to_string(),
+            rfcomm_channel,
+        };
+        if self.cached.as_ref() != Some(&device) {
+            device.save();
+            self.cached = Some(device);
+        }
     }
 
     /// Common packet routing loop (works for both RFCOMM and L2CAP).
-    async fn run_packet_loop(
-        &mut self,
-        mut incoming_rx: tokio::sync::mpsc::Receiver<crate::protocol::HuaweiSppPacket>,
-        outgoing_tx: tokio::sync::mpsc::Sender<crate::protocol::HuaweiSppPacket>,
-        read_task: tokio::task::JoinHandle<()>,
-        write_task: tokio::task::JoinHandle<()>,
-    ) -> Result<()> {
+    async fn run_packet_loop(&mut self, link: Link) -> Result<()> {
+        let Link {
+            incoming: mut incoming_rx,
+            outgoing: outgoing_tx,
+            read_task,
+            mut write_task,
+        } = link;
         // Connect the device manager's outgoing packets to the write channel
         let mut dm_packet_rx = self.device_manager.take_packet_rx().unwrap();
         let outgoing_tx_clone = outgoing_tx.clone();
@@ -130,25 +450,105 @@ impl BluetoothManager {
             }
         });
 
+        self.device_manager.claim_props(&self.address.to_string()).await;
+        put_properties(
+            &self.props,
+            state::CONNECTION_GROUP,
+            HashMap::from([
+                (PROFILE_KEY.to_string(), self.profile_name.to_string()),
+                (READ_ONLY_KEY.to_string(), self.read_only.to_string()),
+            ]),
+        )
+        .await;
+
         // Initialize all handlers — abort if connection dies during init
-        if let Err(e) = self.device_manager.init_handlers().await {
+        let cancel = self.session.clone();
+        let init = tokio::select! {
+            res = self.device_manager.init_handlers().instrument(info_span!("init_handlers")) => res,
+            _ = cancel.cancelled() => Err(socket::Cancelled.into()),
+        };
+        if let Err(e) = init {
             warn!("Handler init failed: {}", e);
             forward_task.abort();
             read_task.abort();
             write_task.abort();
             return Err(e);
         }
+        state::publish(&self.props, ConnectionState::Connected, None).await;
+        startup::mark_connected(&self.props).await;
+        self.stats.connected(Instant::now());
+        stats::publish(&self.props, &self.stats).await;
+        let mut battery_seen = false;
+        let mut link_poll = tokio::time::interval(link::POLL_INTERVAL);
+        let mut refresh = tokio::time::interval(crate::device::REFRESH_CHECK_INTERVAL);
 
         // Take prop_rx for this run (will be None on reconnect if not reset)
         let mut prop_rx = self.prop_rx.take();
 
         // Route incoming packets and property changes
         loop {
+            let sleep_deadline = self.sleep_timer.map(|t| t.deadline);
             tokio::select! {
+                _ = cancel.cancelled() => {
+                    if self.cancel.is_cancelled() {
+                        info!("Shutdown requested, closing connection");
+                    } else {
+                        info!("Switching devices, closing connection");
+                    }
+                    break;
+                }
+                // The send loop only exits on a write error or stall: the link is dead
+                _ = &mut write_task => {
+                    warn!("Write side of the connection failed, tearing down");
+                    break;
+                }
+                _ = refresh.tick() => self.device_manager.refresh_due().await,
+                _ = async {
+                    match sleep_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if self.run_sleep_timer().await {
+                        break;
+                    }
+                }
+                _ = link_poll.tick() => {
+                    let address = self.address;
+                    if let Ok(values) = tokio::task::spawn_blocking(move || link::read(address)).await {
+                        // Replace rather than merge, so values BlueZ stopped reporting go away
+                        replace_group(&self.props, link::LINK_GROUP, values).await;
+                    }
+                    stats::publish(&self.props, &self.stats).await;
+                    if matches!(self.transport, Transport::L2cap(_)) {
+                        let framing = l2cap::framing_diagnostics();
+                        if !framing.is_empty() {
+                            put_properties(&self.props, startup::DIAGNOSTICS_GROUP, framing).await;
+                        }
+                    }
+                }
                 pkt = incoming_rx.recv() => {
                     match pkt {
                         Some(packet) => {
                             self.device_manager.handle_packet(&packet).await;
+                            if self.check_model().await {
+                                break;
+                            }
+                            let (battery, anc_mode) = {
+                                let store = self.props.lock().await;
+                                let anc_mode = store.get("anc").and_then(|g| g.get("mode")).cloned();
+                                (store.get("battery").cloned(), anc_mode)
+                            };
+                            let now = Instant::now();
+                            self.stats.anc_mode(anc_mode.as_deref(), now);
+                            if let Some(battery) = battery {
+                                self.stats.battery(&battery, now);
+                                if !battery_seen {
+                                    battery_seen = true;
+                                    startup::mark_first_battery(&self.props).await;
+                                }
+                                self.publish_battery(battery);
+                            }
                         }
                         None => break, // Connection lost
                     }
@@ -159,11 +559,42 @@ impl BluetoothManager {
                         None => std::future::pending().await,
                     }
                 } => {
-                    if let Some((group, prop, value)) = change {
-                        info!("UI property change: {}.{} = {}", group, prop, value);
-                        if let Err(e) = self.device_manager.set_property(&group, &prop, &value).await {
+                    if let Some(req) = change {
+                        // Link controls: a manual refresh re-reads battery and
+                        // ANC, the rest (e.g. retry now) have nothing to do
+                        // while connected
+                        if req.group == state::CONNECTION_GROUP {
+                            if req.prop == paired::LIST_PROP || req.prop == paired::SWITCH_PROP {
+                                self.device_request(req).await;
+                                continue;
+                            }
+                            if req.prop == sleep_timer::SLEEP_TIMER_PROP {
+                                let result = SleepTimer::parse(&req.value).map(|timer| {
+                                    info!("Sleep timer: {}", req.value);
+                                    self.sleep_timer = timer;
+                                });
+                                sleep_timer::publish(&self.props, self.sleep_timer.as_ref()).await;
+                                req.respond(result);
+                                continue;
+                            }
+                            if req.prop == "refresh" {
+                                info!("Refresh requested");
+                                self.device_manager
+                                    .refresh_now(crate::device::ON_DEMAND_REFRESH)
+                                    .await;
+                            }
+                            req.respond(Ok(()));
+                            continue;
+                        }
+                        info!("UI property change: {}.{} = {}", req.group, req.prop, req.value);
+                        let result = self
+                            .device_manager
+                            .set_property(&req.group, &req.prop, &req.value)
+                            .await;
+                        if let Err(ref e) = result {
                             warn!("Failed to set property: {}", e);
                         }
+                        req.respond(result);
                     }
                 }
             }
@@ -177,45 +608,249 @@ impl BluetoothManager {
         read_task.abort();
         write_task.abort();
 
+        if self.sleep_timer.take().is_some() {
+            info!("Connection ended, dropping the sleep timer");
+        }
+        sleep_timer::publish(&self.props, None).await;
+        if self.asleep && self.mock_socket.is_none() {
+            if let Err(e) = disconnect_device(self.address).await {
+                warn!("Sleep timer could not disconnect the device: {:#}", e);
+            }
+        }
+
+        self.stats.disconnected(Instant::now());
+        stats::publish(&self.props, &self.stats).await;
+
         // Clear property store so UI shows disconnected state
         self.device_manager.clear_props().await;
+        self.publish_battery(HashMap::new());
 
         Ok(())
     }
 
+    /// The sleep timer ran out: pause playback, then switch ANC off and
+    /// mark the device for disconnecting if asked to. Returns whether the
+    /// connection should end.
+    async fn run_sleep_timer(&mut self) -> bool {
+        let Some(timer) = self.sleep_timer.take() else {
+            return false;
+        };
+        info!("Sleep timer ran out");
+        sleep_timer::publish(&self.props, None).await;
+        match tokio::task::spawn_blocking(mpris::pause_playing).await {
+            Ok(paused) if !paused.is_empty() => info!("Sleep timer paused {}", paused.join(", ")),
+            _ => {}
+        }
+        if timer.actions.anc_off {
+            let options = self
+                .props
+                .lock()
+                .await
+                .get("anc")
+                .and_then(|anc| anc.get("mode_options"))
+                .cloned()
+                .unwrap_or_default();
+            match sleep_timer::anc_off_mode(&options) {
+                Some(mode) => {
+                    if let Err(e) = self.device_manager.set_property("anc", "mode", mode).await {
+                        warn!("Sleep timer could not switch ANC off: {}", e);
+                    }
+                }
+                None => debug!("No ANC off mode to switch to"),
+            }
+        }
+        self.asleep = timer.actions.disconnect;
+        self.asleep
+    }
+
+    /// Run [`run`](Self::run), ending the session early when a control
+    /// arrives. The control is left in `pending`.
+    async fn run_session(&mut self, controls: &mut ControlReceiver) -> Result<()> {
+        let session = self.session.clone();
+        let (result, control) = {
+            let run = self.run();
+            tokio::pin!(run);
+            tokio::select! {
+                result = &mut run => (result, None),
+                Some(control) = controls.recv() => {
+                    // Let the session wind down (and clean up) on its own
+                    session.cancel();
+                    (run.await, Some(control))
+                }
+            }
+        };
+        if control.is_some() {
+            self.pending = control;
+        }
+        result
+    }
+
     /// Run with auto-reconnect. Retries on disconnect with exponential backoff.
     /// After repeated failures, resets the BT link to clear stale state.
+    /// A device switch ends the current connection (or connect attempt) and
+    /// starts over with the new device. Returns once the cancellation token
+    /// fires.
     pub async fn run_with_reconnect(&mut self) {
+        let mut controls = self
+            .controls
+            .take()
+            .expect("run_with_reconnect runs once per manager");
         let mut backoff = Duration::from_secs(2);
         let max_backoff = Duration::from_secs(30);
         let mut failures = 0u32;
 
         loop {
+            if self.cancel.is_cancelled() {
+                info!("Bluetooth manager stopped");
+                state::publish(&self.props, ConnectionState::Disconnected, None).await;
+                return;
+            }
+            if let Some(Control::SwitchDevice { address, name }) = self.pending.take() {
+                self.switch_device(address, name).await;
+                backoff = Duration::from_secs(2);
+                failures = 0;
+            }
+            if let Some(profile) = self.refined.take() {
+                self.use_profile(profile).await;
+            }
+            if self.session.is_cancelled() {
+                self.session = self.cancel.child_token();
+            }
+
             // After 3 consecutive failures, reset the BT link
-            if failures >= 3 {
+            if failures >= 3 && self.mock_socket.is_none() {
                 warn!("Multiple connection failures, resetting Bluetooth link");
-                if let Err(e) = reset_bt_link(self.address).await {
-                    warn!("BT link reset failed: {}", e);
+                let cancel = self.session.clone();
+                tokio::select! {
+                    res = reset_bt_link(self.address) => {
+                        if let Err(e) = res {
+                            warn!("BT link reset failed: {}", e);
+                        }
+                    }
+                    _ = cancel.cancelled() => continue,
                 }
                 failures = 0;
                 backoff = Duration::from_secs(3);
             }
 
-            match self.run().await {
+            state::publish(&self.props, ConnectionState::Connecting, None).await;
+            let result = self.run_session(&mut controls).await;
+            if self.pending.is_some() || self.refined.is_some() {
+                continue;
+            }
+            match result {
+                Ok(()) if self.asleep => {
+                    info!("Disconnected by the sleep timer, waiting to be asked to reconnect");
+                    self.asleep = false;
+                    backoff = Duration::from_secs(2);
+                    failures = 0;
+                    state::publish(&self.props, ConnectionState::Asleep, None).await;
+                    // Effectively forever: only a retry request or the case
+                    // opening nearby ends the wait
+                    self.wait_disconnected(Duration::MAX, &mut controls).await;
+                    continue;
+                }
                 Ok(()) => {
                     info!("Connection ended normally");
                     backoff = Duration::from_secs(2);
                     failures = 0;
+                    state::publish(&self.props, ConnectionState::Reconnecting, None).await;
                 }
+                Err(e) if socket::is_cancelled(&e) => continue,
                 Err(e) => {
                     warn!("Connection error: {}", e);
                     failures += 1;
+                    // A cache that never worked this session may point at a
+                    // device that's gone; scan again on the next startup
+                    if !self.connected_once && self.cached.take().is_some() {
+                        info!("Cached device unreachable, clearing device cache");
+                        BondedDevice::clear();
+                    }
+                    let error = match sandbox::bluetooth_hint() {
+                        Some(hint) => format!("{:#}. {}", e, hint),
+                        None => format!("{:#}", e),
+                    };
+                    state::publish(&self.props, ConnectionState::Failed, Some(error)).await;
                 }
             }
 
             info!("Reconnecting in {:?}...", backoff);
-            tokio::time::sleep(backoff).await;
+            state::set_retry_at(&self.props, backoff).await;
+            self.wait_disconnected(backoff, &mut controls).await;
             backoff = (backoff * 2).min(max_backoff);
         }
     }
+
+    /// Sleep for `duration`, answering property requests that arrive in the
+    /// meantime with NotConnected instead of leaving them queued.
+    /// A `connection.retry_now` request ends the wait early, and so does
+    /// opening the case of the same AirPods model nearby (with `watch_case`),
+    /// or a control (left in `pending`).
+    async fn wait_disconnected(&mut self, duration: Duration, controls: &mut ControlReceiver) {
+        let case_model = self
+            .case_model
+            .filter(|_| self.watch_case && matches!(self.transport, Transport::L2cap(_)));
+        let lid_open = async move {
+            if let Some(model) = case_model {
+                match case::wait_lid_open(model).await {
+                    Ok(proximity) => return proximity,
+                    Err(e) => debug!("Not watching for the case lid: {:#}", e),
+                }
+            }
+            std::future::pending().await
+        };
+        tokio::pin!(lid_open);
+
+        let Some(mut prop_rx) = self.prop_rx.take() else {
+            tokio::select! {
+                _ = tokio::time::sleep(duration) => {}
+                _ = self.cancel.cancelled() => {}
+                proximity = &mut lid_open => {
+                    info!("Case opened nearby, reconnecting now");
+                    case::publish(&self.props, &proximity).await;
+                }
+                Some(control) = controls.recv() => self.pending = Some(control),
+            }
+            return;
+        };
+        let sleep = tokio::time::sleep(duration);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                _ = self.cancel.cancelled() => break,
+                proximity = &mut lid_open => {
+                    info!("Case opened nearby, reconnecting now");
+                    case::publish(&self.props, &proximity).await;
+                    break;
+                }
+                Some(control) = controls.recv() => {
+                    self.pending = Some(control);
+                    break;
+                }
+                req = prop_rx.recv() => match req {
+                    Some(req) if req.group == state::CONNECTION_GROUP && req.prop == "retry_now" => {
+                        info!("Retry requested, reconnecting now");
+                        req.respond(Ok(()));
+                        break;
+                    }
+                    Some(req)
+                        if req.group == state::CONNECTION_GROUP
+                            && (req.prop == paired::LIST_PROP || req.prop == paired::SWITCH_PROP) =>
+                    {
+                        self.device_request(req).await;
+                    }
+                    Some(req) => req.respond(Err(SetPropertyError::NotConnected)),
+                    None => {
+                        tokio::select! {
+                            _ = &mut sleep => {}
+                            _ = self.cancel.cancelled() => {}
+                        }
+                        break;
+                    }
+                },
+            }
+        }
+        self.prop_rx = Some(prop_rx);
+    }
 }
@@ -0,0 +1,148 @@
+//! Paired devices for the device pickers.
+//!
+//! `find_device` takes the first supported device BlueZ lists, which is not
+//! necessarily the one in use when several headsets are paired. The UIs ask
+//! for the list with a `connection.list_devices` request; the Bluetooth
+//! manager scans and publishes it under [`PAIRED_GROUP`]. Picking one sends
+//! `connection.switch_device` with its address: the manager drops the
+//! current device and connects to the picked one (see [`super::control`]),
+//! which is saved in the config as `device_address` and `device_name`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{mock, scanner};
+use crate::config::DeviceRule;
+use crate::device::handler::{replace_group, PropertyStore};
+use crate::device::models::rules::DeviceRules;
+
+/// Property group holding the last device list. Survives disconnects and
+/// device switches.
+pub const PAIRED_GROUP: &str = "paired";
+/// Connection property that asks for the device list to be published.
+pub const LIST_PROP: &str = "list_devices";
+/// Connection property that switches to the device with the given address.
+pub const SWITCH_PROP: &str = "switch_device";
+
+/// Give up on a BlueZ scan that takes longer than this.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A supported paired device, as the pickers show it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairedDevice {
+    pub address: String,
+    pub name: String,
+    /// Connected at the Bluetooth level (audio), not necessarily to MyBuds.
+    pub connected: bool,
+    /// Signal strength in dBm, when BlueZ knows it.
+    #[serde(default)]
+    pub rssi: Option<i16>,
+    /// Freedesktop icon name from the device class, e.g. "audio-headset".
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+impl PairedDevice {
+    /// Kind of device going by its icon, e.g. "Headset".
+    pub fn kind(&self) -> Option<&'static str> {
+        match self.icon.as_deref()? {
+            "audio-headset" => Some("Headset"),
+            "audio-headphones" => Some("Headphones"),
+            _ => None,
+        }
+    }
+
+    /// Signal strength for display, e.g. "-62 dBm".
+    pub fn signal(&self) -> Option<String> {
+        self.rssi.map(|rssi| format!("{} dBm", rssi))
+    }
+}
+
+/// Devices in the published group, in BlueZ's order.
+pub fn from_group(group: Option<&HashMap<String, String>>) -> Vec<PairedDevice> {
+    group
+        .and_then(|g| g.get("devices"))
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+/// Why the last scan failed, if it did.
+pub fn error(group: Option<&HashMap<String, String>>) -> Option<&str> {
+    group?.get("error").map(String::as_str)
+}
+
+async fn scan(rules: &[DeviceRule]) -> Result<Vec<PairedDevice>, String> {
+    if mock::socket_path().is_some() {
+        let (address, name) = mock::device();
+        return Ok(vec![PairedDevice {
+            address: address.to_string(),
+            name,
+            connected: true,
+            rssi: None,
+            icon: None,
+        }]);
+    }
+    let rules = DeviceRules::compile(rules);
+    match tokio::time::timeout(SCAN_TIMEOUT, scanner::list_paired_devices(Some(&rules))).await {
+        Ok(Ok(devices)) => Ok(devices
+            .into_iter()
+            .map(|dev| PairedDevice {
+                address: dev.address.to_string(),
+                name: dev.name,
+                connected: dev.connected,
+                rssi: dev.rssi,
+                icon: dev.icon,
+            })
+            .collect()),
+        Ok(Err(e)) => Err(format!("Could not list paired devices: {}", e)),
+        Err(_) => Err("Listing paired devices took too long".to_string()),
+    }
+}
+
+/// List the supported paired devices and publish them.
+pub async fn publish(props: PropertyStore, rules: Vec<DeviceRule>) {
+    let values = match scan(&rules).await {
+        Ok(devices) => HashMap::from([(
+            "devices".to_string(),
+            serde_json::to_string(&devices).unwrap_or_default(),
+        )]),
+        Err(e) => {
+            warn!("{}", e);
+            HashMap::from([("error".to_string(), e)])
+        }
+    };
+    replace_group(&props, PAIRED_GROUP, values).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_published_list() {
+        let devices = vec![PairedDevice {
+            address: "AA:BB:CC:DD:EE:FF".into(),
+            name: "HUAWEI FreeBuds Pro 3".into(),
+            connected: true,
+            rssi: Some(-62),
+            icon: Some("audio-headset".into()),
+        }];
+        let group = HashMap::from([(
+            "devices".to_string(),
+            serde_json::to_string(&devices).unwrap(),
+        )]);
+        assert_eq!(from_group(Some(&group)), devices);
+        assert_eq!(from_group(None), Vec::new());
+        assert_eq!(error(Some(&group)), None);
+        assert_eq!(devices[0].kind(), Some("Headset"));
+        assert_eq!(devices[0].signal().as_deref(), Some("-62 dBm"));
+
+        // Lists published before signal and icon were added still read
+        let old = r#"[{"address": "AA:BB:CC:DD:EE:FF", "name": "Buds", "connected": false}]"#;
+        let group = HashMap::from([("devices".to_string(), old.to_string())]);
+        assert_eq!(from_group(Some(&group))[0].rssi, None);
+    }
+}
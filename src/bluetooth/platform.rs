@@ -0,0 +1,139 @@
+//! OS boundary of the Bluetooth layer.
+//!
+//! Everything above a connected link (framing, handlers, the property store)
+//! is platform independent. A [`SocketProvider`] is what a port supplies: open
+//! an RFCOMM channel or an L2CAP PSM to a paired device and hand back a
+//! [`Link`] of packet channels. [`BlueZ`] is the Linux provider (raw sockets);
+//! [`Unsupported`] stands in everywhere else and fails each connect with a
+//! clear message, so a macOS (IOBluetooth) or Windows (WinRT) backend can be
+//! added next to it without touching `device/` or `protocol/`.
+//!
+//! Discovery, link details and device control (`scanner`, `link`, `case`,
+//! the reset in `BluetoothManager`) still talk to BlueZ directly.
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use bluer::Address;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::connection::RfcommConnection;
+use super::l2cap::L2capConnection;
+use crate::protocol::HuaweiSppPacket;
+
+/// A connected device: packets in and out, and the tasks moving them.
+pub struct Link {
+    pub incoming: mpsc::Receiver<HuaweiSppPacket>,
+    pub outgoing: mpsc::Sender<HuaweiSppPacket>,
+    pub read_task: JoinHandle<()>,
+    pub write_task: JoinHandle<()>,
+}
+
+/// Opens links to a device on one platform.
+#[async_trait]
+pub trait SocketProvider: Send + Sync {
+    /// Name for logs.
+    fn name(&self) -> &'static str;
+
+    /// Connect to an RFCOMM channel (Huawei SPP).
+    async fn connect_rfcomm(
+        &self,
+        address: Address,
+        channel: u8,
+        cancel: &CancellationToken,
+    ) -> Result<Link>;
+
+    /// Connect to an L2CAP PSM and run the AAP handshake.
+    async fn connect_l2cap(
+        &self,
+        address: Address,
+        psm: u16,
+        cancel: &CancellationToken,
+    ) -> Result<Link>;
+}
+
+/// Linux: raw BlueZ sockets.
+pub struct BlueZ;
+
+#[async_trait]
+impl SocketProvider for BlueZ {
+    fn name(&self) -> &'static str {
+        "BlueZ"
+    }
+
+    async fn connect_rfcomm(
+        &self,
+        address: Address,
+        channel: u8,
+        cancel: &CancellationToken,
+    ) -> Result<Link> {
+        Ok(RfcommConnection::connect(address, channel, cancel)
+            .await?
+            .into_split())
+    }
+
+    async fn connect_l2cap(
+        &self,
+        address: Address,
+        psm: u16,
+        cancel: &CancellationToken,
+    ) -> Result<Link> {
+        let conn = L2capConnection::connect(address, psm, cancel).await?;
+        conn.initialize().await?;
+        Ok(conn.into_split())
+    }
+}
+
+/// Platforms without a backend yet.
+pub struct Unsupported;
+
+impl Unsupported {
+    fn fail(&self) -> Result<Link> {
+        bail!("Bluetooth isn't supported on {} yet", std::env::consts::OS)
+    }
+}
+
+#[async_trait]
+impl SocketProvider for Unsupported {
+    fn name(&self) -> &'static str {
+        "unsupported"
+    }
+
+    async fn connect_rfcomm(&self, _: Address, _: u8, _: &CancellationToken) -> Result<Link> {
+        self.fail()
+    }
+
+    async fn connect_l2cap(&self, _: Address, _: u16, _: &CancellationToken) -> Result<Link> {
+        self.fail()
+    }
+}
+
+/// The provider for the platform MyBuds was built for.
+pub fn native() -> Box<dyn SocketProvider> {
+    if cfg!(target_os = "linux") {
+        Box::new(BlueZ)
+    } else {
+        Box::new(Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unsupported_platforms_fail_to_connect() {
+        let cancel = CancellationToken::new();
+        let err = Unsupported
+            .connect_rfcomm(Address::any(), 1, &cancel)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("isn't supported"));
+        assert!(Unsupported
+            .connect_l2cap(Address::any(), 0x1001, &cancel)
+            .await
+            .is_err());
+    }
+}
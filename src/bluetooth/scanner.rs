@@ -2,51 +2,109 @@ use anyhow::Result;
 use bluer::{Address, Session};
 use tracing::{debug, info};
 
+use crate::device::models::rules::DeviceRules;
+
 /// A discovered/paired Bluetooth device.
 #[derive(Debug, Clone)]
 pub struct BluetoothDevice {
     pub name: String,
     pub address: Address,
+    pub connected: bool,
+    /// Signal strength, only known while BlueZ is discovering or connected.
+    pub rssi: Option<i16>,
+    /// Freedesktop icon name from the device class, e.g. "audio-headset".
+    pub icon: Option<String>,
+}
+
+/// Address prefixes (OUIs) registered to vendors whose headphones we support.
+/// Not exhaustive: vendors own many blocks, and this only helps when the name
+/// doesn't.
+const KNOWN_OUIS: &[[u8; 3]] = &[
+    [0x00, 0xE0, 0xFC], // Huawei
+    [0x00, 0x18, 0x82], // Huawei
+    [0x00, 0x03, 0x93], // Apple
+];
+
+fn has_known_oui(addr: &Address) -> bool {
+    KNOWN_OUIS.iter().any(|oui| addr.0[..3] == oui[..])
 }
 
-/// List paired Bluetooth devices, optionally filtering by known device names.
-pub async fn list_paired_devices(filter_known: bool) -> Result<Vec<BluetoothDevice>> {
+/// Whether the icon BlueZ derived from the class of device is audio output.
+fn is_audio_icon(icon: Option<&str>) -> bool {
+    icon.is_some_and(|i| i == "audio-headset" || i == "audio-headphones")
+}
+
+/// List paired Bluetooth devices, optionally keeping only supported ones
+/// (by name, device rule, or vendor address block).
+pub async fn list_paired_devices(filter_known: Option<&DeviceRules>) -> Result<Vec<BluetoothDevice>> {
     let session = Session::new().await?;
     let adapter = session.default_adapter().await?;
     adapter.set_powered(true).await?;
 
-    let devices = adapter.device_addresses().await?;
-    let mut result = Vec::new();
-
-    for addr in devices {
+    // Query devices concurrently; each lookup is a D-Bus round trip
+    let mut lookups = tokio::task::JoinSet::new();
+    for (index, addr) in adapter.device_addresses().await?.into_iter().enumerate() {
         let device = adapter.device(addr)?;
-        let name = device.name().await?.unwrap_or_default();
-        let paired = device.is_paired().await?;
+        lookups.spawn(async move {
+            let (name, paired, connected, rssi, icon) = tokio::try_join!(
+                device.name(),
+                device.is_paired(),
+                device.is_connected(),
+                device.rssi(),
+                device.icon(),
+            )?;
+            Ok::<_, bluer::Error>((index, paired, BluetoothDevice {
+                name: name.unwrap_or_default(),
+                address: addr,
+                connected,
+                rssi,
+                icon,
+            }))
+        });
+    }
+
+    let mut found = Vec::new();
+    while let Some(joined) = lookups.join_next().await {
+        let (index, paired, dev) = joined??;
 
         if !paired {
             continue;
         }
 
-        if filter_known && !is_known_device(&name) {
-            continue;
+        // A renamed device still matches when a supported vendor's address
+        // block and a headphone class agree
+        if let Some(rules) = filter_known {
+            let supported = rules.is_supported(&dev.name, &dev.address.to_string())
+                || (has_known_oui(&dev.address) && is_audio_icon(dev.icon.as_deref()));
+            if !supported {
+                continue;
+            }
         }
 
-        debug!("Found device: {} ({})", name, addr);
-        result.push(BluetoothDevice {
-            name,
-            address: addr,
-        });
+        debug!("Found device: {} ({}, connected: {})", dev.name, dev.address, dev.connected);
+        found.push((index, dev));
     }
 
+    // Keep BlueZ's ordering so the first match is stable
+    found.sort_by_key(|(index, _)| *index);
+    let result: Vec<BluetoothDevice> = found.into_iter().map(|(_, dev)| dev).collect();
+
     info!("Found {} paired devices", result.len());
     Ok(result)
 }
 
-/// Check if a device name matches a known supported device.
-pub fn is_known_device(name: &str) -> bool {
-    name.starts_with("HUAWEI Free")
-        || name.starts_with("HUAWEI FreeClip")
-        || name.starts_with("HONOR Earbuds")
-        || name.starts_with("HUAWEI FreeLace")
-        || name.contains("AirPods")
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oui_match_needs_a_headphone_class() {
+        let huawei = Address::new([0x00, 0xE0, 0xFC, 0x12, 0x34, 0x56]);
+        let other = Address::new([0x11, 0x22, 0x33, 0x12, 0x34, 0x56]);
+        assert!(has_known_oui(&huawei));
+        assert!(!has_known_oui(&other));
+        assert!(is_audio_icon(Some("audio-headphones")));
+        assert!(!is_audio_icon(Some("phone")));
+        assert!(!is_audio_icon(None));
+    }
 }
@@ -0,0 +1,181 @@
+//! Sleep timer: after a set time, pause playback and optionally switch ANC
+//! off and disconnect the buds.
+//!
+//! UIs start it with a `connection.sleep_timer` request whose value is the
+//! number of minutes followed by the optional actions, e.g.
+//! `30,anc_off,disconnect`, and cancel it with `off`. `BluetoothManager`
+//! owns the running timer and publishes its end and actions under the
+//! `connection` group. The timer belongs to the connection: it is dropped
+//! when the link goes down.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::time::Instant;
+
+use super::state::CONNECTION_GROUP;
+use crate::device::handler::{put_properties, PropertyStore};
+use crate::device::request::SetPropertyError;
+
+/// Property of the `connection` group that starts or cancels the timer.
+pub const SLEEP_TIMER_PROP: &str = "sleep_timer";
+
+/// Durations offered by the UIs, in minutes.
+pub const MINUTES: &[u64] = &[15, 30, 45, 60, 90];
+
+/// Published end of the running timer (unix seconds) and its actions.
+const AT_KEY: &str = "sleep_timer_at";
+const ACTIONS_KEY: &str = "sleep_timer_actions";
+
+const ANC_OFF: &str = "anc_off";
+const DISCONNECT: &str = "disconnect";
+
+/// What happens when the timer runs out, besides pausing playback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Actions {
+    pub anc_off: bool,
+    pub disconnect: bool,
+}
+
+impl Actions {
+    /// Request value starting a timer of `minutes` with these actions.
+    pub fn request(self, minutes: u64) -> String {
+        let mut value = minutes.to_string();
+        for (on, name) in [(self.anc_off, ANC_OFF), (self.disconnect, DISCONNECT)] {
+            if on {
+                value.push(',');
+                value.push_str(name);
+            }
+        }
+        value
+    }
+
+    /// Comma-separated actions, `pause` first, as published.
+    fn to_list(self) -> String {
+        let mut list = vec!["pause"];
+        list.extend(self.anc_off.then_some(ANC_OFF));
+        list.extend(self.disconnect.then_some(DISCONNECT));
+        list.join(",")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepTimer {
+    pub deadline: Instant,
+    /// Wall-clock end, for the UIs.
+    ends_at: SystemTime,
+    pub actions: Actions,
+}
+
+impl SleepTimer {
+    /// Parse a `sleep_timer` request: `off` cancels (None), otherwise
+    /// minutes and actions.
+    pub fn parse(value: &str) -> Result<Option<SleepTimer>, SetPropertyError> {
+        let invalid = || SetPropertyError::InvalidValue {
+            prop: SLEEP_TIMER_PROP.to_string(),
+            value: value.to_string(),
+            expected: "off, or minutes followed by anc_off and/or disconnect".to_string(),
+        };
+        if value == "off" {
+            return Ok(None);
+        }
+        let mut parts = value.split(',').map(str::trim);
+        let minutes: u64 = parts
+            .next()
+            .and_then(|m| m.parse().ok())
+            .filter(|m| (1..=24 * 60).contains(m))
+            .ok_or_else(invalid)?;
+        let mut actions = Actions::default();
+        for action in parts {
+            match action {
+                ANC_OFF => actions.anc_off = true,
+                DISCONNECT => actions.disconnect = true,
+                _ => return Err(invalid()),
+            }
+        }
+        let duration = Duration::from_secs(minutes * 60);
+        Ok(Some(SleepTimer {
+            deadline: Instant::now() + duration,
+            ends_at: SystemTime::now() + duration,
+            actions,
+        }))
+    }
+}
+
+/// Publish the running timer, or clear it.
+pub async fn publish(props: &PropertyStore, timer: Option<&SleepTimer>) {
+    let Some(timer) = timer else {
+        if let Some(group) = props.lock().await.get_mut(CONNECTION_GROUP) {
+            group.remove(AT_KEY);
+            group.remove(ACTIONS_KEY);
+        }
+        return;
+    };
+    let at = timer.ends_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut out = HashMap::new();
+    out.insert(AT_KEY.to_string(), at.as_secs().to_string());
+    out.insert(ACTIONS_KEY.to_string(), timer.actions.to_list());
+    put_properties(props, CONNECTION_GROUP, out).await;
+}
+
+/// Time left on the published timer, if one is running.
+pub fn remaining(connection: Option<&HashMap<String, String>>) -> Option<Duration> {
+    let at: u64 = connection?.get(AT_KEY)?.parse().ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(at.saturating_sub(now)))
+}
+
+/// ANC mode that turns noise control off, among the device's options
+/// (`off` on AirPods, `normal` on FreeBuds).
+pub fn anc_off_mode(mode_options: &str) -> Option<&str> {
+    let options: Vec<&str> = mode_options.split(',').collect();
+    ["off", "normal"].into_iter().find(|m| options.contains(m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        let timer = SleepTimer::parse("30,disconnect").unwrap().unwrap();
+        assert_eq!(
+            timer.actions,
+            Actions {
+                anc_off: false,
+                disconnect: true
+            }
+        );
+        assert!(timer.deadline > Instant::now() + Duration::from_secs(29 * 60));
+        assert_eq!(SleepTimer::parse("off").unwrap(), None);
+        assert!(SleepTimer::parse("0").is_err());
+        assert!(SleepTimer::parse("15,explode").is_err());
+
+        let actions = Actions {
+            anc_off: true,
+            disconnect: true,
+        };
+        let parsed = SleepTimer::parse(&actions.request(45)).unwrap().unwrap();
+        assert_eq!(parsed.actions, actions);
+        assert_eq!(actions.to_list(), "pause,anc_off,disconnect");
+    }
+
+    #[tokio::test]
+    async fn publishes_and_clears_the_timer() {
+        let props = PropertyStore::default();
+        let timer = SleepTimer::parse("15").unwrap().unwrap();
+        publish(&props, Some(&timer)).await;
+        let left = remaining(props.lock().await.get(CONNECTION_GROUP)).unwrap();
+        assert!(left > Duration::from_secs(14 * 60) && left <= Duration::from_secs(15 * 60));
+
+        publish(&props, None).await;
+        assert_eq!(remaining(props.lock().await.get(CONNECTION_GROUP)), None);
+    }
+
+    #[test]
+    fn picks_the_mode_that_turns_anc_off() {
+        assert_eq!(anc_off_mode("off,anc,transparency"), Some("off"));
+        assert_eq!(anc_off_mode("normal,cancellation,awareness"), Some("normal"));
+        assert_eq!(anc_off_mode("cancellation"), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Helpers shared by the raw RFCOMM and L2CAP sockets.
+
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::pin::Pin;
+use std::task::{ready, Context as TaskContext, Poll};
+
+use anyhow::{Context, Result};
+use tokio::io::unix::AsyncFd;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// How long a single write may wait for the socket before counting as a stall.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Consecutive stalls after which the link is considered dead.
+pub const MAX_WRITE_STALLS: u32 = 3;
+
+/// Returned (inside anyhow) when a connection attempt was cancelled.
+#[derive(Debug, thiserror::Error)]
+#[error("connection attempt cancelled")]
+pub struct Cancelled;
+
+/// Whether an error chain comes from a cancelled attempt.
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<Cancelled>().is_some()
+}
+
+/// Run a blocking `connect()` on `fd` in the blocking pool.
+///
+/// If `cancel` fires first the socket is shut down to abort the handshake
+/// and `Cancelled` is returned right away. The fd is kept open until the
+/// blocking call actually returns so its number can't be reused under it.
+pub async fn connect_blocking<A: Send + 'static>(
+    fd: OwnedFd,
+    addr: A,
+    cancel: &CancellationToken,
+) -> Result<OwnedFd> {
+    let raw = fd.as_raw_fd();
+    let mut task = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        let ret = unsafe {
+            libc::connect(
+                raw,
+                &addr as *const A as *const libc::sockaddr,
+                std::mem::size_of::<A>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    });
+
+    tokio::select! {
+        res = &mut task => {
+            res.context("spawn_blocking panicked")??;
+            Ok(fd)
+        }
+        _ = cancel.cancelled() => {
+            unsafe {
+                libc::shutdown(raw, libc::SHUT_RDWR);
+            }
+            tokio::spawn(async move {
+                let _ = task.await;
+                drop(fd);
+            });
+            Err(Cancelled.into())
+        }
+    }
+}
+
+/// Write all of `data`, giving up once the socket has stalled
+/// `MAX_WRITE_STALLS` times in a row. Progress resets the stall count, and
+/// partial writes resume where they left off so the stream stays intact.
+pub async fn write_all_timeout<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    let mut written = 0;
+    let mut stalls = 0;
+    while written < data.len() {
+        match tokio::time::timeout(WRITE_TIMEOUT, writer.write(&data[written..])).await {
+            Ok(Ok(0)) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(Ok(n)) => {
+                written += n;
+                stalls = 0;
+            }
+            Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+            Ok(Err(e)) => return Err(e),
+            Err(_) => {
+                stalls += 1;
+                if stalls >= MAX_WRITE_STALLS {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("write stalled {} times", stalls),
+                    ));
+                }
+                warn!("Write stalled ({}/{}), retrying", stalls, MAX_WRITE_STALLS);
+            }
+        }
+    }
+    writer.flush().await
+}
+
+/// Switch a connected socket to non-blocking mode for tokio.
+pub fn set_nonblocking(fd: &OwnedFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+        if flags < 0 || libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Async byte stream over a connected Bluetooth stream socket (RFCOMM).
+///
+/// Registers the fd with tokio via `AsyncFd` and does plain read/write
+/// syscalls, so it works with `tokio::io::split` like any other stream.
+pub struct BtStream {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl BtStream {
+    /// Take ownership of a connected socket and switch it to non-blocking mode.
+    pub fn new(fd: OwnedFd) -> io::Result<Self> {
+        set_nonblocking(&fd)?;
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+        })
+    }
+}
+
+impl AsyncRead for BtStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            let result = guard.try_io(|inner| {
+                let n = unsafe {
+                    libc::read(
+                        inner.as_raw_fd(),
+                        unfilled.as_mut_ptr() as *mut libc::c_void,
+                        unfilled.len(),
+                    )
+                };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+            match result {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for BtStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_write_ready(cx))?;
+            let result = guard.try_io(|inner| {
+                let n = unsafe {
+                    libc::write(
+                        inner.as_raw_fd(),
+                        buf.as_ptr() as *const libc::c_void,
+                        buf.len(),
+                    )
+                };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+            match result {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        // Writes go straight to the socket, nothing buffered here
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let ret = unsafe { libc::shutdown(self.fd.as_raw_fd(), libc::SHUT_WR) };
+        if ret < 0 {
+            Poll::Ready(Err(io::Error::last_os_error()))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::FromRawFd;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// Connected pair of stream sockets standing in for an RFCOMM link.
+    fn socket_pair() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        let ret = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+        assert_eq!(ret, 0, "socketpair failed");
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    #[tokio::test]
+    async fn bt_stream_round_trip_through_split() {
+        let (a, b) = socket_pair();
+        let (mut a_read, mut a_write) = tokio::io::split(BtStream::new(a).unwrap());
+        let mut b = BtStream::new(b).unwrap();
+
+        a_write.write_all(b"\x5a\x00\x03\x00").await.unwrap();
+        let mut buf = [0u8; 4];
+        b.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"\x5a\x00\x03\x00");
+
+        b.write_all(b"pong").await.unwrap();
+        a_read.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn write_gives_up_after_repeated_stalls() {
+        let (a, _b) = socket_pair();
+        let mut a = BtStream::new(a).unwrap();
+
+        // Nobody reads from _b, so the socket buffer eventually fills up
+        let chunk = vec![0u8; 64 * 1024];
+        let err = loop {
+            if let Err(e) = write_all_timeout(&mut a, &chunk).await {
+                break e;
+            }
+        };
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn bt_stream_reports_eof_when_peer_closes() {
+        let (a, b) = socket_pair();
+        let mut a = BtStream::new(a).unwrap();
+        drop(b);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(a.read(&mut buf).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::device::handler::{put_properties, PropertyStore};
+
+/// Property group the connection state is published under.
+/// Survives `DeviceManager::clear_props` so the UIs keep seeing it.
+pub const CONNECTION_GROUP: &str = "connection";
+
+/// Link state as seen by the UIs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionState {
+    /// Nothing attempted yet.
+    #[default]
+    Disconnected,
+    /// No supported paired device found yet.
+    Searching,
+    /// Socket connect / handler init in progress.
+    Connecting,
+    /// Handlers initialized, device is live.
+    Connected,
+    /// Connection dropped, waiting to reconnect.
+    Reconnecting,
+    /// Last attempt failed, waiting to retry.
+    Failed,
+    /// Disconnected by the sleep timer; reconnects only when asked.
+    Asleep,
+}
+
+impl ConnectionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Disconnected => "disconnected",
+            Self::Searching => "searching",
+            Self::Connecting => "connecting",
+            Self::Connected => "connected",
+            Self::Reconnecting => "reconnecting",
+            Self::Failed => "failed",
+            Self::Asleep => "asleep",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "searching" => Self::Searching,
+            "connecting" => Self::Connecting,
+            "connected" => Self::Connected,
+            "reconnecting" => Self::Reconnecting,
+            "failed" => Self::Failed,
+            "asleep" => Self::Asleep,
+            _ => Self::Disconnected,
+        }
+    }
+
+    /// Human-readable label for status lines.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Disconnected => "Not connected",
+            Self::Searching => "Waiting for device...",
+            Self::Connecting => "Connecting...",
+            Self::Connected => "Connected",
+            Self::Reconnecting => "Connection lost, reconnecting...",
+            Self::Failed => "Connection failed",
+            Self::Asleep => "Disconnected by the sleep timer",
+        }
+    }
+
+    /// Time left until the next reconnect attempt, if one is scheduled.
+    pub fn retry_in(group: Option<&HashMap<String, String>>) -> Option<Duration> {
+        let at: u64 = group?.get("retry_at")?.parse().ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Some(Duration::from_secs(at.saturating_sub(now)))
+    }
+
+    /// Read the current state from a property store snapshot.
+    pub fn from_group(group: Option<&HashMap<String, String>>) -> Self {
+        group
+            .and_then(|g| g.get("state"))
+            .map(|s| Self::parse(s))
+            .unwrap_or_default()
+    }
+}
+
+/// Publish a state change. Clears any error / scheduled retry from the
+/// previous state.
+pub async fn publish(props: &PropertyStore, state: ConnectionState, error: Option<String>) {
+    {
+        let mut store = props.lock().await;
+        if let Some(group) = store.get_mut(CONNECTION_GROUP) {
+            group.remove("error");
+            group.remove("retry_at");
+        }
+    }
+    let mut out = HashMap::new();
+    out.insert("state".to_string(), state.as_str().to_string());
+    if let Some(error) = error {
+        out.insert("error".to_string(), error);
+    }
+    put_properties(props, CONNECTION_GROUP, out).await;
+}
+
+/// Record when the next reconnect attempt happens (as a unix timestamp, so
+/// UIs can count down without talking to the manager).
+pub async fn set_retry_at(props: &PropertyStore, delay: Duration) {
+    let at = SystemTime::now() + delay;
+    let secs = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut out = HashMap::new();
+    out.insert("retry_at".to_string(), secs.to_string());
+    put_properties(props, CONNECTION_GROUP, out).await;
+}
@@ -0,0 +1,309 @@
+//! Per-session statistics.
+//!
+//! The transport loops count packets and bytes in [`TRAFFIC`]; the manager
+//! feeds connection, battery and ANC changes to a [`SessionStats`]. Both are
+//! published to the `stats` group for the Statistics page and `mybuds
+//! stats`. Nothing is persisted: a session lasts as long as the process
+//! owning the connection.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::device::handler::{replace_group, PropertyStore};
+
+/// Property group holding the session statistics. Survives disconnects.
+pub const STATS_GROUP: &str = "stats";
+
+/// Prefix of the per-mode ANC time keys (`anc_secs:<mode>`).
+pub const ANC_TIME_PREFIX: &str = "anc_secs:";
+
+/// Least time on battery before a drain rate is reported; shorter spans are
+/// dominated by the 1% steps the buds report in.
+const MIN_DRAIN_SPAN: Duration = Duration::from_secs(15 * 60);
+
+/// Packet and byte counters, shared by every transport.
+pub struct Traffic {
+    packets_sent: AtomicU64,
+    packets_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Traffic {
+    const fn new() -> Self {
+        Self {
+            packets_sent: AtomicU64::new(0),
+            packets_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+        }
+    }
+
+    /// A packet of `bytes` went out.
+    pub fn sent(&self, bytes: usize) {
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// `bytes` were read from the link, framing overhead and junk included.
+    pub fn read(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// A complete packet came out of the framer.
+    pub fn received(&self) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn to_props(&self, out: &mut HashMap<String, String>) {
+        for (key, counter) in [
+            ("packets_sent", &self.packets_sent),
+            ("packets_received", &self.packets_received),
+            ("bytes_sent", &self.bytes_sent),
+            ("bytes_received", &self.bytes_received),
+        ] {
+            out.insert(key.to_string(), counter.load(Ordering::Relaxed).to_string());
+        }
+    }
+}
+
+/// Traffic since startup, across connections.
+pub static TRAFFIC: Traffic = Traffic::new();
+
+/// Connection, battery and ANC history of this session.
+#[derive(Default)]
+pub struct SessionStats {
+    connections: u32,
+    /// Start of the current connection.
+    connected_since: Option<Instant>,
+    /// Connected time of earlier connections.
+    connected_time: Duration,
+    /// Last level seen on battery, and when.
+    last_level: Option<(u8, Instant)>,
+    /// Percentage points lost over `drain_time`.
+    drained: u32,
+    drain_time: Duration,
+    /// Current ANC mode and since when.
+    anc: Option<(String, Instant)>,
+    /// Time spent in each ANC mode, the current one excluded.
+    anc_time: BTreeMap<String, Duration>,
+}
+
+impl SessionStats {
+    pub fn connected(&mut self, now: Instant) {
+        self.connections += 1;
+        self.connected_since = Some(now);
+    }
+
+    /// Close the spans of the connection that just ended.
+    pub fn disconnected(&mut self, now: Instant) {
+        if let Some(since) = self.connected_since.take() {
+            self.connected_time += now - since;
+        }
+        self.last_level = None;
+        self.anc_mode(None, now);
+    }
+
+    /// Record a battery group update. The level is the lower bud (the
+    /// global value on models without per-bud levels); charging spans
+    /// don't count towards the drain rate.
+    pub fn battery(&mut self, battery: &HashMap<String, String>, now: Instant) {
+        let level = |key: &str| battery.get(key).and_then(|v| v.parse::<u8>().ok());
+        let level = match (level("left"), level("right")) {
+            (Some(l), Some(r)) => Some(l.min(r)),
+            (l, r) => l.or(r).or_else(|| level("global")),
+        };
+        let charging = battery.get("is_charging").is_some_and(|v| v == "true");
+        let Some(level) = level.filter(|_| !charging) else {
+            self.last_level = None;
+            return;
+        };
+        match self.last_level {
+            // Same level: keep the older sample so slow drains still add up
+            Some((last, _)) if last == level => return,
+            Some((last, at)) if last > level => {
+                self.drained += u32::from(last - level);
+                self.drain_time += now - at;
+            }
+            _ => {}
+        }
+        self.last_level = Some((level, now));
+    }
+
+    /// Record the current ANC mode (None while unknown or disconnected).
+    pub fn anc_mode(&mut self, mode: Option<&str>, now: Instant) {
+        if self.anc.as_ref().map(|(m, _)| m.as_str()) == mode {
+            return;
+        }
+        if let Some((prev, since)) = self.anc.take() {
+            *self.anc_time.entry(prev).or_default() += now - since;
+        }
+        self.anc = mode.map(|m| (m.to_string(), now));
+    }
+
+    /// Average battery drain in percent per hour, once there is enough
+    /// time on battery to tell.
+    pub fn drain_per_hour(&self) -> Option<f64> {
+        (self.drain_time >= MIN_DRAIN_SPAN)
+            .then(|| f64::from(self.drained) * 3600.0 / self.drain_time.as_secs_f64())
+    }
+
+    /// Property values for [`STATS_GROUP`], with open spans counted up to `now`.
+    pub fn to_props(&self, now: Instant) -> HashMap<String, String> {
+        let mut out = HashMap::new();
+        TRAFFIC.to_props(&mut out);
+        out.insert(
+            "reconnects".to_string(),
+            self.connections.saturating_sub(1).to_string(),
+        );
+        let connected = self.connected_time + self.connected_since.map_or(Duration::ZERO, |s| now - s);
+        out.insert("connected_secs".to_string(), connected.as_secs().to_string());
+        if let Some(rate) = self.drain_per_hour() {
+            out.insert("drain_per_hour".to_string(), format!("{:.1}", rate));
+        }
+        let mut anc_time = self.anc_time.clone();
+        if let Some((mode, since)) = &self.anc {
+            *anc_time.entry(mode.clone()).or_default() += now - *since;
+        }
+        for (mode, time) in anc_time {
+            out.insert(format!("{}{}", ANC_TIME_PREFIX, mode), time.as_secs().to_string());
+        }
+        out
+    }
+}
+
+/// Publish `stats` under [`STATS_GROUP`].
+pub async fn publish(props: &PropertyStore, stats: &SessionStats) {
+    replace_group(props, STATS_GROUP, stats.to_props(Instant::now())).await;
+}
+
+/// Labelled values of a published stats group in display order, shared by
+/// the Statistics page and `mybuds stats`.
+pub fn summary(group: &HashMap<String, String>) -> Vec<(&'static str, String)> {
+    let num = |key: &str| group.get(key).and_then(|v| v.parse::<u64>().ok());
+    let mut rows = Vec::new();
+    if let Some(secs) = num("connected_secs") {
+        rows.push(("Connected", format_duration(secs)));
+    }
+    if let Some(n) = num("reconnects") {
+        rows.push(("Reconnects", n.to_string()));
+    }
+    for (label, packets, bytes) in [
+        ("Sent", "packets_sent", "bytes_sent"),
+        ("Received", "packets_received", "bytes_received"),
+    ] {
+        if let (Some(packets), Some(bytes)) = (num(packets), num(bytes)) {
+            rows.push((label, format!("{} packets, {}", packets, format_bytes(bytes))));
+        }
+    }
+    let drain = match group.get("drain_per_hour") {
+        Some(rate) => format!("{}% per hour", rate),
+        None => "Not enough data yet".to_string(),
+    };
+    rows.push(("Battery Drain", drain));
+    rows
+}
+
+/// ANC modes and their share of the time, longest first, from a published
+/// stats group.
+pub fn anc_distribution(group: &HashMap<String, String>) -> Vec<(String, f64)> {
+    let mut times: Vec<(String, u64)> = group
+        .iter()
+        .filter_map(|(key, secs)| {
+            let mode = key.strip_prefix(ANC_TIME_PREFIX)?;
+            Some((mode.to_string(), secs.parse().ok()?))
+        })
+        .collect();
+    let total: u64 = times.iter().map(|(_, secs)| secs).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    times.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    times
+        .into_iter()
+        .map(|(mode, secs)| (mode, secs as f64 / total as f64))
+        .collect()
+}
+
+/// "1h 05m", "12m 30s" or "45s".
+pub fn format_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// "512 B", "12.4 KiB" or "3.1 MiB".
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(60);
+
+    fn battery(left: u8, right: u8, charging: bool) -> HashMap<String, String> {
+        HashMap::from([
+            ("left".to_string(), left.to_string()),
+            ("right".to_string(), right.to_string()),
+            ("is_charging".to_string(), charging.to_string()),
+        ])
+    }
+
+    #[test]
+    fn drain_rate_skips_charging_spans() {
+        let t0 = Instant::now();
+        let mut stats = SessionStats::default();
+        stats.battery(&battery(90, 92, false), t0);
+        stats.battery(&battery(85, 88, false), t0 + 10 * MIN);
+        assert_eq!(stats.drain_per_hour(), None);
+
+        // Charging back up doesn't count, and neither does the gap
+        stats.battery(&battery(95, 95, true), t0 + 20 * MIN);
+        stats.battery(&battery(95, 95, false), t0 + 40 * MIN);
+        stats.battery(&battery(90, 91, false), t0 + 60 * MIN);
+
+        // 10 points over 30 minutes
+        assert_eq!(stats.drain_per_hour(), Some(20.0));
+    }
+
+    #[test]
+    fn anc_time_includes_the_open_span() {
+        let t0 = Instant::now();
+        let mut stats = SessionStats::default();
+        stats.connected(t0);
+        stats.anc_mode(Some("cancellation"), t0);
+        stats.anc_mode(Some("awareness"), t0 + 3 * MIN);
+        stats.anc_mode(Some("cancellation"), t0 + 4 * MIN);
+        stats.disconnected(t0 + 5 * MIN);
+        stats.connected(t0 + 6 * MIN);
+        stats.anc_mode(Some("awareness"), t0 + 6 * MIN);
+
+        let props = stats.to_props(t0 + 7 * MIN);
+        assert_eq!(props["anc_secs:cancellation"], "240");
+        assert_eq!(props["anc_secs:awareness"], "120");
+        assert_eq!(props["reconnects"], "1");
+        assert_eq!(props["connected_secs"], "360");
+
+        let shares = anc_distribution(&props);
+        assert_eq!(shares[0].0, "cancellation");
+        assert!((shares[0].1 - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn formats_durations_and_sizes() {
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(750), "12m 30s");
+        assert_eq!(format_duration(3900), "1h 05m");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(12_700), "12.4 KiB");
+    }
+}
@@ -0,0 +1,30 @@
+//! `mybuds anc-toggle`: switch between the two favorite ANC modes.
+//!
+//! Meant for a desktop keyboard shortcut: with MyBuds running it attaches
+//! to the running instance, so the switch is immediate.
+
+use anyhow::{bail, Result};
+
+use super::{wait_connected, wait_for};
+use crate::config::AppConfig;
+use crate::device::anc::next_favorite;
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertySender};
+use crate::labels;
+
+pub async fn toggle(props: PropertyStore, tx: PropertySender) -> Result<()> {
+    let favorites = AppConfig::load().favorite_anc_modes;
+    if favorites.len() != 2 {
+        bail!(
+            "Set two favorite ANC modes first, e.g. favorite_anc_modes = [\"normal\", \"cancellation\"] in {}",
+            AppConfig::path().display()
+        );
+    }
+    wait_connected(&props).await?;
+
+    let current = wait_for(&props, |s| s.get("anc")?.get("mode").cloned()).await;
+    let next = next_favorite(&favorites, current.as_deref()).expect("two favorites");
+    request::request(&tx, "anc", "mode", next).await?;
+    println!("ANC \u{2192} {}", labels::anc_mode(next));
+    Ok(())
+}
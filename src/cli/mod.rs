@@ -0,0 +1,92 @@
+//! Non-interactive command-line subcommands.
+//!
+//! Each subcommand runs against a live connection: main starts the
+//! Bluetooth manager in the background as in TUI mode, then hands the
+//! property store and request channel to the subcommand.
+
+pub mod anc;
+pub mod props;
+pub mod setting;
+pub mod snapshot;
+pub mod stats;
+pub mod status;
+pub mod watch;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::device::handler::PropertyStore;
+
+/// How long to wait for the device before giving up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for a connected device to report a value.
+const VALUE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Wait until the device is connected, reporting state changes on stderr.
+pub async fn wait_connected(props: &PropertyStore) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    let mut last = None;
+    loop {
+        let state = ConnectionState::from_group(props.lock().await.get(CONNECTION_GROUP));
+        if state == ConnectionState::Connected {
+            return Ok(());
+        }
+        if last != Some(state) {
+            eprintln!("{}", state.label());
+            last = Some(state);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            bail!("Device did not connect within {:?}", CONNECT_TIMEOUT);
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Wait briefly for `read` to find something in the store, e.g. a value the
+/// handlers are still reading right after connecting.
+pub async fn wait_for<T>(
+    props: &PropertyStore,
+    read: impl Fn(&HashMap<String, HashMap<String, String>>) -> Option<T>,
+) -> Option<T> {
+    let deadline = tokio::time::Instant::now() + VALUE_TIMEOUT;
+    loop {
+        let value = read(&*props.lock().await);
+        if value.is_some() || tokio::time::Instant::now() >= deadline {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Split "group.prop=value" into its parts.
+pub fn parse_assignment(s: &str) -> Option<(&str, &str, &str)> {
+    let (key, value) = s.split_once('=')?;
+    let (group, prop) = key.trim().split_once('.')?;
+    if group.is_empty() || prop.is_empty() {
+        return None;
+    }
+    Some((group, prop, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_assignments() {
+        assert_eq!(
+            parse_assignment("anc.mode=awareness"),
+            Some(("anc", "mode", "awareness"))
+        );
+        // Only the first dot splits group from prop (dual connect uses MACs with colons)
+        assert_eq!(
+            parse_assignment("dual_connect.aabb:connected = true"),
+            Some(("dual_connect", "aabb:connected", "true"))
+        );
+        assert_eq!(parse_assignment("anc=mode"), None);
+        assert_eq!(parse_assignment(".mode=x"), None);
+    }
+}
@@ -0,0 +1,203 @@
+//! `mybuds props`: dump the property store and set raw properties.
+//!
+//! An escape hatch for debugging and for settings that have no dedicated UI
+//! yet. Prints every group as a tree; with `--watch` it keeps printing
+//! changes and reads `group.prop=value` lines from stdin.
+//!
+//! With `--json` the store is printed as one object of groups instead, and
+//! `--watch` prints the whole object again on one line after every change,
+//! from the start rather than once connected, for status bar modules:
+//!
+//! ```text
+//! {"anc":{"mode":"cancellation",...},"battery":{"left":"80",...},"connection":{"state":"connected",...}}
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use super::{parse_assignment, wait_connected};
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertySender};
+use crate::labels;
+
+/// Time for handlers to answer their init reads before the first dump.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+pub(super) const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+pub(super) type Snapshot = BTreeMap<String, BTreeMap<String, String>>;
+
+pub(super) async fn snapshot(props: &PropertyStore) -> Snapshot {
+    props
+        .lock()
+        .await
+        .iter()
+        .map(|(group, values)| {
+            let sorted = values.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            (group.clone(), sorted)
+        })
+        .collect()
+}
+
+/// `value`, followed by its display name when it has one, e.g.
+/// "awareness (Awareness)". The raw value comes first so it can be pasted
+/// back into `group.prop=value`.
+fn show(group: &str, key: &str, value: &str) -> String {
+    match labels::for_property(group, key, value) {
+        Some(label) if label != value => format!("{} ({})", value, label),
+        _ => value.to_string(),
+    }
+}
+
+fn to_json(snap: &Snapshot) -> String {
+    serde_json::to_string(snap).expect("snapshot serializes")
+}
+
+fn print_tree(snap: &Snapshot) {
+    for (group, values) in snap {
+        println!("{}", group);
+        for (key, value) in values {
+            println!("  {} = {}", key, show(group, key, value));
+        }
+    }
+}
+
+/// One property that appeared, changed or went away between two snapshots.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Change<'a> {
+    pub group: &'a str,
+    pub key: &'a str,
+    pub old: Option<&'a str>,
+    pub new: Option<&'a str>,
+}
+
+/// Properties that differ between two snapshots, by group.
+pub(super) fn changes<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Vec<Change<'a>> {
+    static EMPTY: BTreeMap<String, String> = BTreeMap::new();
+    let groups: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut out = Vec::new();
+    for group in groups {
+        let before = old.get(group).unwrap_or(&EMPTY);
+        let after = new.get(group).unwrap_or(&EMPTY);
+        for (key, value) in after {
+            let prev = before.get(key).map(String::as_str);
+            if prev != Some(value.as_str()) {
+                out.push(Change {
+                    group,
+                    key,
+                    old: prev,
+                    new: Some(value),
+                });
+            }
+        }
+        for (key, value) in before.iter().filter(|(k, _)| !after.contains_key(*k)) {
+            out.push(Change {
+                group,
+                key,
+                old: Some(value),
+                new: None,
+            });
+        }
+    }
+    out
+}
+
+/// Print what changed between two snapshots, one line per property.
+pub(super) fn print_changes(old: &Snapshot, new: &Snapshot) {
+    for Change { group, key, old, new } in changes(old, new) {
+        match (old, new) {
+            (None, Some(value)) => println!("+ {}.{} = {}", group, key, show(group, key, value)),
+            (Some(prev), Some(value)) => println!(
+                "~ {}.{}: {} -> {}",
+                group,
+                key,
+                show(group, key, prev),
+                show(group, key, value)
+            ),
+            _ => println!("- {}.{}", group, key),
+        }
+    }
+}
+
+async fn apply(tx: &PropertySender, assignment: &str) -> Result<()> {
+    let Some((group, prop, value)) = parse_assignment(assignment) else {
+        bail!("expected group.prop=value, got {:?}", assignment);
+    };
+    request::request(tx, group, prop, value).await?;
+    println!("set {}.{} = {}", group, prop, value);
+    Ok(())
+}
+
+/// Run the subcommand: apply `--set` assignments, dump, and optionally watch.
+pub async fn run(
+    props: PropertyStore,
+    tx: PropertySender,
+    set: Vec<String>,
+    watch: bool,
+    json: bool,
+) -> Result<()> {
+    // A JSON stream reports the connection state too, so it starts at once
+    let stream = json && watch && set.is_empty();
+    if !stream {
+        wait_connected(&props).await?;
+        tokio::time::sleep(SETTLE_TIME).await;
+    }
+
+    let mut failed = false;
+    for assignment in &set {
+        if let Err(e) = apply(&tx, assignment).await {
+            eprintln!("{}: {}", assignment, e);
+            failed = true;
+        }
+    }
+    if !set.is_empty() {
+        // Let the device answer the re-reads the writes triggered
+        tokio::time::sleep(SETTLE_TIME).await;
+    }
+
+    let mut last = snapshot(&props).await;
+    if json {
+        println!("{}", to_json(&last));
+    } else {
+        print_tree(&last);
+    }
+    if !watch {
+        if failed {
+            bail!("some properties could not be set");
+        }
+        return Ok(());
+    }
+
+    if !json {
+        eprintln!("Watching for changes; type group.prop=value to set, Ctrl-D to quit");
+    }
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let current = snapshot(&props).await;
+                if !json {
+                    print_changes(&last, &current);
+                } else if current != last {
+                    println!("{}", to_json(&current));
+                }
+                last = current;
+            }
+            line = stdin.next_line(), if stdin_open => match line? {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => {
+                    if let Err(e) = apply(&tx, &line).await {
+                        eprintln!("{}", e);
+                    }
+                }
+                // Status bars run the stream without input
+                None if json => stdin_open = false,
+                None => return Ok(()),
+            },
+        }
+    }
+}
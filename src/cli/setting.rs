@@ -0,0 +1,150 @@
+//! `mybuds anc` and `mybuds eq`: read or change one setting.
+//!
+//! Without an action the current value is printed, raw value first so it
+//! can be passed back to `set`. `set` takes a value as the device lists it
+//! in its options, its display name ("Noise Cancelling") or, for EQ
+//! presets, the name without the `equalizer_preset_` prefix ("hardbass").
+
+use anyhow::{bail, Result};
+
+use super::{wait_connected, wait_for};
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertySender};
+use crate::labels;
+
+/// A setting with a fixed list of values.
+pub struct Setting {
+    /// Name in messages.
+    pub name: &'static str,
+    pub group: &'static str,
+    pub prop: &'static str,
+    /// Property listing the values the device accepts.
+    pub options: &'static str,
+    /// Prefix the values share, which may be left out on the command line.
+    pub prefix: &'static str,
+}
+
+pub const ANC: Setting = Setting {
+    name: "ANC mode",
+    group: "anc",
+    prop: "mode",
+    options: "mode_options",
+    prefix: "",
+};
+
+pub const EQUALIZER: Setting = Setting {
+    name: "Equalizer preset",
+    group: "sound",
+    prop: "equalizer_preset",
+    options: "equalizer_preset_options",
+    prefix: "equalizer_preset_",
+};
+
+impl Setting {
+    fn label(&self, value: &str) -> String {
+        labels::for_property(self.group, self.prop, value).unwrap_or_else(|| value.to_string())
+    }
+
+    /// The option `input` names, if any.
+    fn resolve<'a>(&self, options: &'a [String], input: &str) -> Option<&'a str> {
+        let input = input.trim();
+        let by = |matches: &dyn Fn(&str) -> bool| {
+            options.iter().map(String::as_str).find(|o| matches(o))
+        };
+        by(&|o| o == input)
+            .or_else(|| by(&|o| o.strip_prefix(self.prefix) == Some(input)))
+            .or_else(|| by(&|o| self.label(o).eq_ignore_ascii_case(input)))
+    }
+}
+
+/// Print the current value.
+pub async fn get(props: PropertyStore, setting: &Setting) -> Result<()> {
+    wait_connected(&props).await?;
+    let value = wait_for(&props, |s| s.get(setting.group)?.get(setting.prop).cloned()).await;
+    let Some(value) = value else {
+        bail!(
+            "The device didn't report its {}",
+            setting.name.to_lowercase()
+        );
+    };
+    let label = setting.label(&value);
+    if label == value {
+        println!("{}", value);
+    } else {
+        println!("{} ({})", value, label);
+    }
+    Ok(())
+}
+
+/// Change the value to what `input` names.
+pub async fn set(
+    props: PropertyStore,
+    tx: PropertySender,
+    setting: &Setting,
+    input: &str,
+) -> Result<()> {
+    wait_connected(&props).await?;
+    let options = wait_for(&props, |s| {
+        let list = s.get(setting.group)?.get(setting.options)?;
+        Some(
+            list.split(',')
+                .filter(|o| !o.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        )
+    })
+    .await;
+    let Some(options) = options else {
+        bail!("This device has no {} setting", setting.name.to_lowercase());
+    };
+    let Some(value) = setting.resolve(&options, input) else {
+        let names: Vec<String> = options
+            .iter()
+            .map(|o| format!("{} ({})", o, setting.label(o)))
+            .collect();
+        bail!(
+            "Unknown {} {:?}; choose from: {}",
+            setting.name.to_lowercase(),
+            input,
+            names.join(", ")
+        );
+    };
+    request::request(&tx, setting.group, setting.prop, value).await?;
+    println!("{} \u{2192} {}", setting.name, setting.label(value));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_values_names_and_labels() {
+        let presets: Vec<String> = ["equalizer_preset_default", "equalizer_preset_hardbass"]
+            .map(String::from)
+            .into();
+        assert_eq!(
+            EQUALIZER.resolve(&presets, "hardbass"),
+            Some("equalizer_preset_hardbass")
+        );
+        assert_eq!(
+            EQUALIZER.resolve(&presets, "bass boost"),
+            Some("equalizer_preset_hardbass")
+        );
+        assert_eq!(
+            EQUALIZER.resolve(&presets, "equalizer_preset_default"),
+            Some("equalizer_preset_default")
+        );
+        assert_eq!(EQUALIZER.resolve(&presets, "treble"), None);
+
+        let modes: Vec<String> = ["normal", "cancellation", "awareness"]
+            .map(String::from)
+            .into();
+        assert_eq!(ANC.resolve(&modes, "cancellation"), Some("cancellation"));
+        assert_eq!(
+            ANC.resolve(&modes, "Noise Cancelling"),
+            Some("cancellation")
+        );
+        assert_eq!(ANC.resolve(&modes, "off"), Some("normal"));
+    }
+}
@@ -0,0 +1,52 @@
+//! `mybuds snapshot` / `mybuds restore`: save and replay device settings.
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+use super::wait_connected;
+use crate::device::handler::PropertyStore;
+use crate::device::request::PropertySender;
+use crate::device::snapshot::SettingsSnapshot;
+
+/// Time for handlers to answer their init reads before capturing.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+pub async fn save(props: PropertyStore) -> Result<()> {
+    wait_connected(&props).await?;
+    tokio::time::sleep(SETTLE_TIME).await;
+
+    let snapshot = SettingsSnapshot::capture(&*props.lock().await);
+    if snapshot.settings.is_empty() {
+        bail!("The device reported no settings to save");
+    }
+    snapshot.save()?;
+    println!(
+        "Saved {} settings to {}",
+        snapshot.settings.len(),
+        SettingsSnapshot::path().display()
+    );
+    Ok(())
+}
+
+pub async fn restore(props: PropertyStore, tx: PropertySender) -> Result<()> {
+    let snapshot = SettingsSnapshot::load()?;
+    wait_connected(&props).await?;
+    tokio::time::sleep(SETTLE_TIME).await;
+
+    let report = snapshot.restore(&tx).await;
+    for (prop, reason) in &report.failed {
+        eprintln!("{}: {}", prop, reason);
+    }
+    println!(
+        "Restored {} of {} settings from {}",
+        report.applied,
+        snapshot.settings.len(),
+        if snapshot.device.is_empty() {
+            "snapshot"
+        } else {
+            &snapshot.device
+        }
+    );
+    Ok(())
+}
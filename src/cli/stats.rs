@@ -0,0 +1,82 @@
+//! `mybuds stats`: print the session statistics.
+//!
+//! Most useful attached to a running session (GUI or daemon), whose stats
+//! cover its whole lifetime. Run on its own it has to connect first and
+//! reports on that short session.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::wait_connected;
+use crate::bluetooth::stats::{self, STATS_GROUP};
+use crate::device::handler::PropertyStore;
+use crate::labels;
+
+pub async fn run(props: PropertyStore) -> Result<()> {
+    // A session that was connected before has stats even while disconnected
+    if !props.lock().await.contains_key(STATS_GROUP) {
+        wait_connected(&props).await?;
+    }
+    let group = props.lock().await.get(STATS_GROUP).cloned().unwrap_or_default();
+    print!("{}", render(&group));
+    Ok(())
+}
+
+fn render(group: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    for (label, value) in stats::summary(group) {
+        out += &format!("{:<16}{}\n", label, value);
+    }
+    let shares = stats::anc_distribution(group);
+    if !shares.is_empty() {
+        out += "ANC modes\n";
+        for (mode, share) in shares {
+            let secs = group
+                .get(&format!("{}{}", stats::ANC_TIME_PREFIX, mode))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            out += &format!(
+                "  {:<20}{:>3.0}%  {}\n",
+                labels::anc_mode(&mode),
+                share * 100.0,
+                stats::format_duration(secs)
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_summary_and_anc_modes() {
+        let group: HashMap<String, String> = [
+            ("connected_secs", "3900"),
+            ("reconnects", "2"),
+            ("packets_sent", "40"),
+            ("bytes_sent", "900"),
+            ("packets_received", "75"),
+            ("bytes_received", "4096"),
+            ("anc_secs:cancellation", "300"),
+            ("anc_secs:awareness", "100"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        assert_eq!(
+            render(&group),
+            "Connected       1h 05m\n\
+             Reconnects      2\n\
+             Sent            40 packets, 900 B\n\
+             Received        75 packets, 4.0 KiB\n\
+             Battery Drain   Not enough data yet\n\
+             ANC modes\n  \
+             Noise Cancelling     75%  5m 00s\n  \
+             Awareness            25%  1m 40s\n"
+        );
+    }
+}
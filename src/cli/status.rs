@@ -0,0 +1,157 @@
+//! `mybuds status` and `mybuds battery`: the device state at a glance.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::{wait_connected, wait_for};
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::device::handler::PropertyStore;
+use crate::device::DEVICE_KEY;
+use crate::labels;
+
+type Store = HashMap<String, HashMap<String, String>>;
+
+/// Battery levels as "label level% (charging)", buds first. The global
+/// level only when no bud reports one (neckbands, single-level models).
+fn battery_lines(battery: &HashMap<String, String>) -> Vec<(&'static str, String)> {
+    let entry = |label: &'static str, key: &str| {
+        let level = battery.get(key)?.parse::<u8>().ok().filter(|&l| l > 0)?;
+        let charging = battery
+            .get(&format!("{}_charging", key))
+            .is_some_and(|v| v == "true");
+        let suffix = if charging { " (charging)" } else { "" };
+        Some((label, format!("{}%{}", level, suffix)))
+    };
+    let mut lines: Vec<_> = [("Left", "left"), ("Right", "right")]
+        .into_iter()
+        .filter_map(|(label, key)| entry(label, key))
+        .collect();
+    if lines.is_empty() {
+        lines.extend(entry("Battery", "global"));
+    }
+    lines.extend(entry("Case", "case"));
+    lines
+}
+
+/// Wait for the first battery report after connecting.
+async fn wait_battery(props: &PropertyStore) -> Result<()> {
+    wait_connected(props).await?;
+    wait_for(props, |s| {
+        s.get("battery").filter(|b| !b.is_empty()).map(|_| ())
+    })
+    .await;
+    Ok(())
+}
+
+fn render_status(store: &Store) -> String {
+    let mut rows: Vec<(&str, String)> = Vec::new();
+    let info = store.get("info");
+    let connection = store.get(CONNECTION_GROUP);
+    if let Some(name) = info.and_then(|i| i.get("device_name").or_else(|| i.get("device_model"))) {
+        let address = connection.and_then(|c| c.get(DEVICE_KEY));
+        rows.push(match address {
+            Some(address) => ("Device", format!("{} ({})", name, address)),
+            None => ("Device", name.clone()),
+        });
+    }
+    rows.push((
+        "Connection",
+        ConnectionState::from_group(connection).label().to_string(),
+    ));
+    if let Some(battery) = store.get("battery") {
+        rows.extend(battery_lines(battery));
+    }
+    for (label, group, prop) in [
+        ("ANC", "anc", "mode"),
+        ("Equalizer", "sound", "equalizer_preset"),
+        ("Sound quality", "sound", "quality_preference"),
+    ] {
+        if let Some(value) = store.get(group).and_then(|g| g.get(prop)) {
+            let name = labels::for_property(group, prop, value).unwrap_or_else(|| value.clone());
+            rows.push((label, name));
+        }
+    }
+    rows.iter()
+        .map(|(label, value)| format!("{:<16}{}\n", label, value))
+        .collect()
+}
+
+pub async fn status(props: PropertyStore) -> Result<()> {
+    wait_battery(&props).await?;
+    wait_for(&props, |s| s.get("anc")?.get("mode").map(|_| ())).await;
+    print!("{}", render_status(&*props.lock().await));
+    Ok(())
+}
+
+pub async fn battery(props: PropertyStore) -> Result<()> {
+    wait_battery(&props).await?;
+    let battery = props
+        .lock()
+        .await
+        .get("battery")
+        .cloned()
+        .unwrap_or_default();
+    let lines = battery_lines(&battery);
+    if lines.is_empty() {
+        anyhow::bail!("The device didn't report its battery level");
+    }
+    for (label, value) in lines {
+        println!("{:<8}{}", label, value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn renders_the_device_state() {
+        let store: Store = HashMap::from([
+            ("info".into(), group(&[("device_name", "FreeBuds Pro 3")])),
+            (
+                CONNECTION_GROUP.into(),
+                group(&[("state", "connected"), (DEVICE_KEY, "AA:BB:CC:DD:EE:FF")]),
+            ),
+            (
+                "battery".into(),
+                group(&[
+                    ("global", "70"),
+                    ("left", "80"),
+                    ("right", "70"),
+                    ("case", "40"),
+                    ("case_charging", "true"),
+                ]),
+            ),
+            ("anc".into(), group(&[("mode", "cancellation")])),
+            (
+                "sound".into(),
+                group(&[("equalizer_preset", "equalizer_preset_hardbass")]),
+            ),
+        ]);
+        assert_eq!(
+            render_status(&store),
+            "Device          FreeBuds Pro 3 (AA:BB:CC:DD:EE:FF)\n\
+             Connection      Connected\n\
+             Left            80%\n\
+             Right           70%\n\
+             Case            40% (charging)\n\
+             ANC             Noise Cancelling\n\
+             Equalizer       Bass Boost\n"
+        );
+
+        let neckband = group(&[("global", "55"), ("left", "0")]);
+        assert_eq!(
+            battery_lines(&neckband),
+            vec![("Battery", "55%".to_string())]
+        );
+    }
+}
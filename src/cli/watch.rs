@@ -0,0 +1,102 @@
+//! `mybuds watch`: print property changes until interrupted.
+//!
+//! Unlike `props --watch` it doesn't wait for the device or dump the store
+//! first, so connection state changes are reported too. With `--json` each
+//! change is one JSON object per line, for scripts and test tooling:
+//!
+//! ```text
+//! {"group":"anc","key":"mode","old":"cancellation","new":"awareness","timestamp":1760000000.123}
+//! ```
+//!
+//! `old` is null for a property that appeared, `new` for one that went
+//! away. Changes are found by comparing the store every
+//! [`WATCH_INTERVAL`](super::props::WATCH_INTERVAL), so a value that flips
+//! and flips back in between is not reported.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::props::{changes, print_changes, snapshot, Change, WATCH_INTERVAL};
+use crate::device::handler::PropertyStore;
+
+#[derive(Serialize)]
+struct Event<'a> {
+    group: &'a str,
+    key: &'a str,
+    old: Option<&'a str>,
+    new: Option<&'a str>,
+    timestamp: f64,
+}
+
+/// One change as a JSON line.
+fn to_json(change: &Change, timestamp: f64) -> String {
+    let event = Event {
+        group: change.group,
+        key: change.key,
+        old: change.old,
+        new: change.new,
+        timestamp,
+    };
+    serde_json::to_string(&event).expect("event serializes")
+}
+
+/// Unix time in seconds, to the millisecond.
+fn now() -> f64 {
+    let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since.as_millis() as f64 / 1000.0
+}
+
+/// Run the subcommand until Ctrl-C.
+pub async fn run(props: PropertyStore, json: bool) -> Result<()> {
+    let mut last = snapshot(&props).await;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        let current = snapshot(&props).await;
+        if json {
+            let timestamp = now();
+            for change in changes(&last, &current) {
+                println!("{}", to_json(&change, timestamp));
+            }
+        } else {
+            print_changes(&last, &current);
+        }
+        last = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::props::Snapshot;
+
+    fn snap(entries: &[(&str, &str, &str)]) -> Snapshot {
+        let mut snap = Snapshot::new();
+        for (group, key, value) in entries {
+            snap.entry(group.to_string())
+                .or_default()
+                .insert(key.to_string(), value.to_string());
+        }
+        snap
+    }
+
+    #[test]
+    fn reports_each_change_as_json() {
+        let old = snap(&[("anc", "mode", "cancellation"), ("battery", "case", "80")]);
+        let new = snap(&[("anc", "mode", "awareness"), ("battery", "left", "90")]);
+        let lines: Vec<_> = changes(&old, &new).iter().map(|c| to_json(c, 12.5)).collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"group":"anc","key":"mode","old":"cancellation","new":"awareness","timestamp":12.5}"#,
+                r#"{"group":"battery","key":"left","old":null,"new":"90","timestamp":12.5}"#,
+                r#"{"group":"battery","key":"case","old":"80","new":null,"timestamp":12.5}"#,
+            ]
+        );
+    }
+}
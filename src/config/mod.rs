@@ -1,15 +1,365 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Tray title used when the config doesn't set one.
+pub const DEFAULT_TRAY_TITLE: &str = "{name} - {global}%";
 
 /// Application configuration stored as TOML.
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
     /// Selected device Bluetooth address.
     pub device_address: Option<String>,
     /// Selected device name.
     pub device_name: Option<String>,
+    /// Desktop Do Not Disturb integration.
+    pub focus_mode: FocusModeConfig,
+    /// Share battery and ANC state with a phone paired in KDE Connect.
+    pub kde_connect: KdeConnectConfig,
+    /// Ask Huawei's update server whether newer firmware exists (sends the
+    /// device model and firmware version, so off unless enabled).
+    pub check_firmware: bool,
+    /// Take Dual Connect audio back when this PC starts playing.
+    pub auto_reclaim_audio: bool,
+    /// Remix audio to mono and turn off the stored bud's ANC switch while
+    /// only one bud is in use.
+    pub single_bud_mode: bool,
+    /// Alert tones played through the buds.
+    pub alerts: AlertsConfig,
+    /// Desktop notifications about the device.
+    pub notifications: NotificationsConfig,
+    /// Settings lowered to save battery when a bud runs low.
+    pub battery_saver: BatterySaverConfig,
+    /// Gesture notification packets to forward as events, keyed by
+    /// command ID in hex (e.g. "2B5A" = "assistant").
+    pub gesture_events: HashMap<String, String>,
+    /// Command (program and arguments) run on the `assistant` gesture event.
+    pub assistant_command: Vec<String>,
+    /// Gesture event that toggles the default microphone's mute.
+    pub mic_mute_event: Option<String>,
+    /// Extra rules mapping device names/addresses to profiles.
+    pub device_rules: Vec<DeviceRule>,
+    /// Feature switches by profile or device name, for firmware that
+    /// differs from what the built-in profile expects.
+    ///
+    /// ```toml
+    /// [overrides."HUAWEI FreeBuds 5i"]
+    /// triple_tap = false
+    /// dynamic_anc = true
+    /// ```
+    pub overrides: HashMap<String, HashMap<String, bool>>,
+    /// Fetch the latest model database from the project's repository at
+    /// startup (at most daily; nothing about the user is sent).
+    pub model_db_sync: bool,
+    /// Serve the varlink interface (`mybuds.varlink` next to the control
+    /// socket) for integrations that want a typed API.
+    pub varlink: bool,
+    /// Never send setting changes to the device: writes are logged and
+    /// dropped. Also `--read-only` on the command line.
+    pub read_only: bool,
+    /// Seconds between periodic re-reads, by handler id (e.g. "battery").
+    /// 0 turns refreshing off for that handler.
+    pub refresh_intervals: HashMap<String, u64>,
+    /// Tray title and tooltip while connected, with placeholders such as
+    /// `{name}`, `{left}`, `{right}`, `{case}`, `{global}` and `{anc}`.
+    pub tray_title: String,
+    /// Suggest ANC schedules from the local history of mode switches.
+    pub anc_suggestions: bool,
+    /// ANC modes switched to automatically at set times.
+    pub anc_schedules: Vec<AncSchedule>,
+    /// Two ANC modes (e.g. `["normal", "cancellation"]`) that a middle
+    /// click on the tray and `mybuds anc-toggle` switch between.
+    pub favorite_anc_modes: Vec<String>,
+    /// HTTP endpoints receiving device events as JSON.
+    pub webhooks: Vec<WebhookConfig>,
+    /// Report the buds' battery to BlueZ, which UPower and the desktop
+    /// power panels read.
+    pub upower_battery: bool,
+    /// Put battery levels and the ANC mode in the window title (e.g.
+    /// "L82 R79 · ANC - MyBuds"), so taskbar entries show them.
+    pub window_title_status: bool,
+    /// Reconnect AirPods as soon as their case is opened nearby. Runs a BLE
+    /// scan while disconnected, so off unless enabled.
+    pub watch_airpods_case: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            device_address: None,
+            device_name: None,
+            focus_mode: FocusModeConfig::default(),
+            kde_connect: KdeConnectConfig::default(),
+            check_firmware: false,
+            auto_reclaim_audio: false,
+            single_bud_mode: false,
+            alerts: AlertsConfig::default(),
+            notifications: NotificationsConfig::default(),
+            battery_saver: BatterySaverConfig::default(),
+            gesture_events: HashMap::new(),
+            assistant_command: Vec::new(),
+            mic_mute_event: None,
+            device_rules: Vec::new(),
+            overrides: HashMap::new(),
+            model_db_sync: false,
+            varlink: false,
+            read_only: false,
+            refresh_intervals: HashMap::new(),
+            tray_title: DEFAULT_TRAY_TITLE.to_string(),
+            anc_suggestions: true,
+            anc_schedules: Vec::new(),
+            favorite_anc_modes: Vec::new(),
+            webhooks: Vec::new(),
+            upower_battery: false,
+            window_title_status: false,
+            watch_airpods_case: false,
+        }
+    }
+}
+
+/// Focus mode: what to do while the desktop is in Do Not Disturb.
+///
+/// ```toml
+/// [focus_mode]
+/// enabled = true
+/// anc_mode = "cancellation"
+/// eq_preset = "equalizer_preset_voices"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FocusModeConfig {
+    /// Watch the desktop DND state at all.
+    pub enabled: bool,
+    /// ANC mode to switch to while DND is on (e.g. "cancellation").
+    pub anc_mode: Option<String>,
+    /// EQ preset label to switch to while DND is on.
+    pub eq_preset: Option<String>,
+    /// Don't raise MyBuds notifications while DND is on.
+    pub suppress_notifications: bool,
+}
+
+impl Default for FocusModeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            anc_mode: None,
+            eq_preset: None,
+            suppress_notifications: true,
+        }
+    }
+}
+
+/// Phone companion through KDE Connect.
+///
+/// ```toml
+/// [kde_connect]
+/// enabled = true
+/// device = "Pixel 8"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct KdeConnectConfig {
+    /// Send battery and ANC changes to the phone.
+    pub enabled: bool,
+    /// KDE Connect name or id of the phone. Defaults to the first paired
+    /// device that is reachable.
+    pub device: Option<String>,
+}
+
+/// Alert tones played through the default audio output (the buds).
+///
+/// ```toml
+/// [alerts]
+/// enabled = true
+/// low_battery = true
+/// battery_critical = true
+/// volume = 0.4
+/// quiet_hours = "22:00-07:00"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// Play alert tones at all.
+    pub enabled: bool,
+    /// Beep once when a bud drops to `low_battery_threshold`.
+    pub low_battery: bool,
+    /// Battery percentage that counts as low.
+    pub low_battery_threshold: u8,
+    /// Beep twice when a bud is about to die and disconnect (5%).
+    pub battery_critical: bool,
+    /// Tone volume, 0.0 to 1.0.
+    pub volume: f32,
+    /// Local time range with no tones, e.g. "22:00-07:00".
+    pub quiet_hours: Option<String>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            low_battery: true,
+            low_battery_threshold: 20,
+            battery_critical: true,
+            volume: 0.5,
+            quiet_hours: None,
+        }
+    }
+}
+
+/// Desktop notifications about the device, each with an action button.
+///
+/// ```toml
+/// [notifications]
+/// low_battery = true
+/// low_battery_threshold = 15
+/// disconnected = true
+/// reconnected = true
+/// case_opened = true
+/// anc_changed = true
+/// quiet_hours = "22:00-07:00"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Notify when a bud drops to `low_battery_threshold`, offering to
+    /// switch to connectivity mode.
+    pub low_battery: bool,
+    /// Battery percentage that counts as low.
+    pub low_battery_threshold: u8,
+    /// Notify when the connection drops, offering to reconnect now.
+    pub disconnected: bool,
+    /// Notify when the connection comes back after a drop.
+    pub reconnected: bool,
+    /// Notify with a battery summary when the AirPods case is opened
+    /// nearby.
+    pub case_opened: bool,
+    /// Notify when the ANC mode is changed on the earbuds (a gesture or the
+    /// phone app), not from MyBuds.
+    pub anc_changed: bool,
+    /// Local time range, e.g. "22:00-07:00", during which connection
+    /// notifications are held and summed up once it ends.
+    pub quiet_hours: Option<String>,
+    /// Hold connection notifications the same way while the focused
+    /// window is fullscreen.
+    pub quiet_when_fullscreen: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            low_battery: false,
+            low_battery_threshold: 20,
+            disconnected: false,
+            reconnected: false,
+            case_opened: false,
+            anc_changed: false,
+            quiet_hours: None,
+            quiet_when_fullscreen: true,
+        }
+    }
+}
+
+/// Lower settings that cost battery when a bud runs low, and restore them
+/// once the buds are charged.
+///
+/// ```toml
+/// [battery_saver]
+/// enabled = true
+/// threshold = 15
+/// connectivity_mode = true
+/// anc_off = true
+/// notify = true
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BatterySaverConfig {
+    pub enabled: bool,
+    /// Battery percentage of the lowest bud that triggers the saver.
+    pub threshold: u8,
+    /// Switch the sound quality preference to connectivity priority.
+    pub connectivity_mode: bool,
+    /// Turn ANC off.
+    pub anc_off: bool,
+    /// Explain the change in a desktop notification.
+    pub notify: bool,
+}
+
+impl Default for BatterySaverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 20,
+            connectivity_mode: true,
+            anc_off: true,
+            notify: true,
+        }
+    }
+}
+
+/// Device events POSTed as JSON to a URL, e.g. a Home Assistant webhook
+/// or an ntfy topic.
+///
+/// ```toml
+/// [[webhooks]]
+/// url = "https://ha.local/api/webhook/mybuds"
+/// events = ["connected", "battery_low", "anc_changed"]  # default: all
+/// secret = "change-me"
+/// low_battery_threshold = 20
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events to send; all of them when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Key for the HMAC-SHA256 of the body, sent as
+    /// `X-MyBuds-Signature: sha256=<hex>`.
+    pub secret: Option<String>,
+    /// Battery percentage that counts as low.
+    #[serde(default = "default_low_battery_threshold")]
+    pub low_battery_threshold: u8,
+}
+
+fn default_low_battery_threshold() -> u8 {
+    20
+}
+
+/// Switch the ANC mode at a local time on some days.
+///
+/// ```toml
+/// [[anc_schedules]]
+/// days = "weekdays"   # or "daily", "weekends", "mon,wed,fri"
+/// time = "08:30"
+/// mode = "awareness"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AncSchedule {
+    pub days: String,
+    /// "HH:MM", local time.
+    pub time: String,
+    /// ANC mode as the device names it (see `mybuds props`).
+    pub mode: String,
+}
+
+/// Use a known profile for devices it wouldn't be picked for by name.
+/// A rule needs a name pattern, an address prefix, or both.
+///
+/// ```toml
+/// [[device_rules]]
+/// name = "^Buds de Josh$"
+/// profile = "FreeBuds Pro 2"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceRule {
+    /// Regular expression matched against the Bluetooth name.
+    pub name: Option<String>,
+    /// Address prefix such as "AA:BB:CC".
+    pub address_prefix: Option<String>,
+    /// Profile name, e.g. "FreeBuds Pro 2" or "AirPods Pro".
+    pub profile: String,
 }
 
 impl AppConfig {
@@ -35,4 +385,61 @@ impl AppConfig {
         }
         Self::default()
     }
+
+    /// Store `format` as the tray title in the config file. Other keys are
+    /// kept, but comments and formatting in the file are not.
+    pub fn save_tray_title(format: &str) -> Result<()> {
+        Self::edit(|table| {
+            if format == DEFAULT_TRAY_TITLE {
+                table.remove("tray_title");
+            } else {
+                table.insert("tray_title".to_string(), format.into());
+            }
+            Ok(())
+        })
+    }
+
+    /// Store the device picked in a device picker as `device_address` and
+    /// `device_name`, so the next start connects to it; same caveats as
+    /// [`save_tray_title`](Self::save_tray_title).
+    pub fn save_device(address: &str, name: &str) -> Result<()> {
+        Self::edit(|table| {
+            table.insert("device_address".to_string(), address.into());
+            table.insert("device_name".to_string(), name.into());
+            Ok(())
+        })
+    }
+
+    /// Append `schedule` to `anc_schedules` in the config file, with the
+    /// same caveats as [`save_tray_title`](Self::save_tray_title).
+    pub fn add_anc_schedule(schedule: &AncSchedule) -> Result<()> {
+        Self::edit(|table| {
+            let schedules = table
+                .entry("anc_schedules")
+                .or_insert_with(|| toml::Value::Array(Vec::new()));
+            let toml::Value::Array(schedules) = schedules else {
+                anyhow::bail!("anc_schedules in the config is not a list");
+            };
+            schedules.push(toml::Value::try_from(schedule)?);
+            Ok(())
+        })
+    }
+
+    /// Rewrite the config file through `change`.
+    fn edit(change: impl FnOnce(&mut toml::Table) -> Result<()>) -> Result<()> {
+        let path = Self::path();
+        let mut table = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .parse::<toml::Table>()
+                .with_context(|| format!("Cannot parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+        };
+        change(&mut table)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(&table)?)
+            .with_context(|| format!("Cannot write {}", path.display()))
+    }
 }
@@ -0,0 +1,344 @@
+//! Control socket for talking to the running instance.
+//!
+//! The instance holding the lock owns the Bluetooth connection and listens
+//! on a Unix socket next to the lock file. Other invocations send it one
+//! JSON command per connection and read one JSON reply line. Besides `show`
+//! and `quit`, the socket exposes the property store and property writes,
+//! so a second UI (or a CLI command) can attach to the running session
+//! instead of opening its own connection.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::bluetooth::state::{self, ConnectionState};
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertyRequest, PropertySender, SetPropertyError};
+use crate::instance_lock::InstanceLock;
+use crate::varlink;
+
+/// How often an attached UI refreshes its copy of the property store.
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Commands understood by the running instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Raise the window.
+    Show,
+    /// Exit cleanly.
+    Quit,
+    /// Return the whole property store.
+    Props,
+    /// Write a property, as `request::request` would.
+    Set {
+        group: String,
+        prop: String,
+        value: String,
+    },
+}
+
+/// Reply to a [`ControlCommand`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum ControlReply {
+    Ok,
+    Props {
+        store: HashMap<String, HashMap<String, String>>,
+    },
+    Set {
+        result: Result<(), SetPropertyError>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// What the control server acts on.
+#[derive(Clone)]
+pub struct ControlFlags {
+    /// Set on `show`. None when the instance has no window to raise.
+    pub show_window: Option<Arc<AtomicBool>>,
+    /// Cancelled on `quit`.
+    pub shutdown: CancellationToken,
+    /// The session shared with attached clients.
+    pub props: PropertyStore,
+    pub prop_tx: PropertySender,
+    /// Also serve the varlink interface (`varlink` in the config).
+    pub varlink: bool,
+}
+
+/// Socket path, next to the instance lock.
+pub fn socket_path() -> PathBuf {
+    InstanceLock::runtime_dir().join("mybuds.sock")
+}
+
+/// Send a command to the running instance and wait for its reply.
+pub fn send(command: &ControlCommand) -> Result<ControlReply> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("No running instance listening on {}", path.display()))?;
+    stream.set_read_timeout(Some(request::REPLY_TIMEOUT + Duration::from_secs(1)))?;
+    writeln!(stream, "{}", serde_json::to_string(command)?)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    parse_reply(&reply)
+}
+
+/// Send a command that only acknowledges, failing on any other reply.
+pub fn send_expecting_ok(command: &ControlCommand) -> Result<()> {
+    match send(command)? {
+        ControlReply::Ok => Ok(()),
+        ControlReply::Error { message } => bail!("Running instance refused: {}", message),
+        other => bail!("Unexpected reply from running instance: {:?}", other),
+    }
+}
+
+/// Async version of [`send`], for use on a tokio runtime.
+async fn send_async(command: &ControlCommand) -> Result<ControlReply> {
+    let mut stream = tokio::net::UnixStream::connect(socket_path()).await?;
+    let mut line = serde_json::to_string(command)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await?;
+
+    let mut reply = String::new();
+    tokio::io::BufReader::new(stream)
+        .read_line(&mut reply)
+        .await?;
+    parse_reply(&reply)
+}
+
+fn parse_reply(line: &str) -> Result<ControlReply> {
+    if line.trim().is_empty() {
+        return Err(anyhow!("Running instance closed the connection"));
+    }
+    serde_json::from_str(line).context("Malformed reply from running instance")
+}
+
+/// Carry out a command. Shared by every API the instance serves.
+pub async fn handle(command: ControlCommand, flags: &ControlFlags) -> ControlReply {
+    match command {
+        ControlCommand::Show => match &flags.show_window {
+            Some(show) => {
+                info!("Raising window on request of another instance");
+                show.store(true, Ordering::Relaxed);
+                ControlReply::Ok
+            }
+            None => ControlReply::Error {
+                message: "no window to show".into(),
+            },
+        },
+        ControlCommand::Quit => {
+            info!("Quitting on request of another instance");
+            flags.shutdown.cancel();
+            ControlReply::Ok
+        }
+        ControlCommand::Props => ControlReply::Props {
+            store: flags.props.lock().await.clone(),
+        },
+        ControlCommand::Set { group, prop, value } => ControlReply::Set {
+            result: request::request(&flags.prop_tx, &group, &prop, &value).await,
+        },
+    }
+}
+
+async fn serve_connection(stream: tokio::net::UnixStream, flags: ControlFlags) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut line = String::new();
+    tokio::io::BufReader::new(read).read_line(&mut line).await?;
+    let reply = match serde_json::from_str::<ControlCommand>(&line) {
+        Ok(command) => handle(command, &flags).await,
+        Err(e) => ControlReply::Error {
+            message: format!("unknown command: {}", e),
+        },
+    };
+    let mut out = serde_json::to_string(&reply)?;
+    out.push('\n');
+    write.write_all(out.as_bytes()).await?;
+    Ok(())
+}
+
+/// Listen for commands on a background thread. Call only while holding the
+/// instance lock, since this replaces any stale socket file.
+pub fn serve(flags: ControlFlags) {
+    if flags.varlink {
+        listen(varlink::socket_path(), "Varlink", flags.clone(), varlink::serve_connection);
+    }
+    listen(socket_path(), "Control", flags, serve_connection);
+}
+
+/// Accept connections on `path` and hand each to `serve`, on a thread of
+/// its own, until shutdown.
+fn listen<F, Fut>(path: PathBuf, what: &'static str, flags: ControlFlags, serve: F)
+where
+    F: Fn(tokio::net::UnixStream, ControlFlags) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let _ = std::fs::remove_file(&path);
+    let listener = match std::os::unix::net::UnixListener::bind(&path)
+        .and_then(|l| l.set_nonblocking(true).map(|_| l))
+    {
+        Ok(l) => l,
+        Err(e) => {
+            warn!("{} socket unavailable at {}: {}", what, path.display(), e);
+            return;
+        }
+    };
+    debug!("{} socket listening at {}", what, path.display());
+
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => {
+                warn!("{} socket runtime failed to start: {}", what, e);
+                return;
+            }
+        };
+        rt.block_on(async move {
+            let listener = match tokio::net::UnixListener::from_std(listener) {
+                Ok(l) => l,
+                Err(e) => {
+                    warn!("{} socket unavailable: {}", what, e);
+                    return;
+                }
+            };
+            // Stop answering once the app is shutting down; the socket
+            // file goes away with the instance lock
+            let shutdown = flags.shutdown.clone();
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = shutdown.cancelled() => break,
+                };
+                let Ok((stream, _)) = accepted else {
+                    continue;
+                };
+                let connection = serve(stream, flags.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        debug!("{} connection failed: {}", what, e);
+                    }
+                });
+            }
+        });
+    });
+}
+
+/// Attach to the running instance's session.
+///
+/// Returns a local property store mirrored from the running instance and a
+/// request channel forwarded to it, so the UIs work unchanged on top. When
+/// the running instance goes away, the mirror shows it as disconnected.
+pub fn attach() -> Result<(PropertyStore, PropertySender)> {
+    let store = match send(&ControlCommand::Props)? {
+        ControlReply::Props { store } => store,
+        other => bail!("Unexpected reply from running instance: {:?}", other),
+    };
+    let props: PropertyStore = Arc::new(tokio::sync::Mutex::new(store));
+    let (prop_tx, mut prop_rx) = mpsc::channel::<PropertyRequest>(32);
+    info!(
+        "Attached to running instance at {}",
+        socket_path().display()
+    );
+
+    let mirror = props.clone();
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                warn!("Session mirror failed to start: {}", e);
+                return;
+            }
+        };
+        rt.block_on(async move {
+            let mut interval = tokio::time::interval(ATTACH_POLL_INTERVAL);
+            let mut lost = false;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => match send_async(&ControlCommand::Props).await {
+                        Ok(ControlReply::Props { store }) => {
+                            *mirror.lock().await = store;
+                            lost = false;
+                        }
+                        _ if !lost => {
+                            warn!("Lost contact with the running instance");
+                            mirror.lock().await.clear();
+                            state::publish(
+                                &mirror,
+                                ConnectionState::Disconnected,
+                                Some("MyBuds session ended".into()),
+                            )
+                            .await;
+                            lost = true;
+                        }
+                        _ => {}
+                    },
+                    req = prop_rx.recv() => {
+                        let Some(req) = req else { return };
+                        tokio::spawn(async move {
+                            let command = ControlCommand::Set {
+                                group: req.group.clone(),
+                                prop: req.prop.clone(),
+                                value: req.value.clone(),
+                            };
+                            let result = match send_async(&command).await {
+                                Ok(ControlReply::Set { result }) => result,
+                                _ => Err(SetPropertyError::NotConnected),
+                            };
+                            req.respond(result);
+                        });
+                    }
+                }
+            }
+        });
+    });
+
+    Ok((props, prop_tx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_format_round_trips() {
+        let set = ControlCommand::Set {
+            group: "anc".into(),
+            prop: "mode".into(),
+            value: "awareness".into(),
+        };
+        let line = serde_json::to_string(&set).unwrap();
+        assert_eq!(
+            line,
+            r#"{"cmd":"set","group":"anc","prop":"mode","value":"awareness"}"#
+        );
+        assert_eq!(serde_json::from_str::<ControlCommand>(&line).unwrap(), set);
+        assert_eq!(
+            serde_json::from_str::<ControlCommand>(r#"{"cmd":"show"}"#).unwrap(),
+            ControlCommand::Show
+        );
+
+        let reply = ControlReply::Set {
+            result: Err(SetPropertyError::NoHandler("anc".into())),
+        };
+        let line = serde_json::to_string(&reply).unwrap();
+        assert_eq!(parse_reply(&line).unwrap(), reply);
+        assert!(parse_reply("").is_err());
+    }
+}
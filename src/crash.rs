@@ -0,0 +1,190 @@
+//! Panic reporting and recovery.
+//!
+//! The panic hook runs registered cleanups (e.g. restoring the terminal the
+//! TUI put in raw mode), then writes a crash report with the backtrace and
+//! the last log events to ~/.local/state/mybuds/crashes/. Long-running
+//! threads can be wrapped in [`supervise`] so a panic restarts them instead
+//! of leaving the app without a connection.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::panic::{AssertUnwindSafe, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio_util::sync::CancellationToken;
+use tracing::field::{Field, Visit};
+use tracing::{error, Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Log events kept for crash reports.
+const RECENT_EVENTS: usize = 200;
+/// Restarts allowed before a supervised thread is given up on.
+const MAX_RESTARTS: u32 = 5;
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static CLEANUPS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// Tracing layer that keeps the last [`RECENT_EVENTS`] events in memory.
+pub struct RecentEvents;
+
+struct FieldsToString(String);
+
+impl Visit for FieldsToString {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RecentEvents {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = FieldsToString(String::new());
+        event.record(&mut fields);
+        let meta = event.metadata();
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let line = format!(
+            "{:.3} {} {}: {}",
+            secs,
+            meta.level(),
+            meta.target(),
+            fields.0
+        );
+
+        let Ok(mut recent) = RECENT.lock() else {
+            return;
+        };
+        if recent.len() == RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+    }
+}
+
+/// Run `cleanup` when a panic happens, before the report is written.
+pub fn on_panic(cleanup: fn()) {
+    if let Ok(mut cleanups) = CLEANUPS.lock() {
+        cleanups.push(cleanup);
+    }
+}
+
+fn crash_dir() -> PathBuf {
+    crate::logging::state_dir().join("crashes")
+}
+
+fn write_report(info: &PanicHookInfo<'_>) -> std::io::Result<PathBuf> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let thread = std::thread::current();
+
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "MyBuds {} crashed at {}",
+        env!("CARGO_PKG_VERSION"),
+        now
+    );
+    let _ = writeln!(report, "Thread: {}", thread.name().unwrap_or("<unnamed>"));
+    let _ = writeln!(report, "{}\n", info);
+    let _ = writeln!(
+        report,
+        "Backtrace:\n{}",
+        std::backtrace::Backtrace::force_capture()
+    );
+    let _ = writeln!(report, "Recent events:");
+    if let Ok(recent) = RECENT.lock() {
+        for line in recent.iter() {
+            let _ = writeln!(report, "  {}", line);
+        }
+    }
+
+    let dir = crash_dir();
+    crate::logging::create_private_dir(&dir)?;
+    let path = dir.join(format!("crash-{}.txt", now));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// Install the panic hook. Call once, after logging is set up.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Ok(cleanups) = CLEANUPS.lock() {
+            for cleanup in cleanups.iter() {
+                cleanup();
+            }
+        }
+        match write_report(info) {
+            Ok(path) => eprintln!("MyBuds crashed, report saved to {}", path.display()),
+            Err(e) => eprintln!("MyBuds crashed, and the report could not be saved: {}", e),
+        }
+        default_hook(info);
+    }));
+}
+
+/// Run `body` on the current thread, running it again if it panics, until
+/// it returns normally, `cancel` fires, or it has crashed too often.
+pub fn supervise(name: &str, cancel: &CancellationToken, mut body: impl FnMut()) {
+    let mut restarts = 0;
+    loop {
+        if std::panic::catch_unwind(AssertUnwindSafe(&mut body)).is_ok() || cancel.is_cancelled() {
+            return;
+        }
+        restarts += 1;
+        if restarts > MAX_RESTARTS {
+            error!("{} crashed {} times, giving up", name, restarts);
+            return;
+        }
+        error!(
+            "{} crashed, restarting ({}/{})",
+            name, restarts, MAX_RESTARTS
+        );
+        std::thread::sleep(RESTART_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supervise_restarts_after_panic() {
+        // Keep the expected panics out of the test output
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+
+        let cancel = CancellationToken::new();
+        let mut runs = 0;
+        supervise("test", &cancel, || {
+            runs += 1;
+            if runs < 2 {
+                panic!("first run fails");
+            }
+        });
+        assert_eq!(runs, 2);
+
+        // A panic after shutdown was requested is not retried
+        cancel.cancel();
+        let mut runs = 0;
+        supervise("test", &cancel, || {
+            runs += 1;
+            panic!("always fails");
+        });
+        assert_eq!(runs, 1);
+
+        std::panic::set_hook(hook);
+    }
+}
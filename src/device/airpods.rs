@@ -30,6 +30,12 @@ const CMD_EAR_DETECT_CONFIG: CommandId = [aap::CMD_CC_PREFIX, aap::CC_EAR_DETECT
 const CMD_ANC_STRENGTH: CommandId = [aap::CMD_CC_PREFIX, aap::CC_AUTO_ANC_STRENGTH];
 const CMD_LISTENING_CONFIGS: CommandId = [aap::CMD_CC_PREFIX, aap::CC_LISTENING_MODE_CONFIGS];
 const CMD_ONE_BUD_ANC: CommandId = [aap::CMD_CC_PREFIX, aap::CC_ONE_BUD_ANC];
+const CMD_PRESS_SPEED: CommandId = [aap::CMD_CC_PREFIX, aap::CC_DOUBLE_CLICK_INTERVAL];
+const CMD_PRESS_DURATION: CommandId = [aap::CMD_CC_PREFIX, aap::CC_CLICK_HOLD_INTERVAL];
+const CMD_TONE_VOLUME: CommandId = [aap::CMD_CC_PREFIX, aap::CC_CHIME_VOLUME];
+const CMD_SWIPE_INTERVAL: CommandId = [aap::CMD_CC_PREFIX, aap::CC_VOLUME_SWIPE_INTERVAL];
+const CMD_SWIPE_MODE: CommandId = [aap::CMD_CC_PREFIX, aap::CC_VOLUME_SWIPE_MODE];
+const CMD_OWNS_CONNECTION: CommandId = [aap::CMD_CC_PREFIX, aap::CC_OWNS_CONNECTION];
 
 // ============================================================
 // Battery handler
@@ -107,10 +113,10 @@ impl DeviceHandler for AirPodsBatteryHandler {
 
         let is_charging = out
             .get("left_charging")
-            .map_or(false, |s| s == "true")
+            .is_some_and(|s| s == "true")
             || out
                 .get("right_charging")
-                .map_or(false, |s| s == "true");
+                .is_some_and(|s| s == "true");
         out.insert("is_charging".to_string(), is_charging.to_string());
 
         put_properties(props, "battery", out).await;
@@ -143,6 +149,10 @@ impl DeviceHandler for AirPodsEarDetectionHandler {
         "ear_detection"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["ear_detection"]
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_EAR_DETECTION, CMD_EAR_DETECT_CONFIG]
     }
@@ -191,6 +201,76 @@ impl DeviceHandler for AirPodsEarDetectionHandler {
     }
 }
 
+// ============================================================
+// Auto-pause setting
+// ============================================================
+
+/// Auto-pause switch for AirPods. The buds leave pausing to the host, so
+/// this is only kept here (as `config.auto_pause`, like the Huawei device
+/// setting) for the host-side auto-pause to follow; nothing is sent.
+pub struct AirPodsAutoPauseHandler {
+    enabled: bool,
+}
+
+impl Default for AirPodsAutoPauseHandler {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl AirPodsAutoPauseHandler {
+    async fn publish(&self, props: &PropertyStore) {
+        let mut out = HashMap::new();
+        out.insert("auto_pause".to_string(), self.enabled.to_string());
+        put_properties(props, "config", out).await;
+    }
+}
+
+#[async_trait]
+impl DeviceHandler for AirPodsAutoPauseHandler {
+    fn handler_id(&self) -> &'static str {
+        "tws_auto_pause"
+    }
+
+    fn handled_groups(&self) -> &[&'static str] {
+        &["tws_auto_pause", "config"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("config")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "tws_auto_pause" || (group == "config" && prop == "auto_pause")
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &[]
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, props: &PropertyStore) -> Result<()> {
+        self.publish(props).await;
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, _packet: &HuaweiSppPacket, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn set_property(
+        &mut self,
+        _sender: &PacketSender,
+        props: &PropertyStore,
+        _group: &str,
+        _prop: &str,
+        value: &str,
+    ) -> Result<()> {
+        self.enabled = value == "true";
+        self.publish(props).await;
+        Ok(())
+    }
+}
+
 // ============================================================
 // ANC / Listening mode handler
 // ============================================================
@@ -244,6 +324,10 @@ impl DeviceHandler for AirPodsAncHandler {
         "anc"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["anc"]
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_LISTENING_MODE, CMD_LISTENING_CONFIGS, CMD_ANC_STRENGTH, CMD_ONE_BUD_ANC]
     }
@@ -270,13 +354,11 @@ impl DeviceHandler for AirPodsAncHandler {
                 out.insert("anc_strength".to_string(), strength.to_string());
                 put_properties(props, "anc", out).await;
             }
-        } else if packet.command_id == CMD_ONE_BUD_ANC {
-            if !data.is_empty() {
-                let enabled = data[0] == 0x01;
-                let mut out = HashMap::new();
-                out.insert("one_bud_anc".to_string(), enabled.to_string());
-                put_properties(props, "anc", out).await;
-            }
+        } else if packet.command_id == CMD_ONE_BUD_ANC && !data.is_empty() {
+            let enabled = data[0] == 0x01;
+            let mut out = HashMap::new();
+            out.insert("one_bud_anc".to_string(), enabled.to_string());
+            put_properties(props, "anc", out).await;
         }
 
         Ok(())
@@ -327,6 +409,10 @@ impl DeviceHandler for AirPodsConversationAwarenessHandler {
         "conversation_awareness"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["conversation_awareness"]
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_CONVERSATION_DETECT, CMD_CA_NOTIFY]
     }
@@ -392,6 +478,10 @@ impl DeviceHandler for AirPodsPersonalizedVolumeHandler {
         "personalized_volume"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["personalized_volume"]
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_ADAPTIVE_VOLUME]
     }
@@ -428,6 +518,236 @@ impl DeviceHandler for AirPodsPersonalizedVolumeHandler {
     }
 }
 
+// ============================================================
+// Press speed / press and hold duration handlers
+// ============================================================
+
+/// Setting names and wire values of the stem timing controls.
+const PRESS_SPEEDS: &[(&str, u8)] = &[("default", 0x00), ("slower", 0x01), ("slowest", 0x02)];
+const HOLD_DURATIONS: &[(&str, u8)] = &[("default", 0x00), ("shorter", 0x01), ("shortest", 0x02)];
+const SWIPE_INTERVALS: &[(&str, u8)] = &[("default", 0x00), ("longer", 0x01), ("longest", 0x02)];
+
+fn choice_name(choices: &[(&'static str, u8)], byte: u8) -> Option<&'static str> {
+    choices.iter().find(|(_, b)| *b == byte).map(|(name, _)| *name)
+}
+
+fn choice_byte(choices: &[(&str, u8)], name: &str) -> Option<u8> {
+    choices.iter().find(|(n, _)| *n == name).map(|(_, b)| *b)
+}
+
+fn choice_options(choices: &[(&str, u8)]) -> String {
+    choices.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(",")
+}
+
+/// A control command with a few named settings, published as
+/// `<group>.<prop>` with the names in `<prop>_options`.
+pub struct AirPodsChoiceHandler {
+    group: &'static str,
+    prop: &'static str,
+    identifier: u8,
+    command: CommandId,
+    choices: &'static [(&'static str, u8)],
+}
+
+impl AirPodsChoiceHandler {
+    /// Double and triple press speed (control command 0x17).
+    pub fn press_speed() -> Self {
+        Self {
+            group: "press_speed",
+            prop: "speed",
+            identifier: aap::CC_DOUBLE_CLICK_INTERVAL,
+            command: CMD_PRESS_SPEED,
+            choices: PRESS_SPEEDS,
+        }
+    }
+
+    /// Press and hold duration (control command 0x18).
+    pub fn press_duration() -> Self {
+        Self {
+            group: "press_duration",
+            prop: "duration",
+            identifier: aap::CC_CLICK_HOLD_INTERVAL,
+            command: CMD_PRESS_DURATION,
+            choices: HOLD_DURATIONS,
+        }
+    }
+}
+
+#[async_trait]
+impl DeviceHandler for AirPodsChoiceHandler {
+    fn handler_id(&self) -> &'static str {
+        self.group
+    }
+
+    fn handled_groups(&self) -> &[&'static str] {
+        std::slice::from_ref(&self.group)
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        std::slice::from_ref(&self.command)
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let data = packet.find_param(0);
+        if let Some(name) = data.first().and_then(|&b| choice_name(self.choices, b)) {
+            let mut out = HashMap::new();
+            out.insert(self.prop.to_string(), name.to_string());
+            out.insert(format!("{}_options", self.prop), choice_options(self.choices));
+            put_properties(props, self.group, out).await;
+        }
+        Ok(())
+    }
+
+    async fn set_property(
+        &mut self,
+        sender: &PacketSender,
+        _props: &PropertyStore,
+        _group: &str,
+        prop: &str,
+        value: &str,
+    ) -> Result<()> {
+        if prop == self.prop {
+            let byte_val = choice_byte(self.choices, value)
+                .ok_or_else(|| anyhow::anyhow!("Unknown {} setting: {}", self.group, value))?;
+            let pkt = build_control_command(self.identifier, byte_val);
+            sender.send(pkt).await?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================
+// Volume swipe handler
+// ============================================================
+
+/// Volume swipe on the stem: on/off (control command 0x25) and how long
+/// a swipe has to be (0x23).
+pub struct AirPodsVolumeSwipeHandler;
+
+#[async_trait]
+impl DeviceHandler for AirPodsVolumeSwipeHandler {
+    fn handler_id(&self) -> &'static str {
+        "volume_swipe"
+    }
+
+    fn handled_groups(&self) -> &[&'static str] {
+        &["volume_swipe"]
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &[CMD_SWIPE_MODE, CMD_SWIPE_INTERVAL]
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let data = packet.find_param(0);
+        let Some(&value) = data.first() else {
+            return Ok(());
+        };
+
+        let mut out = HashMap::new();
+        if packet.command_id == CMD_SWIPE_MODE {
+            out.insert("enabled".to_string(), (value == 0x01).to_string());
+        } else if let Some(name) = choice_name(SWIPE_INTERVALS, value) {
+            out.insert("interval".to_string(), name.to_string());
+            out.insert("interval_options".to_string(), choice_options(SWIPE_INTERVALS));
+        }
+        put_properties(props, "volume_swipe", out).await;
+        Ok(())
+    }
+
+    async fn set_property(
+        &mut self,
+        sender: &PacketSender,
+        _props: &PropertyStore,
+        _group: &str,
+        prop: &str,
+        value: &str,
+    ) -> Result<()> {
+        match prop {
+            "enabled" => {
+                let byte_val = if value == "true" { 0x01 } else { 0x02 };
+                let pkt = build_control_command(aap::CC_VOLUME_SWIPE_MODE, byte_val);
+                sender.send(pkt).await?;
+            }
+            "interval" => {
+                let byte_val = choice_byte(SWIPE_INTERVALS, value)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown swipe interval: {}", value))?;
+                let pkt = build_control_command(aap::CC_VOLUME_SWIPE_INTERVAL, byte_val);
+                sender.send(pkt).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// ============================================================
+// Tone volume handler
+// ============================================================
+
+/// Lowest tone volume the buds accept, in percent.
+const TONE_VOLUME_MIN: u8 = 15;
+
+/// Volume of the buds' own tones and chimes (control command 0x1F),
+/// 15-100%.
+pub struct AirPodsToneVolumeHandler;
+
+#[async_trait]
+impl DeviceHandler for AirPodsToneVolumeHandler {
+    fn handler_id(&self) -> &'static str {
+        "tone_volume"
+    }
+
+    fn handled_groups(&self) -> &[&'static str] {
+        &["tone_volume"]
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &[CMD_TONE_VOLUME]
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let data = packet.find_param(0);
+        if let Some(&level) = data.first() {
+            let mut out = HashMap::new();
+            out.insert("level".to_string(), level.to_string());
+            out.insert("level_max".to_string(), "100".to_string());
+            put_properties(props, "tone_volume", out).await;
+        }
+        Ok(())
+    }
+
+    async fn set_property(
+        &mut self,
+        sender: &PacketSender,
+        _props: &PropertyStore,
+        _group: &str,
+        prop: &str,
+        value: &str,
+    ) -> Result<()> {
+        if prop == "level" {
+            let level = value.parse::<u8>()?.clamp(TONE_VOLUME_MIN, 100);
+            // The second byte is a fixed 0x50 in captures from iOS
+            let mut pkt = HuaweiSppPacket::new(CMD_TONE_VOLUME);
+            pkt.set_param(0, vec![level, 0x50, 0x00, 0x00]);
+            sender.send(pkt).await?;
+        }
+        Ok(())
+    }
+}
+
 // ============================================================
 // Device Info handler
 // ============================================================
@@ -495,6 +815,61 @@ impl DeviceHandler for AirPodsInfoHandler {
     }
 }
 
+// ============================================================
+// Audio owner handler
+// ============================================================
+
+/// Which host the AirPods play audio for (control command 0x06).
+///
+/// The buds report `owned = false` when another Apple device has taken
+/// them over; writing `take_over` claims them back for this computer,
+/// like "Connect" on an iPhone.
+pub struct AirPodsAudioOwnerHandler;
+
+#[async_trait]
+impl DeviceHandler for AirPodsAudioOwnerHandler {
+    fn handler_id(&self) -> &'static str {
+        "audio_owner"
+    }
+
+    fn handled_groups(&self) -> &[&'static str] {
+        &["audio_owner"]
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &[CMD_OWNS_CONNECTION]
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let data = packet.find_param(0);
+        if let Some(&value) = data.first() {
+            let mut out = HashMap::new();
+            out.insert("owned".to_string(), (value == 0x01).to_string());
+            put_properties(props, "audio_owner", out).await;
+        }
+        Ok(())
+    }
+
+    async fn set_property(
+        &mut self,
+        sender: &PacketSender,
+        _props: &PropertyStore,
+        _group: &str,
+        prop: &str,
+        _value: &str,
+    ) -> Result<()> {
+        if prop == "take_over" {
+            let pkt = build_control_command(aap::CC_OWNS_CONNECTION, 0x01);
+            sender.send(pkt).await?;
+        }
+        Ok(())
+    }
+}
+
 // ============================================================
 // Helpers
 // ============================================================
@@ -502,6 +877,6 @@ impl DeviceHandler for AirPodsInfoHandler {
 /// Build a control command HuaweiSppPacket (for sending via handler).
 fn build_control_command(identifier: u8, value: u8) -> HuaweiSppPacket {
     let mut pkt = HuaweiSppPacket::new([aap::CMD_CC_PREFIX, identifier]);
-    pkt.parameters.insert(0, vec![value, 0x00, 0x00, 0x00]);
+    pkt.set_param(0, vec![value, 0x00, 0x00, 0x00]);
     pkt
 }
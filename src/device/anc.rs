@@ -83,10 +83,16 @@ impl CancelLevel {
     }
 }
 
+/// Upper bound of the continuous cancellation depth (param 3).
+pub const ANC_DEPTH_MAX: u8 = 100;
+
 /// ANC mode switching handler.
 pub struct AncHandler {
     with_cancel_levels: bool,
     with_cancel_dynamic: bool,
+    /// Read the continuous depth (param 3). No profile enables it until a
+    /// capture shows it; the `anc_depth` flag turns it on. Never written.
+    with_cancel_depth: bool,
     with_voice_boost: bool,
     active_mode: u8,
 }
@@ -96,11 +102,18 @@ impl AncHandler {
         Self {
             with_cancel_levels,
             with_cancel_dynamic,
+            with_cancel_depth: false,
             with_voice_boost,
             active_mode: 0,
         }
     }
 
+    /// ANC read request, asking for the depth only where it exists.
+    fn read_request(&self) -> HuaweiSppPacket {
+        let params: &[u8] = if self.with_cancel_depth { &[1, 2, 3] } else { &[1, 2] };
+        HuaweiSppPacket::read_request(CMD_ANC_READ, params)
+    }
+
     fn mode_options(&self) -> Vec<&'static str> {
         vec!["normal", "cancellation", "awareness"]
     }
@@ -130,6 +143,22 @@ impl DeviceHandler for AncHandler {
         "anc"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["anc"]
+    }
+
+    fn set_flag(&mut self, flag: &str, value: bool) -> bool {
+        let field = match flag {
+            "anc_levels" => &mut self.with_cancel_levels,
+            "dynamic_anc" => &mut self.with_cancel_dynamic,
+            "anc_depth" => &mut self.with_cancel_depth,
+            "voice_boost" => &mut self.with_voice_boost,
+            _ => return false,
+        };
+        *field = value;
+        true
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_ANC_READ]
     }
@@ -139,8 +168,7 @@ impl DeviceHandler for AncHandler {
     }
 
     async fn on_init(&mut self, sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
-        let pkt = HuaweiSppPacket::read_request(CMD_ANC_READ, &[1, 2]);
-        sender.send(pkt).await?;
+        sender.send(self.read_request()).await?;
         Ok(())
     }
 
@@ -185,6 +213,21 @@ impl DeviceHandler for AncHandler {
             );
         }
 
+        // Continuous depth, reported alongside the discrete level on some models
+        let depth = packet.find_param(3);
+        let has_depth = self.with_cancel_depth
+            && mode_byte == 1
+            && depth.len() == 1
+            && depth[0] <= ANC_DEPTH_MAX;
+        if has_depth {
+            new_props.insert("depth".into(), depth[0].to_string());
+            new_props.insert("depth_max".into(), ANC_DEPTH_MAX.to_string());
+        } else if let Some(group) = props.lock().await.get_mut("anc") {
+            // Drop a stale depth so the UI falls back to discrete levels
+            group.remove("depth");
+            group.remove("depth_max");
+        }
+
         put_properties(props, "anc", new_props).await;
         Ok(())
     }
@@ -197,6 +240,10 @@ impl DeviceHandler for AncHandler {
         prop: &str,
         value: &str,
     ) -> Result<()> {
+        if prop == "depth" {
+            bail!("ANC depth is read-only");
+        }
+
         let data = if prop == "mode" {
             let mode = AncMode::from_str(value)
                 .ok_or_else(|| anyhow::anyhow!("Unknown ANC mode: {}", value))?;
@@ -221,16 +268,24 @@ impl DeviceHandler for AncHandler {
             vec![self.active_mode, level_byte]
         };
 
-        let pkt = HuaweiSppPacket::write_request(CMD_ANC_WRITE, &[(1, data)]);
+        let pkt = HuaweiSppPacket::write_request(CMD_ANC_WRITE, [(1, data)]);
         sender.send(pkt).await?;
 
         // Re-read current state
-        let read_pkt = HuaweiSppPacket::read_request(CMD_ANC_READ, &[1, 2]);
-        sender.send(read_pkt).await?;
+        sender.send(self.read_request()).await?;
         Ok(())
     }
 }
 
+/// The favorite ANC mode to switch to: the second of `favorites` while the
+/// first is active, else the first. None unless exactly two are set.
+pub fn next_favorite<'a>(favorites: &'a [String], current: Option<&str>) -> Option<&'a str> {
+    let [first, second] = favorites else {
+        return None;
+    };
+    Some(if current == Some(first.as_str()) { second } else { first })
+}
+
 /// Handles legacy ANC change notifications (0x2B03) from on-device button presses.
 pub struct AncLegacyChangeHandler;
 
@@ -258,3 +313,57 @@ impl DeviceHandler for AncLegacyChangeHandler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[tokio::test]
+    async fn depth_published_only_in_cancellation_mode() {
+        let props: PropertyStore = Arc::new(Mutex::new(HashMap::new()));
+        let mut handler = AncHandler::new(true, false, false);
+        handler.set_flag("anc_depth", true);
+
+        let pkt = HuaweiSppPacket::write_request(CMD_ANC_READ, [(1, vec![1, 1]), (3, vec![40])]);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert_eq!(props.lock().await["anc"].get("depth").map(String::as_str), Some("40"));
+
+        let pkt = HuaweiSppPacket::write_request(CMD_ANC_READ, [(1, vec![0, 2]), (3, vec![40])]);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert!(!props.lock().await["anc"].contains_key("depth"));
+
+        // Models without the depth setting may use param 3 for something else
+        let mut handler = AncHandler::new(true, false, false);
+        let pkt = HuaweiSppPacket::write_request(CMD_ANC_READ, [(1, vec![1, 1]), (3, vec![40])]);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert!(!props.lock().await["anc"].contains_key("depth"));
+    }
+
+    #[tokio::test]
+    async fn depth_is_never_written() {
+        let props: PropertyStore = Arc::new(Mutex::new(HashMap::new()));
+        let (sender, mut rx) = tokio::sync::mpsc::channel(4);
+        let mut handler = AncHandler::new(true, false, false);
+        handler.set_flag("anc_depth", true);
+
+        let pkt = HuaweiSppPacket::write_request(CMD_ANC_READ, [(1, vec![1, 1]), (3, vec![40])]);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert!(handler
+            .set_property(&sender, &props, "anc", "depth", "60")
+            .await
+            .is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn favorites_alternate_and_skip_other_modes() {
+        let favorites = vec!["normal".to_string(), "cancellation".to_string()];
+        assert_eq!(next_favorite(&favorites, Some("normal")), Some("cancellation"));
+        assert_eq!(next_favorite(&favorites, Some("cancellation")), Some("normal"));
+        // From a mode that isn't a favorite, go to the first
+        assert_eq!(next_favorite(&favorites, Some("awareness")), Some("normal"));
+        assert_eq!(next_favorite(&favorites[..1], Some("normal")), None);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -7,10 +8,15 @@ use super::handler::{put_properties, DeviceHandler, PacketSender, PropertyStore}
 use crate::protocol::commands::*;
 use crate::protocol::HuaweiSppPacket;
 
+/// Per-earbud components, in the order of params 2 and 3.
+const COMPONENTS: [&str; 3] = ["left", "right", "case"];
+
 /// Battery read handler.
 ///
 /// Reads global battery percentage, per-earbud levels (left/right/case),
-/// and charging state.
+/// and charging state. A per-earbud level of 0 means the part isn't there
+/// (one bud in use, bud or case out of range) and its keys are left out, so
+/// neckbands and single buds only show what they report.
 pub struct BatteryHandler {
     /// Whether to parse per-earbud (TWS) battery data.
     with_tws: bool,
@@ -38,6 +44,11 @@ impl DeviceHandler for BatteryHandler {
         &[CMD_BATTERY_READ, CMD_BATTERY_NOTIFY]
     }
 
+    fn refresh_interval(&self) -> Option<Duration> {
+        // Some firmwares only report battery on connect
+        Some(Duration::from_secs(60))
+    }
+
     async fn on_init(&mut self, sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
         let pkt = HuaweiSppPacket::read_request(CMD_BATTERY_READ, &[1, 2, 3]);
         sender.send(pkt).await?;
@@ -56,20 +67,74 @@ impl DeviceHandler for BatteryHandler {
 
         // Param 2: per-earbud battery [left, right, case] (3 bytes)
         let per_bud = packet.find_param(2);
+        let mut absent = Vec::new();
         if per_bud.len() == 3 && self.with_tws {
-            out.insert("left".into(), per_bud[0].to_string());
-            out.insert("right".into(), per_bud[1].to_string());
-            out.insert("case".into(), per_bud[2].to_string());
+            for (key, level) in COMPONENTS.iter().zip(per_bud) {
+                if *level == 0 {
+                    absent.push(*key);
+                } else {
+                    out.insert(key.to_string(), level.to_string());
+                }
+            }
         }
 
-        // Param 3: charging state
+        // Param 3: charging state, one byte per component on TWS models
         let charging = packet.find_param(3);
         if !charging.is_empty() {
             let is_charging = charging.contains(&0x01);
             out.insert("is_charging".into(), is_charging.to_string());
         }
+        if charging.len() == 3 && self.with_tws {
+            for (key, state) in COMPONENTS.iter().zip(charging) {
+                if !absent.contains(key) {
+                    out.insert(format!("{}_charging", key), (*state == 0x01).to_string());
+                }
+            }
+        }
 
+        if !absent.is_empty() {
+            if let Some(group) = props.lock().await.get_mut("battery") {
+                // Drop what a bud or the case reported before it went away
+                for key in &absent {
+                    group.remove(*key);
+                    group.remove(&format!("{}_charging", key));
+                }
+            }
+        }
         put_properties(props, "battery", out).await;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[tokio::test]
+    async fn absent_buds_and_case_are_dropped() {
+        let props: PropertyStore = Arc::new(Mutex::new(HashMap::new()));
+        let mut handler = BatteryHandler::default();
+
+        let pkt = HuaweiSppPacket::write_request(
+            CMD_BATTERY_READ,
+            [(1, vec![70]), (2, vec![80, 60, 50]), (3, vec![0, 0, 1])],
+        );
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert_eq!(props.lock().await["battery"].get("right").map(String::as_str), Some("60"));
+
+        // Right bud back in the case, case closed out of range
+        let pkt = HuaweiSppPacket::write_request(
+            CMD_BATTERY_READ,
+            [(1, vec![80]), (2, vec![80, 0, 0]), (3, vec![0, 0, 0])],
+        );
+        handler.on_packet(&pkt, &props).await.unwrap();
+        let store = props.lock().await;
+        let battery = &store["battery"];
+        assert_eq!(battery.get("left").map(String::as_str), Some("80"));
+        for key in ["right", "right_charging", "case", "case_charging"] {
+            assert!(!battery.contains_key(key), "{} should be gone", key);
+        }
+    }
+}
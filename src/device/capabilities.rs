@@ -0,0 +1,180 @@
+//! Which features the connected model supports, for the Capabilities page.
+//!
+//! Every feature MyBuds knows about is listed with the handlers that
+//! implement it and the properties that show the device answered. A feature
+//! is supported once those properties are published, unsupported when the
+//! model's profile has no handler for it, and unknown while its handler
+//! failed to initialize or the device hasn't answered.
+
+use std::collections::HashMap;
+
+/// Property group holding each handler's init outcome (`ok` / `failed`),
+/// published by `DeviceManager::init_handlers`, or the firmware it needs
+/// (`needs_firmware:<version>`) once a firmware gate disabled it.
+pub const HANDLERS_GROUP: &str = "handlers";
+
+/// Prefix of the [`HANDLERS_GROUP`] value of a handler disabled by a
+/// firmware gate.
+pub const NEEDS_FIRMWARE: &str = "needs_firmware:";
+
+/// A feature, the handlers implementing it and the (group, key) pairs
+/// showing the device supports it; an empty key means any key.
+pub struct Feature {
+    pub name: &'static str,
+    handlers: &'static [&'static str],
+    evidence: &'static [(&'static str, &'static str)],
+}
+
+const fn feature(
+    name: &'static str,
+    handlers: &'static [&'static str],
+    evidence: &'static [(&'static str, &'static str)],
+) -> Feature {
+    Feature {
+        name,
+        handlers,
+        evidence,
+    }
+}
+
+pub const FEATURES: &[Feature] = &[
+    feature("Battery", &["battery"], &[("battery", "")]),
+    feature("Noise control", &["anc"], &[("anc", "mode")]),
+    feature("Noise control levels", &["anc"], &[("anc", "level_options")]),
+    feature("Noise control depth", &["anc"], &[("anc", "depth")]),
+    feature("Equalizer presets", &["config_eq"], &[("sound", "equalizer_preset_options")]),
+    feature("Sound quality preference", &["config_sound_quality"], &[("sound", "quality_preference")]),
+    feature("Low latency", &["low_latency"], &[("config", "low_latency")]),
+    feature("Auto-pause", &["tws_auto_pause"], &[("config", "auto_pause")]),
+    feature("Ear detection", &["in_ear", "ear_detection"], &[("wear", ""), ("ear_detection", "")]),
+    feature("Dual Connect", &["dual_connect"], &[("dual_connect", "")]),
+    feature(
+        "Tap gestures",
+        &["gesture_double", "gesture_triple"],
+        &[("action", "double_tap_options"), ("action", "triple_tap_options")],
+    ),
+    feature(
+        "Long-press gestures",
+        &["gesture_long_split"],
+        &[("action", "long_tap_options"), ("action", "noise_control_options")],
+    ),
+    feature("Swipe gesture", &["gesture_swipe"], &[("action", "swipe_gesture_options")]),
+    feature("Conversation awareness", &["conversation_awareness"], &[("conversation_awareness", "")]),
+    feature("Personalized volume", &["personalized_volume"], &[("personalized_volume", "")]),
+    feature("Volume swipe", &["volume_swipe"], &[("volume_swipe", "")]),
+    feature("Press speed", &["press_speed"], &[("press_speed", "")]),
+    feature("Press and hold duration", &["press_duration"], &[("press_duration", "")]),
+    feature("Tone volume", &["tone_volume"], &[("tone_volume", "")]),
+    feature("Audio switching", &["audio_owner"], &[("audio_owner", "")]),
+    feature("Device information", &["device_info"], &[("info", "")]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Support {
+    Supported,
+    Unsupported,
+    Unknown,
+}
+
+impl Support {
+    pub fn label(self) -> &'static str {
+        match self {
+            Support::Supported => "Supported",
+            Support::Unsupported => "Not supported",
+            Support::Unknown => "Unknown",
+        }
+    }
+}
+
+/// A feature's support and why.
+pub struct Row {
+    pub feature: &'static str,
+    pub support: Support,
+    pub reason: String,
+}
+
+fn assess(feature: &Feature, store: &HashMap<String, HashMap<String, String>>) -> Row {
+    let row = |support, reason: &str| Row {
+        feature: feature.name,
+        support,
+        reason: reason.to_string(),
+    };
+    let handlers = store.get(HANDLERS_GROUP);
+    let needs_firmware = feature.handlers.iter().find_map(|id| {
+        handlers.and_then(|h| h.get(*id))?.strip_prefix(NEEDS_FIRMWARE)
+    });
+    if let Some(version) = needs_firmware {
+        let reason = format!("Disabled: needs firmware {} or later", version);
+        return row(Support::Unsupported, &reason);
+    }
+    let answered = feature.evidence.iter().any(|&(group, key)| {
+        store
+            .get(group)
+            .is_some_and(|g| if key.is_empty() { !g.is_empty() } else { g.contains_key(key) })
+    });
+    if answered {
+        return row(Support::Supported, "Reported by the device");
+    }
+    let inits: Vec<&str> = feature
+        .handlers
+        .iter()
+        .filter_map(|id| handlers.and_then(|h| h.get(*id)).map(String::as_str))
+        .collect();
+    if inits.is_empty() {
+        row(Support::Unsupported, "Not part of this model's profile")
+    } else if inits.iter().all(|&init| init == "failed") {
+        row(Support::Unknown, "Could not query the device")
+    } else {
+        row(Support::Unknown, "No answer from the device yet")
+    }
+}
+
+/// Support of every known feature, in [`FEATURES`] order. Empty until the
+/// handlers of a connection have been initialized.
+pub fn matrix(store: &HashMap<String, HashMap<String, String>>) -> Vec<Row> {
+    if !store.contains_key(HANDLERS_GROUP) {
+        return Vec::new();
+    }
+    FEATURES.iter().map(|f| assess(f, store)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn classifies_features() {
+        let mut store = HashMap::new();
+        assert!(matrix(&store).is_empty());
+
+        store.insert(
+            HANDLERS_GROUP.to_string(),
+            group(&[
+                ("battery", "ok"),
+                ("anc", "ok"),
+                ("dual_connect", "failed"),
+                ("low_latency", "needs_firmware:1.0.0.200"),
+            ]),
+        );
+        store.insert("anc".to_string(), group(&[("mode", "cancellation")]));
+        store.insert("config".to_string(), group(&[("low_latency", "false")]));
+
+        let rows = matrix(&store);
+        assert_eq!(rows.len(), FEATURES.len());
+        let support = |name: &str| rows.iter().find(|r| r.feature == name).unwrap().support;
+        assert_eq!(support("Noise control"), Support::Supported);
+        assert_eq!(support("Noise control depth"), Support::Unknown);
+        assert_eq!(support("Battery"), Support::Unknown);
+        assert_eq!(support("Dual Connect"), Support::Unknown);
+        assert_eq!(support("Swipe gesture"), Support::Unsupported);
+        // Disabled by a firmware gate even though the device answered
+        assert_eq!(support("Low latency"), Support::Unsupported);
+    }
+}
@@ -16,6 +16,18 @@ impl DeviceHandler for AutoPauseHandler {
         "tws_auto_pause"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["tws_auto_pause", "config"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("config")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "tws_auto_pause" || (group == "config" && prop == "auto_pause")
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_AUTO_PAUSE_READ, CMD_AUTO_PAUSE_WRITE]
     }
@@ -41,17 +53,46 @@ impl DeviceHandler for AutoPauseHandler {
         &mut self,
         sender: &PacketSender,
         props: &PropertyStore,
-        group: &str,
+        _group: &str,
         prop: &str,
         value: &str,
     ) -> Result<()> {
         let byte_val = if value == "true" { 1u8 } else { 0u8 };
-        let pkt = HuaweiSppPacket::write_request(CMD_AUTO_PAUSE_WRITE, &[(1, vec![byte_val])]);
+        let pkt = HuaweiSppPacket::write_request(CMD_AUTO_PAUSE_WRITE, [(1, vec![byte_val])]);
         sender.send(pkt).await?;
 
         let mut out = HashMap::new();
         out.insert(prop.to_string(), value.to_string());
-        put_properties(props, group, out).await;
+        put_properties(props, "config", out).await;
+        Ok(())
+    }
+}
+
+/// In-ear state notifications (param 8 of 0x2B03: 1 = worn), published as
+/// `wear.in_ear` for host-side auto-pause.
+pub struct InEarHandler;
+
+#[async_trait]
+impl DeviceHandler for InEarHandler {
+    fn handler_id(&self) -> &'static str {
+        "in_ear"
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &[CMD_IN_EAR_NOTIFY]
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let data = packet.find_param(8);
+        if data.len() == 1 {
+            let mut out = HashMap::new();
+            out.insert("in_ear".into(), (data[0] == 1).to_string());
+            put_properties(props, "wear", out).await;
+        }
         Ok(())
     }
 }
@@ -65,6 +106,18 @@ impl DeviceHandler for LowLatencyHandler {
         "low_latency"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["low_latency", "config"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("config")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "low_latency" || (group == "config" && prop == "low_latency")
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_LOW_LATENCY]
     }
@@ -99,7 +152,7 @@ impl DeviceHandler for LowLatencyHandler {
         } else {
             vec![0x00]
         };
-        let pkt = HuaweiSppPacket::write_request(CMD_LOW_LATENCY, &[(1, byte_val)]);
+        let pkt = HuaweiSppPacket::write_request(CMD_LOW_LATENCY, [(1, byte_val)]);
         sender.send(pkt).await?;
 
         // Re-read after a delay (device needs time to apply)
@@ -134,6 +187,18 @@ impl DeviceHandler for SoundQualityHandler {
         "config_sound_quality"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["config_sound_quality", "sound"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("sound")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "config_sound_quality" || (group == "sound" && prop == "quality_preference")
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_SOUND_QUALITY_READ]
     }
@@ -174,7 +239,7 @@ impl DeviceHandler for SoundQualityHandler {
         let byte_val =
             quality_pref_value(value).ok_or_else(|| anyhow::anyhow!("Unknown quality pref: {}", value))?;
         let pkt =
-            HuaweiSppPacket::write_request(CMD_SOUND_QUALITY_WRITE, &[(1, vec![byte_val])]);
+            HuaweiSppPacket::write_request(CMD_SOUND_QUALITY_WRITE, [(1, vec![byte_val])]);
         sender.send(pkt).await?;
 
         // Re-read
@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
 
 use super::handler::{put_properties, DeviceHandler, PacketSender, PropertyStore};
@@ -41,7 +43,8 @@ impl DualConnectDevice {
             .copied()
             .take_while(|&b| b != 0)
             .collect::<Vec<_>>();
-        let name = String::from_utf8(name).unwrap_or_default();
+        // Names are whatever the phone calls itself; don't drop them on bad UTF-8
+        let name = String::from_utf8_lossy(&name).into_owned();
 
         let status = packet.find_param(6);
         let connected = status.first().copied().unwrap_or(0) == 1;
@@ -59,29 +62,99 @@ impl DualConnectDevice {
         })
     }
 
-    fn to_json_value(&self) -> String {
-        format!(
-            r#"{{"name":"{}","connected":{},"playing":{},"auto_connect":{}}}"#,
-            self.name, self.connected, self.playing, self.auto_connect
-        )
+    fn to_json_value(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "connected": self.connected,
+            "playing": self.playing,
+            "auto_connect": self.auto_connect,
+        })
+    }
+}
+
+/// How long to wait for the rest of an enumeration before publishing what arrived.
+const ENUMERATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most devices kept from one enumeration. The count comes from the buds,
+/// which remember far fewer, so anything beyond this is a corrupt packet.
+const MAX_DEVICES: u8 = 16;
+
+/// One in-flight device enumeration.
+#[derive(Default)]
+struct Enumeration {
+    /// Bumped on every restart so stale timeouts can tell they lost.
+    generation: u64,
+    /// Device count announced by the buds, once the first entry arrives.
+    count: Option<u8>,
+    devices: BTreeMap<u8, DualConnectDevice>,
+    done: bool,
+}
+
+impl Enumeration {
+    /// Every index below the announced count has arrived.
+    fn is_complete(&self) -> bool {
+        self.count
+            .is_some_and(|n| (0..n).all(|i| self.devices.contains_key(&i)))
     }
 }
 
 /// Dual connect handler: manages multi-device connections.
 pub struct DualConnectHandler {
     with_auto_connect: bool,
-    pending_devices: HashMap<i8, DualConnectDevice>,
-    devices_count: i8,
+    enumeration: Arc<Mutex<Enumeration>>,
+    /// Kept from init so change events can trigger a re-enumeration.
+    sender: Option<PacketSender>,
 }
 
 impl DualConnectHandler {
     pub fn new(with_auto_connect: bool) -> Self {
         Self {
             with_auto_connect,
-            pending_devices: HashMap::new(),
-            devices_count: 0,
+            enumeration: Arc::new(Mutex::new(Enumeration::default())),
+            sender: None,
         }
     }
+
+    /// Forget any partial list and ask the buds for a fresh one. If it
+    /// doesn't complete within ENUMERATION_TIMEOUT, whatever arrived is
+    /// published so the UI never waits forever.
+    async fn start_enumeration(&self, sender: &PacketSender, props: &PropertyStore) -> Result<()> {
+        let generation = {
+            let mut en = self.enumeration.lock().unwrap();
+            *en = Enumeration {
+                generation: en.generation + 1,
+                ..Default::default()
+            };
+            en.generation
+        };
+
+        let enumeration = self.enumeration.clone();
+        let props = props.clone();
+        let connection = sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ENUMERATION_TIMEOUT).await;
+            let devices = {
+                let mut en = enumeration.lock().unwrap();
+                // A closed sender means the connection ended and its
+                // properties were cleared; don't bring the group back
+                if en.generation != generation || en.done || connection.is_closed() {
+                    return;
+                }
+                tracing::warn!(
+                    "Dual connect enumeration timed out ({} of {:?} devices)",
+                    en.devices.len(),
+                    en.count
+                );
+                en.done = true;
+                en.devices.values().cloned().collect::<Vec<_>>()
+            };
+            publish_devices(&devices, &props).await;
+        });
+
+        let pkt = HuaweiSppPacket::write_request(CMD_DUAL_CONNECT_ENUMERATE, [(1, vec![])]);
+        sender.send(pkt).await?;
+        Ok(())
+    }
 }
 
 impl Default for DualConnectHandler {
@@ -96,6 +169,10 @@ impl DeviceHandler for DualConnectHandler {
         "dual_connect"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["dual_connect"]
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[
             CMD_DUAL_CONNECT_ENUMERATE,
@@ -112,18 +189,19 @@ impl DeviceHandler for DualConnectHandler {
         ]
     }
 
-    async fn on_init(&mut self, sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+    fn refresh_interval(&self) -> Option<Duration> {
+        // Connection changes of the other devices aren't always pushed
+        Some(Duration::from_secs(120))
+    }
+
+    async fn on_init(&mut self, sender: &PacketSender, props: &PropertyStore) -> Result<()> {
+        self.sender = Some(sender.clone());
+
         // Read enabled state
         let pkt = HuaweiSppPacket::read_request(CMD_DUAL_CONNECT_ENABLED_READ, &[1]);
         sender.send(pkt).await?;
 
-        // Start enumeration
-        self.pending_devices.clear();
-        self.devices_count = 0;
-        let pkt = HuaweiSppPacket::write_request(CMD_DUAL_CONNECT_ENUMERATE, &[(1, vec![])]);
-        sender.send(pkt).await?;
-
-        Ok(())
+        self.start_enumeration(sender, props).await
     }
 
     async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
@@ -140,32 +218,39 @@ impl DeviceHandler for DualConnectHandler {
 
         if packet.command_id == CMD_DUAL_CONNECT_CHANGE_EVENT {
             tracing::debug!("Dual connect change event received");
-            // State changed, clear pending devices so we re-enumerate on next update
-            self.pending_devices.clear();
-            self.devices_count = 0;
+            // Any list in flight is now stale; start over
+            if let Some(sender) = self.sender.clone() {
+                self.start_enumeration(&sender, props).await?;
+            }
             return Ok(());
         }
 
         // Enumeration response
-        if let Some(device) = DualConnectDevice::from_packet(packet, self.with_auto_connect) {
-            let dev_index = packet
-                .find_param(3)
-                .first()
-                .copied()
-                .unwrap_or(0) as i8;
-            self.devices_count = packet
-                .find_param(2)
-                .first()
-                .copied()
-                .unwrap_or(0) as i8;
-
-            self.pending_devices.insert(dev_index, device);
-
-            // Check if all devices received
-            if self.pending_devices.len() as i8 >= self.devices_count {
-                self.process_devices(props).await;
+        let devices = {
+            let mut en = self.enumeration.lock().unwrap();
+            if en.done {
+                tracing::debug!("Dual connect entry outside an enumeration, ignoring");
+                return Ok(());
             }
-        }
+            if let Some(&count) = packet.find_param(2).first() {
+                en.count = Some(count.min(MAX_DEVICES));
+            }
+            if let Some(device) = DualConnectDevice::from_packet(packet, self.with_auto_connect) {
+                let index = packet.find_param(3).first().copied().unwrap_or(0);
+                if index >= MAX_DEVICES || en.count.is_some_and(|n| index >= n) {
+                    tracing::debug!("Dual connect entry {} out of range, ignoring", index);
+                } else {
+                    // A repeated index replaces the earlier entry
+                    en.devices.insert(index, device);
+                }
+            }
+            if !en.is_complete() {
+                return Ok(());
+            }
+            en.done = true;
+            en.devices.values().cloned().collect::<Vec<_>>()
+        };
+        publish_devices(&devices, props).await;
 
         Ok(())
     }
@@ -173,7 +258,7 @@ impl DeviceHandler for DualConnectHandler {
     async fn set_property(
         &mut self,
         sender: &PacketSender,
-        _props: &PropertyStore,
+        props: &PropertyStore,
         _group: &str,
         prop: &str,
         value: &str,
@@ -182,7 +267,7 @@ impl DeviceHandler for DualConnectHandler {
             let byte_val = if value == "true" { 1u8 } else { 0u8 };
             let pkt = HuaweiSppPacket::write_request(
                 CMD_DUAL_CONNECT_ENABLED_WRITE,
-                &[(1, vec![byte_val])],
+                [(1, vec![byte_val])],
             );
             sender.send(pkt).await?;
 
@@ -191,17 +276,17 @@ impl DeviceHandler for DualConnectHandler {
             sender.send(read_pkt).await?;
 
             // Re-enumerate devices
-            self.pending_devices.clear();
-            self.devices_count = 0;
-            let enum_pkt = HuaweiSppPacket::write_request(CMD_DUAL_CONNECT_ENUMERATE, &[(1, vec![])]);
-            sender.send(enum_pkt).await?;
+            self.start_enumeration(sender, props).await?;
         } else if prop == "preferred_device" {
             let mac_bytes = hex_to_bytes(value)?;
             let pkt = HuaweiSppPacket::write_request(
                 CMD_DUAL_CONNECT_PREFERRED_WRITE,
-                &[(1, mac_bytes)],
+                [(1, mac_bytes)],
             );
             sender.send(pkt).await?;
+
+            // Re-enumerate so the new preference shows up
+            self.start_enumeration(sender, props).await?;
         } else if let Some((address, sub_prop)) = prop.split_once(':') {
             let mac_bytes = hex_to_bytes(address)?;
             let cmd_id = match (sub_prop, value) {
@@ -209,12 +294,17 @@ impl DeviceHandler for DualConnectHandler {
                 ("connected", "false") => commands::DISCONNECT,
                 ("auto_connect", "true") => commands::ENABLE_AUTO,
                 ("auto_connect", "false") => commands::DISABLE_AUTO,
-                ("name", "") => commands::UNPAIR,
+                ("unpair", "true") => commands::UNPAIR,
+                // No capture shows a rename command
+                ("name", _) => bail!(
+                    "Renaming isn't supported (use {}:unpair to forget it)",
+                    address
+                ),
                 _ => return Ok(()),
             };
             let pkt = HuaweiSppPacket::write_request(
                 CMD_DUAL_CONNECT_EXECUTE,
-                &[(cmd_id, mac_bytes)],
+                [(cmd_id, mac_bytes)],
             );
             sender.send(pkt).await?;
         }
@@ -222,34 +312,35 @@ impl DeviceHandler for DualConnectHandler {
     }
 }
 
-impl DualConnectHandler {
-    async fn process_devices(&self, props: &PropertyStore) {
-        let mut devices_json = HashMap::new();
-        let mut preferred = String::new();
-
-        for i in 0..self.devices_count {
-            if let Some(device) = self.pending_devices.get(&i) {
-                devices_json.insert(device.mac.clone(), device.to_json_value());
-                if device.preferred {
-                    preferred = device.mac.clone();
-                }
-            }
-        }
+/// Publish an enumerated device list to the `dual_connect` group.
+async fn publish_devices(devices: &[DualConnectDevice], props: &PropertyStore) {
+    let mut devices_json = serde_json::Map::new();
+    let mut preferred = String::new();
 
-        let json_str = format!(
-            "{{{}}}",
-            devices_json
-                .iter()
-                .map(|(k, v)| format!(r#""{}": {}"#, k, v))
-                .collect::<Vec<_>>()
-                .join(",")
-        );
-
-        let mut out = HashMap::new();
-        out.insert("devices".into(), json_str);
-        out.insert("preferred_device".into(), preferred);
-        put_properties(props, "dual_connect", out).await;
+    for device in devices {
+        devices_json.insert(device.mac.clone(), device.to_json_value());
+        if device.preferred {
+            preferred = device.mac.clone();
+        }
     }
+
+    let mut out = HashMap::new();
+    out.insert(
+        "devices".into(),
+        serde_json::Value::Object(devices_json).to_string(),
+    );
+    out.insert("preferred_device".into(), preferred);
+    put_properties(props, "dual_connect", out).await;
+}
+
+/// Name of the device currently playing, from a `dual_connect` property group.
+pub fn playing_device_name(group: &HashMap<String, String>) -> Option<String> {
+    let devices: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(group.get("devices")?).ok()?;
+    devices.values().find_map(|dev| {
+        let playing = dev.get("playing")?.as_bool()?;
+        playing.then(|| dev.get("name")?.as_str().map(String::from))?
+    })
 }
 
 fn hex_to_bytes(hex: &str) -> Result<Vec<u8>> {
@@ -262,3 +353,147 @@ fn hex_to_bytes(hex: &str) -> Result<Vec<u8>> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn entry(count: u8, index: u8, mac_last: u8, name: &[u8]) -> HuaweiSppPacket {
+        HuaweiSppPacket::write_request(
+            CMD_DUAL_CONNECT_ENUMERATE,
+            [
+                (2, vec![count]),
+                (3, vec![index]),
+                (4, vec![0xaa, 0xbb, 0xcc, 0xdd, 0xee, mac_last]),
+                (6, vec![1, 0]),
+                (9, name.to_vec()),
+            ],
+        )
+    }
+
+    async fn setup() -> (DualConnectHandler, mpsc::Receiver<HuaweiSppPacket>, PropertyStore) {
+        let (tx, rx) = mpsc::channel(16);
+        let props: PropertyStore = Default::default();
+        let mut handler = DualConnectHandler::default();
+        handler.on_init(&tx, &props).await.unwrap();
+        (handler, rx, props)
+    }
+
+    async fn published(props: &PropertyStore) -> Option<serde_json::Map<String, serde_json::Value>> {
+        let store = props.lock().await;
+        let json = store.get("dual_connect")?.get("devices")?;
+        serde_json::from_str(json).ok()
+    }
+
+    fn enumerate_requests(rx: &mut mpsc::Receiver<HuaweiSppPacket>) -> usize {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|p| p.command_id == CMD_DUAL_CONNECT_ENUMERATE)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn complete_enumeration_publishes_all_devices() {
+        let (mut handler, _rx, props) = setup().await;
+        handler.on_packet(&entry(2, 1, 2, b"Laptop"), &props).await.unwrap();
+        assert!(published(&props).await.is_none());
+        handler.on_packet(&entry(2, 0, 1, b"Pixel 8"), &props).await.unwrap();
+
+        let devices = published(&props).await.unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices["aabbccddee01"]["name"], "Pixel 8");
+    }
+
+    #[tokio::test]
+    async fn duplicate_indices_do_not_complete_enumeration() {
+        let (mut handler, _rx, props) = setup().await;
+        handler.on_packet(&entry(2, 0, 1, b"Pixel 8"), &props).await.unwrap();
+        handler.on_packet(&entry(2, 0, 1, b"Pixel 8"), &props).await.unwrap();
+        assert!(published(&props).await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn partial_enumeration_publishes_after_timeout() {
+        let (mut handler, _rx, props) = setup().await;
+        handler.on_packet(&entry(3, 0, 1, b"Pixel 8"), &props).await.unwrap();
+        assert!(published(&props).await.is_none());
+
+        tokio::time::sleep(ENUMERATION_TIMEOUT + Duration::from_millis(100)).await;
+        assert_eq!(published(&props).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_after_disconnect_publishes_nothing() {
+        let (mut handler, rx, props) = setup().await;
+        handler.on_packet(&entry(3, 0, 1, b"Pixel 8"), &props).await.unwrap();
+        drop(rx);
+
+        tokio::time::sleep(ENUMERATION_TIMEOUT + Duration::from_millis(100)).await;
+        assert!(published(&props).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn announced_count_is_capped() {
+        let (mut handler, _rx, props) = setup().await;
+        for index in 0..MAX_DEVICES + 4 {
+            handler.on_packet(&entry(255, index, index, b"Phone"), &props).await.unwrap();
+        }
+        assert_eq!(published(&props).await.unwrap().len(), MAX_DEVICES as usize);
+    }
+
+    #[tokio::test]
+    async fn non_utf8_names_are_kept() {
+        let (mut handler, _rx, props) = setup().await;
+        handler
+            .on_packet(&entry(1, 0, 1, b"Caf\xe9 \"phone\"\0junk"), &props)
+            .await
+            .unwrap();
+
+        let devices = published(&props).await.unwrap();
+        assert_eq!(devices["aabbccddee01"]["name"], "Caf\u{fffd} \"phone\"");
+    }
+
+    #[tokio::test]
+    async fn unpair_is_an_explicit_action() {
+        let (mut handler, mut rx, props) = setup().await;
+        enumerate_requests(&mut rx);
+        let sender = handler.sender.clone().unwrap();
+
+        // Neither a new name nor an empty one is sent to the buds
+        for name in ["Work laptop", ""] {
+            assert!(handler
+                .set_property(&sender, &props, "dual_connect", "aabbccddee01:name", name)
+                .await
+                .is_err());
+        }
+        assert!(rx.try_recv().is_err());
+
+        handler
+            .set_property(&sender, &props, "dual_connect", "aabbccddee01:unpair", "true")
+            .await
+            .unwrap();
+        let unpair = rx.try_recv().unwrap();
+        assert_eq!(unpair.command_id, CMD_DUAL_CONNECT_EXECUTE);
+        assert_eq!(unpair.find_param(commands::UNPAIR), [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn change_event_mid_enumeration_restarts_it() {
+        let (mut handler, mut rx, props) = setup().await;
+        assert_eq!(enumerate_requests(&mut rx), 1);
+        handler.on_packet(&entry(2, 0, 1, b"Pixel 8"), &props).await.unwrap();
+
+        let event = HuaweiSppPacket::new(CMD_DUAL_CONNECT_CHANGE_EVENT);
+        handler.on_packet(&event, &props).await.unwrap();
+        assert_eq!(enumerate_requests(&mut rx), 1);
+
+        // The entry from before the event doesn't count towards the new list
+        handler.on_packet(&entry(2, 1, 2, b"Laptop"), &props).await.unwrap();
+        assert!(published(&props).await.is_none());
+
+        // Only the restarted enumeration's timeout fires
+        tokio::time::sleep(ENUMERATION_TIMEOUT + Duration::from_millis(100)).await;
+        let devices = published(&props).await.unwrap();
+        assert_eq!(devices.keys().collect::<Vec<_>>(), ["aabbccddee02"]);
+    }
+}
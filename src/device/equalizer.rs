@@ -4,6 +4,7 @@ use anyhow::Result;
 use async_trait::async_trait;
 
 use super::handler::{put_properties, DeviceHandler, PacketSender, PropertyStore};
+use super::preset_labels::UNKNOWN_PREFIX;
 use crate::protocol::commands::*;
 use crate::protocol::HuaweiSppPacket;
 
@@ -65,6 +66,18 @@ impl DeviceHandler for EqualizerHandler {
         "config_eq"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["config_eq", "sound"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("sound")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "config_eq" || (group == "sound" && prop == "equalizer_preset")
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_EQUALIZER_READ]
     }
@@ -88,9 +101,10 @@ impl DeviceHandler for EqualizerHandler {
             // Rebuild preset list from device response
             let mut new_presets = Vec::new();
             for &id in available {
+                // Unnamed presets get a key per ID, for preset_labels to name
                 let name = builtin_preset_name(id)
-                    .unwrap_or("unknown")
-                    .to_string();
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("{}{}", UNKNOWN_PREFIX, id));
                 new_presets.push(PresetEntry {
                     id: id as i16,
                     label: name,
@@ -185,12 +199,11 @@ impl DeviceHandler for EqualizerHandler {
             if let Some(preset) = preset {
                 let mode_id = preset.id;
 
-                if preset.data.is_some() {
+                if let Some(data) = &preset.data {
                     // Custom mode: send full payload
-                    let data = preset.data.as_ref().unwrap();
                     let pkt = HuaweiSppPacket::write_request(
                         CMD_EQUALIZER_WRITE,
-                        &[
+                        [
                             (1, vec![mode_id as u8]),
                             (2, vec![data.len() as u8]),
                             (3, data.clone()),
@@ -203,7 +216,7 @@ impl DeviceHandler for EqualizerHandler {
                     // Built-in mode: just send ID
                     let pkt = HuaweiSppPacket::write_request(
                         CMD_EQUALIZER_WRITE,
-                        &[(1, vec![mode_id as u8])],
+                        [(1, vec![mode_id as u8])],
                     );
                     sender.send(pkt).await?;
                 }
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tracing::{debug, info, warn};
+
+use super::handler::{DeviceHandler, PacketSender, PropertyStore};
+use crate::protocol::commands::CommandId;
+use crate::protocol::HuaweiSppPacket;
+
+/// Property group holding gesture event counters.
+pub const EVENTS_GROUP: &str = "events";
+
+/// Parse a command ID written as four hex digits, e.g. "2B5A".
+pub fn parse_command_id(s: &str) -> Option<CommandId> {
+    let s = s.trim().trim_start_matches("0x");
+    if s.len() != 4 {
+        return None;
+    }
+    let value = u16::from_str_radix(s, 16).ok()?;
+    Some(value.to_be_bytes())
+}
+
+/// Forwards gesture notifications the buds send to the host as named events.
+///
+/// Which packets mean what differs per model, so the mapping comes from the
+/// `[gesture_events]` config table (command ID -> event name). Each packet
+/// bumps a counter at `events.<name>`; integrations watch the counter with
+/// an [`EventWatcher`].
+pub struct GestureEventHandler {
+    commands: Vec<CommandId>,
+    names: Vec<String>,
+}
+
+impl GestureEventHandler {
+    /// Build from the config mapping. Returns None if nothing valid is mapped.
+    pub fn from_config(map: &HashMap<String, String>) -> Option<Self> {
+        let mut commands = Vec::new();
+        let mut names = Vec::new();
+        for (id, name) in map {
+            match parse_command_id(id) {
+                Some(cmd) => {
+                    commands.push(cmd);
+                    names.push(name.clone());
+                }
+                None => warn!("Ignoring gesture event with invalid command ID {:?}", id),
+            }
+        }
+        (!commands.is_empty()).then_some(Self { commands, names })
+    }
+}
+
+#[async_trait]
+impl DeviceHandler for GestureEventHandler {
+    fn handler_id(&self) -> &'static str {
+        "gesture_events"
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &self.commands
+    }
+
+    async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let Some(idx) = self.commands.iter().position(|c| *c == packet.command_id) else {
+            return Ok(());
+        };
+        let name = &self.names[idx];
+        info!("Gesture event: {}", name);
+
+        let mut store = props.lock().await;
+        let counter = store
+            .entry(EVENTS_GROUP.to_string())
+            .or_default()
+            .entry(name.clone())
+            .or_insert_with(|| "0".into());
+        let next = counter.parse::<u64>().unwrap_or(0) + 1;
+        *counter = next.to_string();
+        Ok(())
+    }
+}
+
+/// Detects new occurrences of one named gesture event.
+pub struct EventWatcher {
+    name: String,
+    last: Option<String>,
+}
+
+impl EventWatcher {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            last: None,
+        }
+    }
+
+    /// Whether the event fired since the last call. The counter resets when
+    /// the device disconnects, so any change counts as a new event.
+    pub async fn fired(&mut self, props: &PropertyStore) -> bool {
+        let current = props
+            .lock()
+            .await
+            .get(EVENTS_GROUP)
+            .and_then(|g| g.get(&self.name))
+            .cloned();
+        if current == self.last {
+            return false;
+        }
+        let fired = current.is_some();
+        if fired {
+            debug!("Event '{}' fired", self.name);
+        }
+        self.last = current;
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mapped_packets_bump_event_counters() {
+        let props: PropertyStore = Default::default();
+        let map = HashMap::from([("2B5A".to_string(), "assistant".to_string())]);
+        let mut handler = GestureEventHandler::from_config(&map).unwrap();
+        let mut watcher = EventWatcher::new("assistant");
+
+        assert!(!watcher.fired(&props).await);
+        let pkt = HuaweiSppPacket::new([0x2B, 0x5A]);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert!(watcher.fired(&props).await);
+        assert!(!watcher.fired(&props).await);
+
+        // A disconnect clears the counter; the next event still fires
+        props.lock().await.clear();
+        assert!(!watcher.fired(&props).await);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert!(watcher.fired(&props).await);
+    }
+
+    #[test]
+    fn parses_command_ids() {
+        assert_eq!(parse_command_id("2B5A"), Some([0x2B, 0x5A]));
+        assert_eq!(parse_command_id("0x0127"), Some([0x01, 0x27]));
+        assert_eq!(parse_command_id("2B5"), None);
+        assert_eq!(parse_command_id("zz00"), None);
+    }
+}
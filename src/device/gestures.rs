@@ -46,12 +46,133 @@ fn call_action_value(name: &str) -> Option<i8> {
     }
 }
 
+/// Gestures assigned per side, as `<prefix>_left` / `<prefix>_right` in the
+/// "action" group.
+const SIDED_GESTURES: [&str; 4] = ["double_tap", "triple_tap", "long_tap", "noise_control"];
+
+/// Whether the device takes one assignment per gesture for both buds
+/// (`double_tap` rather than `double_tap_left`), as FreeClip does: its buds
+/// fit either ear and swap roles on their own.
+pub fn both_buds(actions: &HashMap<String, String>) -> bool {
+    SIDED_GESTURES.iter().any(|prefix| actions.contains_key(*prefix))
+}
+
+/// Writes that mirror the left and right gesture assignments, for
+/// left-handed users and single-bud wearers. Gestures the device reports for
+/// one side only, or with the same action on both, are left alone.
+pub fn swap_sides(actions: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut writes = Vec::new();
+    for prefix in SIDED_GESTURES {
+        let (left_key, right_key) = (format!("{}_left", prefix), format!("{}_right", prefix));
+        if let (Some(left), Some(right)) = (actions.get(&left_key), actions.get(&right_key)) {
+            if left != right {
+                writes.push((left_key, right.clone()));
+                writes.push((right_key, left.clone()));
+            }
+        }
+    }
+    writes
+}
+
+/// A gesture setup that likely isn't what the user wants, with the hint the
+/// Gestures pages show next to `gesture`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Gesture prefix the hint belongs to ("double_tap", "noise_control"),
+    /// or "" for the page as a whole.
+    pub gesture: &'static str,
+    pub hint: String,
+}
+
+/// A rule reports the conflicts it finds in the "action" group.
+type ConflictRule = fn(&HashMap<String, String>) -> Vec<Conflict>;
+
+const CONFLICT_RULES: &[ConflictRule] = &[both_sides_off, nothing_assigned, cycle_without_long_tap];
+
+const TAP_GESTURES: [(&str, &str); 3] = [
+    ("double_tap", "Double tap"),
+    ("triple_tap", "Triple tap"),
+    ("long_tap", "Long tap"),
+];
+const OFF: &str = "tap_action_off";
+
+/// Values of `prefix` on the sides the device reports, or as "both" for
+/// a single assignment covering both buds.
+fn sides<'a>(actions: &'a HashMap<String, String>, prefix: &str) -> Vec<(&'static str, &'a str)> {
+    if let Some(value) = actions.get(prefix) {
+        return vec![("both", value.as_str())];
+    }
+    ["left", "right"]
+        .into_iter()
+        .filter_map(|side| Some((side, actions.get(&format!("{}_{}", prefix, side))?.as_str())))
+        .collect()
+}
+
+/// A gesture reported for both buds and off on both does nothing.
+fn both_sides_off(actions: &HashMap<String, String>) -> Vec<Conflict> {
+    TAP_GESTURES
+        .into_iter()
+        .filter(|(prefix, _)| {
+            let values = sides(actions, prefix);
+            let covers_both = values.len() == 2 || values.iter().any(|(side, _)| *side == "both");
+            covers_both && values.iter().all(|(_, v)| *v == OFF)
+        })
+        .map(|(gesture, name)| Conflict {
+            gesture,
+            hint: format!("{} is off on both buds and does nothing", name),
+        })
+        .collect()
+}
+
+/// Every tap gesture off: the buds can't control playback or ANC at all.
+fn nothing_assigned(actions: &HashMap<String, String>) -> Vec<Conflict> {
+    let values: Vec<&str> = TAP_GESTURES
+        .into_iter()
+        .flat_map(|(prefix, _)| sides(actions, prefix))
+        .map(|(_, v)| v)
+        .chain(actions.get("swipe_gesture").map(String::as_str))
+        .collect();
+    if values.is_empty() || values.iter().any(|v| *v != OFF) {
+        return Vec::new();
+    }
+    vec![Conflict {
+        gesture: "",
+        hint: "Every gesture is off: the buds can't control playback or noise cancelling"
+            .to_string(),
+    }]
+}
+
+/// The ANC cycle runs on a long tap that switches ANC, so it has no effect
+/// on a side where the long tap is off.
+fn cycle_without_long_tap(actions: &HashMap<String, String>) -> Vec<Conflict> {
+    let long_tap = sides(actions, "long_tap");
+    sides(actions, "noise_control")
+        .into_iter()
+        .filter(|(side, _)| long_tap.iter().any(|(s, v)| s == side && *v == OFF))
+        .map(|(side, _)| Conflict {
+            gesture: "noise_control",
+            hint: format!(
+                "The {} ANC cycle has no effect while the {} long tap is off",
+                side, side
+            ),
+        })
+        .collect()
+}
+
+/// Conflicts in the gesture assignments of the "action" group.
+pub fn conflicts(actions: &HashMap<String, String>) -> Vec<Conflict> {
+    CONFLICT_RULES.iter().flat_map(|rule| rule(actions)).collect()
+}
+
 /// Generic multi-tap handler (double tap / triple tap).
 pub struct TapActionHandler {
     prop_prefix: &'static str,
     cmd_read: CommandId,
     cmd_write: CommandId,
     with_in_call: bool,
+    /// One assignment for both buds, published as `<prefix>` (see
+    /// [`both_buds`]).
+    both_buds: bool,
 }
 
 impl TapActionHandler {
@@ -61,6 +182,7 @@ impl TapActionHandler {
             cmd_read: CMD_DUAL_TAP_READ,
             cmd_write: CMD_DUAL_TAP_WRITE,
             with_in_call,
+            both_buds: false,
         }
     }
 
@@ -70,8 +192,16 @@ impl TapActionHandler {
             cmd_read: CMD_TRIPLE_TAP_READ,
             cmd_write: CMD_TRIPLE_TAP_WRITE,
             with_in_call: false,
+            both_buds: false,
         }
     }
+
+    /// Same gesture, set once for both buds (FreeClip). Writes go to both
+    /// sides; reads take the left bud's value.
+    pub fn for_both_buds(mut self) -> Self {
+        self.both_buds = true;
+        self
+    }
 }
 
 #[async_trait]
@@ -84,6 +214,30 @@ impl DeviceHandler for TapActionHandler {
         }
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        match self.prop_prefix {
+            "double_tap" => &["gesture_double", "action"],
+            _ => &["gesture_triple", "action"],
+        }
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("action")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == self.handler_id() || (group == "action" && prop.starts_with(self.prop_prefix))
+    }
+
+    fn set_flag(&mut self, flag: &str, value: bool) -> bool {
+        // double_tap_in_call
+        if flag.strip_suffix("_in_call") != Some(self.prop_prefix) {
+            return false;
+        }
+        self.with_in_call = value;
+        true
+    }
+
     fn commands(&self) -> &[CommandId] {
         // We return a static slice - need to leak or use a different pattern
         // For now, we'll match on the handler type
@@ -109,14 +263,16 @@ impl DeviceHandler for TapActionHandler {
         let left = packet.find_param(1);
         if left.len() == 1 {
             let value = left[0] as i8;
-            out.insert(
-                format!("{}_left", self.prop_prefix),
-                tap_action_name(value).to_string(),
-            );
+            let key = if self.both_buds {
+                self.prop_prefix.to_string()
+            } else {
+                format!("{}_left", self.prop_prefix)
+            };
+            out.insert(key, tap_action_name(value).to_string());
         }
 
         let right = packet.find_param(2);
-        if right.len() == 1 {
+        if right.len() == 1 && !self.both_buds {
             let value = right[0] as i8;
             out.insert(
                 format!("{}_right", self.prop_prefix),
@@ -156,16 +312,18 @@ impl DeviceHandler for TapActionHandler {
         &mut self,
         sender: &PacketSender,
         props: &PropertyStore,
-        group: &str,
+        _group: &str,
         prop: &str,
         value: &str,
     ) -> Result<()> {
-        let (p_type, is_call) = if prop.ends_with("_left") {
-            (1u8, false)
+        let (p_types, is_call): (&[u8], bool) = if prop.ends_with("_left") {
+            (&[1], false)
         } else if prop.ends_with("_right") {
-            (2u8, false)
+            (&[2], false)
         } else if prop.ends_with("_in_call") {
-            (4u8, true)
+            (&[4], true)
+        } else if self.both_buds && prop == self.prop_prefix {
+            (&[1, 2], false)
         } else {
             return Ok(());
         };
@@ -177,14 +335,16 @@ impl DeviceHandler for TapActionHandler {
         }
         .ok_or_else(|| anyhow::anyhow!("Unknown action: {}", value))?;
 
-        let pkt =
-            HuaweiSppPacket::write_request(self.cmd_write, &[(p_type, vec![byte_val as u8])]);
+        let pkt = HuaweiSppPacket::write_request(
+            self.cmd_write,
+            p_types.iter().map(|&p_type| (p_type, vec![byte_val as u8])),
+        );
         sender.send(pkt).await?;
 
         // Update local prop
         let mut out = HashMap::new();
         out.insert(prop.to_string(), value.to_string());
-        put_properties(props, group, out).await;
+        put_properties(props, "action", out).await;
         Ok(())
     }
 }
@@ -256,6 +416,30 @@ impl DeviceHandler for LongTapSplitHandler {
         "gesture_long_split"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["gesture_long_split", "action"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("action")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "gesture_long_split" || (group == "action" && (prop.starts_with("long_tap") || prop.starts_with("noise_control")))
+    }
+
+    fn set_flag(&mut self, flag: &str, value: bool) -> bool {
+        let field = match flag {
+            "long_tap_left" => &mut self.with_left,
+            "long_tap_right" => &mut self.with_right,
+            "long_tap_in_call" => &mut self.with_in_call,
+            "long_tap_anc" => &mut self.with_anc,
+            _ => return false,
+        };
+        *field = value;
+        true
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[
             CMD_LONG_TAP_SPLIT_READ_BASE,
@@ -350,7 +534,7 @@ impl DeviceHandler for LongTapSplitHandler {
         &mut self,
         sender: &PacketSender,
         props: &PropertyStore,
-        group: &str,
+        _group: &str,
         prop: &str,
         value: &str,
     ) -> Result<()> {
@@ -374,7 +558,7 @@ impl DeviceHandler for LongTapSplitHandler {
 
             let pkt = HuaweiSppPacket::write_request(
                 CMD_LONG_TAP_SPLIT_WRITE_BASE,
-                &[(p_type, vec![byte_val as u8])],
+                [(p_type, vec![byte_val as u8])],
             );
             sender.send(pkt).await?;
         } else if prop.starts_with("noise_control") {
@@ -384,14 +568,14 @@ impl DeviceHandler for LongTapSplitHandler {
 
             let pkt = HuaweiSppPacket::write_request(
                 CMD_LONG_TAP_SPLIT_WRITE_ANC,
-                &[(p_type, vec![byte_val as u8])],
+                [(p_type, vec![byte_val as u8])],
             );
             sender.send(pkt).await?;
         }
 
         let mut out = HashMap::new();
         out.insert(prop.to_string(), value.to_string());
-        put_properties(props, group, out).await;
+        put_properties(props, "action", out).await;
         Ok(())
     }
 }
@@ -421,6 +605,18 @@ impl DeviceHandler for SwipeGestureHandler {
         "gesture_swipe"
     }
 
+    fn handled_groups(&self) -> &[&'static str] {
+        &["gesture_swipe", "action"]
+    }
+
+    fn store_group(&self) -> Option<&'static str> {
+        Some("action")
+    }
+
+    fn handles_property(&self, group: &str, prop: &str) -> bool {
+        group == "gesture_swipe" || (group == "action" && prop.starts_with("swipe"))
+    }
+
     fn commands(&self) -> &[CommandId] {
         &[CMD_SWIPE_READ, CMD_SWIPE_WRITE]
     }
@@ -458,7 +654,7 @@ impl DeviceHandler for SwipeGestureHandler {
         &mut self,
         sender: &PacketSender,
         props: &PropertyStore,
-        group: &str,
+        _group: &str,
         _prop: &str,
         value: &str,
     ) -> Result<()> {
@@ -467,7 +663,7 @@ impl DeviceHandler for SwipeGestureHandler {
 
         let pkt = HuaweiSppPacket::write_request(
             CMD_SWIPE_WRITE,
-            &[
+            [
                 (1, vec![byte_val as u8]),
                 (2, vec![byte_val as u8]),
             ],
@@ -476,7 +672,100 @@ impl DeviceHandler for SwipeGestureHandler {
 
         let mut out = HashMap::new();
         out.insert("swipe_gesture".into(), value.to_string());
-        put_properties(props, group, out).await;
+        put_properties(props, "action", out).await;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swapping_sides_mirrors_each_pair() {
+        let actions: HashMap<String, String> = [
+            ("double_tap_left", "tap_action_pause"),
+            ("double_tap_right", "tap_action_next"),
+            ("triple_tap_left", "tap_action_prev"),
+            ("triple_tap_right", "tap_action_prev"),
+            ("long_tap_left", "long_tap_action_anc"),
+            ("swipe_gesture", "swipe_action_volume"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let writes = swap_sides(&actions);
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            writes,
+            vec![
+                pair("double_tap_left", "tap_action_next"),
+                pair("double_tap_right", "tap_action_pause"),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicting_gestures_get_hints() {
+        let actions = |values: &[(&str, &str)]| -> HashMap<String, String> {
+            values
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let fine = actions(&[
+            ("double_tap_left", "tap_action_pause"),
+            ("double_tap_right", OFF),
+            ("long_tap_left", "tap_action_switch_anc"),
+            ("noise_control_left", "noise_control_off_on"),
+        ]);
+        assert_eq!(conflicts(&fine), Vec::new());
+
+        let off = actions(&[
+            ("double_tap_left", OFF),
+            ("double_tap_right", OFF),
+            ("long_tap_left", OFF),
+            ("long_tap_right", "tap_action_switch_anc"),
+            ("noise_control_left", "noise_control_off_on"),
+            ("noise_control_right", "noise_control_on_aw"),
+        ]);
+        let found = conflicts(&off);
+        let gestures: Vec<&str> = found.iter().map(|c| c.gesture).collect();
+        assert_eq!(gestures, ["double_tap", "noise_control"]);
+        assert!(found[1].hint.contains("left long tap"));
+
+        let nothing = actions(&[("double_tap_left", OFF), ("swipe_gesture", OFF)]);
+        assert_eq!(conflicts(&nothing)[0].gesture, "");
+    }
+
+    #[tokio::test]
+    async fn both_buds_taps_are_set_once() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let props: PropertyStore = Default::default();
+        let mut handler = TapActionHandler::double_tap(false).for_both_buds();
+
+        let read = HuaweiSppPacket::write_request(
+            CMD_DUAL_TAP_READ,
+            [(1, vec![1]), (2, vec![2])],
+        );
+        handler.on_packet(&read, &props).await.unwrap();
+        {
+            let store = props.lock().await;
+            let action = &store["action"];
+            assert_eq!(action["double_tap"], "tap_action_pause");
+            assert!(!action.contains_key("double_tap_right"));
+            // Without a list from the buds nothing is offered
+            assert!(!action.contains_key("double_tap_options"));
+            assert!(both_buds(action));
+        }
+
+        handler
+            .set_property(&tx, &props, "action", "double_tap", "tap_action_next")
+            .await
+            .unwrap();
+        let write = rx.try_recv().unwrap();
+        assert_eq!(write.find_param(1), [2]);
+        assert_eq!(write.find_param(2), [2]);
+    }
+}
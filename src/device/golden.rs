@@ -0,0 +1,175 @@
+//! Golden tests: init conversations replayed through each profile.
+//!
+//! Each `testdata/<profile>.log` holds the packets a device sends while
+//! MyBuds connects, as `RX:` (Huawei) or `AAP → handler:` (AirPods) lines in
+//! the format of a `RUST_LOG=mybuds=debug` log; other lines are ignored.
+//! The logs in the tree are synthetic, written by hand rather than captured,
+//! and say so in their header: they pin what the handlers make of a reply,
+//! not what a device really sends. Swap in a real capture when one exists.
+//! The replay runs the profile's init, feeds every received packet through the
+//! `DeviceManager`, and compares what the handlers sent plus the resulting
+//! property store with `testdata/<profile>.snap`.
+//!
+//! After an intended change in handler output, rewrite the snapshots with
+//! `UPDATE_GOLDEN=1 cargo test golden` and review the diff.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use tokio::sync::mpsc;
+
+use super::handler::PropertyStore;
+use super::models::*;
+use super::DeviceManager;
+use crate::protocol::HuaweiSppPacket;
+
+/// Log prefixes of packets received from the device.
+const RX_MARKERS: &[&str] = &["RX: ", "AAP → handler: "];
+
+/// Parse a packet back from its `Display` form (`cmd=2B2A p1=0301`).
+fn parse_packet(text: &str) -> HuaweiSppPacket {
+    let mut fields = text.split_whitespace();
+    let cmd = fields
+        .next()
+        .and_then(|f| f.strip_prefix("cmd="))
+        .unwrap_or_else(|| panic!("No command in {:?}", text));
+    let cmd = decode_hex(cmd);
+    let mut pkt = HuaweiSppPacket::new([cmd[0], cmd[1]]);
+    for field in fields {
+        let (param, value) = field
+            .strip_prefix('p')
+            .and_then(|f| f.split_once('='))
+            .unwrap_or_else(|| panic!("Bad parameter {:?} in {:?}", field, text));
+        pkt.set_param(param.parse().expect("parameter type"), decode_hex(value));
+    }
+    pkt
+}
+
+fn decode_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("hex byte"))
+        .collect()
+}
+
+/// Received packets in a log, in order.
+fn received(log: &str) -> Vec<HuaweiSppPacket> {
+    log.lines()
+        .filter_map(|line| {
+            RX_MARKERS
+                .iter()
+                .find_map(|marker| line.split_once(marker).map(|(_, pkt)| pkt))
+        })
+        .map(parse_packet)
+        .collect()
+}
+
+fn drain_sent(rx: &mut mpsc::Receiver<HuaweiSppPacket>, out: &mut String) {
+    while let Ok(pkt) = rx.try_recv() {
+        writeln!(out, "TX: {}", pkt).unwrap();
+    }
+}
+
+/// Replay a logged conversation and render the outcome as a snapshot.
+async fn replay(profile: DeviceProfile, log: &str) -> String {
+    let props = PropertyStore::default();
+    let mut dm = DeviceManager::new(profile.handlers, props.clone());
+    let mut rx = dm.take_packet_rx().unwrap();
+
+    let mut out = String::new();
+    dm.init_handlers().await.unwrap();
+    drain_sent(&mut rx, &mut out);
+    for pkt in received(log) {
+        writeln!(out, "RX: {}", pkt).unwrap();
+        dm.handle_packet(&pkt).await;
+        drain_sent(&mut rx, &mut out);
+    }
+
+    out.push('\n');
+    let store = props.lock().await;
+    let sorted: BTreeMap<_, BTreeMap<_, _>> = store
+        .iter()
+        .map(|(group, values)| (group, values.iter().collect()))
+        .collect();
+    for (group, values) in sorted {
+        for (prop, value) in values {
+            writeln!(out, "{}.{} = {}", group, prop, value).unwrap();
+        }
+    }
+    out
+}
+
+async fn check(name: &str, profile: fn() -> DeviceProfile) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/device/testdata");
+    let log = std::fs::read_to_string(dir.join(format!("{}.log", name)))
+        .unwrap_or_else(|e| panic!("Recording for {}: {}", name, e));
+    let actual = replay(profile(), &log).await;
+
+    let snap = dir.join(format!("{}.snap", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&snap, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&snap).unwrap_or_default();
+    if actual != expected {
+        let diff: Vec<String> = diff_lines(&expected, &actual);
+        panic!(
+            "{} no longer matches {} (UPDATE_GOLDEN=1 to accept):\n{}",
+            name,
+            snap.display(),
+            diff.join("\n")
+        );
+    }
+}
+
+/// Lines only in one side, marked `-` (expected) or `+` (actual).
+fn diff_lines(expected: &str, actual: &str) -> Vec<String> {
+    let removed = expected
+        .lines()
+        .filter(|l| !actual.lines().any(|a| a == *l))
+        .map(|l| format!("- {}", l));
+    let added = actual
+        .lines()
+        .filter(|l| !expected.lines().any(|e| e == *l))
+        .map(|l| format!("+ {}", l));
+    removed.chain(added).collect()
+}
+
+#[test]
+fn parses_logged_packets() {
+    let log = "2026-01-01T00:00:00Z DEBUG mybuds::bluetooth::connection: RX: cmd=2B2A p1=0001 p2=\n\
+               DEBUG TX: cmd=0108 p1= p2=\n";
+    let pkts = received(log);
+    assert_eq!(pkts.len(), 1);
+    assert_eq!(pkts[0].command_id, [0x2B, 0x2A]);
+    assert_eq!(pkts[0].find_param(1), [0x00, 0x01]);
+    assert!(pkts[0].has_param(2) && pkts[0].find_param(2).is_empty());
+}
+
+macro_rules! golden {
+    ($($profile:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $profile() {
+                check(stringify!($profile), super::models::$profile).await;
+            }
+        )*
+    };
+}
+
+golden!(
+    freebuds_pro3,
+    freebuds_pro4,
+    freeclip,
+    freebuds_pro2,
+    freebuds_5i,
+    freebuds_6i,
+    freebuds_4i,
+    freebuds_se2,
+    generic_probe,
+    freebuds_5,
+    airpods_pro,
+    airpods_max,
+    airpods_generic,
+);
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use tokio::sync::Mutex;
+use tracing::warn;
 
 use crate::protocol::commands::CommandId;
 use crate::protocol::HuaweiSppPacket;
@@ -28,9 +30,46 @@ pub trait DeviceHandler: Send + Sync {
         &[]
     }
 
+    /// Property groups this handler accepts writes for. Empty for read-only handlers.
+    fn handled_groups(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Store group the handler publishes into, when it also takes writes
+    /// under its handler ID (e.g. "sound" for "config_eq"). Writes are
+    /// validated against that group. None when it publishes under the groups
+    /// it accepts writes for.
+    fn store_group(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Whether a write to `group.prop` belongs to this handler. Override when
+    /// several handlers share a store group and split it by property name.
+    fn handles_property(&self, group: &str, _prop: &str) -> bool {
+        self.handled_groups().contains(&group)
+    }
+
+    /// Turn one of the handler's feature flags on or off, for the
+    /// `[overrides]` config tables. Returns false for flags it doesn't have.
+    fn set_flag(&mut self, _flag: &str, _value: bool) -> bool {
+        false
+    }
+
     /// Called once after connection to fetch initial state.
     async fn on_init(&mut self, sender: &PacketSender, props: &PropertyStore) -> Result<()>;
 
+    /// How often to re-read this handler's state while connected, for
+    /// firmwares that never push updates. None (the default) never refreshes.
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Re-read the device state, called every `refresh_interval`.
+    /// Defaults to sending the same requests as `on_init`.
+    async fn on_refresh(&mut self, sender: &PacketSender, props: &PropertyStore) -> Result<()> {
+        self.on_init(sender, props).await
+    }
+
     /// Handle an incoming packet matching one of our command IDs.
     async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()>;
 
@@ -47,16 +86,101 @@ pub trait DeviceHandler: Send + Sync {
     }
 }
 
+/// Most groups the store holds; writes that would add another are dropped.
+pub const MAX_GROUPS: usize = 64;
+
+/// Most properties one group holds.
+pub const MAX_GROUP_PROPS: usize = 256;
+
+/// Longest value stored, in bytes. Device-supplied lists (dual-connect
+/// devices, EQ presets) are the only values that get anywhere near it.
+pub const MAX_VALUE_LEN: usize = 16 * 1024;
+
 /// Helper to update multiple properties in a group at once.
 pub async fn put_properties(
     props: &PropertyStore,
     group: &str,
     values: HashMap<String, String>,
+) {
+    merge_capped(&mut *props.lock().await, group, values);
+}
+
+/// Replace a whole group, so values no longer reported go away. Same limits
+/// as [`put_properties`].
+pub async fn replace_group(
+    props: &PropertyStore,
+    group: &str,
+    values: HashMap<String, String>,
 ) {
     let mut store = props.lock().await;
+    store.remove(group);
+    merge_capped(&mut store, group, values);
+}
+
+/// Merge `values` into `group`, dropping whatever would take the store over
+/// [`MAX_GROUPS`], [`MAX_GROUP_PROPS`] or [`MAX_VALUE_LEN`].
+pub fn merge_capped(
+    store: &mut HashMap<String, HashMap<String, String>>,
+    group: &str,
+    values: HashMap<String, String>,
+) {
+    if !store.contains_key(group) && store.len() >= MAX_GROUPS {
+        warn!("Property store is full, dropping group '{}'", group);
+        return;
+    }
     let entry = store.entry(group.to_string()).or_default();
     for (k, v) in values {
-        entry.insert(k, v);
+        if v.len() > MAX_VALUE_LEN {
+            warn!("Dropping {}.{}: {} bytes is over the limit", group, k, v.len());
+        } else if !entry.contains_key(&k) && entry.len() >= MAX_GROUP_PROPS {
+            warn!("Group '{}' is full, dropping {}", group, k);
+        } else {
+            entry.insert(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn merge_drops_oversized_values_and_extra_keys() {
+        let mut store = HashMap::new();
+        let long = "x".repeat(MAX_VALUE_LEN + 1);
+        merge_capped(&mut store, "g", values(&[("ok", "1"), ("long", &long)]));
+        assert_eq!(store["g"], values(&[("ok", "1")]));
+
+        let many = (0..MAX_GROUP_PROPS + 10).map(|i| (i.to_string(), String::new())).collect();
+        merge_capped(&mut store, "g", many);
+        assert_eq!(store["g"].len(), MAX_GROUP_PROPS);
+        // Existing keys still update when the group is full
+        merge_capped(&mut store, "g", values(&[("ok", "2")]));
+        assert_eq!(store["g"]["ok"], "2");
+    }
+
+    #[test]
+    fn merge_stops_adding_groups_at_the_cap() {
+        let mut store = HashMap::new();
+        for i in 0..MAX_GROUPS {
+            merge_capped(&mut store, &i.to_string(), values(&[("k", "v")]));
+        }
+        merge_capped(&mut store, "extra", values(&[("k", "v")]));
+        assert!(!store.contains_key("extra"));
+        merge_capped(&mut store, "0", values(&[("k2", "v")]));
+        assert_eq!(store["0"].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn replace_group_drops_old_values() {
+        let props = PropertyStore::default();
+        put_properties(&props, "g", values(&[("a", "1"), ("b", "2")])).await;
+        replace_group(&props, "g", values(&[("b", "3")])).await;
+        assert_eq!(props.lock().await["g"], values(&[("b", "3")]));
     }
 }
 
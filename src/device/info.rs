@@ -57,14 +57,13 @@ impl DeviceHandler for InfoHandler {
 
     async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
         let mut out = HashMap::new();
-        let mut model_code: Option<String> = None;
 
-        for (&key, value) in &packet.parameters {
+        for (key, value) in packet.params() {
             // Special case: per-earphone serial numbers (param 24)
             if key == 24 {
-                if let Ok(s) = String::from_utf8(value.clone()) {
+                if let Ok(s) = std::str::from_utf8(value) {
                     if s.starts_with("L-") {
-                        parse_per_earphone_sn(&mut out, &s);
+                        parse_per_earphone_sn(&mut out, s);
                         continue;
                     }
                 }
@@ -78,20 +77,16 @@ impl DeviceHandler for InfoHandler {
             };
 
             // Try to decode as UTF-8, fall back to hex
-            let decoded = String::from_utf8(value.clone())
+            let decoded = String::from_utf8(value.to_vec())
                 .unwrap_or_else(|_| value.iter().map(|b| format!("{:02x}", b)).collect());
 
-            // Save model code for friendly name lookup
-            if key == 15 || key == 10 {
-                model_code = Some(decoded.clone());
-            }
-
             out.insert(name, decoded);
         }
 
         // Add friendly device name if we can map the model code
+        let model_code = out.get("device_model").or_else(|| out.get("device_submodel"));
         if let Some(code) = model_code {
-            if let Some(friendly_name) = friendly_device_name(&code) {
+            if let Some(friendly_name) = friendly_device_name(code) {
                 out.insert("device_name".into(), friendly_name.into());
             }
         }
@@ -103,11 +98,11 @@ impl DeviceHandler for InfoHandler {
 
 fn parse_per_earphone_sn(out: &mut HashMap<String, String>, data: &str) {
     if let Some((left, right)) = data.split_once(',') {
-        if left.starts_with("L-") {
-            out.insert("left_serial_number".into(), left[2..].to_string());
+        if let Some(sn) = left.strip_prefix("L-") {
+            out.insert("left_serial_number".into(), sn.to_string());
         }
-        if right.starts_with("R-") {
-            out.insert("right_serial_number".into(), right[2..].to_string());
+        if let Some(sn) = right.strip_prefix("R-") {
+            out.insert("right_serial_number".into(), sn.to_string());
         }
     }
 }
@@ -1,50 +1,134 @@
 pub mod airpods;
 pub mod anc;
 pub mod battery;
+pub mod capabilities;
 pub mod config;
 pub mod dual_connect;
 pub mod equalizer;
+pub mod events;
 pub mod gestures;
+#[cfg(test)]
+mod golden;
 pub mod handler;
 pub mod info;
 pub mod models;
+pub mod preset_labels;
+pub mod request;
+pub mod schema;
+pub mod snapshot;
+#[cfg(test)]
+pub mod test_support;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::Result;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
+use crate::bluetooth::paired::PAIRED_GROUP;
+use crate::bluetooth::state::CONNECTION_GROUP;
+use crate::bluetooth::stats::STATS_GROUP;
+use crate::startup::DIAGNOSTICS_GROUP;
 use crate::protocol::commands::CommandId;
 use crate::protocol::HuaweiSppPacket;
 use handler::{DeviceHandler, PacketSender, PropertyStore};
+use models::gates::FirmwareGate;
+use request::SetPropertyError;
+
+/// How often the connection loop asks the manager for due refreshes.
+pub const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Handlers re-read on a manual refresh (`connection.refresh`).
+pub const ON_DEMAND_REFRESH: &[&str] = &["battery", "anc"];
+
+/// Groups describing the link and the app rather than the device; they
+/// survive disconnects and device changes.
+const APP_GROUPS: &[&str] = &[CONNECTION_GROUP, DIAGNOSTICS_GROUP, STATS_GROUP, PAIRED_GROUP];
+
+/// Key in the connection group naming the device (its address) the device
+/// groups belong to.
+pub const DEVICE_KEY: &str = "device";
+
+/// Key in the connection group naming the profile (model) in use.
+pub const PROFILE_KEY: &str = "profile";
+
+/// Key in the connection group set to `true` while writes are disabled.
+pub const READ_ONLY_KEY: &str = "read_only";
+
+/// When each property was last changed through [`DeviceManager::set_property`].
+static LOCAL_WRITES: Mutex<BTreeMap<(String, String), std::time::Instant>> =
+    Mutex::new(BTreeMap::new());
+
+/// Whether `group.prop` was changed through MyBuds within `window`, to tell
+/// changes made on the device itself (gestures, the phone app) from ours.
+pub fn written_locally(group: &str, prop: &str, window: Duration) -> bool {
+    let writes = LOCAL_WRITES.lock().unwrap();
+    writes
+        .get(&(group.to_string(), prop.to_string()))
+        .is_some_and(|at| at.elapsed() < window)
+}
 
 /// Manages device handlers and coordinates packet routing.
 pub struct DeviceManager {
     handlers: Vec<Box<dyn DeviceHandler>>,
-    command_map: HashMap<CommandId, usize>,
+    /// Command -> every handler interested in it, in profile order.
+    command_map: HashMap<CommandId, Vec<usize>>,
     ignore_set: HashMap<CommandId, ()>,
     props: PropertyStore,
     packet_tx: PacketSender,
     packet_rx: Option<mpsc::Receiver<HuaweiSppPacket>>,
+    /// Per handler: refresh interval, and when its state was last read.
+    refresh: Vec<(Option<Duration>, Instant)>,
+    /// Drop property writes instead of sending them.
+    read_only: bool,
+    /// Firmware constraints of the profile, checked once the version is known.
+    gates: Vec<FirmwareGate>,
+    /// Handlers the firmware is too old for, with the version they need.
+    gated: HashMap<usize, &'static str>,
+    gates_checked: bool,
 }
 
 impl DeviceManager {
     pub fn new(handlers: Vec<Box<dyn DeviceHandler>>, props: PropertyStore) -> Self {
         let (packet_tx, packet_rx) = mpsc::channel(32);
 
-        let mut command_map = HashMap::new();
+        let mut command_map: HashMap<CommandId, Vec<usize>> = HashMap::new();
         let mut ignore_set = HashMap::new();
 
         for (idx, handler) in handlers.iter().enumerate() {
             for cmd in handler.commands() {
-                command_map.insert(*cmd, idx);
+                command_map.entry(*cmd).or_default().push(idx);
             }
             for cmd in handler.ignore_commands() {
                 ignore_set.insert(*cmd, ());
             }
         }
 
+        // Shared commands are dispatched to every claimant; make that visible
+        for (cmd, idxs) in &command_map {
+            if idxs.len() > 1 {
+                let ids: Vec<_> = idxs.iter().map(|&i| handlers[i].handler_id()).collect();
+                debug!(
+                    "Command {:02X}{:02X} shared by handlers {:?}",
+                    cmd[0], cmd[1], ids
+                );
+            }
+            if ignore_set.contains_key(cmd) {
+                warn!(
+                    "Command {:02X}{:02X} is both handled and ignored; ignore wins",
+                    cmd[0], cmd[1]
+                );
+            }
+        }
+
+        let refresh = handlers
+            .iter()
+            .map(|h| (h.refresh_interval(), Instant::now()))
+            .collect();
+
         Self {
             handlers,
             command_map,
@@ -52,9 +136,35 @@ impl DeviceManager {
             props,
             packet_tx,
             packet_rx: Some(packet_rx),
+            refresh,
+            read_only: false,
+            gates: Vec::new(),
+            gated: HashMap::new(),
+            gates_checked: false,
+        }
+    }
+
+    /// Override handler refresh intervals, in seconds by handler id
+    /// (0 turns refreshing off).
+    pub fn set_refresh_intervals(&mut self, overrides: &HashMap<String, u64>) {
+        for (handler, (interval, _)) in self.handlers.iter().zip(&mut self.refresh) {
+            if let Some(&secs) = overrides.get(handler.handler_id()) {
+                *interval = (secs > 0).then(|| Duration::from_secs(secs));
+            }
         }
     }
 
+    /// Drop every property write with a warning instead of sending it.
+    /// Reads and refreshes still go out.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Firmware version constraints to apply to this profile's handlers.
+    pub fn set_firmware_gates(&mut self, gates: Vec<FirmwareGate>) {
+        self.gates = gates;
+    }
+
     /// Take the packet receiver (can only be called once).
     pub fn take_packet_rx(&mut self) -> Option<mpsc::Receiver<HuaweiSppPacket>> {
         self.packet_rx.take()
@@ -65,6 +175,9 @@ impl DeviceManager {
         let (packet_tx, packet_rx) = mpsc::channel(32);
         self.packet_tx = packet_tx;
         self.packet_rx = Some(packet_rx);
+        // The firmware may have been updated in between
+        self.gated.clear();
+        self.gates_checked = false;
     }
 
     /// Initialize all handlers (call after connection is established).
@@ -72,6 +185,7 @@ impl DeviceManager {
     pub async fn init_handlers(&mut self) -> Result<()> {
         info!("Initializing handlers...");
 
+        let mut outcomes = HashMap::new();
         for handler in &mut self.handlers {
             // Check if the outgoing channel is still alive
             if self.packet_tx.is_closed() {
@@ -110,6 +224,7 @@ impl DeviceManager {
             if !success {
                 warn!("Skipping handler '{}' after failed init attempts", id);
             }
+            outcomes.insert(id.to_string(), if success { "ok" } else { "failed" }.to_string());
 
             // Small yield to let write errors propagate before next handler
             tokio::task::yield_now().await;
@@ -122,21 +237,70 @@ impl DeviceManager {
         }
 
         info!("All handlers initialized");
+        handler::replace_group(&self.props, capabilities::HANDLERS_GROUP, outcomes).await;
+        let now = Instant::now();
+        for (_, last) in &mut self.refresh {
+            *last = now;
+        }
         Ok(())
     }
 
+    /// Re-read the state of handlers whose refresh interval has passed.
+    pub async fn refresh_due(&mut self) {
+        for idx in 0..self.handlers.len() {
+            let (interval, last) = self.refresh[idx];
+            if interval.is_some_and(|interval| last.elapsed() >= interval) {
+                self.refresh_handler(idx).await;
+            }
+        }
+    }
+
+    /// Re-read the state of the handlers with the given ids right away.
+    pub async fn refresh_now(&mut self, ids: &[&str]) {
+        for idx in 0..self.handlers.len() {
+            if ids.contains(&self.handlers[idx].handler_id()) {
+                self.refresh_handler(idx).await;
+            }
+        }
+    }
+
+    async fn refresh_handler(&mut self, idx: usize) {
+        if self.packet_tx.is_closed() || self.gated.contains_key(&idx) {
+            return;
+        }
+        self.refresh[idx].1 = Instant::now();
+        let handler = &mut self.handlers[idx];
+        debug!("Refreshing handler '{}'", handler.handler_id());
+        match tokio::time::timeout(
+            Duration::from_secs(3),
+            handler.on_refresh(&self.packet_tx, &self.props),
+        )
+        .await
+        {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Handler '{}' refresh error: {}", handler.handler_id(), e),
+            Err(_) => warn!("Handler '{}' refresh timeout", handler.handler_id()),
+        }
+    }
+
     /// Route an incoming packet to the appropriate handler.
     pub async fn handle_packet(&mut self, packet: &HuaweiSppPacket) {
         if self.ignore_set.contains_key(&packet.command_id) {
             return;
         }
 
-        if let Some(&idx) = self.command_map.get(&packet.command_id) {
-            if let Err(e) = self.handlers[idx].on_packet(packet, &self.props).await {
-                warn!(
-                    "Handler error for cmd {:02X}{:02X}: {}",
-                    packet.command_id[0], packet.command_id[1], e
-                );
+        if let Some(idxs) = self.command_map.get(&packet.command_id) {
+            for &idx in idxs {
+                let handler = &mut self.handlers[idx];
+                if let Err(e) = handler.on_packet(packet, &self.props).await {
+                    warn!(
+                        "Handler '{}' error for cmd {:02X}{:02X}: {}",
+                        handler.handler_id(),
+                        packet.command_id[0],
+                        packet.command_id[1],
+                        e
+                    );
+                }
             }
         } else {
             debug!(
@@ -144,30 +308,576 @@ impl DeviceManager {
                 packet.command_id[0], packet.command_id[1]
             );
         }
+        if !self.gates_checked && !self.gates.is_empty() {
+            self.check_firmware_gates().await;
+        }
     }
 
-    /// Set a property value, routing to the correct handler.
-    pub async fn set_property(&mut self, group: &str, prop: &str, value: &str) -> Result<()> {
-        for handler in &mut self.handlers {
-            if handler.handler_id() == group
-                || handler
-                    .commands()
-                    .iter()
-                    .any(|_| handler.handler_id() == group)
-            {
-                handler
-                    .set_property(&self.packet_tx, &self.props, group, prop, value)
-                    .await?;
-                return Ok(());
+    /// Disable the handlers the reported firmware is too old for, once
+    /// `info.software_ver` is known.
+    async fn check_firmware_gates(&mut self) {
+        let version = {
+            let store = self.props.lock().await;
+            match store.get("info").and_then(|info| info.get("software_ver")) {
+                Some(version) => version.clone(),
+                None => return,
             }
+        };
+        self.gates_checked = true;
+        let mut outcomes = HashMap::new();
+        for gate in self.gates.iter().filter(|gate| gate.blocks(&version)) {
+            let Some(idx) = self.handlers.iter().position(|h| h.handler_id() == gate.handler) else {
+                continue;
+            };
+            warn!(
+                "Firmware {} is older than {}, disabling handler '{}'",
+                version, gate.min_version, gate.handler
+            );
+            self.gated.insert(idx, gate.min_version);
+            outcomes.insert(
+                gate.handler.to_string(),
+                format!("{}{}", capabilities::NEEDS_FIRMWARE, gate.min_version),
+            );
+        }
+        if !outcomes.is_empty() {
+            handler::put_properties(&self.props, capabilities::HANDLERS_GROUP, outcomes).await;
+        }
+    }
+
+    /// Index of the handler that accepts writes to `group.prop`, if any.
+    fn handler_for(&self, group: &str, prop: &str) -> Option<usize> {
+        self.handlers
+            .iter()
+            .position(|h| h.handles_property(group, prop))
+    }
+
+    /// Set a property value, routing to the correct handler.
+    pub async fn set_property(
+        &mut self,
+        group: &str,
+        prop: &str,
+        value: &str,
+    ) -> Result<(), SetPropertyError> {
+        if self.packet_tx.is_closed() {
+            return Err(SetPropertyError::NotConnected);
         }
-        anyhow::bail!("No handler found for group '{}'", group)
+        let Some(idx) = self.handler_for(group, prop) else {
+            return Err(SetPropertyError::NoHandler(group.to_string()));
+        };
+        if let Some(min_version) = self.gated.get(&idx) {
+            return Err(SetPropertyError::Rejected(format!(
+                "needs firmware {} or later",
+                min_version
+            )));
+        }
+        let store_group = self.handlers[idx].store_group().unwrap_or(group);
+        schema::validate(&*self.props.lock().await, store_group, group, prop, value)?;
+        if self.read_only {
+            warn!("Read-only mode: not setting {}.{} = {}", group, prop, value);
+            return Ok(());
+        }
+        self.handlers[idx]
+            .set_property(&self.packet_tx, &self.props, group, prop, value)
+            .await
+            .map_err(|e| {
+                if self.packet_tx.is_closed() {
+                    SetPropertyError::NotConnected
+                } else {
+                    SetPropertyError::Rejected(e.to_string())
+                }
+            })?;
+        let key = (group.to_string(), prop.to_string());
+        LOCAL_WRITES.lock().unwrap().insert(key, std::time::Instant::now());
+        Ok(())
     }
 
-    /// Clear all properties (call on disconnect so UI shows disconnected state).
+    /// Clear device properties (call on disconnect so UI shows disconnected state).
+    /// The connection state, diagnostics and stats groups are kept, they
+    /// describe the link and the app, not the device.
     pub async fn clear_props(&self) {
         let mut store = self.props.lock().await;
-        store.clear();
+        store.retain(|group, _| APP_GROUPS.contains(&group.as_str()));
+    }
+
+    /// Mark the store as holding `device`'s properties (call on connect).
+    /// Groups left over from a different device are evicted first, so a
+    /// store reused across devices never mixes their state.
+    pub async fn claim_props(&self, device: &str) {
+        let mut store = self.props.lock().await;
+        let owner = store.get(CONNECTION_GROUP).and_then(|g| g.get(DEVICE_KEY));
+        if let Some(owner) = owner.filter(|owner| *owner != device) {
+            info!("Evicting properties of {} for {}", owner, device);
+            store.retain(|group, _| APP_GROUPS.contains(&group.as_str()));
+        }
+        handler::merge_capped(
+            &mut store,
+            CONNECTION_GROUP,
+            HashMap::from([(DEVICE_KEY.to_string(), device.to_string())]),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use handler::put_properties;
+    use models::*;
+
+    /// Shared log of (handler id, event) pairs recorded by mock handlers.
+    type EventLog = Arc<Mutex<Vec<(&'static str, String)>>>;
+
+    /// Handler that records everything it is asked to do.
+    struct MockHandler {
+        id: &'static str,
+        commands: Vec<CommandId>,
+        ignore: Vec<CommandId>,
+        log: EventLog,
+    }
+
+    impl MockHandler {
+        fn boxed(
+            id: &'static str,
+            commands: &[CommandId],
+            ignore: &[CommandId],
+            log: &EventLog,
+        ) -> Box<dyn DeviceHandler> {
+            Box::new(Self {
+                id,
+                commands: commands.to_vec(),
+                ignore: ignore.to_vec(),
+                log: log.clone(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl DeviceHandler for MockHandler {
+        fn handler_id(&self) -> &'static str {
+            self.id
+        }
+
+        fn commands(&self) -> &[CommandId] {
+            &self.commands
+        }
+
+        fn ignore_commands(&self) -> &[CommandId] {
+            &self.ignore
+        }
+
+        async fn on_init(&mut self, _sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+            self.log.lock().unwrap().push((self.id, "init".into()));
+            Ok(())
+        }
+
+        async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+            let cmd = format!("{:02X}{:02X}", packet.command_id[0], packet.command_id[1]);
+            self.log.lock().unwrap().push((self.id, cmd.clone()));
+            let mut out = HashMap::new();
+            out.insert("last".to_string(), cmd);
+            put_properties(props, self.id, out).await;
+            Ok(())
+        }
+    }
+
+    const CMD_A: CommandId = [0x01, 0x01];
+    const CMD_B: CommandId = [0x01, 0x02];
+    const CMD_SHARED: CommandId = [0x02, 0x01];
+
+    fn logged(log: &EventLog) -> Vec<(&'static str, String)> {
+        log.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn routes_packets_by_command_id() {
+        let log = EventLog::default();
+        let mut dm = DeviceManager::new(
+            vec![
+                MockHandler::boxed("a", &[CMD_A], &[], &log),
+                MockHandler::boxed("b", &[CMD_B], &[], &log),
+            ],
+            PropertyStore::default(),
+        );
+
+        dm.handle_packet(&HuaweiSppPacket::new(CMD_B)).await;
+        dm.handle_packet(&HuaweiSppPacket::new(CMD_A)).await;
+        // Nobody claims this one
+        dm.handle_packet(&HuaweiSppPacket::new([0x7F, 0x7F])).await;
+
+        assert_eq!(
+            logged(&log),
+            vec![("b", "0102".to_string()), ("a", "0101".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn overlapping_command_goes_to_every_handler_in_order() {
+        let log = EventLog::default();
+        let mut dm = DeviceManager::new(
+            vec![
+                MockHandler::boxed("first", &[CMD_SHARED], &[], &log),
+                MockHandler::boxed("second", &[CMD_SHARED], &[], &log),
+            ],
+            PropertyStore::default(),
+        );
+
+        dm.handle_packet(&HuaweiSppPacket::new(CMD_SHARED)).await;
+
+        assert_eq!(
+            logged(&log),
+            vec![("first", "0201".to_string()), ("second", "0201".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignored_commands_reach_no_handler() {
+        let log = EventLog::default();
+        let mut dm = DeviceManager::new(
+            vec![
+                MockHandler::boxed("a", &[CMD_A], &[], &log),
+                // Another handler ignoring CMD_A wins over a's claim
+                MockHandler::boxed("b", &[CMD_B], &[CMD_A], &log),
+            ],
+            PropertyStore::default(),
+        );
+
+        dm.handle_packet(&HuaweiSppPacket::new(CMD_A)).await;
+        dm.handle_packet(&HuaweiSppPacket::new(CMD_B)).await;
+
+        assert_eq!(logged(&log), vec![("b", "0102".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn init_runs_handlers_in_profile_order() {
+        let log = EventLog::default();
+        let mut dm = DeviceManager::new(
+            vec![
+                MockHandler::boxed("c", &[], &[], &log),
+                MockHandler::boxed("a", &[], &[], &log),
+                MockHandler::boxed("b", &[], &[], &log),
+            ],
+            PropertyStore::default(),
+        );
+        // Keep the packet channel open so init doesn't bail
+        let _rx = dm.take_packet_rx();
+
+        dm.init_handlers().await.unwrap();
+
+        let order: Vec<_> = logged(&log).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(order, vec!["c", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn airpods_writes_are_valid_aap() {
+        let mut dm = manager(airpods_pro());
+        let mut rx = dm.take_packet_rx().unwrap();
+        let writes = [
+            ("ear_detection", "enabled", "false"),
+            ("anc", "mode", "adaptive"),
+            ("anc", "anc_strength", "50"),
+            ("anc", "one_bud_anc", "true"),
+            ("conversation_awareness", "enabled", "true"),
+            ("personalized_volume", "enabled", "false"),
+            ("press_speed", "speed", "slowest"),
+            ("press_duration", "duration", "shorter"),
+            ("volume_swipe", "enabled", "true"),
+            ("volume_swipe", "interval", "longer"),
+            ("tone_volume", "level", "40"),
+            ("audio_owner", "take_over", "true"),
+        ];
+        for (group, prop, value) in writes {
+            dm.set_property(group, prop, value).await.unwrap();
+            let pkt = rx.try_recv().unwrap_or_else(|_| panic!("{}.{} sent nothing", group, prop));
+            if let Err(e) = crate::protocol::aap::AapPacket::from_handler_packet(&pkt) {
+                panic!("{}.{} = {}: {:#}", group, prop, value, e);
+            }
+        }
+        assert!(written_locally("tone_volume", "level", Duration::from_secs(60)));
+        assert!(!written_locally("tone_volume", "enabled", Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_sends_nothing() {
+        let mut dm = manager(airpods_pro());
+        let mut rx = dm.take_packet_rx().unwrap();
+        dm.set_read_only(true);
+        dm.set_property("anc", "mode", "adaptive").await.unwrap();
+        assert!(rx.try_recv().is_err());
+        // Writes nobody handles are still reported
+        assert!(dm.set_property("battery", "left", "50").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn old_firmware_disables_gated_handlers() {
+        let mut dm = manager(freebuds_pro3());
+        let _rx = dm.take_packet_rx();
+        dm.set_firmware_gates(vec![FirmwareGate {
+            handler: "anc",
+            min_version: "1.0.0.200",
+        }]);
+        dm.props.lock().await.insert(
+            "info".to_string(),
+            HashMap::from([("software_ver".to_string(), "1.0.0.186".to_string())]),
+        );
+        dm.handle_packet(&HuaweiSppPacket::new([0x7F, 0x7F])).await;
+
+        assert!(matches!(
+            dm.set_property("anc", "mode", "awareness").await,
+            Err(SetPropertyError::Rejected(_))
+        ));
+        let store = dm.props.lock().await;
+        assert_eq!(store[capabilities::HANDLERS_GROUP]["anc"], "needs_firmware:1.0.0.200");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refreshes_handlers_on_their_interval() {
+        let log = EventLog::default();
+        let mut dm = DeviceManager::new(
+            vec![
+                MockHandler::boxed("a", &[], &[], &log),
+                MockHandler::boxed("b", &[], &[], &log),
+            ],
+            PropertyStore::default(),
+        );
+        let _rx = dm.take_packet_rx();
+        dm.set_refresh_intervals(&HashMap::from([("a".to_string(), 10)]));
+        dm.init_handlers().await.unwrap();
+        log.lock().unwrap().clear();
+
+        dm.refresh_due().await;
+        assert!(logged(&log).is_empty());
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        dm.refresh_due().await;
+        dm.refresh_due().await;
+        // Only "a" has an interval, and it isn't due again right away
+        assert_eq!(logged(&log), vec![("a", "init".to_string())]);
+
+        dm.refresh_now(&["b"]).await;
+        assert_eq!(
+            logged(&log),
+            vec![("a", "init".to_string()), ("b", "init".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn clear_props_keeps_connection_state() {
+        let log = EventLog::default();
+        let props = PropertyStore::default();
+        let mut dm = DeviceManager::new(
+            vec![MockHandler::boxed("a", &[CMD_A], &[], &log)],
+            props.clone(),
+        );
+
+        dm.handle_packet(&HuaweiSppPacket::new(CMD_A)).await;
+        assert_eq!(
+            props.lock().await.get("a").and_then(|g| g.get("last")).cloned(),
+            Some("0101".to_string())
+        );
+
+        props
+            .lock()
+            .await
+            .entry(CONNECTION_GROUP.to_string())
+            .or_default()
+            .insert("state".into(), "reconnecting".into());
+
+        dm.clear_props().await;
+        let store = props.lock().await;
+        assert!(!store.contains_key("a"));
+        assert_eq!(
+            store.get(CONNECTION_GROUP).and_then(|g| g.get("state")).map(String::as_str),
+            Some("reconnecting")
+        );
+    }
+
+    #[tokio::test]
+    async fn claim_props_evicts_groups_of_another_device() {
+        let props = PropertyStore::default();
+        let dm = DeviceManager::new(Vec::new(), props.clone());
+        dm.claim_props("AA:AA:AA:AA:AA:AA").await;
+        put_properties(&props, "battery", HashMap::from([("left".into(), "50".into())])).await;
+
+        // Reconnecting the same device keeps its last known state
+        dm.claim_props("AA:AA:AA:AA:AA:AA").await;
+        assert!(props.lock().await.contains_key("battery"));
+
+        dm.claim_props("BB:BB:BB:BB:BB:BB").await;
+        let store = props.lock().await;
+        assert!(!store.contains_key("battery"));
+        assert_eq!(
+            store.get(CONNECTION_GROUP).and_then(|g| g.get(DEVICE_KEY)).map(String::as_str),
+            Some("BB:BB:BB:BB:BB:BB")
+        );
+    }
+
+    fn manager(profile: DeviceProfile) -> DeviceManager {
+        DeviceManager::new(profile.handlers, PropertyStore::default())
+    }
+
+    fn routed_to(dm: &DeviceManager, group: &str, prop: &str) -> Option<&'static str> {
+        dm.handler_for(group, prop)
+            .map(|idx| dm.handlers[idx].handler_id())
+    }
+
+    fn all_profiles() -> Vec<DeviceProfile> {
+        vec![
+            freebuds_pro3(),
+            freebuds_pro4(),
+            freeclip(),
+            freebuds_pro2(),
+            freebuds_5i(),
+            freebuds_6i(),
+            freebuds_4i(),
+            freebuds_se2(),
+            generic_probe(),
+            freebuds_5(),
+            airpods_pro(),
+            airpods_max(),
+            airpods_generic(),
+        ]
+    }
+
+    #[test]
+    fn routes_handler_id_groups() {
+        let dm = manager(freebuds_5());
+        let cases = [
+            ("anc", "mode", "anc"),
+            ("config_eq", "equalizer_preset", "config_eq"),
+            ("config_sound_quality", "quality_preference", "config_sound_quality"),
+            ("low_latency", "low_latency", "low_latency"),
+            ("tws_auto_pause", "auto_pause", "tws_auto_pause"),
+            ("dual_connect", "enabled", "dual_connect"),
+            ("gesture_double", "double_tap_left", "gesture_double"),
+            ("gesture_triple", "triple_tap_right", "gesture_triple"),
+            ("gesture_long_split", "long_tap_left", "gesture_long_split"),
+            ("gesture_swipe", "swipe_gesture", "gesture_swipe"),
+        ];
+        for (group, prop, expected) in cases {
+            assert_eq!(routed_to(&dm, group, prop), Some(expected), "{}.{}", group, prop);
+        }
+    }
+
+    #[test]
+    fn routes_shared_store_groups_by_property() {
+        let dm = manager(freebuds_5());
+        let cases = [
+            ("sound", "equalizer_preset", "config_eq"),
+            ("sound", "quality_preference", "config_sound_quality"),
+            ("config", "auto_pause", "tws_auto_pause"),
+            ("config", "low_latency", "low_latency"),
+            ("action", "double_tap_left", "gesture_double"),
+            ("action", "double_tap_in_call", "gesture_double"),
+            ("action", "triple_tap_right", "gesture_triple"),
+            ("action", "long_tap_right", "gesture_long_split"),
+            ("action", "noise_control_left", "gesture_long_split"),
+            ("action", "swipe_gesture", "gesture_swipe"),
+        ];
+        for (group, prop, expected) in cases {
+            assert_eq!(routed_to(&dm, group, prop), Some(expected), "{}.{}", group, prop);
+        }
+    }
+
+    #[test]
+    fn write_groups_resolve_to_the_published_group() {
+        // A handler taking writes under several groups publishes into one,
+        // which is what those writes are validated against
+        for profile in all_profiles() {
+            for handler in &profile.handlers {
+                let groups = handler.handled_groups();
+                let resolved: Vec<&str> = groups
+                    .iter()
+                    .map(|g| handler.store_group().unwrap_or(g))
+                    .collect();
+                assert!(
+                    resolved.windows(2).all(|w| w[0] == w[1]),
+                    "{} in {}: {:?}",
+                    handler.handler_id(),
+                    profile.name,
+                    resolved
+                );
+            }
+        }
+        let dm = manager(freebuds_5());
+        let idx = dm.handler_for("gesture_swipe", "swipe_gesture").unwrap();
+        assert_eq!(dm.handlers[idx].store_group(), Some("action"));
+    }
+
+    #[test]
+    fn routes_both_buds_taps() {
+        // FreeClip: unsided tap props, two tap handlers sharing the action group
+        let dm = manager(freeclip());
+        let cases = [
+            ("action", "double_tap", "gesture_double"),
+            ("action", "double_tap_in_call", "gesture_double"),
+            ("action", "triple_tap", "gesture_triple"),
+            ("gesture_double", "double_tap", "gesture_double"),
+            ("gesture_triple", "triple_tap", "gesture_triple"),
+        ];
+        for (group, prop, expected) in cases {
+            assert_eq!(routed_to(&dm, group, prop), Some(expected), "{}.{}", group, prop);
+        }
+        assert_eq!(routed_to(&dm, "anc", "mode"), None);
+    }
+
+    #[test]
+    fn routes_airpods_groups() {
+        let dm = manager(airpods_pro());
+        let cases = [
+            ("anc", "mode", "anc"),
+            ("ear_detection", "enabled", "ear_detection"),
+            ("conversation_awareness", "enabled", "conversation_awareness"),
+            ("personalized_volume", "enabled", "personalized_volume"),
+            ("tws_auto_pause", "auto_pause", "tws_auto_pause"),
+            ("press_speed", "speed", "press_speed"),
+            ("press_duration", "duration", "press_duration"),
+            ("volume_swipe", "interval", "volume_swipe"),
+            ("tone_volume", "level", "tone_volume"),
+            ("audio_owner", "take_over", "audio_owner"),
+        ];
+        for (group, prop, expected) in cases {
+            assert_eq!(routed_to(&dm, group, prop), Some(expected), "{}.{}", group, prop);
+        }
     }
 
+    #[test]
+    fn read_only_groups_have_no_handler() {
+        for profile in all_profiles() {
+            let name = profile.name;
+            let dm = manager(profile);
+            for group in ["battery", "info", "device_info", "anc_change"] {
+                assert_eq!(routed_to(&dm, group, "anything"), None, "{}: {}", name, group);
+            }
+        }
+    }
+
+    #[test]
+    fn every_handled_group_routes_back_to_a_single_handler() {
+        for profile in all_profiles() {
+            let name = profile.name;
+            let dm = manager(profile);
+            for handler in &dm.handlers {
+                // Each handler must at least own writes to its own id group
+                if handler.handled_groups().is_empty() {
+                    continue;
+                }
+                let id = handler.handler_id();
+                assert!(
+                    handler.handled_groups().contains(&id),
+                    "{}: '{}' doesn't list its own id",
+                    name,
+                    id
+                );
+                let claimants = dm
+                    .handlers
+                    .iter()
+                    .filter(|h| h.handled_groups().contains(&id))
+                    .count();
+                assert_eq!(claimants, 1, "{}: group '{}' claimed {} times", name, id, claimants);
+            }
+        }
+    }
 }
@@ -0,0 +1,235 @@
+//! Model database: device support shipped as data instead of code.
+//!
+//! `models.toml` maps Bluetooth names the built-in registry doesn't know to
+//! a profile, with optional RFCOMM channel, feature switches and model
+//! codes, plus names for EQ presets by profile (see
+//! [`crate::device::preset_labels`]). A copy is bundled; with `model_db_sync = true` MyBuds also fetches
+//! the latest one from the project's repository, at most once a day, and
+//! caches it in `~/.cache/mybuds/models.toml`. The fetch is a plain GET of a
+//! static file: nothing about the user or their devices is sent. Entries
+//! apply from the next connection.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use super::{overrides, profile_by_name, DeviceProfile, Transport};
+
+const BUNDLED: &str = include_str!("models.toml");
+const SYNC_URL: &str =
+    "https://raw.githubusercontent.com/joshuapassos/mybuds/main/src/device/models/models.toml";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// A cached copy younger than this is not fetched again.
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Format version this build reads.
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Deserialize)]
+struct ModelEntry {
+    /// Exact Bluetooth name.
+    name: String,
+    /// Built-in profile the model uses.
+    profile: String,
+    #[serde(default)]
+    model_codes: Vec<String>,
+    channel: Option<u16>,
+    #[serde(default)]
+    features: HashMap<String, bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Database {
+    version: u32,
+    models: Vec<ModelEntry>,
+    /// EQ preset names by profile, then preset ID.
+    #[serde(default)]
+    preset_labels: HashMap<String, HashMap<String, String>>,
+}
+
+/// Database in use, read on first use.
+static DATABASE: Mutex<Option<Database>> = Mutex::new(None);
+
+fn cache_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("mybuds")
+        .join("models.toml")
+}
+
+fn parse(text: &str) -> Result<Database> {
+    let db: Database = toml::from_str(text)?;
+    if db.version != VERSION {
+        bail!("version {} (this build reads {})", db.version, VERSION);
+    }
+    Ok(db)
+}
+
+/// The cached copy if it is readable, else the bundled one.
+fn read() -> Database {
+    if let Ok(text) = std::fs::read_to_string(cache_path()) {
+        match parse(&text) {
+            Ok(db) => return db,
+            Err(e) => warn!("Ignoring cached model database: {:#}", e),
+        }
+    }
+    parse(BUNDLED).expect("bundled models.toml")
+}
+
+fn with_database<T>(f: impl FnOnce(&Database) -> T) -> T {
+    let mut db = DATABASE.lock().unwrap();
+    f(db.get_or_insert_with(read))
+}
+
+fn with_models<T>(f: impl FnOnce(&[ModelEntry]) -> T) -> T {
+    with_database(|db| f(&db.models))
+}
+
+fn find<'a>(models: &'a [ModelEntry], name: &str) -> Option<&'a ModelEntry> {
+    models.iter().find(|m| m.name == name)
+}
+
+/// Whether the database lists this Bluetooth name.
+pub fn knows(name: &str) -> bool {
+    with_models(|models| find(models, name).is_some())
+}
+
+/// Profile for a device the database lists.
+pub fn profile(name: &str) -> Option<DeviceProfile> {
+    with_models(|models| build(find(models, name)?))
+}
+
+fn build(entry: &ModelEntry) -> Option<DeviceProfile> {
+    let Some(mut profile) = profile_by_name(&entry.profile) else {
+        warn!(
+            "Model database names unknown profile '{}' for {}",
+            entry.profile, entry.name
+        );
+        return None;
+    };
+    info!("{} is in the model database, using {}", entry.name, profile.name);
+    match (entry.channel, &mut profile.transport) {
+        (Some(channel), Transport::Rfcomm(current)) => *current = channel,
+        (Some(_), Transport::L2cap(_)) => warn!("Model database channel ignored for {}", entry.name),
+        (None, _) => {}
+    }
+    let mut features: Vec<_> = entry.features.iter().collect();
+    features.sort();
+    for (flag, &value) in features {
+        if !overrides::apply_one(&mut profile, flag, value) {
+            warn!("Ignoring unknown model database feature {} for {}", flag, entry.name);
+        }
+    }
+    Some(profile)
+}
+
+/// Profile of the entry listing `code` among its model codes, when that
+/// entry uses a profile other than `profile`.
+pub fn refine(profile: &str, code: &str) -> Option<DeviceProfile> {
+    with_models(|models| build(refining(models, profile, code)?))
+}
+
+fn refining<'a>(models: &'a [ModelEntry], profile: &str, code: &str) -> Option<&'a ModelEntry> {
+    models
+        .iter()
+        .filter(|m| !m.profile.eq_ignore_ascii_case(profile))
+        .find(|m| m.model_codes.iter().any(|c| c == code))
+}
+
+/// Marketing name for a reported model code (`info.device_model`).
+pub fn model_name(code: &str) -> Option<String> {
+    with_models(|models| {
+        models
+            .iter()
+            .find(|m| m.model_codes.iter().any(|c| c == code))
+            .map(|m| m.name.clone())
+    })
+}
+
+/// Name for EQ preset `id` of `profile`.
+pub fn preset_label(profile: &str, id: u8) -> Option<String> {
+    with_database(|db| db.preset_labels.get(profile)?.get(&id.to_string()).cloned())
+}
+
+/// Fetch the latest database unless the cached copy is fresh. Blocking.
+fn sync() -> Result<()> {
+    let path = cache_path();
+    let age = std::fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if age.is_some_and(|age| age < MAX_AGE) {
+        debug!("Model database is up to date");
+        return Ok(());
+    }
+
+    let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+    let text = agent
+        .get(SYNC_URL)
+        .call()
+        .context("request failed")?
+        .into_string()
+        .context("unreadable response")?;
+    let db = parse(&text).context("not a model database this build can read")?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("toml.tmp");
+    std::fs::write(&tmp, &text)?;
+    std::fs::rename(&tmp, &path)?;
+    info!("Model database updated: {} models", db.models.len());
+    *DATABASE.lock().unwrap() = Some(db);
+    Ok(())
+}
+
+/// Fetch the latest database in the background.
+pub fn spawn_sync() {
+    tokio::task::spawn_blocking(|| {
+        if let Err(e) = sync() {
+            warn!("Model database sync failed: {:#}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_build_on_a_profile() {
+        assert!(parse(BUNDLED).is_ok());
+        assert!(parse("version = 2\nmodels = []").is_err());
+
+        let db = parse(
+            r#"
+            version = 1
+            [[models]]
+            name = "HUAWEI FreeBuds 9"
+            profile = "FreeBuds Pro 3"
+            model_codes = ["T0099"]
+            channel = 3
+            features = { double_tap = false }
+
+            [preset_labels."FreeBuds 5"]
+            7 = "Classical"
+            "#,
+        )
+        .unwrap();
+        let models = &db.models;
+        let profile = build(find(models, "HUAWEI FreeBuds 9").unwrap()).unwrap();
+        assert_eq!(profile.name, "FreeBuds Pro 3");
+        assert!(matches!(profile.transport, Transport::Rfcomm(3)));
+        assert!(!profile.handlers.iter().any(|h| h.handler_id() == "gesture_double"));
+        assert!(find(models, "HUAWEI FreeBuds 10").is_none());
+
+        assert!(refining(models, "FreeBuds Pro 2", "T0099").is_some());
+        assert!(refining(models, "FreeBuds Pro 3", "T0099").is_none());
+        assert!(refining(models, "FreeBuds Pro 2", "T0001").is_none());
+        assert_eq!(db.preset_labels["FreeBuds 5"]["7"], "Classical");
+    }
+}
@@ -0,0 +1,80 @@
+//! Firmware version constraints on features.
+//!
+//! Some commands misbehave on old firmware. A gate names a profile, the
+//! handler implementing the feature and the oldest firmware it is safe on.
+//! `DeviceManager` checks the gates once the device has reported
+//! `info.software_ver`: gated handlers stop accepting writes and refreshes,
+//! and the Capabilities page says which firmware they need.
+
+use std::cmp::Ordering;
+
+/// A handler that needs at least `min_version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareGate {
+    pub handler: &'static str,
+    pub min_version: &'static str,
+}
+
+/// Gates by profile name. Add an entry once a misbehaving firmware has been
+/// confirmed, with the version that fixed it:
+///
+/// ```ignore
+/// ("FreeBuds 5i", FirmwareGate { handler: "gesture_swipe", min_version: "1.9.0.150" }),
+/// ```
+const GATES: &[(&str, FirmwareGate)] = &[];
+
+/// Gates that apply to `profile`.
+pub fn for_profile(profile: &str) -> Vec<FirmwareGate> {
+    GATES
+        .iter()
+        .filter(|(name, _)| *name == profile)
+        .map(|(_, gate)| *gate)
+        .collect()
+}
+
+/// Numeric parts of a version such as "1.0.0.186" or "2.0.0.126(C00)";
+/// parsing stops at the first part that isn't a number.
+fn parts(version: &str) -> Vec<u32> {
+    let version = version.split(|c: char| !c.is_ascii_digit() && c != '.').next().unwrap_or("");
+    version.split('.').map_while(|p| p.parse().ok()).collect()
+}
+
+/// Compare two firmware versions part by part; missing parts count as 0.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (parts(a), parts(b));
+    let len = a.len().max(b.len());
+    let at = |v: &[u32], i: usize| v.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| at(&a, i).cmp(&at(&b, i)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+impl FirmwareGate {
+    /// Whether `version` is too old for the feature. Versions without any
+    /// number can't be judged and pass.
+    pub fn blocks(&self, version: &str) -> bool {
+        !parts(version).is_empty() && compare(version, self.min_version) == Ordering::Less
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_firmware_versions() {
+        assert_eq!(compare("1.0.0.186", "1.0.0.200"), Ordering::Less);
+        assert_eq!(compare("1.10.0.1", "1.9.9.9"), Ordering::Greater);
+        assert_eq!(compare("2.0.0.126(C00)", "2.0.0.126"), Ordering::Equal);
+        assert_eq!(compare("1.0", "1.0.0.0"), Ordering::Equal);
+
+        let gate = FirmwareGate {
+            handler: "gesture_swipe",
+            min_version: "1.0.0.200",
+        };
+        assert!(gate.blocks("1.0.0.186"));
+        assert!(!gate.blocks("1.0.0.200"));
+        assert!(!gate.blocks("unknown"));
+    }
+}
@@ -1,17 +1,23 @@
 
 use super::airpods::{
-    AirPodsAncHandler, AirPodsBatteryHandler, AirPodsConversationAwarenessHandler,
-    AirPodsEarDetectionHandler, AirPodsInfoHandler, AirPodsPersonalizedVolumeHandler,
+    AirPodsAncHandler, AirPodsAudioOwnerHandler, AirPodsAutoPauseHandler, AirPodsBatteryHandler, AirPodsChoiceHandler,
+    AirPodsConversationAwarenessHandler, AirPodsEarDetectionHandler, AirPodsInfoHandler,
+    AirPodsPersonalizedVolumeHandler, AirPodsToneVolumeHandler, AirPodsVolumeSwipeHandler,
 };
 use super::anc::{AncHandler, AncLegacyChangeHandler};
 use super::battery::BatteryHandler;
-use super::config::{AutoPauseHandler, LowLatencyHandler, SoundQualityHandler};
+use super::config::{AutoPauseHandler, InEarHandler, LowLatencyHandler, SoundQualityHandler};
 use super::dual_connect::DualConnectHandler;
 use super::equalizer::EqualizerHandler;
 use super::gestures::{LongTapSplitHandler, SwipeGestureHandler, TapActionHandler};
 use super::handler::DeviceHandler;
 use super::info::InfoHandler;
 
+pub mod database;
+pub mod gates;
+pub mod overrides;
+pub mod rules;
+
 /// Bluetooth transport type.
 #[derive(Debug, Clone, Copy)]
 pub enum Transport {
@@ -32,7 +38,7 @@ pub struct DeviceProfile {
 // Huawei / HONOR profiles
 // ============================================================
 
-/// Build handlers for FreeBuds Pro 3 / Pro 4 / FreeClip.
+/// Build handlers for FreeBuds Pro 3.
 pub fn freebuds_pro3() -> DeviceProfile {
     DeviceProfile {
         name: "FreeBuds Pro 3",
@@ -50,6 +56,7 @@ pub fn freebuds_pro3() -> DeviceProfile {
                 (9, "voice"),
             ])),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
             Box::new(DualConnectHandler::default()),
             Box::new(TapActionHandler::double_tap(false)),
             Box::new(LongTapSplitHandler::new(true, true, false, true)),
@@ -59,6 +66,41 @@ pub fn freebuds_pro3() -> DeviceProfile {
     }
 }
 
+/// Build handlers for FreeBuds Pro 4: the Pro 3 profile under its own
+/// name, until a capture shows where the two differ.
+pub fn freebuds_pro4() -> DeviceProfile {
+    DeviceProfile {
+        name: "FreeBuds Pro 4",
+        ..freebuds_pro3()
+    }
+}
+
+/// Build handlers for FreeClip: open-ear clips without ANC, long tap or
+/// swipe. The buds fit either ear, so taps are set once for both.
+pub fn freeclip() -> DeviceProfile {
+    DeviceProfile {
+        name: "FreeClip",
+        transport: Transport::Rfcomm(1),
+        handlers: vec![
+            Box::new(InfoHandler),
+            Box::new(BatteryHandler::default()),
+            Box::new(SoundQualityHandler),
+            Box::new(EqualizerHandler::with_presets(vec![
+                (1, "default"),
+                (2, "hardbass"),
+                (3, "treble"),
+                (9, "voices"),
+            ])),
+            Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
+            Box::new(DualConnectHandler::default()),
+            Box::new(TapActionHandler::double_tap(true).for_both_buds()),
+            Box::new(TapActionHandler::triple_tap().for_both_buds()),
+            Box::new(LowLatencyHandler),
+        ],
+    }
+}
+
 /// Build handlers for FreeBuds Pro 2.
 pub fn freebuds_pro2() -> DeviceProfile {
     DeviceProfile {
@@ -77,6 +119,7 @@ pub fn freebuds_pro2() -> DeviceProfile {
                 (9, "voice"),
             ])),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
             Box::new(DualConnectHandler::default()),
             Box::new(TapActionHandler::double_tap(false)),
             Box::new(LongTapSplitHandler::new(true, true, false, true)),
@@ -101,6 +144,7 @@ pub fn freebuds_5i() -> DeviceProfile {
             Box::new(LongTapSplitHandler::new(true, true, false, true)),
             Box::new(SwipeGestureHandler),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
             Box::new(SoundQualityHandler),
             Box::new(LowLatencyHandler),
             Box::new(EqualizerHandler::with_presets(vec![
@@ -129,6 +173,7 @@ pub fn freebuds_6i() -> DeviceProfile {
             Box::new(LongTapSplitHandler::new(true, true, false, true)),
             Box::new(SwipeGestureHandler),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
             Box::new(SoundQualityHandler),
             Box::new(LowLatencyHandler),
             Box::new(EqualizerHandler::with_presets(vec![
@@ -155,6 +200,7 @@ pub fn freebuds_4i() -> DeviceProfile {
             Box::new(TapActionHandler::double_tap(false)),
             Box::new(LongTapSplitHandler::default()),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
         ],
     }
 }
@@ -193,6 +239,7 @@ pub fn generic_probe() -> DeviceProfile {
             Box::new(AncHandler::new(true, true, true)),
             Box::new(AncLegacyChangeHandler),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
             Box::new(TapActionHandler::double_tap(true)),
             Box::new(LongTapSplitHandler::new(true, true, true, true)),
             Box::new(SwipeGestureHandler),
@@ -214,6 +261,7 @@ pub fn freebuds_5() -> DeviceProfile {
             Box::new(AncHandler::new(true, false, false)),
             Box::new(AncLegacyChangeHandler),
             Box::new(AutoPauseHandler),
+            Box::new(InEarHandler),
             Box::new(TapActionHandler::double_tap(true)),
             Box::new(TapActionHandler::triple_tap()),
             Box::new(LongTapSplitHandler::new(true, true, false, true)),
@@ -244,9 +292,15 @@ pub fn airpods_pro() -> DeviceProfile {
             Box::new(AirPodsInfoHandler),
             Box::new(AirPodsBatteryHandler),
             Box::new(AirPodsEarDetectionHandler),
+            Box::new(AirPodsAutoPauseHandler::default()),
+            Box::new(AirPodsAudioOwnerHandler),
             Box::new(AirPodsAncHandler::new(true)),
             Box::new(AirPodsConversationAwarenessHandler),
             Box::new(AirPodsPersonalizedVolumeHandler),
+            Box::new(AirPodsChoiceHandler::press_speed()),
+            Box::new(AirPodsChoiceHandler::press_duration()),
+            Box::new(AirPodsVolumeSwipeHandler),
+            Box::new(AirPodsToneVolumeHandler),
         ],
     }
 }
@@ -260,6 +314,8 @@ pub fn airpods_max() -> DeviceProfile {
             Box::new(AirPodsInfoHandler),
             Box::new(AirPodsBatteryHandler),
             Box::new(AirPodsEarDetectionHandler),
+            Box::new(AirPodsAutoPauseHandler::default()),
+            Box::new(AirPodsAudioOwnerHandler),
             Box::new(AirPodsAncHandler::new(true)),
             Box::new(AirPodsConversationAwarenessHandler),
             Box::new(AirPodsPersonalizedVolumeHandler),
@@ -276,6 +332,8 @@ pub fn airpods_generic() -> DeviceProfile {
             Box::new(AirPodsInfoHandler),
             Box::new(AirPodsBatteryHandler),
             Box::new(AirPodsEarDetectionHandler),
+            Box::new(AirPodsAutoPauseHandler::default()),
+            Box::new(AirPodsAudioOwnerHandler),
         ],
     }
 }
@@ -284,24 +342,133 @@ pub fn airpods_generic() -> DeviceProfile {
 // Device lookup
 // ============================================================
 
-/// Get device profile by Bluetooth device name.
+/// How a registry entry matches a Bluetooth device name.
+#[derive(Debug, Clone, Copy)]
+enum NameMatch {
+    Exact(&'static str),
+    Prefix(&'static str),
+    Contains(&'static str),
+}
+
+impl NameMatch {
+    fn matches(self, name: &str) -> bool {
+        match self {
+            NameMatch::Exact(s) => name == s,
+            NameMatch::Prefix(s) => name.starts_with(s),
+            NameMatch::Contains(s) => name.contains(s),
+        }
+    }
+}
+
+type ProfileBuilder = fn() -> DeviceProfile;
+
+/// Known device names and the profile each one uses, first match wins.
+/// Both profile selection and the scanner's device filter read this table.
+const REGISTRY: &[(NameMatch, ProfileBuilder)] = &[
+    // Huawei / HONOR
+    (NameMatch::Exact("HUAWEI FreeBuds Pro 3"), freebuds_pro3),
+    (NameMatch::Exact("HUAWEI FreeBuds Pro 4"), freebuds_pro4),
+    (NameMatch::Exact("HUAWEI FreeClip"), freeclip),
+    (NameMatch::Exact("HUAWEI FreeBuds Pro 2"), freebuds_pro2),
+    (NameMatch::Exact("HUAWEI FreeBuds Pro"), freebuds_pro2),
+    (NameMatch::Exact("HUAWEI FreeBuds 5"), freebuds_5),
+    (NameMatch::Exact("HUAWEI FreeBuds 5i"), freebuds_5i),
+    (NameMatch::Exact("HUAWEI FreeBuds 6i"), freebuds_6i),
+    (NameMatch::Exact("HUAWEI FreeBuds 4i"), freebuds_4i),
+    (NameMatch::Exact("HONOR Earbuds 2"), freebuds_4i),
+    (NameMatch::Exact("HONOR Earbuds 2 SE"), freebuds_4i),
+    (NameMatch::Exact("HONOR Earbuds 2 Lite"), freebuds_4i),
+    (NameMatch::Exact("HUAWEI FreeBuds SE 2"), freebuds_se2),
+    // Other Huawei / HONOR models speak the same protocol; probe for features
+    (NameMatch::Prefix("HUAWEI Free"), generic_probe),
+    (NameMatch::Prefix("HONOR Earbuds"), generic_probe),
+    // AirPods / Beats (AACP)
+    (NameMatch::Contains("AirPods Pro"), airpods_pro),
+    (NameMatch::Contains("AirPods Max"), airpods_max),
+    (NameMatch::Contains("AirPods"), airpods_generic),
+    (NameMatch::Contains("Beats"), airpods_generic),
+];
+
+fn lookup(name: &str) -> Option<ProfileBuilder> {
+    REGISTRY
+        .iter()
+        .find(|(pattern, _)| pattern.matches(name))
+        .map(|(_, build)| *build)
+}
+
+/// Every profile, for looking one up by name.
+const PROFILES: &[ProfileBuilder] = &[
+    freebuds_pro3,
+    freebuds_pro4,
+    freeclip,
+    freebuds_pro2,
+    freebuds_5,
+    freebuds_5i,
+    freebuds_6i,
+    freebuds_4i,
+    freebuds_se2,
+    generic_probe,
+    airpods_pro,
+    airpods_max,
+    airpods_generic,
+];
+
+/// Profile with the given name (case-insensitive), e.g. "FreeBuds Pro 2".
+pub fn profile_by_name(name: &str) -> Option<DeviceProfile> {
+    PROFILES
+        .iter()
+        .map(|build| build())
+        .find(|profile| profile.name.eq_ignore_ascii_case(name))
+}
+
+/// Profiles a reported model code (`info.device_model`) moves a device to,
+/// by the profile picked from its name. For models that share a Bluetooth
+/// name with an older one, or were renamed by the user. Add an entry once
+/// the code has been confirmed on a device:
+///
+/// ```ignore
+/// ("FreeBuds Pro 3", "T0052", freebuds_pro4),
+/// ```
+const REFINEMENTS: &[(&str, &str, ProfileBuilder)] = &[];
+
+/// A better profile than `profile` for a device reporting `model_code`:
+/// a built-in refinement, else the model database entry listing the code.
+pub fn refine(profile: &str, model_code: &str) -> Option<DeviceProfile> {
+    REFINEMENTS
+        .iter()
+        .find(|(base, code, _)| *base == profile && *code == model_code)
+        .map(|(_, _, build)| build())
+        .or_else(|| database::refine(profile, model_code))
+        .filter(|refined| refined.name != profile)
+}
+
+/// Whether a device name belongs to a supported device family.
+pub fn is_supported_device(name: &str) -> bool {
+    database::knows(name) || lookup(name).is_some()
+}
+
+/// Get device profile by Bluetooth device name: the model database's
+/// entry, else the registry's. Unknown names get the generic probe.
 pub fn profile_for_device(name: &str) -> DeviceProfile {
-    match name {
-        // Huawei / HONOR
-        "HUAWEI FreeBuds Pro 3" | "HUAWEI FreeBuds Pro 4" | "HUAWEI FreeClip" => freebuds_pro3(),
-        "HUAWEI FreeBuds Pro 2" | "HUAWEI FreeBuds Pro" => freebuds_pro2(),
-        "HUAWEI FreeBuds 5" => freebuds_5(),
-        "HUAWEI FreeBuds 5i" => freebuds_5i(),
-        "HUAWEI FreeBuds 6i" => freebuds_6i(),
-        "HUAWEI FreeBuds 4i" | "HONOR Earbuds 2" | "HONOR Earbuds 2 SE"
-        | "HONOR Earbuds 2 Lite" => freebuds_4i(),
-        "HUAWEI FreeBuds SE 2" => freebuds_se2(),
-
-        // AirPods
-        n if n.contains("AirPods Pro") => airpods_pro(),
-        n if n.contains("AirPods Max") => airpods_max(),
-        n if n.contains("AirPods") => airpods_generic(),
-
-        _ => generic_probe(),
+    database::profile(name)
+        .unwrap_or_else(|| lookup(name).map_or_else(generic_probe, |build| build()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_drives_profiles_and_filter() {
+        assert_eq!(profile_for_device("HUAWEI FreeClip").name, freeclip().name);
+        assert_eq!(profile_for_device("HUAWEI FreeBuds Pro 4").name, "FreeBuds Pro 4");
+        assert_eq!(profile_by_name("freebuds pro 4").unwrap().name, "FreeBuds Pro 4");
+        assert_eq!(profile_for_device("Josh's AirPods Pro").name, "AirPods Pro");
+        assert_eq!(profile_for_device("Beats Studio Buds").name, "AirPods");
+        assert_eq!(profile_for_device("HUAWEI FreeBuds 7").name, "Generic Huawei");
+
+        assert!(is_supported_device("HUAWEI FreeLace Pro"));
+        assert!(is_supported_device("Josh's AirPods"));
+        assert!(!is_supported_device("JBL Flip 5"));
     }
 }
@@ -0,0 +1,136 @@
+//! Per-profile feature overrides from the `[overrides."<name>"]` config
+//! tables.
+//!
+//! Firmware updates add or drop features without a new model name. A table
+//! keyed by the profile name or the device's Bluetooth name can switch
+//! whole features (handlers) on and off, or flip the flags handlers are
+//! built with (see `DeviceHandler::set_flag`).
+
+use std::collections::HashMap;
+
+use tracing::{info, warn};
+
+use super::{DeviceProfile, Transport};
+use crate::device::anc::AncHandler;
+use crate::device::config::{AutoPauseHandler, InEarHandler, LowLatencyHandler, SoundQualityHandler};
+use crate::device::dual_connect::DualConnectHandler;
+use crate::device::equalizer::EqualizerHandler;
+use crate::device::gestures::{LongTapSplitHandler, SwipeGestureHandler, TapActionHandler};
+use crate::device::handler::DeviceHandler;
+
+/// Builds the handler added when a feature is switched on for a profile
+/// without it.
+type Build = fn() -> Box<dyn DeviceHandler>;
+
+/// Feature switches: override key, handler id and builder (Huawei only).
+const FEATURES: &[(&str, &str, Build)] = &[
+    ("anc", "anc", || Box::new(AncHandler::default())),
+    ("double_tap", "gesture_double", || Box::new(TapActionHandler::double_tap(false))),
+    ("triple_tap", "gesture_triple", || Box::new(TapActionHandler::triple_tap())),
+    ("long_tap", "gesture_long_split", || Box::new(LongTapSplitHandler::default())),
+    ("swipe_gesture", "gesture_swipe", || Box::new(SwipeGestureHandler)),
+    ("equalizer", "config_eq", || {
+        Box::new(EqualizerHandler::with_presets(vec![
+            (1, "default"),
+            (2, "hardbass"),
+            (3, "treble"),
+            (9, "voices"),
+        ]))
+    }),
+    ("dual_connect", "dual_connect", || Box::new(DualConnectHandler::default())),
+    ("low_latency", "low_latency", || Box::new(LowLatencyHandler)),
+    ("sound_quality", "config_sound_quality", || Box::new(SoundQualityHandler)),
+    ("auto_pause", "tws_auto_pause", || Box::new(AutoPauseHandler)),
+    ("wear_detection", "in_ear", || Box::new(InEarHandler)),
+];
+
+/// Apply the override tables naming `profile` or `device_name` (case
+/// insensitive). Unknown keys are logged and skipped.
+pub fn apply(
+    profile: &mut DeviceProfile,
+    device_name: &str,
+    overrides: &HashMap<String, HashMap<String, bool>>,
+) {
+    let tables = overrides.iter().filter(|(name, _)| {
+        name.eq_ignore_ascii_case(profile.name) || name.eq_ignore_ascii_case(device_name)
+    });
+    for (table, flags) in tables {
+        // Sorted so the outcome doesn't depend on hash order
+        let mut flags: Vec<(&String, &bool)> = flags.iter().collect();
+        flags.sort();
+        for (flag, &value) in flags {
+            if apply_one(profile, flag, value) {
+                info!("Override from [overrides.\"{}\"]: {} = {}", table, flag, value);
+            } else {
+                warn!("Ignoring unknown override [overrides.\"{}\"] {}", table, flag);
+            }
+        }
+    }
+}
+
+/// Apply one feature switch or handler flag; false if nothing knows it.
+pub(super) fn apply_one(profile: &mut DeviceProfile, flag: &str, value: bool) -> bool {
+    if let Some(&(_, id, build)) = FEATURES.iter().find(|(key, ..)| *key == flag) {
+        let present = profile.handlers.iter().any(|h| h.handler_id() == id);
+        if !value {
+            profile.handlers.retain(|h| h.handler_id() != id);
+        } else if !present {
+            if matches!(profile.transport, Transport::L2cap(_)) {
+                warn!("{} can't be added to the {} profile", flag, profile.name);
+            } else {
+                profile.handlers.push(build());
+            }
+        }
+        return true;
+    }
+    // Every handler with the flag gets it, not just the first
+    let mut found = false;
+    for handler in &mut profile.handlers {
+        found |= handler.set_flag(flag, value);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::models::freebuds_5i;
+
+    fn ids(profile: &DeviceProfile) -> Vec<&'static str> {
+        profile.handlers.iter().map(|h| h.handler_id()).collect()
+    }
+
+    #[test]
+    fn switches_features_of_matching_profiles() {
+        let overrides = HashMap::from([
+            (
+                "huawei freebuds 5i".to_string(),
+                HashMap::from([
+                    ("triple_tap".to_string(), false),
+                    ("dynamic_anc".to_string(), true),
+                    ("warp_drive".to_string(), true),
+                ]),
+            ),
+            (
+                "FreeBuds Pro 2".to_string(),
+                HashMap::from([("triple_tap".to_string(), false)]),
+            ),
+        ]);
+
+        let mut profile = freebuds_5i();
+        apply(&mut profile, "HUAWEI FreeBuds 5i", &overrides);
+        assert!(!ids(&profile).contains(&"gesture_triple"));
+        assert!(ids(&profile).contains(&"gesture_double"));
+
+        // Matched by profile name too; adding a missing feature
+        let overrides = HashMap::from([(
+            "FreeBuds 5i".to_string(),
+            HashMap::from([("triple_tap".to_string(), true)]),
+        )]);
+        apply(&mut profile, "Some Name", &overrides);
+        assert_eq!(ids(&profile).iter().filter(|&&id| id == "gesture_triple").count(), 1);
+
+        assert!(apply_one(&mut profile, "dynamic_anc", true));
+        assert!(!apply_one(&mut profile, "warp_drive", true));
+    }
+}
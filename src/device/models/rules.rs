@@ -0,0 +1,131 @@
+//! User-defined device match rules from the `[[device_rules]]` config table.
+//!
+//! Lets a renamed device (or a regional name variant) use a known profile
+//! without code changes. Rules are checked before the built-in registry.
+
+use regex_automata::meta::Regex;
+use tracing::warn;
+
+use super::{is_supported_device, profile_by_name, profile_for_device, DeviceProfile};
+use crate::config::DeviceRule;
+
+struct CompiledRule {
+    name: Option<Regex>,
+    /// Uppercase, so "aa:bb:cc" matches "AA:BB:CC:..".
+    address_prefix: Option<String>,
+    profile: String,
+}
+
+impl CompiledRule {
+    fn matches(&self, name: &str, address: &str) -> bool {
+        let name_ok = self.name.as_ref().is_none_or(|re| re.is_match(name));
+        let address_ok = self
+            .address_prefix
+            .as_ref()
+            .is_none_or(|p| address.to_ascii_uppercase().starts_with(p.as_str()));
+        name_ok && address_ok
+    }
+}
+
+/// Compiled `[[device_rules]]`. Invalid rules are logged and skipped.
+#[derive(Default)]
+pub struct DeviceRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl DeviceRules {
+    pub fn compile(rules: &[DeviceRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| {
+                if rule.name.is_none() && rule.address_prefix.is_none() {
+                    warn!(
+                        "Device rule for '{}' has no name or address_prefix, ignoring",
+                        rule.profile
+                    );
+                    return None;
+                }
+                if profile_by_name(&rule.profile).is_none() {
+                    warn!(
+                        "Device rule names unknown profile '{}', ignoring",
+                        rule.profile
+                    );
+                    return None;
+                }
+                let name = match rule.name.as_deref().map(Regex::new).transpose() {
+                    Ok(name) => name,
+                    Err(e) => {
+                        warn!(
+                            "Device rule for '{}' has an invalid name pattern: {}",
+                            rule.profile, e
+                        );
+                        return None;
+                    }
+                };
+                Some(CompiledRule {
+                    name,
+                    address_prefix: rule.address_prefix.as_ref().map(|p| p.to_ascii_uppercase()),
+                    profile: rule.profile.clone(),
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    fn matching(&self, name: &str, address: &str) -> Option<&CompiledRule> {
+        self.rules.iter().find(|r| r.matches(name, address))
+    }
+
+    /// Whether the device is supported, by a rule or by its name.
+    pub fn is_supported(&self, name: &str, address: &str) -> bool {
+        self.matching(name, address).is_some() || is_supported_device(name)
+    }
+
+    /// Profile for the device: the first matching rule's, else the one the
+    /// built-in registry picks from the name.
+    pub fn profile(&self, name: &str, address: &str) -> DeviceProfile {
+        self.matching(name, address)
+            .and_then(|rule| profile_by_name(&rule.profile))
+            .unwrap_or_else(|| profile_for_device(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: Option<&str>, address_prefix: Option<&str>, profile: &str) -> DeviceRule {
+        DeviceRule {
+            name: name.map(Into::into),
+            address_prefix: address_prefix.map(Into::into),
+            profile: profile.into(),
+        }
+    }
+
+    #[test]
+    fn rules_override_the_registry() {
+        let rules = DeviceRules::compile(&[
+            rule(Some("^Buds de Josh$"), None, "FreeBuds Pro 2"),
+            rule(None, Some("aa:bb:cc"), "airpods pro"),
+            rule(Some("("), None, "FreeBuds Pro 2"),
+            rule(Some("x"), None, "No Such Buds"),
+        ]);
+        assert_eq!(rules.rules.len(), 2);
+
+        let addr = "11:22:33:44:55:66";
+        assert_eq!(rules.profile("Buds de Josh", addr).name, "FreeBuds Pro 2");
+        assert!(rules.is_supported("Buds de Josh", addr));
+        assert_eq!(rules.profile("Buds de Josh 2", addr).name, "Generic Huawei");
+        assert!(!rules.is_supported("Buds de Josh 2", addr));
+
+        assert_eq!(
+            rules.profile("Kitchen", "AA:BB:CC:01:02:03").name,
+            "AirPods Pro"
+        );
+        // Without rules, names still resolve through the registry
+        assert_eq!(
+            rules.profile("HUAWEI FreeBuds 5i", addr).name,
+            "FreeBuds 5i"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Names for EQ presets the equalizer handler has no built-in name for.
+//!
+//! The handler publishes such presets as `equalizer_preset_unknown_<id>`.
+//! Names come from the user's `preset_labels.toml` (written by the Sound
+//! page) and then from the `[preset_labels]` of the model database, so
+//! names confirmed there reach everyone with `model_db_sync`. Both are keyed
+//! by profile name and preset ID. The user's labels stay in their own file:
+//! the synced database copy is replaced on every update.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+use super::models::database;
+use crate::labels;
+
+/// Prefix of the keys the handler gives unnamed presets.
+pub const UNKNOWN_PREFIX: &str = "equalizer_preset_unknown_";
+
+/// The user's labels, read on first use and kept in step by [`save`].
+static USER: Mutex<Option<toml::Table>> = Mutex::new(None);
+
+fn user_path() -> PathBuf {
+    crate::config::AppConfig::path().with_file_name("preset_labels.toml")
+}
+
+fn read_user() -> toml::Table {
+    match std::fs::read_to_string(user_path()) {
+        Ok(contents) => contents.parse().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring invalid preset_labels.toml: {}", e);
+            toml::Table::new()
+        }),
+        Err(_) => toml::Table::new(),
+    }
+}
+
+fn lookup_in(table: &toml::Table, model: &str, id: u8) -> Option<String> {
+    table
+        .get(model)?
+        .get(id.to_string())?
+        .as_str()
+        .map(String::from)
+}
+
+/// Preset ID of an `equalizer_preset_unknown_<id>` key.
+pub fn unknown_id(key: &str) -> Option<u8> {
+    key.strip_prefix(UNKNOWN_PREFIX)?.parse().ok()
+}
+
+/// Name for preset `id` of `model`: the user's label, then the model
+/// database's.
+pub fn lookup(model: &str, id: u8) -> Option<String> {
+    let user = lookup_in(USER.lock().unwrap().get_or_insert_with(read_user), model, id);
+    user.or_else(|| database::preset_label(model, id))
+}
+
+/// Display name for a preset key, naming unknown presets from the labels
+/// of `model` when there is one.
+pub fn display(model: Option<&str>, key: &str) -> String {
+    unknown_id(key)
+        .zip(model)
+        .and_then(|(id, model)| lookup(model, id))
+        .unwrap_or_else(|| labels::eq_preset(key))
+}
+
+/// Store `label` for preset `id` of `model` in the user's labels; an empty
+/// label removes it.
+pub fn save(model: &str, id: u8, label: &str) -> Result<()> {
+    let path = user_path();
+    let mut user = USER.lock().unwrap();
+    let table = user.insert(read_user());
+    let models = table
+        .entry(model)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let toml::Value::Table(presets) = models else {
+        anyhow::bail!("{} in {} is not a table", model, path.display());
+    };
+    match label.trim() {
+        "" => presets.remove(&id.to_string()),
+        label => presets.insert(id.to_string(), label.into()),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, toml::to_string(table)?)
+        .with_context(|| format!("Cannot write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_unknown_presets() {
+        assert_eq!(unknown_id("equalizer_preset_unknown_12"), Some(12));
+        assert_eq!(unknown_id("equalizer_preset_voices"), None);
+        assert_eq!(display(None, "equalizer_preset_unknown_12"), "Preset 12 (unknown)");
+        assert_eq!(display(None, "equalizer_preset_hardbass"), "Bass Boost");
+
+        let table: toml::Table = "[\"FreeBuds 5\"]\n7 = \"Classical\"\n".parse().unwrap();
+        assert_eq!(lookup_in(&table, "FreeBuds 5", 7).as_deref(), Some("Classical"));
+        assert_eq!(lookup_in(&table, "FreeBuds 5", 8), None);
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+/// How long a caller waits for the device manager to act on a request.
+/// Requests are only served while a connection is up, so a timeout means
+/// the device is (still) disconnected.
+pub const REPLY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Why a property change did not go through.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+pub enum SetPropertyError {
+    /// No handler of the connected device owns this group.
+    #[error("no handler for group '{0}'")]
+    NoHandler(String),
+    /// The value doesn't match what the device reported as allowed.
+    #[error("invalid value '{value}' for {prop}, expected {expected}")]
+    InvalidValue {
+        prop: String,
+        value: String,
+        expected: String,
+    },
+    /// The handler refused the value (unknown option, unsupported feature...).
+    #[error("rejected: {0}")]
+    Rejected(String),
+    /// No device connection to send the change over.
+    #[error("device not connected")]
+    NotConnected,
+}
+
+/// A property change request sent from a UI (or integration) to the
+/// device manager, with an optional channel for the outcome.
+#[derive(Debug)]
+pub struct PropertyRequest {
+    pub group: String,
+    pub prop: String,
+    pub value: String,
+    pub reply: Option<oneshot::Sender<Result<(), SetPropertyError>>>,
+}
+
+/// Sender half of the property request channel.
+pub type PropertySender = mpsc::Sender<PropertyRequest>;
+
+/// Receiver half of the property request channel.
+pub type PropertyReceiver = mpsc::Receiver<PropertyRequest>;
+
+impl PropertyRequest {
+    /// Build a request and the receiver its outcome will arrive on.
+    pub fn new(
+        group: &str,
+        prop: &str,
+        value: &str,
+    ) -> (Self, oneshot::Receiver<Result<(), SetPropertyError>>) {
+        let (tx, rx) = oneshot::channel();
+        let mut req = Self::fire_and_forget(group, prop, value);
+        req.reply = Some(tx);
+        (req, rx)
+    }
+
+    /// Build a request nobody waits on (tray, background integrations).
+    pub fn fire_and_forget(group: &str, prop: &str, value: &str) -> Self {
+        Self {
+            group: group.to_string(),
+            prop: prop.to_string(),
+            value: value.to_string(),
+            reply: None,
+        }
+    }
+
+    /// Report the outcome back to the caller, if it is still listening.
+    pub fn respond(self, result: Result<(), SetPropertyError>) {
+        if let Some(reply) = self.reply {
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Send a request and wait for its outcome.
+/// Ok means the change was accepted and the packet queued for the device.
+pub async fn request(
+    tx: &PropertySender,
+    group: &str,
+    prop: &str,
+    value: &str,
+) -> Result<(), SetPropertyError> {
+    let (req, rx) = PropertyRequest::new(group, prop, value);
+    tx.send(req)
+        .await
+        .map_err(|_| SetPropertyError::NotConnected)?;
+    match tokio::time::timeout(REPLY_TIMEOUT, rx).await {
+        Ok(Ok(result)) => result,
+        // Manager dropped the request or never got to it
+        Ok(Err(_)) | Err(_) => Err(SetPropertyError::NotConnected),
+    }
+}
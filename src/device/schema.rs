@@ -0,0 +1,126 @@
+//! Validation of property writes against what the device reported.
+//!
+//! Handlers publish the allowed values next to each property (`<prop>_options`,
+//! `<prop>_max`, or a boolean current value). Checking writes against those
+//! before they reach a handler means a bad value comes back to the caller as
+//! `InvalidValue` instead of turning into a malformed packet.
+
+use std::collections::HashMap;
+
+use super::request::SetPropertyError;
+
+/// Allowed options for `prop`: `<prop>_options`, or for per-side
+/// properties (`double_tap_left`) the shared `<base>_options`.
+fn options_for<'a>(values: &'a HashMap<String, String>, prop: &str) -> Option<&'a str> {
+    if let Some(opts) = values.get(&format!("{}_options", prop)) {
+        return Some(opts);
+    }
+    let base = prop
+        .strip_suffix("_left")
+        .or_else(|| prop.strip_suffix("_right"))?;
+    values.get(&format!("{}_options", base)).map(String::as_str)
+}
+
+fn invalid(group: &str, prop: &str, value: &str, expected: String) -> SetPropertyError {
+    SetPropertyError::InvalidValue {
+        prop: format!("{}.{}", group, prop),
+        value: value.to_string(),
+        expected,
+    }
+}
+
+/// Check `group.prop = value` against the device's published schema in
+/// `store_group`, the group the handler taking the write publishes into
+/// (see `DeviceHandler::store_group`). Properties the device hasn't
+/// described yet are let through.
+pub fn validate(
+    store: &HashMap<String, HashMap<String, String>>,
+    store_group: &str,
+    group: &str,
+    prop: &str,
+    value: &str,
+) -> Result<(), SetPropertyError> {
+    let Some(values) = store.get(store_group) else {
+        return Ok(());
+    };
+
+    if let Some(options) = options_for(values, prop) {
+        if options.split(',').any(|o| o == value) {
+            return Ok(());
+        }
+        return Err(invalid(group, prop, value, format!("one of {}", options)));
+    }
+
+    if let Some(max) = values
+        .get(&format!("{}_max", prop))
+        .and_then(|m| m.parse::<u32>().ok())
+    {
+        return match value.parse::<u32>() {
+            Ok(v) if v <= max => Ok(()),
+            _ => Err(invalid(group, prop, value, format!("a number from 0 to {}", max))),
+        };
+    }
+
+    let is_bool = |s: &str| s == "true" || s == "false";
+    if values.get(prop).is_some_and(|cur| is_bool(cur)) && !is_bool(value) {
+        return Err(invalid(group, prop, value, "true or false".into()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::test_support::group;
+
+    fn store() -> HashMap<String, HashMap<String, String>> {
+        HashMap::from([
+            (
+                "anc".to_string(),
+                group(&[
+                    ("mode", "cancellation"),
+                    ("level_options", "comfort,normal,ultra"),
+                    ("depth", "40"),
+                    ("depth_max", "100"),
+                ]),
+            ),
+            (
+                "action".to_string(),
+                group(&[("double_tap_options", "tap_action_off,tap_action_pause")]),
+            ),
+            ("config".to_string(), group(&[("auto_pause", "true")])),
+        ])
+    }
+
+    #[test]
+    fn rejects_values_outside_published_options() {
+        let store = store();
+        assert!(validate(&store, "anc", "anc", "level", "ultra").is_ok());
+        assert!(matches!(
+            validate(&store, "anc", "anc", "level", "dynamic"),
+            Err(SetPropertyError::InvalidValue { .. })
+        ));
+        // Per-side gesture props share one option list
+        assert!(validate(&store, "action", "action", "double_tap_left", "tap_action_pause").is_ok());
+        assert!(validate(&store, "action", "action", "double_tap_right", "tap_action_next").is_err());
+    }
+
+    #[test]
+    fn checks_ranges_and_booleans() {
+        let store = store();
+        assert!(validate(&store, "anc", "anc", "depth", "100").is_ok());
+        assert!(validate(&store, "anc", "anc", "depth", "101").is_err());
+        assert!(validate(&store, "anc", "anc", "depth", "loud").is_err());
+        // Handler-ID groups are checked against the handler's store group
+        assert!(validate(&store, "config", "tws_auto_pause", "auto_pause", "false").is_ok());
+        assert!(validate(&store, "config", "tws_auto_pause", "auto_pause", "yes").is_err());
+    }
+
+    #[test]
+    fn unknown_properties_pass_through() {
+        let store = store();
+        assert!(validate(&store, "anc", "anc", "mode", "whatever").is_ok());
+        assert!(validate(&store, "dual_connect", "dual_connect", "enabled", "true").is_ok());
+    }
+}
@@ -0,0 +1,196 @@
+//! Snapshot and restore of the device's writable settings.
+//!
+//! Taken before experimenting (generic probe on unknown firmware, a factory
+//! reset) so the previous setup can be replayed afterwards. Stored as TOML at
+//! ~/.config/mybuds/settings_snapshot.toml.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::request::{self, PropertySender};
+use crate::integrations::inhibit::SuspendInhibitor;
+
+/// Writable settings, in restore order. ANC mode goes first because the
+/// level only applies within a mode.
+const SETTINGS: &[(&str, &[&str])] = &[
+    ("anc", &["mode", "level", "anc_strength", "one_bud_anc"]),
+    ("sound", &["equalizer_preset", "quality_preference"]),
+    ("config", &["auto_pause", "low_latency"]),
+    ("dual_connect", &["enabled", "preferred_device"]),
+    ("ear_detection", &["enabled"]),
+    ("conversation_awareness", &["enabled"]),
+    ("personalized_volume", &["enabled"]),
+    ("press_speed", &["speed"]),
+    ("press_duration", &["duration"]),
+    ("volume_swipe", &["enabled", "interval"]),
+    ("tone_volume", &["level"]),
+];
+
+/// Gesture assignments, all of which are writable.
+const ACTION_GROUP: &str = "action";
+
+/// One saved property.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Setting {
+    pub group: String,
+    pub prop: String,
+    pub value: String,
+}
+
+/// Saved settings of one device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsSnapshot {
+    /// Device name when the snapshot was taken, for display only.
+    pub device: String,
+    pub settings: Vec<Setting>,
+}
+
+/// Outcome of replaying a snapshot.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub applied: usize,
+    /// `group.prop` and the reason it couldn't be set.
+    pub failed: Vec<(String, String)>,
+}
+
+impl SettingsSnapshot {
+    /// Snapshot file path: ~/.config/mybuds/settings_snapshot.toml
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("mybuds")
+            .join("settings_snapshot.toml")
+    }
+
+    /// Collect every writable setting the device currently reports.
+    pub fn capture(store: &HashMap<String, HashMap<String, String>>) -> Self {
+        let mut settings = Vec::new();
+        let mut push = |group: &str, prop: &str, value: &str| {
+            settings.push(Setting {
+                group: group.to_string(),
+                prop: prop.to_string(),
+                value: value.to_string(),
+            })
+        };
+
+        for (group, props) in SETTINGS {
+            let Some(values) = store.get(*group) else {
+                continue;
+            };
+            for prop in *props {
+                // Empty means unset (e.g. no preferred device); nothing to replay
+                if let Some(value) = values.get(*prop).filter(|v| !v.is_empty()) {
+                    push(group, prop, value);
+                }
+            }
+        }
+        if let Some(actions) = store.get(ACTION_GROUP) {
+            let mut props: Vec<_> = actions
+                .iter()
+                .filter(|(k, _)| !k.ends_with("_options"))
+                .collect();
+            props.sort();
+            for (prop, value) in props {
+                push(ACTION_GROUP, prop, value);
+            }
+        }
+
+        let device = store
+            .get("info")
+            .and_then(|i| i.get("device_name"))
+            .cloned()
+            .unwrap_or_default();
+        Self { device, settings }
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("No settings snapshot at {}", path.display()))?;
+        toml::from_str(&contents).context("Settings snapshot is unreadable")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Write every saved setting back to the device, continuing past
+    /// failures (a setting the current firmware doesn't have shouldn't stop
+    /// the rest). Suspend is held off meanwhile, so the device isn't left
+    /// half restored.
+    pub async fn restore(&self, tx: &PropertySender) -> RestoreReport {
+        let _inhibitor = tokio::task::spawn_blocking(|| {
+            SuspendInhibitor::try_acquire("Restoring headphone settings")
+        })
+        .await
+        .ok()
+        .flatten();
+        let mut report = RestoreReport::default();
+        for s in &self.settings {
+            match request::request(tx, &s.group, &s.prop, &s.value).await {
+                Ok(()) => report.applied += 1,
+                Err(e) => report
+                    .failed
+                    .push((format!("{}.{}", s.group, s.prop), e.to_string())),
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::test_support::group;
+
+    #[test]
+    fn captures_only_writable_settings() {
+        let store = HashMap::from([
+            (
+                "anc".to_string(),
+                group(&[
+                    ("level", "ultra"),
+                    ("mode", "cancellation"),
+                    ("mode_options", "normal,cancellation"),
+                ]),
+            ),
+            ("battery".to_string(), group(&[("global", "80")])),
+            (
+                "action".to_string(),
+                group(&[
+                    ("double_tap_right", "tap_action_next"),
+                    ("double_tap_left", "tap_action_pause"),
+                    ("double_tap_options", "tap_action_off,tap_action_pause"),
+                ]),
+            ),
+        ]);
+
+        let snap = SettingsSnapshot::capture(&store);
+        let keys: Vec<_> = snap
+            .settings
+            .iter()
+            .map(|s| format!("{}.{}={}", s.group, s.prop, s.value))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "anc.mode=cancellation",
+                "anc.level=ultra",
+                "action.double_tap_left=tap_action_pause",
+                "action.double_tap_right=tap_action_next",
+            ]
+        );
+
+        let round_trip: SettingsSnapshot =
+            toml::from_str(&toml::to_string(&snap).unwrap()).unwrap();
+        assert_eq!(round_trip, snap);
+    }
+}
@@ -0,0 +1,11 @@
+//! Fixtures shared by unit tests.
+
+use std::collections::HashMap;
+
+/// One property-store group from `(key, value)` pairs.
+pub fn group(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
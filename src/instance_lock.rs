@@ -3,6 +3,16 @@ use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::control::{self, ControlCommand};
+use crate::{logging, sandbox};
+use crate::varlink;
+
+/// How long `--replace` waits for the old instance to exit.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Single instance lock using flock(2)
 pub struct InstanceLock {
@@ -18,7 +28,7 @@ impl InstanceLock {
 
         // Create parent directory if it doesn't exist
         if let Some(parent) = lock_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            logging::create_private_dir(parent)?;
         }
 
         // Open (or create) the lock file
@@ -36,7 +46,8 @@ impl InstanceLock {
         if result != 0 {
             return Err(anyhow!(
                 "Another instance of MyBuds is already running.\n\
-                 Only one instance is allowed at a time.\n\
+                 Only one instance is allowed at a time; use --show to raise it\n\
+                 or --replace to take over.\n\
                  Lock file: {}",
                 lock_path.display()
             ));
@@ -54,14 +65,46 @@ impl InstanceLock {
         })
     }
 
-    fn lock_file_path() -> Result<PathBuf> {
-        // Use XDG_RUNTIME_DIR if available (better for locks), fallback to /tmp
-        let lock_dir = std::env::var("XDG_RUNTIME_DIR")
-            .ok()
-            .map(PathBuf::from)
-            .unwrap_or_else(|| PathBuf::from("/tmp"));
+    /// Take over from a running instance: ask it to quit over the control
+    /// socket (or SIGTERM it if that fails), then wait for the lock.
+    pub fn acquire_replacing() -> Result<Self> {
+        if let Err(e) = control::send_expecting_ok(&ControlCommand::Quit) {
+            warn!("{:#}", e);
+            let pid = std::fs::read_to_string(Self::lock_file_path()?)
+                .ok()
+                .and_then(|s| s.trim().parse::<libc::pid_t>().ok());
+            let Some(pid) = pid else {
+                return Err(anyhow!("Could not reach the running instance"));
+            };
+            info!("Sending SIGTERM to running instance (pid {})", pid);
+            unsafe { libc::kill(pid, libc::SIGTERM) };
+        }
+
+        let deadline = Instant::now() + REPLACE_TIMEOUT;
+        loop {
+            match Self::acquire() {
+                Ok(lock) => return Ok(lock),
+                Err(e) if Instant::now() >= deadline => return Err(e),
+                Err(_) => std::thread::sleep(Duration::from_millis(100)),
+            }
+        }
+    }
+
+    /// Per-user runtime directory for the lock and control socket. Inside
+    /// Flatpak, the app's directory shared by all its instances; without
+    /// XDG_RUNTIME_DIR, the state dir.
+    pub fn runtime_dir() -> PathBuf {
+        let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) else {
+            return logging::state_dir();
+        };
+        match sandbox::app_id() {
+            Some(app) => dir.join("app").join(app),
+            None => dir,
+        }
+    }
 
-        Ok(lock_dir.join("mybuds.lock"))
+    fn lock_file_path() -> Result<PathBuf> {
+        Ok(Self::runtime_dir().join("mybuds.lock"))
     }
 }
 
@@ -69,7 +112,10 @@ impl InstanceLock {
 impl Drop for InstanceLock {
     fn drop(&mut self) {
         // flock is automatically released when the file descriptor is closed
-        // Delete the lock file to clean up
+        // Delete the control socket and lock file to clean up. The socket
+        // goes first so a replacing instance can't bind before we're gone.
+        let _ = std::fs::remove_file(control::socket_path());
+        let _ = std::fs::remove_file(varlink::socket_path());
         let _ = std::fs::remove_file(&self.lock_path);
     }
 }
@@ -0,0 +1,47 @@
+//! Activity detection: notice when the buds switch audio source.
+//!
+//! Watches the Dual Connect device list and raises a notification when a
+//! different device starts playing, so a silent pause has an explanation.
+
+use std::time::Duration;
+
+use tracing::info;
+
+use super::notify;
+use crate::device::dual_connect::playing_device_name;
+use crate::device::handler::PropertyStore;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Spawn the audio source watcher on the current tokio runtime.
+pub fn spawn(props: PropertyStore) {
+    tokio::spawn(async move {
+        let mut current: Option<String> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let playing = props
+                .lock()
+                .await
+                .get("dual_connect")
+                .and_then(playing_device_name);
+            let Some(name) = playing else {
+                continue;
+            };
+            if current.as_deref() == Some(name.as_str()) {
+                continue;
+            }
+            // Only announce switches, not the first source we see
+            if current.is_some() {
+                info!("Audio source switched to {}", name);
+                notify::send(
+                    "Audio source changed",
+                    &format!("Now playing from {}", name),
+                );
+            }
+            current = Some(name);
+        }
+    });
+}
@@ -0,0 +1,261 @@
+//! Alert tones played through the buds.
+//!
+//! Short beeps are synthesized to WAV files and played on the default audio
+//! output with `pw-play` (PipeWire) or `paplay` (PulseAudio). Tones are
+//! skipped during quiet hours and while focus mode suppresses notifications.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use super::dnd;
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::AlertsConfig;
+use crate::device::handler::PropertyStore;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Battery level at which the buds are about to shut off.
+const CRITICAL_LEVEL: u8 = 5;
+const SAMPLE_RATE: u32 = 22_050;
+
+/// Events that have a tone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alert {
+    LowBattery,
+    BatteryCritical,
+}
+
+impl Alert {
+    /// Number of beeps; more urgent alerts beep more.
+    fn beeps(self) -> u32 {
+        match self {
+            Self::LowBattery => 1,
+            Self::BatteryCritical => 2,
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::LowBattery => "alert-low-battery.wav",
+            Self::BatteryCritical => "alert-battery-critical.wav",
+        }
+    }
+}
+
+/// Parse "HH:MM-HH:MM" into minutes since midnight.
+pub(super) fn parse_quiet_hours(s: &str) -> Option<(u32, u32)> {
+    let parse = |t: &str| -> Option<u32> {
+        let (h, m) = t.trim().split_once(':')?;
+        let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+        (h < 24 && m < 60).then_some(h * 60 + m)
+    };
+    let (start, end) = s.split_once('-')?;
+    Some((parse(start)?, parse(end)?))
+}
+
+/// Whether `now` (minutes since midnight) falls in the range, which may
+/// wrap past midnight.
+pub(super) fn in_quiet_hours((start, end): (u32, u32), now: u32) -> bool {
+    if start <= end {
+        (start..end).contains(&now)
+    } else {
+        now >= start || now < end
+    }
+}
+
+/// Local wall-clock time in minutes since midnight.
+pub(super) fn local_minutes() -> u32 {
+    // SAFETY: localtime_r only writes to the tm we hand it.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        (tm.tm_hour * 60 + tm.tm_min) as u32
+    }
+}
+
+/// Synthesize `beeps` short 880 Hz beeps as a 16-bit mono WAV.
+fn tone_wav(beeps: u32) -> Vec<u8> {
+    let beep = SAMPLE_RATE as usize * 150 / 1000;
+    let gap = SAMPLE_RATE as usize * 100 / 1000;
+    let mut samples: Vec<i16> = Vec::new();
+    for i in 0..beeps {
+        if i > 0 {
+            samples.extend(std::iter::repeat_n(0, gap));
+        }
+        samples.extend((0..beep).map(|n| {
+            let t = n as f32 / SAMPLE_RATE as f32;
+            // Short fade in/out avoids clicks
+            let edge = (n.min(beep - n) as f32 / 200.0).min(1.0);
+            ((t * 880.0 * std::f32::consts::TAU).sin() * edge * i16::MAX as f32 * 0.8) as i16
+        }));
+    }
+
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        wav.extend_from_slice(&s.to_le_bytes());
+    }
+    wav
+}
+
+/// Write the tone for `alert` to the cache dir (once) and return its path.
+fn tone_file(alert: Alert) -> std::io::Result<PathBuf> {
+    let dir = dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("mybuds");
+    let path = dir.join(alert.file_name());
+    if !path.exists() {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, tone_wav(alert.beeps()))?;
+    }
+    Ok(path)
+}
+
+/// Play a tone on the default output. Blocking; run off the async runtime.
+fn play_blocking(alert: Alert, volume: f32) {
+    let path = match tone_file(alert) {
+        Ok(p) => p,
+        Err(e) => {
+            warn!("Failed to write alert tone: {}", e);
+            return;
+        }
+    };
+    let volume = volume.clamp(0.0, 1.0);
+    let pw = std::process::Command::new("pw-play")
+        .arg(format!("--volume={}", volume))
+        .arg(&path)
+        .status();
+    if pw.is_ok_and(|s| s.success()) {
+        return;
+    }
+    // paplay volume is linear, 65536 = 100%
+    let pa = std::process::Command::new("paplay")
+        .arg(format!("--volume={}", (volume * 65536.0) as u32))
+        .arg(&path)
+        .status();
+    if !pa.is_ok_and(|s| s.success()) {
+        warn!("No audio player (pw-play/paplay) could play the alert tone");
+    }
+}
+
+/// Lowest reported bud battery level, ignoring the case and empty slots.
+pub(super) fn lowest_level(battery: &std::collections::HashMap<String, String>) -> Option<u8> {
+    ["left", "right", "global"]
+        .iter()
+        .filter_map(|k| battery.get(*k)?.parse::<u8>().ok())
+        .filter(|&v| v > 0)
+        .min()
+}
+
+/// Spawn the alert watcher on the current tokio runtime.
+pub fn spawn(config: AlertsConfig, props: PropertyStore) {
+    let quiet = config.quiet_hours.as_deref().and_then(|s| {
+        let parsed = parse_quiet_hours(s);
+        if parsed.is_none() {
+            warn!("Ignoring invalid alerts.quiet_hours {:?}", s);
+        }
+        parsed
+    });
+
+    tokio::spawn(async move {
+        let mut last_level: Option<u8> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let (connected, level, charging) = {
+                let store = props.lock().await;
+                let battery = store.get("battery");
+                (
+                    ConnectionState::from_group(store.get(CONNECTION_GROUP))
+                        == ConnectionState::Connected,
+                    battery.and_then(lowest_level),
+                    battery
+                        .and_then(|b| b.get("is_charging"))
+                        .is_some_and(|v| v == "true"),
+                )
+            };
+            // Tones go to the buds, so only while they're the output
+            if !connected || charging {
+                last_level = None;
+                continue;
+            }
+            let Some(level) = level else {
+                continue;
+            };
+            let previous = last_level.replace(level);
+
+            let crossed =
+                |threshold: u8| previous.is_some_and(|p| p > threshold) && level <= threshold;
+            let alert = if config.battery_critical && crossed(CRITICAL_LEVEL) {
+                Alert::BatteryCritical
+            } else if config.low_battery && crossed(config.low_battery_threshold) {
+                Alert::LowBattery
+            } else {
+                continue;
+            };
+
+            if quiet.is_some_and(|q| in_quiet_hours(q, local_minutes())) {
+                debug!("Alert {:?} skipped during quiet hours", alert);
+                continue;
+            }
+            if dnd::notifications_suppressed() {
+                debug!("Alert {:?} suppressed by focus mode", alert);
+                continue;
+            }
+
+            info!("Playing {:?} alert at {}%", alert, level);
+            let volume = config.volume;
+            tokio::task::spawn_blocking(move || play_blocking(alert, volume));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_hours_wrap_past_midnight() {
+        let q = parse_quiet_hours("22:00-07:00").unwrap();
+        assert_eq!(q, (22 * 60, 7 * 60));
+        assert!(in_quiet_hours(q, 23 * 60));
+        assert!(in_quiet_hours(q, 6 * 60 + 59));
+        assert!(!in_quiet_hours(q, 7 * 60));
+        assert!(!in_quiet_hours(q, 12 * 60));
+
+        let day = parse_quiet_hours("12:30-13:00").unwrap();
+        assert!(in_quiet_hours(day, 12 * 60 + 45));
+        assert!(!in_quiet_hours(day, 13 * 60));
+
+        assert!(parse_quiet_hours("25:00-07:00").is_none());
+        assert!(parse_quiet_hours("22:00").is_none());
+    }
+
+    #[test]
+    fn tone_wav_header_matches_data() {
+        let wav = tone_wav(2);
+        assert_eq!(&wav[..4], b"RIFF");
+        let data_len = u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize;
+        assert_eq!(wav.len(), 44 + data_len);
+        assert_eq!(
+            u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize,
+            wav.len() - 8
+        );
+    }
+}
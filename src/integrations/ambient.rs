@@ -0,0 +1,304 @@
+//! Ambient sound player: rain or noise played to the buds, for focus and
+//! sleep.
+//!
+//! The sound is synthesized on the fly and streamed to `pacat` (PulseAudio,
+//! and PipeWire through pipewire-pulse) or `pw-cat`, on the buds' Bluetooth
+//! sink when it can be found and the default output otherwise. There is one
+//! player per process, shared by the Relax page and the tray. A sleep timer
+//! fades the sound out and stops it.
+
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tracing::{debug, info};
+
+const SAMPLE_RATE: u32 = 44_100;
+/// Samples written per pipe write, 100 ms.
+const CHUNK: usize = SAMPLE_RATE as usize / 10;
+/// How long the sleep timer takes to fade the sound out.
+const FADE_OUT: Duration = Duration::from_secs(10);
+
+/// Sleep timer choices offered by the UIs, in minutes.
+pub const SLEEP_TIMERS: &[u64] = &[15, 30, 60, 90];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Rain,
+    BrownNoise,
+    WhiteNoise,
+}
+
+impl Sound {
+    pub const ALL: [Sound; 3] = [Sound::Rain, Sound::BrownNoise, Sound::WhiteNoise];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Sound::Rain => "Rain",
+            Sound::BrownNoise => "Brown noise",
+            Sound::WhiteNoise => "White noise",
+        }
+    }
+}
+
+impl std::fmt::Display for Sound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Sample source for one sound, in -1.0..=1.0.
+struct Generator {
+    sound: Sound,
+    rng: u64,
+    /// Brown noise integrator.
+    brown: f32,
+    /// Pink noise filter state (rain bed).
+    pink: [f32; 3],
+    /// Envelope of the current rain drop.
+    drop: f32,
+}
+
+impl Generator {
+    fn new(sound: Sound) -> Self {
+        Self {
+            sound,
+            rng: 0x2545_f491_4f6c_dd1d,
+            brown: 0.0,
+            pink: [0.0; 3],
+            drop: 0.0,
+        }
+    }
+
+    /// Uniform noise in -1.0..1.0 (xorshift64).
+    fn white(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    fn next(&mut self) -> f32 {
+        let white = self.white();
+        let sample = match self.sound {
+            Sound::WhiteNoise => white * 0.5,
+            Sound::BrownNoise => {
+                // Leaky integration keeps the walk from drifting off
+                self.brown = (self.brown + 0.02 * white) / 1.02;
+                self.brown * 3.5
+            }
+            Sound::Rain => {
+                // Pink noise (Paul Kellet's economy filter) as the steady bed
+                let [b0, b1, b2] = &mut self.pink;
+                *b0 = 0.99765 * *b0 + white * 0.099_046;
+                *b1 = 0.963 * *b1 + white * 0.296_516_4;
+                *b2 = 0.57 * *b2 + white * 1.052_691_3;
+                let bed = (*b0 + *b1 + *b2 + white * 0.1848) * 0.12;
+                // Scattered drops: short bursts of noise with a fast decay
+                if self.white() > 0.9996 {
+                    self.drop = 0.3 + 0.3 * self.white().abs();
+                }
+                self.drop *= 0.996;
+                bed + self.drop * self.white()
+            }
+        };
+        sample.clamp(-1.0, 1.0)
+    }
+}
+
+struct Player {
+    sound: Sound,
+    stop: Arc<AtomicBool>,
+    ends_at: Option<Instant>,
+    thread: JoinHandle<()>,
+}
+
+static PLAYER: Mutex<Option<Player>> = Mutex::new(None);
+/// Volume in percent, applied to the running player too.
+static VOLUME: AtomicU8 = AtomicU8::new(30);
+
+pub fn volume() -> u8 {
+    VOLUME.load(Ordering::Relaxed)
+}
+
+pub fn set_volume(percent: u8) {
+    VOLUME.store(percent.min(100), Ordering::Relaxed);
+}
+
+/// The sound playing and the time left on its sleep timer, if any.
+pub fn status() -> Option<(Sound, Option<Duration>)> {
+    let mut player = PLAYER.lock().unwrap();
+    if player.as_ref().is_some_and(|p| p.thread.is_finished()) {
+        *player = None;
+    }
+    let now = Instant::now();
+    player
+        .as_ref()
+        .map(|p| (p.sound, p.ends_at.map(|end| end.saturating_duration_since(now))))
+}
+
+/// Play `sound` until stopped or, with a `timer`, until it runs out.
+/// `device` is the Bluetooth address of the buds to route the sound to.
+pub fn start(sound: Sound, timer: Option<Duration>, device: Option<&str>) -> Result<()> {
+    stop();
+    let sink = device.and_then(|address| {
+        let sink = bluetooth_sink(address);
+        if sink.is_none() {
+            debug!("No audio sink for {}, using the default output", address);
+        }
+        sink
+    });
+    let mut child = spawn_output(sink.as_deref())?;
+    let stdin = child.stdin.take().context("Audio player has no input")?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let ends_at = timer.map(|t| Instant::now() + t);
+    let thread = std::thread::spawn({
+        let stop = stop.clone();
+        move || stream(sound, stdin, child, &stop, ends_at)
+    });
+    info!(
+        "Playing {} on {}{}",
+        sound.label(),
+        sink.as_deref().unwrap_or("the default output"),
+        timer.map(|t| format!(" for {} min", t.as_secs() / 60)).unwrap_or_default()
+    );
+    *PLAYER.lock().unwrap() = Some(Player {
+        sound,
+        stop,
+        ends_at,
+        thread,
+    });
+    Ok(())
+}
+
+pub fn stop() {
+    if let Some(player) = PLAYER.lock().unwrap().take() {
+        info!("Stopping {}", player.sound.label());
+        player.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Gain the sleep timer leaves at `now`: 1.0 until the fade starts, 0.0
+/// once it has run out.
+fn fade(ends_at: Option<Instant>, now: Instant) -> f32 {
+    match ends_at {
+        Some(end) => (end.saturating_duration_since(now).as_secs_f32() / FADE_OUT.as_secs_f32()).min(1.0),
+        None => 1.0,
+    }
+}
+
+/// Feed the player until stopped, the timer runs out or the player exits.
+fn stream(sound: Sound, mut stdin: ChildStdin, mut child: Child, stop: &AtomicBool, ends_at: Option<Instant>) {
+    let mut generator = Generator::new(sound);
+    let mut buf = Vec::with_capacity(CHUNK * 2);
+    while !stop.load(Ordering::Relaxed) {
+        let fade = fade(ends_at, Instant::now());
+        if fade == 0.0 {
+            info!("Sleep timer ran out, stopping {}", sound.label());
+            break;
+        }
+        let gain = fade * f32::from(volume()) / 100.0 * f32::from(i16::MAX);
+        buf.clear();
+        for _ in 0..CHUNK {
+            buf.extend_from_slice(&((generator.next() * gain) as i16).to_le_bytes());
+        }
+        // Blocks while the player's buffer is full, which paces the loop
+        if let Err(e) = stdin.write_all(&buf) {
+            debug!("Audio player stopped reading: {}", e);
+            break;
+        }
+    }
+    drop(stdin);
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Start a player reading raw 16-bit mono samples from stdin.
+fn spawn_output(sink: Option<&str>) -> Result<Child> {
+    let mut pacat = Command::new("pacat");
+    pacat.args(["--playback", "--format=s16le", "--channels=1"]);
+    pacat.arg(format!("--rate={}", SAMPLE_RATE));
+    pacat.arg("--client-name=MyBuds").arg("--stream-name=Ambient sound");
+    if let Some(sink) = sink {
+        pacat.arg(format!("--device={}", sink));
+    }
+    match pacat.stdin(Stdio::piped()).spawn() {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        result => return result.context("Cannot start pacat"),
+    }
+
+    let mut pw_cat = Command::new("pw-cat");
+    pw_cat.args(["--playback", "--raw", "--format=s16", "--channels=1"]);
+    pw_cat.arg(format!("--rate={}", SAMPLE_RATE));
+    if let Some(sink) = sink {
+        pw_cat.arg(format!("--target={}", sink));
+    }
+    pw_cat
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("No audio player found (tried pacat and pw-cat)")
+}
+
+/// Name of the Bluetooth sink for `address`, from `pactl list short sinks`.
+pub fn bluetooth_sink(address: &str) -> Option<String> {
+    let out = Command::new("pactl")
+        .args(["list", "short", "sinks"])
+        .output()
+        .ok()?;
+    find_sink(&String::from_utf8_lossy(&out.stdout), address)
+}
+
+/// PipeWire names Bluetooth sinks `bluez_output.<address>.<n>`, PulseAudio
+/// `bluez_sink.<address>.<profile>`, with underscores in the address.
+fn find_sink(sinks: &str, address: &str) -> Option<String> {
+    let address = address.replace(':', "_");
+    sinks
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .find(|name| {
+            (name.starts_with("bluez_output.") || name.starts_with("bluez_sink."))
+                && name.contains(&address)
+        })
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_sink_of_the_buds() {
+        let sinks = "48\talsa_output.pci-0000_00_1f.3.analog-stereo\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED\n\
+                     77\tbluez_output.AA_BB_CC_DD_EE_FF.1\tPipeWire\ts16le 2ch 48000Hz\tRUNNING\n";
+        assert_eq!(
+            find_sink(sinks, "AA:BB:CC:DD:EE:FF").as_deref(),
+            Some("bluez_output.AA_BB_CC_DD_EE_FF.1")
+        );
+        assert_eq!(find_sink(sinks, "11:22:33:44:55:66"), None);
+    }
+
+    #[test]
+    fn sounds_stay_in_range_and_are_not_silent() {
+        for sound in Sound::ALL {
+            let mut generator = Generator::new(sound);
+            let samples: Vec<f32> = (0..SAMPLE_RATE).map(|_| generator.next()).collect();
+            let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+            assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)), "{}", sound);
+            assert!(rms > 0.02, "{} too quiet: {}", sound, rms);
+        }
+    }
+
+    #[test]
+    fn sleep_timer_fades_out() {
+        let now = Instant::now();
+        assert_eq!(fade(None, now), 1.0);
+        assert_eq!(fade(Some(now + Duration::from_secs(60)), now), 1.0);
+        assert_eq!(fade(Some(now + FADE_OUT / 2), now), 0.5);
+        assert_eq!(fade(Some(now), now), 0.0);
+    }
+}
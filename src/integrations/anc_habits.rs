@@ -0,0 +1,470 @@
+//! ANC habits: scheduled mode changes, and schedule suggestions drawn from
+//! a local history of the switches the user makes.
+//!
+//! Every ANC mode switch seen while connected is appended to `anc_history`
+//! in the state directory (unless `anc_suggestions` is off). A switch that
+//! repeats around the same time on several days becomes a suggestion under
+//! the `suggestion` group, which the Home page shows as a card; accepting
+//! it adds an `[[anc_schedules]]` entry that this task then runs. Nothing
+//! leaves the machine.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+
+use crate::config::{AncSchedule, AppConfig};
+use crate::device::handler::{replace_group, PropertyStore};
+use crate::device::request::{self, PropertySender};
+use crate::labels;
+use crate::logging;
+
+/// Property group holding the current suggestion, if any.
+pub const SUGGESTION_GROUP: &str = "suggestion";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const ANALYSIS_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Switches older than this don't count towards suggestions.
+const HISTORY_WINDOW_SECS: i64 = 28 * 24 * 3600;
+/// History lines kept on disk.
+const MAX_HISTORY: usize = 1000;
+/// Distinct days a switch has to repeat on before it is suggested.
+const MIN_DAYS: usize = 4;
+/// Minutes apart two switches can be and still count as the same habit.
+const TIME_TOLERANCE: u32 = 15;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Weekday bits, Sunday first as in `tm_wday`.
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const DAILY: u8 = 0b111_1111;
+const WEEKDAYS: u8 = 0b011_1110;
+const WEEKENDS: u8 = 0b100_0001;
+
+/// One recorded mode switch, in local time.
+#[derive(Debug, Clone)]
+pub struct Switch {
+    /// Local calendar day, only meaningful for telling days apart.
+    pub date: i32,
+    /// 0 = Sunday.
+    pub weekday: u32,
+    pub minute: u32,
+    pub mode: String,
+}
+
+/// A schedule the history suggests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub mode: String,
+    /// Weekday bits.
+    pub days: u8,
+    /// Minute of the day.
+    pub minute: u32,
+}
+
+impl Suggestion {
+    /// Stable name, remembered when the suggestion is dismissed.
+    pub fn id(&self) -> String {
+        format!("{}@{}@{}", self.mode, format_days(self.days), format_time(self.minute))
+    }
+
+    pub fn text(&self) -> String {
+        format!(
+            "You switch to {} {} at {}:{:02} — create a schedule?",
+            labels::anc_mode(&self.mode),
+            describe_days(self.days),
+            self.minute / 60,
+            self.minute % 60
+        )
+    }
+
+    pub fn schedule(&self) -> AncSchedule {
+        AncSchedule {
+            days: format_days(self.days),
+            time: format_time(self.minute),
+            mode: self.mode.clone(),
+        }
+    }
+
+    /// Whether `schedule` already does what this suggests.
+    fn covered_by(&self, schedule: &AncSchedule) -> bool {
+        let (Some(days), Some(minute)) = (parse_days(&schedule.days), parse_time(&schedule.time))
+        else {
+            return false;
+        };
+        schedule.mode == self.mode
+            && days & self.days != 0
+            && minutes_apart(minute, self.minute) <= TIME_TOLERANCE
+    }
+
+    fn to_props(&self) -> HashMap<String, String> {
+        let schedule = self.schedule();
+        HashMap::from([
+            ("id".to_string(), self.id()),
+            ("text".to_string(), self.text()),
+            ("mode".to_string(), schedule.mode),
+            ("days".to_string(), schedule.days),
+            ("time".to_string(), schedule.time),
+        ])
+    }
+}
+
+/// Parse "daily", "weekdays", "weekends" or a list like "mon,wed,fri".
+pub fn parse_days(s: &str) -> Option<u8> {
+    match s.trim() {
+        "daily" => Some(DAILY),
+        "weekdays" => Some(WEEKDAYS),
+        "weekends" => Some(WEEKENDS),
+        list => list.split(',').try_fold(0u8, |days, name| {
+            let day = DAY_NAMES.iter().position(|d| *d == name.trim())?;
+            Some(days | 1 << day)
+        }),
+    }
+}
+
+fn format_days(days: u8) -> String {
+    match days {
+        DAILY => "daily".into(),
+        WEEKDAYS => "weekdays".into(),
+        WEEKENDS => "weekends".into(),
+        _ => (0..7)
+            .filter(|d| days & 1 << d != 0)
+            .map(|d| DAY_NAMES[d])
+            .collect::<Vec<_>>()
+            .join(","),
+    }
+}
+
+fn describe_days(days: u8) -> String {
+    match days {
+        DAILY => "every day".into(),
+        WEEKDAYS => "every weekday".into(),
+        WEEKENDS => "on weekends".into(),
+        _ => {
+            let names: Vec<String> = (0..7)
+                .filter(|d| days & 1 << d != 0)
+                .map(|d| labels::humanize(DAY_NAMES[d]))
+                .collect();
+            format!("on {}", names.join(", "))
+        }
+    }
+}
+
+/// Parse "HH:MM" into minutes since midnight.
+pub fn parse_time(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+fn format_time(minute: u32) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Distance between two times of day, across midnight if shorter.
+fn minutes_apart(a: u32, b: u32) -> u32 {
+    let d = a.abs_diff(b);
+    d.min(MINUTES_PER_DAY - d)
+}
+
+/// The most frequent habit in `history` that isn't scheduled or dismissed.
+pub fn suggest(
+    history: &[Switch],
+    schedules: &[AncSchedule],
+    dismissed: &[String],
+) -> Option<Suggestion> {
+    let mut best: Option<(usize, Suggestion)> = None;
+    for anchor in history {
+        let cluster: Vec<&Switch> = history
+            .iter()
+            .filter(|s| s.mode == anchor.mode && minutes_apart(s.minute, anchor.minute) <= TIME_TOLERANCE)
+            .collect();
+        let dates: BTreeSet<i32> = cluster.iter().map(|s| s.date).collect();
+        if dates.len() < MIN_DAYS || best.as_ref().is_some_and(|(n, _)| *n >= dates.len()) {
+            continue;
+        }
+
+        let seen = cluster.iter().fold(0u8, |days, s| days | 1 << s.weekday);
+        let days = if seen & !WEEKDAYS == 0 && seen.count_ones() >= 3 {
+            WEEKDAYS
+        } else if seen == WEEKENDS {
+            WEEKENDS
+        } else if seen.count_ones() >= 5 {
+            DAILY
+        } else {
+            seen
+        };
+        let mut minutes: Vec<u32> = cluster.iter().map(|s| s.minute).collect();
+        minutes.sort_unstable();
+        // Median, rounded to 5 minutes
+        let minute = (minutes[minutes.len() / 2] + 2) / 5 * 5 % MINUTES_PER_DAY;
+
+        let suggestion = Suggestion {
+            mode: anchor.mode.clone(),
+            days,
+            minute,
+        };
+        if dismissed.contains(&suggestion.id()) || schedules.iter().any(|s| suggestion.covered_by(s)) {
+            continue;
+        }
+        best = Some((dates.len(), suggestion));
+    }
+    best.map(|(_, suggestion)| suggestion)
+}
+
+/// Schedules due at `minute` on `weekday`.
+fn due(schedules: &[AncSchedule], weekday: u32, minute: u32) -> impl Iterator<Item = &AncSchedule> {
+    schedules.iter().filter(move |s| {
+        parse_days(&s.days).is_some_and(|days| days & 1 << weekday != 0)
+            && parse_time(&s.time) == Some(minute)
+    })
+}
+
+/// Local calendar day, weekday (0 = Sunday) and minute of the day of a
+/// unix time.
+fn local_time(secs: i64) -> (i32, u32, u32) {
+    // SAFETY: localtime_r only writes to the tm we hand it.
+    unsafe {
+        let time = secs as libc::time_t;
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        (
+            tm.tm_year * 366 + tm.tm_yday,
+            tm.tm_wday as u32,
+            (tm.tm_hour * 60 + tm.tm_min) as u32,
+        )
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+fn history_path() -> PathBuf {
+    logging::state_dir().join("anc_history")
+}
+
+fn dismissed_path() -> PathBuf {
+    logging::state_dir().join("anc_suggestions_dismissed")
+}
+
+/// Append a switch as "<unix secs> <mode>".
+fn record_switch(secs: i64, mode: &str) {
+    let path = history_path();
+    let result = std::fs::create_dir_all(logging::state_dir()).and_then(|_| {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{} {}", secs, mode)
+    });
+    if let Err(e) = result {
+        warn!("Failed to record ANC switch in {}: {}", path.display(), e);
+    }
+}
+
+/// Switches of the last `HISTORY_WINDOW_SECS`, trimming the file when it
+/// has grown past `MAX_HISTORY` lines.
+fn load_history(now: i64) -> Vec<Switch> {
+    let path = history_path();
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    if lines.len() > MAX_HISTORY {
+        let kept = lines[lines.len() - MAX_HISTORY..].join("\n") + "\n";
+        if let Err(e) = std::fs::write(&path, kept) {
+            warn!("Failed to trim {}: {}", path.display(), e);
+        }
+    }
+    lines
+        .iter()
+        .filter_map(|line| {
+            let (secs, mode) = line.split_once(' ')?;
+            let secs: i64 = secs.parse().ok()?;
+            (now - secs <= HISTORY_WINDOW_SECS).then(|| {
+                let (date, weekday, minute) = local_time(secs);
+                Switch {
+                    date,
+                    weekday,
+                    minute,
+                    mode: mode.to_string(),
+                }
+            })
+        })
+        .collect()
+}
+
+fn dismissed() -> Vec<String> {
+    std::fs::read_to_string(dismissed_path())
+        .map(|s| s.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Never suggest `id` again.
+pub fn dismiss(id: &str) -> Result<()> {
+    std::fs::create_dir_all(logging::state_dir())?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dismissed_path())?;
+    writeln!(file, "{}", id)?;
+    Ok(())
+}
+
+async fn publish_suggestion(props: &PropertyStore, config: &AppConfig) {
+    let suggestion = config
+        .anc_suggestions
+        .then(|| suggest(&load_history(now_secs()), &config.anc_schedules, &dismissed()))
+        .flatten();
+    match suggestion {
+        Some(suggestion) => {
+            debug!("ANC suggestion: {}", suggestion.id());
+            replace_group(props, SUGGESTION_GROUP, suggestion.to_props()).await;
+        }
+        None => {
+            props.lock().await.remove(SUGGESTION_GROUP);
+        }
+    }
+}
+
+/// Modification time of the config file, to notice edits (such as an
+/// accepted suggestion) without re-parsing it every minute.
+fn config_mtime() -> Option<SystemTime> {
+    std::fs::metadata(AppConfig::path()).ok()?.modified().ok()
+}
+
+/// Spawn the history recorder, scheduler and suggestion analysis.
+pub fn spawn(props: PropertyStore, prop_tx: PropertySender) {
+    tokio::spawn(async move {
+        let mut config = AppConfig::load();
+        let mut mtime = config_mtime();
+        let mut current: Option<String> = None;
+        // Mode a schedule just asked for; that switch isn't a habit
+        let mut scheduled: Option<String> = None;
+        let mut last_minute = None;
+        let mut next_analysis = Instant::now();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let mode = props.lock().await.get("anc").and_then(|g| g.get("mode")).cloned();
+            match (&current, &mode) {
+                (Some(prev), Some(new)) if prev != new => {
+                    if scheduled.as_ref() == Some(new) {
+                        debug!("ANC switched to {} by a schedule", new);
+                    } else if config.anc_suggestions {
+                        record_switch(now_secs(), new);
+                    }
+                    scheduled = None;
+                }
+                // Just connected: analyse now rather than at the next interval
+                (None, Some(_)) => next_analysis = Instant::now(),
+                _ => {}
+            }
+            current = mode;
+
+            let (_, weekday, minute) = local_time(now_secs());
+            if last_minute == Some(minute) {
+                continue;
+            }
+            last_minute = Some(minute);
+
+            let modified = config_mtime();
+            if modified != mtime {
+                mtime = modified;
+                config = AppConfig::load();
+                next_analysis = Instant::now();
+            }
+
+            for schedule in due(&config.anc_schedules, weekday, minute) {
+                if current.is_none() || current.as_deref() == Some(schedule.mode.as_str()) {
+                    continue;
+                }
+                info!("Scheduled ANC switch to {}", schedule.mode);
+                scheduled = Some(schedule.mode.clone());
+                if let Err(e) = request::request(&prop_tx, "anc", "mode", &schedule.mode).await {
+                    warn!("Scheduled ANC switch to {} failed: {}", schedule.mode, e);
+                }
+            }
+
+            if current.is_some() && Instant::now() >= next_analysis {
+                next_analysis = Instant::now() + ANALYSIS_INTERVAL;
+                publish_suggestion(&props, &config).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switch(date: i32, weekday: u32, minute: u32, mode: &str) -> Switch {
+        Switch {
+            date,
+            weekday,
+            minute,
+            mode: mode.to_string(),
+        }
+    }
+
+    /// Awareness around 8:30 on Monday to Thursday, plus noise.
+    fn weekday_mornings() -> Vec<Switch> {
+        vec![
+            switch(1, 1, 8 * 60 + 28, "awareness"),
+            switch(2, 2, 8 * 60 + 31, "awareness"),
+            switch(2, 2, 13 * 60, "cancellation"),
+            switch(3, 3, 8 * 60 + 33, "awareness"),
+            switch(4, 4, 8 * 60 + 30, "awareness"),
+            switch(6, 6, 8 * 60 + 30, "cancellation"),
+        ]
+    }
+
+    #[test]
+    fn repeated_switch_becomes_a_weekday_suggestion() {
+        let suggestion = suggest(&weekday_mornings(), &[], &[]).unwrap();
+        assert_eq!(suggestion.id(), "awareness@weekdays@08:30");
+        assert_eq!(
+            suggestion.text(),
+            "You switch to Awareness every weekday at 8:30 — create a schedule?"
+        );
+    }
+
+    #[test]
+    fn few_days_suggest_nothing() {
+        let history = &weekday_mornings()[..4];
+        assert_eq!(suggest(history, &[], &[]), None);
+    }
+
+    #[test]
+    fn scheduled_or_dismissed_habits_are_not_suggested() {
+        let history = weekday_mornings();
+        let suggestion = suggest(&history, &[], &[]).unwrap();
+        assert_eq!(suggest(&history, &[suggestion.schedule()], &[]), None);
+        assert_eq!(suggest(&history, &[], &[suggestion.id()]), None);
+    }
+
+    #[test]
+    fn parses_schedule_days_and_times() {
+        assert_eq!(parse_days("weekdays"), Some(WEEKDAYS));
+        assert_eq!(parse_days("mon, fri"), Some(0b010_0010));
+        assert_eq!(parse_days("someday"), None);
+        assert_eq!(format_days(0b010_0010), "mon,fri");
+        assert_eq!(parse_time("08:30"), Some(510));
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(minutes_apart(23 * 60 + 55, 5), 10);
+
+        let schedules = [AncSchedule {
+            days: "weekends".into(),
+            time: "09:00".into(),
+            mode: "cancellation".into(),
+        }];
+        assert_eq!(due(&schedules, 0, 540).count(), 1);
+        assert_eq!(due(&schedules, 1, 540).count(), 0);
+    }
+}
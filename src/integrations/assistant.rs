@@ -0,0 +1,42 @@
+//! Voice assistant: run a local command when the assistant gesture fires.
+//!
+//! On phones the assistant gesture opens Google Assistant / Celia; on Linux
+//! nothing listens for it. This maps the `assistant` gesture event to a
+//! configurable executable.
+
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::device::events::EventWatcher;
+use crate::device::handler::PropertyStore;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Spawn the assistant launcher on the current tokio runtime.
+/// `command` is the program followed by its arguments.
+pub fn spawn(command: Vec<String>, props: PropertyStore) {
+    tokio::spawn(async move {
+        let mut watcher = EventWatcher::new("assistant");
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            if !watcher.fired(&props).await {
+                continue;
+            }
+
+            info!("Launching assistant: {:?}", command);
+            match std::process::Command::new(&command[0])
+                .args(&command[1..])
+                .spawn()
+            {
+                // Reap the child so it doesn't linger as a zombie
+                Ok(mut child) => {
+                    tokio::task::spawn_blocking(move || child.wait());
+                }
+                Err(e) => warn!("Failed to launch assistant {:?}: {}", command[0], e),
+            }
+        }
+    });
+}
@@ -0,0 +1,87 @@
+//! Audio reclaim: take the buds back when the host starts playing.
+//!
+//! With Dual Connect, audio stays with whichever device played last. When a
+//! local MPRIS player starts playing while another paired device holds the
+//! audio, this asks the buds to connect (and route audio to) this PC.
+
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use super::mpris;
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertySender};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Don't fight the other device if it grabs the audio right back.
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+/// MAC of the local Bluetooth adapter, in the dual-connect list format.
+async fn host_mac() -> Option<String> {
+    let session = bluer::Session::new().await.ok()?;
+    let adapter = session.default_adapter().await.ok()?;
+    let addr = adapter.address().await.ok()?;
+    Some(addr.0.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Whether another device in the dual-connect list currently holds the audio.
+async fn other_device_playing(props: &PropertyStore, host: &str) -> bool {
+    let store = props.lock().await;
+    let Some(dc) = store.get("dual_connect") else {
+        return false;
+    };
+    if dc.get("enabled").is_none_or(|s| s != "true") {
+        return false;
+    }
+    let Some(devices) = dc
+        .get("devices")
+        .and_then(|s| serde_json::from_str::<serde_json::Map<String, Value>>(s).ok())
+    else {
+        return false;
+    };
+    devices.iter().any(|(mac, dev)| {
+        mac != host
+            && dev
+                .get("playing")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+    })
+}
+
+/// Spawn the audio reclaim watcher on the current tokio runtime.
+pub fn spawn(props: PropertyStore, prop_tx: PropertySender) {
+    tokio::spawn(async move {
+        let Some(host) = host_mac().await else {
+            warn!("Audio reclaim: no local Bluetooth adapter, disabled");
+            return;
+        };
+        let mut was_playing = false;
+        let mut last_reclaim: Option<Instant> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let playing = tokio::task::spawn_blocking(mpris::any_playing)
+                .await
+                .unwrap_or(false);
+            let started = playing && !was_playing;
+            was_playing = playing;
+            if !started || last_reclaim.is_some_and(|t| t.elapsed() < COOLDOWN) {
+                continue;
+            }
+            if !other_device_playing(&props, &host).await {
+                debug!("Audio reclaim: host started playing, buds already free");
+                continue;
+            }
+
+            info!("Audio reclaim: host started playing, taking audio back");
+            last_reclaim = Some(Instant::now());
+            let prop = format!("{}:connected", host);
+            if let Err(e) = request::request(&prop_tx, "dual_connect", &prop, "true").await {
+                warn!("Audio reclaim: failed to connect this PC: {}", e);
+            }
+        }
+    });
+}
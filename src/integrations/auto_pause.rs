@@ -0,0 +1,153 @@
+//! Auto-pause: pause local playback when the buds come out of the ears.
+//!
+//! Reads the wear state the device reports (AirPods ear detection, or the
+//! Huawei in-ear notification) and pauses playing MPRIS players once the
+//! buds have been out for a moment, resuming the same players when they go
+//! back in. Governed by `config.auto_pause`, the one Settings toggle for
+//! both families (on Huawei it also drives the buds' own pausing).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info};
+
+use super::mpris;
+use crate::device::handler::PropertyStore;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long the buds must stay out before playback is paused, so
+/// adjusting the fit doesn't stop the music.
+const OUT_DELAY: Duration = Duration::from_secs(1);
+
+/// Whether the buds are being worn, or None if the device doesn't say.
+fn worn(store: &HashMap<String, HashMap<String, String>>) -> Option<bool> {
+    if let Some(ears) = store.get("ear_detection") {
+        let states: Vec<&str> = ["primary", "secondary"]
+            .iter()
+            .filter_map(|k| ears.get(*k).map(String::as_str))
+            .collect();
+        if !states.is_empty() {
+            // A bud left in the case doesn't count as taken out
+            return Some(states.contains(&"in_ear") && !states.contains(&"out"));
+        }
+    }
+    store
+        .get("wear")
+        .and_then(|w| w.get("in_ear"))
+        .map(|v| v == "true")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Nothing,
+    Pause,
+    Resume,
+}
+
+/// Debounces wear changes into pause / resume steps.
+#[derive(Default)]
+struct WearTracker {
+    out_since: Option<Instant>,
+    paused: bool,
+}
+
+impl WearTracker {
+    fn update(&mut self, worn: bool, now: Instant) -> Step {
+        if worn {
+            self.out_since = None;
+            return if std::mem::take(&mut self.paused) {
+                Step::Resume
+            } else {
+                Step::Nothing
+            };
+        }
+        let since = *self.out_since.get_or_insert(now);
+        if !self.paused && now.duration_since(since) >= OUT_DELAY {
+            self.paused = true;
+            return Step::Pause;
+        }
+        Step::Nothing
+    }
+}
+
+/// Spawn the auto-pause watcher on the current tokio runtime.
+pub fn spawn(props: PropertyStore) {
+    tokio::spawn(async move {
+        let mut tracker = WearTracker::default();
+        let mut paused_players: Vec<String> = Vec::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let (enabled, worn) = {
+                let store = props.lock().await;
+                let enabled = store
+                    .get("config")
+                    .and_then(|c| c.get("auto_pause"))
+                    .is_some_and(|v| v == "true");
+                (enabled, worn(&store))
+            };
+            let (true, Some(worn)) = (enabled, worn) else {
+                tracker = WearTracker::default();
+                paused_players.clear();
+                continue;
+            };
+
+            match tracker.update(worn, Instant::now()) {
+                Step::Nothing => {}
+                Step::Pause => {
+                    paused_players = tokio::task::spawn_blocking(mpris::pause_playing)
+                        .await
+                        .unwrap_or_default();
+                    if paused_players.is_empty() {
+                        debug!("Buds taken out, nothing playing");
+                    } else {
+                        info!("Buds taken out, paused {:?}", paused_players);
+                    }
+                }
+                Step::Resume => {
+                    let players = std::mem::take(&mut paused_players);
+                    if !players.is_empty() {
+                        info!("Buds back in, resuming {:?}", players);
+                        let _ = tokio::task::spawn_blocking(move || mpris::resume(&players)).await;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::test_support::group;
+
+    #[test]
+    fn pauses_after_the_buds_stay_out() {
+        let mut tracker = WearTracker::default();
+        let start = Instant::now();
+        assert_eq!(tracker.update(false, start), Step::Nothing);
+        // Back in before the delay: a fit adjustment, nothing to resume
+        assert_eq!(tracker.update(true, start + OUT_DELAY / 2), Step::Nothing);
+
+        let out = start + OUT_DELAY;
+        assert_eq!(tracker.update(false, out), Step::Nothing);
+        assert_eq!(tracker.update(false, out + OUT_DELAY), Step::Pause);
+        assert_eq!(tracker.update(false, out + OUT_DELAY * 2), Step::Nothing);
+        assert_eq!(tracker.update(true, out + OUT_DELAY * 3), Step::Resume);
+    }
+
+    #[test]
+    fn reads_wear_state_of_both_families() {
+        let airpods = HashMap::from([(
+            "ear_detection".to_string(),
+            group(&[("primary", "in_ear"), ("secondary", "in_case")]),
+        )]);
+        assert_eq!(worn(&airpods), Some(true));
+
+        let huawei = HashMap::from([("wear".to_string(), group(&[("in_ear", "false")]))]);
+        assert_eq!(worn(&huawei), Some(false));
+        assert_eq!(worn(&HashMap::new()), None);
+    }
+}
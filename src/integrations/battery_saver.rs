@@ -0,0 +1,265 @@
+//! Battery saver: trade sound for runtime when a bud runs low.
+//!
+//! Once the lowest bud level drops to the threshold, the sound quality
+//! preference is set to connectivity priority and/or ANC is turned off,
+//! with a notification saying so. When the buds come back charged (well
+//! above the threshold), the settings the saver changed are put back,
+//! unless they were changed again in the meantime. A setting changed by
+//! hand while the saver is on is left alone until the buds have recharged.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use super::alerts::lowest_level;
+use super::notify;
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::BatterySaverConfig;
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertySender};
+use crate::labels;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Points above the threshold that count as charged.
+const RECHARGED_MARGIN: u8 = 10;
+const CONNECTIVITY_MODE: &str = "sqp_connectivity";
+/// ANC off as Huawei and AirPods name it.
+const ANC_OFF: [&str; 2] = ["normal", "off"];
+
+type Store = HashMap<String, HashMap<String, String>>;
+
+/// A setting the saver changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    group: &'static str,
+    prop: &'static str,
+    before: String,
+    after: String,
+}
+
+impl Change {
+    /// "Connectivity Priority", "ANC Off".
+    fn describe(&self, value: &str) -> String {
+        let label = labels::for_property(self.group, self.prop, value)
+            .unwrap_or_else(|| value.to_string());
+        match self.group {
+            "anc" => format!("ANC {}", label),
+            _ => label,
+        }
+    }
+}
+
+fn options<'a>(store: &'a Store, group: &str, prop: &str) -> Vec<&'a str> {
+    store
+        .get(group)
+        .and_then(|g| g.get(prop))
+        .map(|o| o.split(',').collect())
+        .unwrap_or_default()
+}
+
+/// Settings to lower now, skipping those the device lacks or that are
+/// already lowered.
+fn downgrades(config: &BatterySaverConfig, store: &Store) -> Vec<Change> {
+    let current = |group: &str, prop: &str| store.get(group)?.get(prop).cloned();
+    let mut out = Vec::new();
+    if config.connectivity_mode {
+        let supported =
+            options(store, "sound", "quality_preference_options").contains(&CONNECTIVITY_MODE);
+        if let Some(before) = current("sound", "quality_preference").filter(|_| supported) {
+            out.push(Change {
+                group: "sound",
+                prop: "quality_preference",
+                before,
+                after: CONNECTIVITY_MODE.to_string(),
+            });
+        }
+    }
+    if config.anc_off {
+        let modes = options(store, "anc", "mode_options");
+        let off = ANC_OFF.into_iter().find(|m| modes.contains(m));
+        if let (Some(before), Some(off)) = (current("anc", "mode"), off) {
+            out.push(Change {
+                group: "anc",
+                prop: "mode",
+                before,
+                after: off.to_string(),
+            });
+        }
+    }
+    out.retain(|c| c.before != c.after);
+    out
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Lower(Vec<Change>),
+    Restore(Vec<Change>),
+}
+
+#[derive(Debug)]
+struct Saver {
+    /// Settings lowered and not restored yet; kept across disconnects.
+    lowered: Vec<Change>,
+    /// Whether a drop to the threshold lowers settings; off from lowering
+    /// until the buds have recharged.
+    armed: bool,
+}
+
+impl Saver {
+    fn new() -> Self {
+        Self {
+            lowered: Vec::new(),
+            armed: true,
+        }
+    }
+
+    fn poll(&mut self, config: &BatterySaverConfig, store: &Store) -> Option<Step> {
+        if ConnectionState::from_group(store.get(CONNECTION_GROUP)) != ConnectionState::Connected {
+            return None;
+        }
+        let level = store.get("battery").and_then(lowest_level)?;
+        if level > config.threshold.saturating_add(RECHARGED_MARGIN) {
+            self.armed = true;
+            // Put back only what nobody changed since
+            let restore: Vec<Change> = std::mem::take(&mut self.lowered)
+                .into_iter()
+                .filter(|c| store.get(c.group).and_then(|g| g.get(c.prop)) == Some(&c.after))
+                .collect();
+            return (!restore.is_empty()).then_some(Step::Restore(restore));
+        }
+        if level <= config.threshold && self.armed {
+            self.armed = false;
+            let lower = downgrades(config, store);
+            return (!lower.is_empty()).then_some(Step::Lower(lower));
+        }
+        None
+    }
+}
+
+/// Request each change's `value`, returning the ones the device took.
+async fn apply(
+    prop_tx: &PropertySender,
+    changes: Vec<Change>,
+    value: impl Fn(&Change) -> &str,
+) -> Vec<Change> {
+    let mut done = Vec::new();
+    for change in changes {
+        match request::request(prop_tx, change.group, change.prop, value(&change)).await {
+            Ok(()) => done.push(change),
+            Err(e) => warn!(
+                "Battery saver could not set {}.{}: {}",
+                change.group, change.prop, e
+            ),
+        }
+    }
+    done
+}
+
+/// Spawn the battery saver on the current tokio runtime.
+pub fn spawn(config: BatterySaverConfig, props: PropertyStore, prop_tx: PropertySender) {
+    tokio::spawn(async move {
+        let mut saver = Saver::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let store = props.lock().await.clone();
+            match saver.poll(&config, &store) {
+                Some(Step::Lower(changes)) => {
+                    let done = apply(&prop_tx, changes, |c| &c.after).await;
+                    if done.is_empty() {
+                        continue;
+                    }
+                    let what: Vec<String> = done.iter().map(|c| c.describe(&c.after)).collect();
+                    info!("Battery low, battery saver switched to {}", what.join(", "));
+                    if config.notify {
+                        let level = store.get("battery").and_then(lowest_level).unwrap_or(0);
+                        let body = format!(
+                            "{}% left: switched to {} to last longer. Restored once the buds are charged.",
+                            level,
+                            what.join(" and ")
+                        );
+                        notify::send("Battery saver on", &body);
+                    }
+                    saver.lowered = done;
+                }
+                Some(Step::Restore(changes)) => {
+                    let done = apply(&prop_tx, changes, |c| &c.before).await;
+                    if done.is_empty() {
+                        continue;
+                    }
+                    let what: Vec<String> = done.iter().map(|c| c.describe(&c.before)).collect();
+                    info!("Buds charged, battery saver restored {}", what.join(", "));
+                    if config.notify {
+                        let body = format!("Buds charged: back to {}", what.join(" and "));
+                        notify::send("Battery saver off", &body);
+                    }
+                }
+                None => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::test_support::group;
+
+    fn store(level: &str, quality: &str, anc: &str) -> Store {
+        HashMap::from([
+            (CONNECTION_GROUP.into(), group(&[("state", "connected")])),
+            ("battery".into(), group(&[("left", level), ("right", "90")])),
+            (
+                "sound".into(),
+                group(&[
+                    ("quality_preference", quality),
+                    ("quality_preference_options", "sqp_connectivity,sqp_quality"),
+                ]),
+            ),
+            (
+                "anc".into(),
+                group(&[
+                    ("mode", anc),
+                    ("mode_options", "normal,cancellation,awareness"),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn lowers_once_and_restores_untouched_settings() {
+        let config = BatterySaverConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let mut saver = Saver::new();
+        assert_eq!(
+            saver.poll(&config, &store("40", "sqp_quality", "cancellation")),
+            None
+        );
+
+        let Some(Step::Lower(changes)) =
+            saver.poll(&config, &store("20", "sqp_quality", "cancellation"))
+        else {
+            panic!("expected the saver to lower settings");
+        };
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].after, "normal");
+        saver.lowered = changes;
+
+        // ANC turned back on by hand: not lowered again, and not restored
+        assert_eq!(
+            saver.poll(&config, &store("15", "sqp_connectivity", "awareness")),
+            None
+        );
+        let Some(Step::Restore(restored)) =
+            saver.poll(&config, &store("35", "sqp_connectivity", "awareness"))
+        else {
+            panic!("expected the saver to restore settings");
+        };
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].before, "sqp_quality");
+        assert!(saver.armed);
+    }
+}
@@ -0,0 +1,316 @@
+//! Desktop notifications about the device: low battery and lost links.
+//!
+//! Both come with a button that acts on the device through the property
+//! channel. A low battery offers the connectivity-priority sound mode,
+//! which is lighter on power than high-quality codecs; a dropped link
+//! offers to reconnect without waiting for the backoff. Opening the
+//! AirPods case nearby shows the levels it advertises, like phones do, and
+//! an ANC mode switched on the earbuds is confirmed on screen.
+//!
+//! During `quiet_hours`, or while a fullscreen window has focus, drop and
+//! reconnect notifications are held and summed up in one notification
+//! afterwards, so a flaky link can't interrupt a film or the night.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use super::alerts::{in_quiet_hours, local_minutes, lowest_level, parse_quiet_hours};
+use super::fullscreen::focused_window_is_fullscreen;
+use super::notify::{self, NotifyAction};
+use crate::bluetooth::case::CASE_GROUP;
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::NotificationsConfig;
+use crate::device::handler::PropertyStore;
+use crate::device::request::PropertySender;
+use crate::device::written_locally;
+use crate::labels;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// An ANC change this soon after MyBuds set the mode is the device
+/// confirming it, not a change made on the earbuds.
+const LOCAL_CHANGE_WINDOW: Duration = Duration::from_secs(10);
+
+const CONNECTIVITY_MODE: &str = "sqp_connectivity";
+
+const SWITCH_TO_CONNECTIVITY: NotifyAction = NotifyAction {
+    label: "Switch to connectivity mode",
+    group: "sound",
+    prop: "quality_preference",
+    value: CONNECTIVITY_MODE,
+};
+
+const RECONNECT_NOW: NotifyAction = NotifyAction {
+    label: "Reconnect now",
+    group: CONNECTION_GROUP,
+    prop: "retry_now",
+    value: "true",
+};
+
+/// "Left 80%, Right 70%, Case 50%" from the published case group.
+fn case_summary(case: &HashMap<String, String>) -> String {
+    [("Left", "left"), ("Right", "right"), ("Case", "case")]
+        .iter()
+        .filter_map(|(name, key)| case.get(*key).map(|l| format!("{} {}%", name, l)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkEvent {
+    Lost,
+    Restored,
+}
+
+/// Connection notifications held back while quiet.
+#[derive(Debug, Default)]
+struct HeldNotices {
+    drops: u32,
+    reconnects: u32,
+}
+
+impl HeldNotices {
+    fn hold(&mut self, event: LinkEvent) {
+        match event {
+            LinkEvent::Lost => self.drops += 1,
+            LinkEvent::Restored => self.reconnects += 1,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.drops == 0 && self.reconnects == 0
+    }
+
+    /// Body of the summary notification, clearing what was held; None if
+    /// nothing was.
+    fn take_summary(&mut self, connected: bool) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let held = std::mem::take(self);
+        let mut what = Vec::new();
+        if held.drops > 0 {
+            what.push(format!("dropped {}", times(held.drops)));
+        }
+        if held.reconnects > 0 {
+            what.push(format!("came back {}", times(held.reconnects)));
+        }
+        Some(format!(
+            "The connection {} while notifications were held. {}",
+            what.join(" and "),
+            if connected { "Connected now." } else { "Not connected now." }
+        ))
+    }
+}
+
+fn times(n: u32) -> String {
+    match n {
+        1 => "once".to_string(),
+        2 => "twice".to_string(),
+        n => format!("{} times", n),
+    }
+}
+
+/// Whether connection notifications should be held right now.
+async fn quiet_now(quiet_hours: Option<(u32, u32)>, when_fullscreen: bool) -> bool {
+    if quiet_hours.is_some_and(|q| in_quiet_hours(q, local_minutes())) {
+        return true;
+    }
+    when_fullscreen
+        && tokio::task::spawn_blocking(focused_window_is_fullscreen)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(false)
+}
+
+/// Spawn the notification watcher on the current tokio runtime.
+pub fn spawn(config: NotificationsConfig, props: PropertyStore, prop_tx: PropertySender) {
+    let quiet_hours = config.quiet_hours.as_deref().and_then(|s| {
+        let parsed = parse_quiet_hours(s);
+        if parsed.is_none() {
+            warn!("Ignoring invalid notifications.quiet_hours {:?}", s);
+        }
+        parsed
+    });
+
+    tokio::spawn(async move {
+        let mut last_state = ConnectionState::default();
+        let mut link_lost = false;
+        let mut held = HeldNotices::default();
+        let mut last_level: Option<u8> = None;
+        let mut lid_was_open = false;
+        let mut last_anc: Option<String> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let (state, level, charging, can_switch, case, anc) = {
+                let store = props.lock().await;
+                let battery = store.get("battery");
+                let sound = store.get("sound");
+                (
+                    ConnectionState::from_group(store.get(CONNECTION_GROUP)),
+                    battery.and_then(lowest_level),
+                    battery
+                        .and_then(|b| b.get("is_charging"))
+                        .is_some_and(|v| v == "true"),
+                    sound.is_some_and(|s| {
+                        s.get("quality_preference_options")
+                            .is_some_and(|o| o.split(',').any(|v| v == CONNECTIVITY_MODE))
+                            && s.get("quality_preference").map(String::as_str)
+                                != Some(CONNECTIVITY_MODE)
+                    }),
+                    store.get(CASE_GROUP).cloned(),
+                    store.get("anc").and_then(|a| a.get("mode")).cloned(),
+                )
+            };
+
+            let lid_open = case
+                .as_ref()
+                .and_then(|c| c.get("lid_open"))
+                .is_some_and(|v| v == "true");
+            if lid_open && !lid_was_open && config.case_opened {
+                info!("Case opened, notifying");
+                notify::send("Case opened", &case_summary(case.as_ref().unwrap()));
+            }
+            lid_was_open = lid_open;
+
+            // The first mode read after connecting is no change
+            let anc = anc.filter(|_| state == ConnectionState::Connected);
+            if let (Some(mode), Some(previous)) = (&anc, &last_anc) {
+                if mode != previous
+                    && config.anc_changed
+                    && !written_locally("anc", "mode", LOCAL_CHANGE_WINDOW)
+                {
+                    info!("ANC mode changed on the earbuds, notifying");
+                    notify::send(&labels::anc_mode(mode), "Noise control changed on the earbuds");
+                }
+            }
+            last_anc = anc;
+
+            // Disconnected is what a shutdown publishes and Asleep what the
+            // sleep timer does on purpose, neither is a lost link
+            let lost = last_state == ConnectionState::Connected
+                && !matches!(
+                    state,
+                    ConnectionState::Connected
+                        | ConnectionState::Disconnected
+                        | ConnectionState::Asleep
+                );
+            let restored = link_lost && state == ConnectionState::Connected;
+            match state {
+                _ if lost => link_lost = true,
+                ConnectionState::Connected
+                | ConnectionState::Disconnected
+                | ConnectionState::Asleep => link_lost = false,
+                _ => {}
+            }
+            last_state = state;
+
+            let event = if lost && config.disconnected {
+                Some(LinkEvent::Lost)
+            } else if restored && config.reconnected {
+                Some(LinkEvent::Restored)
+            } else {
+                None
+            };
+            // The fullscreen check spawns xprop; only ask when it matters
+            let quiet = (event.is_some() || !held.is_empty())
+                && quiet_now(quiet_hours, config.quiet_when_fullscreen).await;
+            if !quiet {
+                let connected = state == ConnectionState::Connected;
+                if let Some(summary) = held.take_summary(connected) {
+                    info!("Quiet time over, summing up held notifications");
+                    if connected {
+                        notify::send("Headphone connection", &summary);
+                    } else {
+                        notify::send_with_action(
+                            "Headphone connection",
+                            &summary,
+                            RECONNECT_NOW,
+                            prop_tx.clone(),
+                        );
+                    }
+                }
+            }
+            match event {
+                Some(event) if quiet => {
+                    debug!("Holding {:?} notification during quiet time", event);
+                    held.hold(event);
+                }
+                Some(LinkEvent::Lost) => {
+                    info!("Connection lost, notifying");
+                    notify::send_with_action(
+                        "Headphones disconnected",
+                        "MyBuds keeps trying to reconnect in the background",
+                        RECONNECT_NOW,
+                        prop_tx.clone(),
+                    );
+                }
+                Some(LinkEvent::Restored) => {
+                    info!("Connection restored, notifying");
+                    notify::send("Headphones reconnected", "The connection is back");
+                }
+                None => {}
+            }
+
+            if state != ConnectionState::Connected || charging {
+                last_level = None;
+                continue;
+            }
+            let Some(level) = level else {
+                continue;
+            };
+            let previous = last_level.replace(level);
+            let threshold = config.low_battery_threshold;
+            let crossed = previous.is_some_and(|p| p > threshold) && level <= threshold;
+            if !config.low_battery || !crossed {
+                continue;
+            }
+
+            info!("Battery low ({}%), notifying", level);
+            let body = format!("{}% left", level);
+            if can_switch {
+                notify::send_with_action(
+                    "Headphone battery low",
+                    &body,
+                    SWITCH_TO_CONNECTIVITY,
+                    prop_tx.clone(),
+                );
+            } else {
+                notify::send("Headphone battery low", &body);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_up_held_notifications() {
+        let mut held = HeldNotices::default();
+        assert_eq!(held.take_summary(true), None);
+
+        for _ in 0..3 {
+            held.hold(LinkEvent::Lost);
+        }
+        held.hold(LinkEvent::Restored);
+        held.hold(LinkEvent::Restored);
+        assert_eq!(
+            held.take_summary(false).as_deref(),
+            Some("The connection dropped 3 times and came back twice while notifications were held. Not connected now.")
+        );
+        assert!(held.is_empty());
+
+        held.hold(LinkEvent::Lost);
+        assert_eq!(
+            held.take_summary(true).as_deref(),
+            Some("The connection dropped once while notifications were held. Connected now.")
+        );
+    }
+}
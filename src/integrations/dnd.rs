@@ -0,0 +1,285 @@
+//! Focus mode: follow the desktop's Do Not Disturb switch.
+//!
+//! DND state is read from the freedesktop notification server's `Inhibited`
+//! property (KDE Plasma and others), falling back to GNOME's
+//! `org.gnome.desktop.notifications show-banners` setting. While DND is on,
+//! the configured ANC mode / EQ preset is applied, also when the buds
+//! connect later; when it turns off, the values the device reported before
+//! are restored, unless they were changed in the meantime.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+use tracing::{debug, info, warn};
+
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::FocusModeConfig;
+use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertySender};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Set while DND is on and `suppress_notifications` is enabled.
+static SUPPRESS_NOTIFICATIONS: AtomicBool = AtomicBool::new(false);
+
+/// Whether MyBuds should currently stay quiet (desktop is in DND).
+/// Anything that raises desktop notifications must check this first.
+pub fn notifications_suppressed() -> bool {
+    SUPPRESS_NOTIFICATIONS.load(Ordering::Relaxed)
+}
+
+/// Query the desktop DND state. Returns None if no known source answers.
+fn query_dnd() -> Option<bool> {
+    query_notification_inhibited().or_else(query_gnome_show_banners)
+}
+
+fn query_notification_inhibited() -> Option<bool> {
+    let conn = Connection::new_session().ok()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        Duration::from_millis(500),
+    );
+    proxy
+        .get::<bool>("org.freedesktop.Notifications", "Inhibited")
+        .ok()
+}
+
+fn query_gnome_show_banners() -> Option<bool> {
+    let output = std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "false" => Some(true),
+        "true" => Some(false),
+        _ => None,
+    }
+}
+
+type Store = HashMap<String, HashMap<String, String>>;
+
+/// A setting focus mode switches while DND is on.
+#[derive(Debug)]
+struct Target {
+    /// Group the device publishes the setting in.
+    group: &'static str,
+    /// Group writes go to.
+    write_group: &'static str,
+    prop: &'static str,
+    value: String,
+}
+
+fn targets(config: &FocusModeConfig) -> Vec<Target> {
+    let mut out = Vec::new();
+    if let Some(ref mode) = config.anc_mode {
+        out.push(Target {
+            group: "anc",
+            write_group: "anc",
+            prop: "mode",
+            value: mode.clone(),
+        });
+    }
+    if let Some(ref preset) = config.eq_preset {
+        out.push(Target {
+            group: "sound",
+            write_group: "config_eq",
+            prop: "equalizer_preset",
+            value: preset.clone(),
+        });
+    }
+    out
+}
+
+/// Focus mode state across polls. The buds may connect after DND turned on
+/// or drop out while it is on, so targets are applied whenever the device
+/// is connected and has reported the setting, and restored the same way.
+struct Focus {
+    targets: Vec<Target>,
+    /// DND was on at the last poll.
+    active: bool,
+    /// Values the device reported before focus mode changed them, by target;
+    /// kept across disconnects until restored.
+    saved: Vec<Option<String>>,
+    /// Targets applied since the device last connected with DND on.
+    applied: Vec<bool>,
+}
+
+impl Focus {
+    fn new(config: &FocusModeConfig) -> Self {
+        let targets = targets(config);
+        Self {
+            saved: vec![None; targets.len()],
+            applied: vec![false; targets.len()],
+            targets,
+            active: false,
+        }
+    }
+
+    /// Writes to send now, as (target index, value).
+    fn poll(&mut self, dnd: bool, store: &Store) -> Vec<(usize, String)> {
+        if dnd != self.active {
+            self.active = dnd;
+            self.applied.fill(false);
+            if dnd {
+                info!("Desktop DND enabled, entering focus mode");
+            } else {
+                info!("Desktop DND disabled, leaving focus mode");
+            }
+        }
+        if ConnectionState::from_group(store.get(CONNECTION_GROUP)) != ConnectionState::Connected {
+            // Apply again once the buds are back
+            self.applied.fill(false);
+            return Vec::new();
+        }
+
+        let mut writes = Vec::new();
+        for (i, target) in self.targets.iter().enumerate() {
+            // Not reported yet: nothing to save, so nothing to change either
+            let Some(current) = store.get(target.group).and_then(|g| g.get(target.prop)) else {
+                continue;
+            };
+            if self.active {
+                if self.applied[i] {
+                    continue;
+                }
+                self.applied[i] = true;
+                self.saved[i].get_or_insert_with(|| current.clone());
+                if *current != target.value {
+                    writes.push((i, target.value.clone()));
+                }
+            } else if let Some(before) = self.saved[i].take() {
+                // Put back only what nobody changed since
+                if *current == target.value && before != target.value {
+                    writes.push((i, before));
+                }
+            }
+        }
+        writes
+    }
+
+    /// A write from `poll` didn't go through: try it again on a later poll.
+    fn failed(&mut self, index: usize, value: String) {
+        if self.active {
+            self.applied[index] = false;
+        } else {
+            self.saved[index] = Some(value);
+        }
+    }
+}
+
+/// Spawn the focus mode watcher on the current tokio runtime.
+pub fn spawn(config: FocusModeConfig, props: PropertyStore, prop_tx: PropertySender) {
+    tokio::spawn(async move {
+        let mut focus = Focus::new(&config);
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let dnd = tokio::task::spawn_blocking(query_dnd)
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or(false);
+            SUPPRESS_NOTIFICATIONS.store(dnd && config.suppress_notifications, Ordering::Relaxed);
+
+            let store = props.lock().await.clone();
+            for (i, value) in focus.poll(dnd, &store) {
+                let target = &focus.targets[i];
+                if !send(&prop_tx, target.write_group, target.prop, &value).await {
+                    focus.failed(i, value);
+                }
+            }
+        }
+    });
+}
+
+/// Request `group.prop = value`, returning whether the device took it.
+async fn send(tx: &PropertySender, group: &str, prop: &str, value: &str) -> bool {
+    debug!("Focus mode: {}.{} = {}", group, prop, value);
+    match request::request(tx, group, prop, value).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Focus mode: failed to set {}.{}: {}", group, prop, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(state: &str, anc: Option<&str>) -> Store {
+        let mut store = HashMap::from([(
+            CONNECTION_GROUP.to_string(),
+            HashMap::from([("state".to_string(), state.to_string())]),
+        )]);
+        if let Some(anc) = anc {
+            store.insert(
+                "anc".into(),
+                HashMap::from([("mode".to_string(), anc.to_string())]),
+            );
+        }
+        store
+    }
+
+    fn focus() -> Focus {
+        Focus::new(&FocusModeConfig {
+            enabled: true,
+            anc_mode: Some("cancellation".into()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn applies_once_connected_and_restores() {
+        let mut focus = focus();
+        // DND already on at startup, buds not there yet
+        assert!(focus.poll(true, &store("disconnected", None)).is_empty());
+        // Connected, but the mode isn't reported yet
+        assert!(focus.poll(true, &store("connected", None)).is_empty());
+        assert_eq!(focus.saved[0], None);
+
+        let applied = focus.poll(true, &store("connected", Some("awareness")));
+        assert_eq!(applied, [(0, "cancellation".to_string())]);
+        assert!(focus
+            .poll(true, &store("connected", Some("cancellation")))
+            .is_empty());
+
+        // Reconnecting with DND on applies it again, keeping the first value
+        assert!(focus.poll(true, &store("reconnecting", None)).is_empty());
+        let applied = focus.poll(true, &store("connected", Some("normal")));
+        assert_eq!(applied, [(0, "cancellation".to_string())]);
+
+        let restored = focus.poll(false, &store("connected", Some("cancellation")));
+        assert_eq!(restored, [(0, "awareness".to_string())]);
+    }
+
+    #[test]
+    fn failed_writes_are_retried_and_changed_values_kept() {
+        let mut focus = focus();
+        let applied = focus.poll(true, &store("connected", Some("normal")));
+        focus.failed(0, applied[0].1.clone());
+        let applied = focus.poll(true, &store("connected", Some("normal")));
+        assert_eq!(applied, [(0, "cancellation".to_string())]);
+
+        // DND ends while disconnected: restored on the next connection
+        assert!(focus.poll(false, &store("disconnected", None)).is_empty());
+        let restored = focus.poll(false, &store("connected", Some("cancellation")));
+        assert_eq!(restored, [(0, "normal".to_string())]);
+
+        // A mode picked by hand during DND is left alone afterwards
+        focus.poll(true, &store("connected", Some("normal")));
+        assert!(focus
+            .poll(false, &store("connected", Some("awareness")))
+            .is_empty());
+    }
+}
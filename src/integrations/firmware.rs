@@ -0,0 +1,263 @@
+//! Firmware version check against Huawei's public update service.
+//!
+//! MyBuds can't flash firmware, but it can tell the user that a newer
+//! version exists (and what changed) so they know to update from the
+//! phone app. The check sends the device model and firmware version to
+//! Huawei, so it only runs with `check_firmware = true`. Results land in
+//! the `firmware` property group:
+//!
+//! - `latest_version`: newest version the server knows about
+//! - `update_available`: "true" / "false"
+//! - `changelog`: plain-text release notes, if published
+//! - `error`: set instead of the above when the check failed
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+
+use crate::device::handler::{put_properties, PropertyStore};
+use crate::device::models::gates;
+
+const CHECK_URL: &str = "https://query.hicloud.com/sp_ard_common/v2/Check.action?latest=true";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Changelogs can be long; the Device Info page only needs the gist.
+const MAX_CHANGELOG_LEN: usize = 2000;
+
+/// Result of a single version check.
+#[derive(Debug, Clone)]
+struct CheckResult {
+    latest_version: String,
+    changelog: Option<String>,
+}
+
+/// Spawn the firmware checker on the current tokio runtime.
+///
+/// Waits for `info.device_model` and `info.software_ver` to appear, then
+/// checks once per (model, version) pair. Answers are cached so reconnects
+/// don't hit the server again; a failed check is retried on the next
+/// connection.
+pub fn spawn(props: PropertyStore) {
+    tokio::spawn(async move {
+        let mut cache: HashMap<(String, String), HashMap<String, String>> = HashMap::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let (model, version, skip) = {
+                let store = props.lock().await;
+                let info = store.get("info");
+                // AirPods report a manufacturer; their firmware isn't on Huawei's server
+                let apple = info
+                    .and_then(|g| g.get("manufacturer"))
+                    .is_some_and(|m| m.contains("Apple"));
+                (
+                    info.and_then(|g| g.get("device_model")).cloned(),
+                    info.and_then(|g| g.get("software_ver")).cloned(),
+                    apple || store.contains_key("firmware"),
+                )
+            };
+            let (Some(model), Some(version)) = (model, version) else {
+                continue;
+            };
+            if skip {
+                continue;
+            }
+
+            let key = (model.clone(), version.clone());
+            let out = match cache.get(&key) {
+                Some(out) => out.clone(),
+                None => {
+                    info!("Checking firmware updates for {} ({})", model, version);
+                    let (m, v) = key.clone();
+                    let result = tokio::task::spawn_blocking(move || check(&m, &v))
+                        .await
+                        .context("firmware check task panicked")
+                        .and_then(|r| r);
+                    let failed = result.is_err();
+                    let out = result_to_props(&version, result);
+                    if !failed {
+                        cache.insert(key, out.clone());
+                    }
+                    out
+                }
+            };
+            put_properties(&props, "firmware", out).await;
+        }
+    });
+}
+
+fn result_to_props(current: &str, result: Result<Option<CheckResult>>) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    match result {
+        Ok(Some(res)) => {
+            let newer = gates::compare(&res.latest_version, current) == Ordering::Greater;
+            info!(
+                "Latest firmware: {} (update available: {})",
+                res.latest_version, newer
+            );
+            out.insert("latest_version".into(), res.latest_version);
+            out.insert("update_available".into(), newer.to_string());
+            if let Some(changelog) = res.changelog {
+                out.insert("changelog".into(), changelog);
+            }
+        }
+        Ok(None) => {
+            debug!("Update server has no newer firmware for this model");
+            out.insert("latest_version".into(), current.to_string());
+            out.insert("update_available".into(), "false".into());
+        }
+        Err(e) => {
+            warn!("Firmware check failed: {:#}", e);
+            out.insert("error".into(), e.to_string());
+        }
+    }
+    out
+}
+
+/// Ask the update server for the newest firmware of `model`.
+/// Returns None when the server reports nothing newer than `version`.
+fn check(model: &str, version: &str) -> Result<Option<CheckResult>> {
+    let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+
+    let body = json!({
+        "components": [{
+            "PackageName": "firmware",
+            "PackageVersionName": version,
+        }],
+        "rules": {
+            "DeviceName": model,
+            "FirmWare": version,
+        },
+    });
+
+    let resp: Value = agent
+        .post(CHECK_URL)
+        .send_json(body)
+        .context("update server request failed")?
+        .into_json()
+        .context("invalid update server response")?;
+
+    let Some(component) = resp
+        .get("components")
+        .and_then(Value::as_array)
+        .and_then(|c| c.first())
+    else {
+        return Ok(None);
+    };
+
+    let latest_version = component
+        .get("version")
+        .and_then(Value::as_str)
+        .context("update server response has no version")?
+        .to_string();
+
+    let changelog = component
+        .get("url")
+        .and_then(Value::as_str)
+        .and_then(|url| fetch_changelog(&agent, url));
+
+    Ok(Some(CheckResult {
+        latest_version,
+        changelog,
+    }))
+}
+
+/// Changelogs are published next to the package as `full/changelog.xml`.
+fn fetch_changelog(agent: &ureq::Agent, base_url: &str) -> Option<String> {
+    let url = format!("{}/full/changelog.xml", base_url.trim_end_matches('/'));
+    let xml = agent.get(&url).call().ok()?.into_string().ok()?;
+    let text = strip_xml(&xml);
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Reduce changelog XML to its text content, one line per element.
+fn strip_xml(xml: &str) -> String {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut in_tag = false;
+
+    for c in xml.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                let line = current.trim();
+                if !line.is_empty() {
+                    lines.push(line.to_string());
+                }
+                current.clear();
+            }
+            '>' => in_tag = false,
+            _ if !in_tag => current.push(c),
+            _ => {}
+        }
+    }
+
+    let mut text = lines.join("\n");
+    if text.len() > MAX_CHANGELOG_LEN {
+        let mut end = MAX_CHANGELOG_LEN;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changelog_xml_becomes_lines() {
+        let xml = "<?xml version=\"1.0\"?><root><feature>Improves ANC</feature>\n  <feature> Fixes dropouts </feature></root>";
+        assert_eq!(strip_xml(xml), "Improves ANC\nFixes dropouts");
+
+        let long = format!("<a>{}</a>", "é".repeat(MAX_CHANGELOG_LEN));
+        let text = strip_xml(&long);
+        assert!(text.ends_with('…'));
+        assert!(text.len() <= MAX_CHANGELOG_LEN + '…'.len_utf8());
+    }
+
+    #[test]
+    fn check_results_become_props() {
+        let out = result_to_props(
+            "1.0.0.136(C00)",
+            Ok(Some(CheckResult {
+                latest_version: "1.0.0.150".into(),
+                changelog: Some("Fixes".into()),
+            })),
+        );
+        assert_eq!(out["update_available"], "true");
+        assert_eq!(out["latest_version"], "1.0.0.150");
+        assert_eq!(out["changelog"], "Fixes");
+
+        let out = result_to_props(
+            "1.0.0.150",
+            Ok(Some(CheckResult {
+                latest_version: "1.0.0.136".into(),
+                changelog: None,
+            })),
+        );
+        assert_eq!(out["update_available"], "false");
+        assert!(!out.contains_key("changelog"));
+
+        let out = result_to_props("1.0.0.150", Ok(None));
+        assert_eq!(out["latest_version"], "1.0.0.150");
+        assert_eq!(out["update_available"], "false");
+
+        let out = result_to_props("1.0.0.150", Err(anyhow::anyhow!("timed out")));
+        assert_eq!(out["error"], "timed out");
+        assert!(!out.contains_key("update_available"));
+    }
+}
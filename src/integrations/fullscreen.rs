@@ -0,0 +1,48 @@
+//! Whether the focused window is fullscreen (games, videos, slides).
+//!
+//! Asks the X server with `xprop`, which covers X11 sessions and XWayland
+//! windows under Wayland. Native Wayland windows can't be inspected this
+//! way and read as not fullscreen.
+
+use std::process::Command;
+
+/// Blocking; None when there's no X server or `xprop` to ask.
+pub fn focused_window_is_fullscreen() -> Option<bool> {
+    let out = Command::new("xprop")
+        .args(["-root", "_NET_ACTIVE_WINDOW"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let root = String::from_utf8_lossy(&out.stdout);
+    let Some(window) = active_window(&root) else {
+        return Some(false);
+    };
+    let out = Command::new("xprop")
+        .args(["-id", window, "_NET_WM_STATE"])
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&out.stdout).contains("_NET_WM_STATE_FULLSCREEN"))
+}
+
+/// Window id from `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007`;
+/// None when nothing has focus.
+fn active_window(xprop: &str) -> Option<&str> {
+    let (_, ids) = xprop.split_once('#')?;
+    let id = ids.split(',').next()?.trim();
+    (id.starts_with("0x") && id != "0x0").then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_active_window() {
+        assert_eq!(
+            active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007\n"),
+            Some("0x3a00007")
+        );
+        assert_eq!(active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0\n"), None);
+        assert_eq!(active_window("_NET_ACTIVE_WINDOW:  not found.\n"), None);
+    }
+}
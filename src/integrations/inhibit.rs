@@ -0,0 +1,61 @@
+//! Keep the machine awake during long-running device operations.
+//!
+//! Takes a systemd-logind "sleep" inhibitor lock. The lock is a file
+//! descriptor handed out by logind; it's released when the guard drops.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dbus::arg::OwnedFd;
+use dbus::blocking::Connection;
+use tracing::{debug, info};
+
+/// An active sleep inhibitor. Suspend is blocked until this is dropped.
+///
+/// Hold one for the whole of any operation that must not be interrupted,
+/// such as restoring a settings snapshot.
+pub struct SuspendInhibitor {
+    why: String,
+    _fd: OwnedFd,
+}
+
+impl SuspendInhibitor {
+    /// Ask logind to block suspend. `why` is shown by `systemd-inhibit --list`.
+    pub fn acquire(why: &str) -> Result<Self> {
+        let conn = Connection::new_system().context("Failed to connect to the system bus")?;
+        let proxy = conn.with_proxy(
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            Duration::from_secs(2),
+        );
+        let (fd,): (OwnedFd,) = proxy
+            .method_call(
+                "org.freedesktop.login1.Manager",
+                "Inhibit",
+                ("sleep", "MyBuds", why, "block"),
+            )
+            .context("logind refused the sleep inhibitor")?;
+        info!("Suspend inhibited: {}", why);
+        Ok(Self {
+            why: why.to_string(),
+            _fd: fd,
+        })
+    }
+
+    /// Like `acquire`, but runs without the lock when logind isn't available.
+    pub fn try_acquire(why: &str) -> Option<Self> {
+        match Self::acquire(why) {
+            Ok(guard) => Some(guard),
+            Err(e) => {
+                debug!("Continuing without suspend inhibitor: {:#}", e);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for SuspendInhibitor {
+    fn drop(&mut self) {
+        info!("Suspend no longer inhibited: {}", self.why);
+    }
+}
@@ -0,0 +1,190 @@
+//! KDE Connect bridge: keep a paired phone up to date with the headphones.
+//!
+//! Battery levels and the ANC mode are sent to the phone as KDE Connect
+//! pings through the daemon's D-Bus interface, when the ANC mode changes
+//! and when a level crosses a 10% step. A phone that is out of reach gets
+//! the state once it is back.
+//!
+//! Remote ANC switches come the other way through KDE Connect's Run Command
+//! plugin: commands such as `mybuds props --set anc.mode=cancellation`
+//! attach to the running instance like any other CLI invocation (see the
+//! README).
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+use tracing::{debug, info};
+
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::KdeConnectConfig;
+use crate::device::handler::PropertyStore;
+use crate::labels;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DBUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+const SERVICE: &str = "org.kde.kdeconnect";
+const DAEMON_PATH: &str = "/modules/kdeconnect";
+
+/// Battery levels by label, in display order.
+fn levels(battery: &HashMap<String, String>) -> Vec<(&'static str, u8)> {
+    let level = |key: &str| battery.get(key).and_then(|v| v.parse::<u8>().ok());
+    let mut out: Vec<_> = [("L", "left"), ("R", "right"), ("Case", "case")]
+        .into_iter()
+        .filter_map(|(label, key)| Some((label, level(key)?)))
+        .collect();
+    if out.iter().all(|(label, _)| *label == "Case") {
+        out.extend(level("global").map(|l| ("Battery", l)));
+    }
+    out
+}
+
+/// What the phone was last told: levels in 10% steps and the ANC mode.
+type Shared = (Vec<(&'static str, u8)>, Option<String>);
+
+/// The ping for the current state, and what it shares. None while the
+/// headphones are not connected.
+fn status(store: &HashMap<String, HashMap<String, String>>) -> Option<(String, Shared)> {
+    if ConnectionState::from_group(store.get(CONNECTION_GROUP)) != ConnectionState::Connected {
+        return None;
+    }
+    let levels = store.get("battery").map(levels).unwrap_or_default();
+    let anc = store.get("anc").and_then(|a| a.get("mode")).cloned();
+
+    let name = store
+        .get("info")
+        .and_then(|i| i.get("device_name").or_else(|| i.get("device_model")))
+        .map_or("Headphones", String::as_str);
+    let mut parts: Vec<String> = levels
+        .iter()
+        .map(|(label, level)| format!("{} {}%", label, level))
+        .collect();
+    parts.extend(anc.as_deref().map(labels::anc_mode));
+    let message = format!("{}: {}", name, parts.join(", "));
+
+    let steps = levels.into_iter().map(|(label, level)| (label, level / 10)).collect();
+    Some((message, (steps, anc)))
+}
+
+/// KDE Connect id of the phone: `wanted` (a name or id) or the first
+/// reachable paired device.
+fn find_device(conn: &Connection, wanted: Option<&str>) -> Option<String> {
+    let daemon = conn.with_proxy(SERVICE, DAEMON_PATH, DBUS_TIMEOUT);
+    let (ids,): (Vec<String>,) = daemon
+        .method_call("org.kde.kdeconnect.daemon", "devices", (true, true))
+        .ok()?;
+    let Some(wanted) = wanted else {
+        return ids.into_iter().next();
+    };
+    ids.into_iter().find(|id| {
+        let path = format!("{}/devices/{}", DAEMON_PATH, id);
+        id == wanted
+            || conn
+                .with_proxy(SERVICE, path, DBUS_TIMEOUT)
+                .get::<String>("org.kde.kdeconnect.device", "name")
+                .is_ok_and(|name| name == wanted)
+    })
+}
+
+/// Ping the phone with `message`. False when no phone is reachable.
+fn send_ping(wanted: Option<&str>, message: &str) -> bool {
+    let Ok(conn) = Connection::new_session() else {
+        return false;
+    };
+    let Some(id) = find_device(&conn, wanted) else {
+        debug!("No reachable KDE Connect device");
+        return false;
+    };
+    let path = format!("{}/devices/{}/ping", DAEMON_PATH, id);
+    let sent: Result<(), _> = conn.with_proxy(SERVICE, path, DBUS_TIMEOUT).method_call(
+        "org.kde.kdeconnect.device.ping",
+        "sendPing",
+        (message,),
+    );
+    match sent {
+        Ok(()) => true,
+        Err(e) => {
+            debug!("KDE Connect ping to {} failed: {}", id, e);
+            false
+        }
+    }
+}
+
+/// Spawn the bridge on the current tokio runtime.
+pub fn spawn(config: KdeConnectConfig, props: PropertyStore) {
+    tokio::spawn(async move {
+        let mut last: Option<Shared> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = status(&*props.lock().await);
+            let Some((message, shared)) = current else {
+                last = None;
+                continue;
+            };
+            if last.as_ref() == Some(&shared) {
+                continue;
+            }
+            let device = config.device.clone();
+            let ping = message.clone();
+            let sent = tokio::task::spawn_blocking(move || send_ping(device.as_deref(), &ping))
+                .await
+                .unwrap_or(false);
+            if sent {
+                info!("Shared with the phone: {}", message);
+                last = Some(shared);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(entries: &[(&str, &[(&str, &str)])]) -> HashMap<String, HashMap<String, String>> {
+        entries
+            .iter()
+            .map(|(group, values)| {
+                let values = values.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+                (group.to_string(), values.collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pings_on_mode_changes_and_level_steps() {
+        let connected: (&str, &[(&str, &str)]) = (CONNECTION_GROUP, &[("state", "connected")]);
+        let info: (&str, &[(&str, &str)]) = ("info", &[("device_name", "FreeBuds Pro 3")]);
+        let (message, first) = status(&store(&[
+            connected,
+            info,
+            ("battery", &[("left", "81"), ("right", "75"), ("case", "60")]),
+            ("anc", &[("mode", "cancellation")]),
+        ]))
+        .unwrap();
+        assert_eq!(message, "FreeBuds Pro 3: L 81%, R 75%, Case 60%, Noise Cancelling");
+
+        // Within the same 10% step: nothing new to share
+        let (_, same) = status(&store(&[
+            connected,
+            info,
+            ("battery", &[("left", "80"), ("right", "75"), ("case", "60")]),
+            ("anc", &[("mode", "cancellation")]),
+        ]))
+        .unwrap();
+        assert_eq!(same, first);
+
+        let (message, _) = status(&store(&[
+            connected,
+            ("battery", &[("global", "40")]),
+            ("anc", &[("mode", "awareness")]),
+        ]))
+        .unwrap();
+        assert_eq!(message, "Headphones: Battery 40%, Awareness");
+
+        assert!(status(&store(&[("battery", &[("global", "40")])])).is_none());
+    }
+}
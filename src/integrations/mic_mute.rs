@@ -0,0 +1,73 @@
+//! Microphone mute: use a gesture as a physical mute button in calls.
+//!
+//! Toggles the default PipeWire source with `wpctl` (falling back to
+//! `pactl` on PulseAudio) when the configured gesture event fires, then
+//! reports the new state with a notification.
+
+use std::process::Command;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use super::notify;
+use crate::device::events::EventWatcher;
+use crate::device::handler::PropertyStore;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Toggle the default source's mute. Returns the new state if it could be read.
+fn toggle_mute() -> Option<bool> {
+    let wpctl = Command::new("wpctl")
+        .args(["set-mute", "@DEFAULT_AUDIO_SOURCE@", "toggle"])
+        .status();
+    if wpctl.is_ok_and(|s| s.success()) {
+        let out = Command::new("wpctl")
+            .args(["get-volume", "@DEFAULT_AUDIO_SOURCE@"])
+            .output()
+            .ok()?;
+        return Some(String::from_utf8_lossy(&out.stdout).contains("[MUTED]"));
+    }
+
+    let pactl = Command::new("pactl")
+        .args(["set-source-mute", "@DEFAULT_SOURCE@", "toggle"])
+        .status();
+    if !pactl.is_ok_and(|s| s.success()) {
+        warn!("Could not toggle microphone mute (tried wpctl and pactl)");
+        return None;
+    }
+    let out = Command::new("pactl")
+        .args(["get-source-mute", "@DEFAULT_SOURCE@"])
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&out.stdout).contains("yes"))
+}
+
+/// Spawn the mute toggler on the current tokio runtime, bound to `event`.
+pub fn spawn(event: String, props: PropertyStore) {
+    tokio::spawn(async move {
+        let mut watcher = EventWatcher::new(&event);
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            if !watcher.fired(&props).await {
+                continue;
+            }
+
+            let Ok(muted) = tokio::task::spawn_blocking(toggle_mute).await else {
+                continue;
+            };
+            match muted {
+                Some(true) => {
+                    info!("Microphone muted");
+                    notify::send("Microphone muted", "Tap again to unmute");
+                }
+                Some(false) => {
+                    info!("Microphone unmuted");
+                    notify::send("Microphone unmuted", "Your microphone is live");
+                }
+                None => {}
+            }
+        }
+    });
+}
@@ -0,0 +1,26 @@
+//! Optional integrations with the desktop environment.
+//!
+//! Each integration runs as a background task next to the Bluetooth
+//! manager, reads device state from the PropertyStore and requests changes
+//! through the same property channel the UIs use.
+
+pub mod activity;
+pub mod ambient;
+pub mod alerts;
+pub mod anc_habits;
+pub mod assistant;
+pub mod audio_reclaim;
+pub mod auto_pause;
+pub mod battery_saver;
+pub mod device_notices;
+pub mod dnd;
+pub mod firmware;
+pub mod fullscreen;
+pub mod inhibit;
+pub mod kde_connect;
+pub mod mic_mute;
+pub mod mpris;
+pub mod notify;
+pub mod single_bud;
+pub mod upower;
+pub mod webhooks;
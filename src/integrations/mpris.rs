@@ -0,0 +1,74 @@
+//! MPRIS players on the session bus, shared by the integrations that pause,
+//! resume or watch local playback. All calls are blocking.
+
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::{Connection, Proxy};
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Bus names of the MPRIS players on the session bus.
+fn players(conn: &Connection) -> Vec<String> {
+    let bus = conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", TIMEOUT);
+    bus.method_call::<(Vec<String>,), _, _, _>("org.freedesktop.DBus", "ListNames", ())
+        .map(|(names,)| {
+            names
+                .into_iter()
+                .filter(|n| n.starts_with("org.mpris.MediaPlayer2."))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn player<'a>(conn: &'a Connection, name: &'a str) -> Proxy<'a, &'a Connection> {
+    conn.with_proxy(name, "/org/mpris/MediaPlayer2", TIMEOUT)
+}
+
+/// Whether `player` reports `status` ("Playing", "Paused").
+fn has_status(player: &Proxy<'_, &Connection>, status: &str) -> bool {
+    player
+        .get::<String>(PLAYER_INTERFACE, "PlaybackStatus")
+        .is_ok_and(|s| s == status)
+}
+
+/// Whether any player is playing.
+pub fn any_playing() -> bool {
+    let Ok(conn) = Connection::new_session() else {
+        return false;
+    };
+    players(&conn)
+        .iter()
+        .any(|name| has_status(&player(&conn, name), "Playing"))
+}
+
+/// Pause every playing player; returns the ones paused.
+pub fn pause_playing() -> Vec<String> {
+    let Ok(conn) = Connection::new_session() else {
+        return Vec::new();
+    };
+    players(&conn)
+        .into_iter()
+        .filter(|name| {
+            let player = player(&conn, name);
+            has_status(&player, "Playing")
+                && player
+                    .method_call::<(), _, _, _>(PLAYER_INTERFACE, "Pause", ())
+                    .is_ok()
+        })
+        .collect()
+}
+
+/// Resume `names` that are still paused.
+pub fn resume(names: &[String]) {
+    let Ok(conn) = Connection::new_session() else {
+        return;
+    };
+    for name in names {
+        let player = player(&conn, name);
+        if has_status(&player, "Paused") {
+            let _ = player.method_call::<(), _, _, _>(PLAYER_INTERFACE, "Play", ());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Desktop notifications via the freedesktop notification server.
+
+use notify_rust::Notification;
+use tracing::{debug, info, warn};
+
+use super::dnd;
+use crate::device::request::{PropertyRequest, PropertySender};
+
+/// Identifier of the single button [`send_with_action`] adds.
+const ACTION_ID: &str = "mybuds-action";
+
+/// A notification button and the property change it requests.
+#[derive(Debug, Clone, Copy)]
+pub struct NotifyAction {
+    pub label: &'static str,
+    pub group: &'static str,
+    pub prop: &'static str,
+    pub value: &'static str,
+}
+
+/// Build a notification, or None while focus mode suppresses them.
+fn build(summary: &str, body: &str) -> Option<Notification> {
+    if dnd::notifications_suppressed() {
+        debug!("Notification suppressed by focus mode: {}", summary);
+        return None;
+    }
+    let mut notification = Notification::new();
+    notification
+        .appname("MyBuds")
+        .summary(summary)
+        .body(body)
+        .icon("audio-headphones");
+    Some(notification)
+}
+
+/// Show a desktop notification, unless focus mode is suppressing them.
+/// Runs on a blocking thread so callers never wait on D-Bus.
+pub fn send(summary: &str, body: &str) {
+    let Some(notification) = build(summary, body) else {
+        return;
+    };
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = notification.show() {
+            warn!("Failed to show notification: {}", e);
+        }
+    });
+}
+
+/// Like [`send`], with a button that requests `action`'s property change
+/// through `prop_tx` when clicked. A blocking thread waits for the click
+/// until the notification is closed.
+pub fn send_with_action(summary: &str, body: &str, action: NotifyAction, prop_tx: PropertySender) {
+    let Some(mut notification) = build(summary, body) else {
+        return;
+    };
+    notification.action(ACTION_ID, action.label);
+    tokio::task::spawn_blocking(move || {
+        let handle = match notification.show() {
+            Ok(handle) => handle,
+            Err(e) => {
+                warn!("Failed to show notification: {}", e);
+                return;
+            }
+        };
+        handle.wait_for_action(|id| {
+            if id == ACTION_ID {
+                info!("Notification action: {}", action.label);
+                let _ = prop_tx.blocking_send(PropertyRequest::fire_and_forget(
+                    action.group,
+                    action.prop,
+                    action.value,
+                ));
+            }
+        });
+    });
+}
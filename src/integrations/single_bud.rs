@@ -0,0 +1,242 @@
+//! Single-bud mode: adapt to wearing one earbud.
+//!
+//! The buds stop reporting a level for a bud that is in the closed case or
+//! out of range, so one bud in use shows up as a battery report with only
+//! `left` or `right`. Once that has held for a moment, the buds' audio is
+//! remixed to mono (a PipeWire/PulseAudio remap sink made the default
+//! output) so nothing is lost on the missing side, and a long-press ANC
+//! switch on the stored bud is turned off. Both are undone when the other
+//! bud comes back, the gesture once the buds are connected again if they
+//! dropped in between. The worn bud is published as `connection.single_bud`
+//! for the UIs.
+//!
+//! The gesture is remembered in memory only: if MyBuds exits in single-bud
+//! mode, set it again on the Gestures page.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
+use super::ambient;
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::device::handler::{put_properties, PropertyStore};
+use crate::device::request::{self, PropertySender};
+use crate::device::DEVICE_KEY;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a bud must stay away before switching, so a level missing from
+/// one report doesn't flip the audio.
+const SETTLE: Duration = Duration::from_secs(6);
+/// Key in the connection group naming the worn bud.
+pub const SINGLE_BUD_KEY: &str = "single_bud";
+const MONO_SINK: &str = "mybuds_mono";
+const ANC_SWITCH: &str = "tap_action_switch_anc";
+
+/// The worn bud when exactly one reports a level.
+pub fn worn_bud(battery: Option<&HashMap<String, String>>) -> Option<&'static str> {
+    let battery = battery?;
+    match (battery.contains_key("left"), battery.contains_key("right")) {
+        (true, false) => Some("left"),
+        (false, true) => Some("right"),
+        _ => None,
+    }
+}
+
+fn other_side(side: &str) -> &'static str {
+    if side == "left" {
+        "right"
+    } else {
+        "left"
+    }
+}
+
+fn pactl(args: &[&str]) -> Option<String> {
+    let out = Command::new("pactl").args(args).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Ids of remap modules an earlier run left loaded, from
+/// `pactl list short modules`.
+fn leftover_modules(modules: &str) -> Vec<String> {
+    modules
+        .lines()
+        .filter(|line| line.contains(&format!("sink_name={}", MONO_SINK)))
+        .filter_map(|line| line.split('\t').next())
+        .map(String::from)
+        .collect()
+}
+
+/// Mono output in front of the buds' sink, made the default.
+struct MonoSink {
+    module: String,
+    previous_default: Option<String>,
+}
+
+impl MonoSink {
+    fn load(address: &str) -> Option<Self> {
+        let Some(master) = ambient::bluetooth_sink(address) else {
+            debug!("No audio sink for {}, leaving audio as is", address);
+            return None;
+        };
+        let previous_default = pactl(&["get-default-sink"]);
+        let module = pactl(&[
+            "load-module",
+            "module-remap-sink",
+            &format!("sink_name={}", MONO_SINK),
+            &format!("master={}", master),
+            "channels=1",
+            "channel_map=mono",
+            "sink_properties=device.description=\"MyBuds mono\"",
+        ]);
+        let Some(module) = module else {
+            warn!("Could not load a mono sink for {} (is pactl installed?)", master);
+            return None;
+        };
+        pactl(&["set-default-sink", MONO_SINK]);
+        Some(Self {
+            module,
+            previous_default,
+        })
+    }
+
+    fn unload(self) {
+        if pactl(&["get-default-sink"]).as_deref() == Some(MONO_SINK) {
+            if let Some(previous) = &self.previous_default {
+                pactl(&["set-default-sink", previous]);
+            }
+        }
+        pactl(&["unload-module", &self.module]);
+    }
+}
+
+/// Mono sink in place while one bud is in use.
+struct Active {
+    side: &'static str,
+    mono: Option<MonoSink>,
+}
+
+/// Switch to single-bud mode. Returns the stored bud's long-press gesture
+/// if it was turned off, to restore later.
+async fn enter(
+    side: &'static str,
+    store: &HashMap<String, HashMap<String, String>>,
+    prop_tx: &PropertySender,
+) -> (Active, Option<(String, String)>) {
+    info!("Only the {} bud is in use, switching to single-bud mode", side);
+    let address = store
+        .get(CONNECTION_GROUP)
+        .and_then(|c| c.get(DEVICE_KEY))
+        .cloned();
+    let mono = match address {
+        Some(address) => tokio::task::spawn_blocking(move || MonoSink::load(&address))
+            .await
+            .ok()
+            .flatten(),
+        None => None,
+    };
+
+    let prop = format!("long_tap_{}", other_side(side));
+    let current = store.get("action").and_then(|a| a.get(&prop));
+    let mut gesture = None;
+    if current.is_some_and(|v| v == ANC_SWITCH) {
+        match request::request(prop_tx, "action", &prop, "tap_action_off").await {
+            Ok(()) => gesture = Some((prop, ANC_SWITCH.to_string())),
+            Err(e) => warn!("Could not turn off ANC switching on the stored bud: {}", e),
+        }
+    }
+    (Active { side, mono }, gesture)
+}
+
+/// Spawn the single-bud watcher on the current tokio runtime.
+pub fn spawn(props: PropertyStore, prop_tx: PropertySender) {
+    tokio::spawn(async move {
+        let _ = tokio::task::spawn_blocking(|| {
+            let modules = pactl(&["list", "short", "modules"]).unwrap_or_default();
+            for id in leftover_modules(&modules) {
+                pactl(&["unload-module", &id]);
+            }
+        })
+        .await;
+
+        let mut active: Option<Active> = None;
+        // Stored bud's gesture, kept across disconnects until both buds are back
+        let mut turned_off: Option<(String, String)> = None;
+        // Worn bud as last seen, and since when
+        let mut seen: (Option<&'static str>, Instant) = (None, Instant::now());
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let store = props.lock().await.clone();
+            let connected = ConnectionState::from_group(store.get(CONNECTION_GROUP))
+                == ConnectionState::Connected;
+            let worn = worn_bud(store.get("battery")).filter(|_| connected);
+            if worn != seen.0 {
+                seen = (worn, Instant::now());
+            }
+            let settled = seen.1.elapsed() >= SETTLE || !connected;
+            if !settled {
+                continue;
+            }
+
+            if connected && worn.is_none() {
+                if let Some((prop, value)) = turned_off.take() {
+                    if let Err(e) = request::request(&prop_tx, "action", &prop, &value).await {
+                        warn!("Could not restore {}: {}", prop, e);
+                        turned_off = Some((prop, value));
+                    }
+                }
+            }
+            if worn == active.as_ref().map(|a| a.side) {
+                continue;
+            }
+
+            if let Some(previous) = active.take() {
+                info!("Leaving single-bud mode");
+                if let Some(mono) = previous.mono {
+                    let _ = tokio::task::spawn_blocking(move || mono.unload()).await;
+                }
+                if let Some(group) = props.lock().await.get_mut(CONNECTION_GROUP) {
+                    group.remove(SINGLE_BUD_KEY);
+                }
+            }
+            if let Some(side) = worn {
+                let (entered, gesture) = enter(side, &store, &prop_tx).await;
+                active = Some(entered);
+                turned_off = turned_off.or(gesture);
+                let value = HashMap::from([(SINGLE_BUD_KEY.to_string(), side.to_string())]);
+                put_properties(&props, CONNECTION_GROUP, value).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(keys: &[&str]) -> HashMap<String, String> {
+        keys.iter().map(|k| (k.to_string(), "50".to_string())).collect()
+    }
+
+    #[test]
+    fn detects_the_worn_bud() {
+        assert_eq!(worn_bud(Some(&battery(&["left", "case"]))), Some("left"));
+        assert_eq!(worn_bud(Some(&battery(&["global", "right"]))), Some("right"));
+        assert_eq!(worn_bud(Some(&battery(&["left", "right", "case"]))), None);
+        // Neckbands only report a global level
+        assert_eq!(worn_bud(Some(&battery(&["global"]))), None);
+        assert_eq!(worn_bud(None), None);
+    }
+
+    #[test]
+    fn finds_leftover_mono_sinks() {
+        let modules = "536870913\tmodule-always-sink\t\t\n\
+                       536870920\tmodule-remap-sink\tsink_name=mybuds_mono master=bluez_output.AA_BB_CC_DD_EE_FF.1 channels=1\t\n";
+        assert_eq!(leftover_modules(modules), vec!["536870920".to_string()]);
+    }
+}
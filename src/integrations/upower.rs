@@ -0,0 +1,209 @@
+//! Buds battery in the desktop's power panel.
+//!
+//! UPower has no API for programs to add devices of their own: it lists the
+//! `org.bluez.Battery1` objects BlueZ creates for Bluetooth devices. BlueZ
+//! takes those levels from battery providers, so MyBuds registers one with
+//! the adapter (`org.bluez.BatteryProviderManager1`) and exports the buds'
+//! level as an `org.bluez.BatteryProvider1` object. GNOME and KDE then show
+//! the headphones like a mouse or a keyboard.
+//!
+//! BlueZ keeps one battery per device, so the level is the emptier bud's
+//! (or the single level of models that report one); the per-bud and case
+//! levels stay in the MyBuds UIs and the tray. BlueZ ignores a provided
+//! level for a device that already reports one itself (HFP battery
+//! indicators), and some BlueZ releases only offer the provider API with
+//! `bluetoothd --experimental`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bluer::Address;
+use dbus::arg::{prop_cast, RefArg, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{ObjectManager, PropertiesPropertiesChanged};
+use dbus::blocking::SyncConnection;
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::Path;
+use dbus_crossroads::{Crossroads, IfaceBuilder};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use super::alerts::lowest_level;
+
+const DBUS_TIMEOUT: Duration = Duration::from_secs(2);
+/// Root of the provider's objects, handed to BlueZ on registration.
+const PROVIDER_PATH: &str = "/io/github/joshuapassos/mybuds/battery";
+const PROVIDER_IFACE: &str = "org.bluez.BatteryProvider1";
+const MANAGER_IFACE: &str = "org.bluez.BatteryProviderManager1";
+
+/// What the battery object reports.
+struct Battery {
+    device: Path<'static>,
+    percentage: u8,
+}
+
+/// Object path of the battery for `address`, under the provider root.
+fn battery_path(address: Address) -> Path<'static> {
+    let name = address.to_string().replace(':', "_");
+    Path::from(format!("{}/dev_{}", PROVIDER_PATH, name))
+}
+
+/// BlueZ object paths of the device with `address` and of its adapter.
+fn bluez_paths(conn: &SyncConnection, address: Address) -> Result<(Path<'static>, Path<'static>)> {
+    let objects = conn
+        .with_proxy("org.bluez", "/", DBUS_TIMEOUT)
+        .get_managed_objects()
+        .context("Could not list BlueZ devices")?;
+    let wanted = address.to_string();
+    objects
+        .into_iter()
+        .find_map(|(path, interfaces)| {
+            let device = interfaces.get("org.bluez.Device1")?;
+            let found = prop_cast::<String>(device, "Address")?;
+            let adapter = prop_cast::<Path<'static>>(device, "Adapter")?.clone();
+            found
+                .eq_ignore_ascii_case(&wanted)
+                .then_some((path, adapter))
+        })
+        .with_context(|| format!("BlueZ has no device {}", wanted))
+}
+
+/// Devices the Bluetooth manager targets, as address and name.
+type Devices = watch::Receiver<(Address, String)>;
+
+/// Export the battery level from `battery` for `address` until the manager
+/// switches devices or goes away. Returns whether it switched. Blocking.
+fn serve(
+    address: Address,
+    devices: &mut Devices,
+    battery: &mut watch::Receiver<HashMap<String, String>>,
+) -> Result<bool> {
+    let conn = Arc::new(SyncConnection::new_system().context("No system bus")?);
+    let (device, adapter) = bluez_paths(&conn, address)?;
+    let path = battery_path(address);
+
+    let mut cr = Crossroads::new();
+    let token = cr.register(PROVIDER_IFACE, |b: &mut IfaceBuilder<Battery>| {
+        b.property("Device")
+            .get(|_, battery| Ok(battery.device.clone()));
+        b.property("Percentage")
+            .get(|_, battery| Ok(battery.percentage));
+        b.property("Source").get(|_, _| Ok("MyBuds".to_string()));
+    });
+    let object_manager = cr.object_manager();
+    cr.insert(PROVIDER_PATH, &[object_manager], ());
+    // Batteries appearing and going away reach BlueZ as InterfacesAdded
+    // and InterfacesRemoved
+    cr.set_object_manager_support(Some(conn.clone()));
+    let cr = Arc::new(Mutex::new(cr));
+
+    let handler = cr.clone();
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            let _ = handler.lock().unwrap().handle_message(msg, conn);
+            true
+        }),
+    );
+    conn.with_proxy("org.bluez", &adapter, DBUS_TIMEOUT)
+        .method_call::<(), _, _, _>(
+            MANAGER_IFACE,
+            "RegisterBatteryProvider",
+            (Path::from(PROVIDER_PATH),),
+        )
+        .context("BlueZ refused the battery provider")?;
+    info!("Reporting the buds' battery to BlueZ for the desktop power panel");
+
+    let mut shown: Option<u8> = None;
+    loop {
+        let level = lowest_level(&battery.borrow_and_update());
+        if level != shown {
+            let mut cr = cr.lock().unwrap();
+            match (shown, level) {
+                (_, None) => {
+                    cr.remove::<Battery>(&path);
+                }
+                (None, Some(percentage)) => {
+                    let device = device.clone();
+                    cr.insert(path.clone(), &[token], Battery { device, percentage });
+                }
+                (Some(_), Some(percentage)) => {
+                    if let Some(battery) = cr.data_mut::<Battery>(&path) {
+                        battery.percentage = percentage;
+                    }
+                    let changed = PropertiesPropertiesChanged {
+                        interface_name: PROVIDER_IFACE.to_string(),
+                        changed_properties: HashMap::from([(
+                            "Percentage".to_string(),
+                            Variant(Box::new(percentage) as Box<dyn RefArg>),
+                        )]),
+                        invalidated_properties: Vec::new(),
+                    };
+                    let _ = conn.send(changed.to_emit_message(&path));
+                }
+            }
+            debug!("Battery provider level: {:?}", level);
+            shown = level;
+        }
+
+        // Answer BlueZ until the next battery change, or until the
+        // Bluetooth manager switches devices or is gone
+        loop {
+            let switched = match (devices.has_changed(), battery.has_changed()) {
+                (Ok(true), _) => true,
+                (Err(_), _) | (_, Err(_)) => false,
+                (Ok(false), Ok(true)) => break,
+                (Ok(false), Ok(false)) => {
+                    conn.process(Duration::from_secs(1))?;
+                    continue;
+                }
+            };
+            let _ = conn
+                .with_proxy("org.bluez", &adapter, DBUS_TIMEOUT)
+                .method_call::<(), _, _, _>(
+                    MANAGER_IFACE,
+                    "UnregisterBatteryProvider",
+                    (Path::from(PROVIDER_PATH),),
+                );
+            return Ok(switched);
+        }
+    }
+}
+
+/// Report the buds' battery to BlueZ (and so UPower) on a thread of its
+/// own, for as long as `battery` is fed. Follows the manager to the device
+/// it switches to.
+pub fn spawn(mut devices: Devices, mut battery: watch::Receiver<HashMap<String, String>>) {
+    let spawned = std::thread::Builder::new()
+        .name("upower-battery".into())
+        .spawn(move || loop {
+            let address = devices.borrow_and_update().0;
+            match serve(address, &mut devices, &mut battery) {
+                Ok(true) => debug!("Device switched, reporting the battery of the new one"),
+                Ok(false) => return,
+                Err(e) => {
+                    warn!("Desktop battery reporting stopped: {:#}", e);
+                    return;
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Could not start desktop battery reporting: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batteries_live_under_the_provider_root() {
+        let address: Address = "AA:BB:CC:DD:EE:FF".parse().unwrap();
+        assert_eq!(
+            &*battery_path(address),
+            "/io/github/joshuapassos/mybuds/battery/dev_AA_BB_CC_DD_EE_FF"
+        );
+    }
+}
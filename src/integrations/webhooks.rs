@@ -0,0 +1,338 @@
+//! Webhooks: POST device events as JSON to user-configured URLs.
+//!
+//! Each `[[webhooks]]` entry gets its own watcher on the PropertyStore and
+//! is sent the events it asked for:
+//!
+//! - `connected`: the link came up, with the battery levels known so far;
+//! - `battery_low`: the lowest bud level dropped to the entry's threshold;
+//! - `anc_changed`: the ANC mode changed while connected.
+//!
+//! A body looks like `{"event": "anc_changed", "device": "HUAWEI FreeBuds
+//! Pro 3", "address": "AA:BB:CC:DD:EE:FF", "timestamp": 1760000000, "data":
+//! {"mode": "cancellation", "previous": "normal"}}`. With a `secret`, the
+//! HMAC-SHA256 of the body is sent as `X-MyBuds-Signature: sha256=<hex>`.
+//! Network errors, 429 and 5xx responses are retried a few times with
+//! growing delays; other responses are final.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ring::hmac;
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+
+use super::alerts::lowest_level;
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::WebhookConfig;
+use crate::device::handler::PropertyStore;
+use crate::device::DEVICE_KEY;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Wait before each retry of a failed delivery.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(120),
+];
+
+type Store = HashMap<String, HashMap<String, String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Connected,
+    BatteryLow,
+    AncChanged,
+}
+
+impl Event {
+    const ALL: [Event; 3] = [Self::Connected, Self::BatteryLow, Self::AncChanged];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::BatteryLow => "battery_low",
+            Self::AncChanged => "anc_changed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s)
+    }
+}
+
+/// What a watcher saw on the last poll.
+#[derive(Debug, Default)]
+struct Seen {
+    connected: bool,
+    level: Option<u8>,
+    anc: Option<String>,
+}
+
+impl Seen {
+    /// Events since the last poll, with their data.
+    fn update(&mut self, store: &Store, threshold: u8) -> Vec<(Event, Value)> {
+        let mut events = Vec::new();
+        if ConnectionState::from_group(store.get(CONNECTION_GROUP)) != ConnectionState::Connected {
+            *self = Self::default();
+            return events;
+        }
+        let battery = store.get("battery");
+
+        if !std::mem::replace(&mut self.connected, true) {
+            let levels: serde_json::Map<_, _> = ["left", "right", "case", "global"]
+                .into_iter()
+                .filter_map(|key| {
+                    let level = battery?.get(key)?.parse::<u8>().ok()?;
+                    Some((key.to_string(), level.into()))
+                })
+                .collect();
+            events.push((Event::Connected, json!({ "battery": levels })));
+        }
+
+        if let Some(level) = battery.and_then(lowest_level) {
+            let previous = self.level.replace(level);
+            if previous.is_some_and(|p| p > threshold) && level <= threshold {
+                events.push((
+                    Event::BatteryLow,
+                    json!({ "level": level, "threshold": threshold }),
+                ));
+            }
+        }
+
+        let anc = store.get("anc").and_then(|a| a.get("mode"));
+        if let Some(mode) = anc.filter(|&m| self.anc.as_ref() != Some(m)) {
+            // The first mode after connecting is no change
+            if let Some(previous) = self.anc.replace(mode.clone()) {
+                events.push((
+                    Event::AncChanged,
+                    json!({ "mode": mode, "previous": previous }),
+                ));
+            }
+        }
+        events
+    }
+}
+
+fn body(event: Event, data: Value, store: &Store) -> String {
+    let device = store
+        .get("info")
+        .and_then(|i| i.get("device_name").or_else(|| i.get("device_model")));
+    let address = store.get(CONNECTION_GROUP).and_then(|c| c.get(DEVICE_KEY));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    json!({
+        "event": event.as_str(),
+        "device": device,
+        "address": address,
+        "timestamp": timestamp,
+        "data": data,
+    })
+    .to_string()
+}
+
+/// `sha256=<hex>` HMAC of `body` keyed with `secret`.
+fn signature(secret: &str, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body.as_bytes());
+    let hex: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// Host part of `url`, for logs: webhook paths often hold secrets.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+enum Outcome {
+    Sent,
+    /// Worth trying again later.
+    Failed(String),
+    Rejected(String),
+}
+
+/// POST `body` to `url`. Blocking.
+fn post(url: &str, event: Event, body: &str, signature: Option<&str>) -> Outcome {
+    let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+    let mut request = agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .set("X-MyBuds-Event", event.as_str());
+    if let Some(signature) = signature {
+        request = request.set("X-MyBuds-Signature", signature);
+    }
+    match request.send_string(body) {
+        Ok(_) => Outcome::Sent,
+        Err(ureq::Error::Status(code, _)) if code == 429 || code >= 500 => {
+            Outcome::Failed(format!("HTTP {}", code))
+        }
+        Err(ureq::Error::Status(code, _)) => Outcome::Rejected(format!("HTTP {}", code)),
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// Send one event, retrying failed attempts.
+async fn deliver(url: String, event: Event, body: String, signature: Option<String>) {
+    let delays = std::iter::once(Duration::ZERO).chain(RETRY_DELAYS);
+    for (attempt, delay) in delays.enumerate() {
+        tokio::time::sleep(delay).await;
+        let (u, b, s) = (url.clone(), body.clone(), signature.clone());
+        let outcome = tokio::task::spawn_blocking(move || post(&u, event, &b, s.as_deref()))
+            .await
+            .unwrap_or_else(|e| Outcome::Failed(e.to_string()));
+        match outcome {
+            Outcome::Sent => {
+                debug!("Webhook {} delivered to {}", event.as_str(), host(&url));
+                return;
+            }
+            Outcome::Rejected(reason) => {
+                warn!(
+                    "{} rejected webhook {}: {}",
+                    host(&url),
+                    event.as_str(),
+                    reason
+                );
+                return;
+            }
+            Outcome::Failed(reason) => warn!(
+                "Webhook {} to {} failed (attempt {}): {}",
+                event.as_str(),
+                host(&url),
+                attempt + 1,
+                reason
+            ),
+        }
+    }
+    warn!("Giving up on webhook {} to {}", event.as_str(), host(&url));
+}
+
+/// Spawn the watcher for one webhook on the current tokio runtime.
+pub fn spawn(config: WebhookConfig, props: PropertyStore) {
+    let mut events: Vec<Event> = config
+        .events
+        .iter()
+        .filter_map(|name| {
+            let event = Event::parse(name);
+            if event.is_none() {
+                warn!("Ignoring unknown webhook event {:?}", name);
+            }
+            event
+        })
+        .collect();
+    if config.events.is_empty() {
+        events = Event::ALL.to_vec();
+    }
+
+    tokio::spawn(async move {
+        let mut seen = Seen::default();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let store = props.lock().await.clone();
+            for (event, data) in seen.update(&store, config.low_battery_threshold) {
+                if !events.contains(&event) {
+                    continue;
+                }
+                info!(
+                    "Sending webhook {} to {}",
+                    event.as_str(),
+                    host(&config.url)
+                );
+                let body = body(event, data, &store);
+                let signature = config.secret.as_deref().map(|s| signature(s, &body));
+                tokio::spawn(deliver(config.url.clone(), event, body, signature));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(state: &str, battery: &[(&str, &str)], anc: Option<&str>) -> Store {
+        let mut store = Store::new();
+        store.insert(
+            CONNECTION_GROUP.into(),
+            HashMap::from([("state".into(), state.into())]),
+        );
+        let battery = battery.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        store.insert("battery".into(), battery.collect());
+        if let Some(mode) = anc {
+            store.insert("anc".into(), HashMap::from([("mode".into(), mode.into())]));
+        }
+        store
+    }
+
+    fn names(events: &[(Event, Value)]) -> Vec<&'static str> {
+        events.iter().map(|(e, _)| e.as_str()).collect()
+    }
+
+    #[test]
+    fn reports_transitions_once() {
+        let mut seen = Seen::default();
+        let events = seen.update(
+            &store(
+                "connected",
+                &[("left", "30"), ("right", "25")],
+                Some("normal"),
+            ),
+            20,
+        );
+        assert_eq!(names(&events), ["connected"]);
+        assert_eq!(
+            events[0].1,
+            json!({ "battery": { "left": 30, "right": 25 } })
+        );
+
+        let events = seen.update(
+            &store(
+                "connected",
+                &[("left", "30"), ("right", "20")],
+                Some("normal"),
+            ),
+            20,
+        );
+        assert_eq!(names(&events), ["battery_low"]);
+        let events = seen.update(
+            &store(
+                "connected",
+                &[("left", "30"), ("right", "19")],
+                Some("cancellation"),
+            ),
+            20,
+        );
+        assert_eq!(names(&events), ["anc_changed"]);
+        assert_eq!(
+            events[0].1,
+            json!({ "mode": "cancellation", "previous": "normal" })
+        );
+
+        assert!(seen
+            .update(&store("reconnecting", &[], None), 20)
+            .is_empty());
+        let events = seen.update(
+            &store("connected", &[("left", "30")], Some("awareness")),
+            20,
+        );
+        assert_eq!(names(&events), ["connected"]);
+    }
+
+    #[test]
+    fn signs_and_redacts() {
+        assert_eq!(
+            signature("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert_eq!(
+            host("https://ha.local:8123/api/webhook/abc"),
+            "ha.local:8123"
+        );
+        assert_eq!(host("https://ntfy.sh?x=1"), "ntfy.sh");
+        assert_eq!(Event::parse("battery_low"), Some(Event::BatteryLow));
+        assert_eq!(Event::parse("disconnected"), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Display names for device values.
+//!
+//! The GUI, TUI, tray and CLI all name protocol values through these
+//! functions, so a mode or preset reads the same everywhere. There is no
+//! translation layer yet; when one lands, this is the place it hooks into.
+
+use crate::device::preset_labels;
+
+/// "double_tap_left" -> "Double tap left". Fallback for values without a
+/// dedicated name.
+pub fn humanize(name: &str) -> String {
+    let name = name.replace('_', " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// ANC / listening mode (Huawei and AirPods names).
+pub fn anc_mode(mode: &str) -> String {
+    match mode {
+        "normal" | "off" => "Off".into(),
+        "cancellation" | "noise_cancelling" | "anc" => "Noise Cancelling".into(),
+        "awareness" => "Awareness".into(),
+        "transparency" => "Transparency".into(),
+        "adaptive" => "Adaptive".into(),
+        other => humanize(other),
+    }
+}
+
+/// ANC strength within the current mode.
+pub fn anc_level(level: &str) -> String {
+    match level {
+        "voice_boost" => "Voice Boost".into(),
+        other => humanize(other),
+    }
+}
+
+pub fn eq_preset(key: &str) -> String {
+    match key {
+        "equalizer_preset_default" => "Default".into(),
+        "equalizer_preset_hardbass" => "Bass Boost".into(),
+        "equalizer_preset_treble" => "Treble Boost".into(),
+        "equalizer_preset_voices" | "equalizer_preset_voice" => "Voice".into(),
+        other if other.starts_with(preset_labels::UNKNOWN_PREFIX) => {
+            format!("Preset {} (unknown)", &other[preset_labels::UNKNOWN_PREFIX.len()..])
+        }
+        other => other.replace("equalizer_preset_", "").replace('_', " "),
+    }
+}
+
+pub fn sound_quality(key: &str) -> String {
+    match key {
+        "sqp_connectivity" => "Connectivity Priority".into(),
+        "sqp_quality" => "Sound Quality Priority".into(),
+        other => other.to_string(),
+    }
+}
+
+/// Action assigned to a gesture.
+pub fn gesture(name: &str) -> String {
+    match name {
+        "tap_action_off" => "Disabled".into(),
+        "tap_action_pause" => "Play/Pause".into(),
+        "tap_action_next" => "Next Track".into(),
+        "tap_action_prev" => "Previous Track".into(),
+        "tap_action_assistant" => "Voice Assistant".into(),
+        "tap_action_answer" => "Answer Call".into(),
+        "tap_action_switch_anc" => "Switch ANC".into(),
+        "tap_action_change_volume" => "Volume Control".into(),
+        "noise_control_off_on" => "Off / NC".into(),
+        "noise_control_off_on_aw" => "Off / NC / Awareness".into(),
+        "noise_control_on_aw" => "NC / Awareness".into(),
+        "noise_control_off_aw" => "Off / Awareness".into(),
+        other => humanize(other),
+    }
+}
+
+/// Display name of `group.prop = value`, for properties whose values have
+/// names. None for everything else (numbers, addresses, free text).
+pub fn for_property(group: &str, prop: &str, value: &str) -> Option<String> {
+    match (group, prop) {
+        ("anc", "mode") => Some(anc_mode(value)),
+        ("anc", "level") => Some(anc_level(value)),
+        (_, "equalizer_preset") => Some(eq_preset(value)),
+        (_, "quality_preference") => Some(sound_quality(value)),
+        ("action", _) if !prop.ends_with("_options") => Some(gesture(value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_values_the_same_for_every_vendor() {
+        assert_eq!(anc_mode("cancellation"), "Noise Cancelling");
+        assert_eq!(anc_mode("anc"), "Noise Cancelling");
+        assert_eq!(anc_level("comfort"), "Comfort");
+        assert_eq!(humanize("double_tap"), "Double tap");
+        assert_eq!(
+            for_property("action", "double_tap_left", "tap_action_next").as_deref(),
+            Some("Next Track")
+        );
+        assert_eq!(for_property("battery", "left", "80"), None);
+    }
+}
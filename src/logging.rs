@@ -0,0 +1,81 @@
+//! Logging setup and the per-user state directory.
+//!
+//! Logs, crash reports and other files that aren't config live under
+//! `$XDG_STATE_HOME/mybuds` (usually ~/.local/state/mybuds), created
+//! readable by the user only since logs include device addresses.
+
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use tracing_subscriber::prelude::*;
+
+/// Where the log is being written, if it goes to a file.
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// `$XDG_STATE_HOME/mybuds`, falling back to the cache dir on systems
+/// without a state dir.
+pub fn state_dir() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::cache_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("mybuds")
+}
+
+/// Create `dir` (and missing parents) accessible by the current user only.
+pub fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+/// Log file used when none is given on the command line.
+pub fn default_log_file() -> PathBuf {
+    state_dir().join("mybuds.log")
+}
+
+/// The file logs are written to, or None when logging to the terminal.
+pub fn log_file() -> Option<&'static Path> {
+    LOG_FILE.get().map(|p| p.as_path())
+}
+
+fn open_log_file(path: &Path) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        create_private_dir(dir)
+            .with_context(|| format!("Cannot create log directory {}", dir.display()))?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Cannot open log file {}", path.display()))
+}
+
+/// Install the global subscriber. Logs go to `file` when given, otherwise
+/// to the terminal. Recent events are also kept in memory for crash reports.
+pub fn init(file: Option<PathBuf>) -> Result<()> {
+    let env_filter = tracing_subscriber::EnvFilter::from_default_env()
+        .add_directive("mybuds=debug".parse().unwrap())
+        .add_directive("bluer=info".parse().unwrap());
+    let registry = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(crate::crash::RecentEvents);
+
+    match file {
+        Some(path) => {
+            let log_file = open_log_file(&path)?;
+            registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_writer(log_file)
+                        .with_ansi(false),
+                )
+                .init();
+            let _ = LOG_FILE.set(path);
+        }
+        None => registry.with(tracing_subscriber::fmt::layer()).init(),
+    }
+    Ok(())
+}
@@ -1,24 +1,42 @@
 mod bluetooth;
+mod cli;
 mod config;
+mod control;
+mod crash;
 mod device;
 mod instance_lock;
+mod integrations;
+mod labels;
+mod logging;
 mod protocol;
+mod sandbox;
+mod startup;
 mod tray;
 mod tui;
 mod ui;
+mod varlink;
 
 use std::sync::Arc;
 
 use anyhow::Result;
 use bluer::Address;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tokio::sync::{mpsc, Mutex};
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
 
+use bluetooth::cache::BondedDevice;
 use bluetooth::scanner;
+use bluetooth::sleep_timer::SLEEP_TIMER_PROP;
+use bluetooth::state::{ConnectionState, CONNECTION_GROUP};
 use config::AppConfig;
 use device::handler::PropertyStore;
-use device::models::profile_for_device;
+use device::events::GestureEventHandler;
+use device::models::overrides;
+use device::models::rules::DeviceRules;
+use device::models::{self, DeviceProfile};
+use device::request::{PropertyReceiver, PropertyRequest, PropertySender};
+use control::{ControlCommand, ControlFlags};
 use tray::TrayFlags;
 
 #[derive(Parser)]
@@ -27,120 +45,436 @@ struct Cli {
     /// Run in terminal UI mode instead of GUI
     #[arg(long)]
     tui: bool,
+
+    /// Show a read-only, auto-refreshing status screen in the terminal
+    /// (for tmux panes and monitors). Implies --tui
+    #[arg(long)]
+    dashboard: bool,
+
+    /// If MyBuds is already running, raise its window and exit
+    #[arg(long, conflicts_with = "replace")]
+    show: bool,
+
+    /// If MyBuds is already running, close it and take over
+    #[arg(long)]
+    replace: bool,
+
+    /// Write logs to this file instead of the default
+    /// (~/.local/state/mybuds/mybuds.log in TUI and CLI modes, the terminal otherwise)
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Never send setting changes to the device, only read its state
+    /// (same as `read_only = true` in the config)
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print every device property, optionally setting some first
+    Props {
+        /// Keep running and print changes; set properties by typing group.prop=value
+        #[arg(long)]
+        watch: bool,
+
+        /// Set a property before printing (repeatable)
+        #[arg(long = "set", value_name = "GROUP.PROP=VALUE")]
+        set: Vec<String>,
+
+        /// Print the properties as one JSON object; with --watch, again on
+        /// one line after every change (for waybar/polybar modules)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the device, connection, battery and sound settings
+    Status,
+    /// Print battery levels
+    Battery,
+    /// Print the ANC mode, or change it (e.g. `mybuds anc set cancellation`)
+    Anc {
+        #[command(subcommand)]
+        action: Option<SettingAction>,
+    },
+    /// Print the equalizer preset, or change it (e.g. `mybuds eq set hardbass`)
+    Eq {
+        #[command(subcommand)]
+        action: Option<SettingAction>,
+    },
+    /// Save the device's current settings so they can be restored later
+    Snapshot,
+    /// Write the saved settings back to the device
+    Restore,
+    /// Print this session's statistics (traffic, reconnects, battery drain, ANC time)
+    Stats,
+    /// Switch between the two favorite ANC modes, e.g. from a keyboard shortcut
+    AncToggle,
+    /// Print property changes until interrupted
+    Watch {
+        /// One JSON object per change: group, key, old, new, timestamp
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run without a UI, keeping the connection for UIs that attach later
+    Daemon,
+}
+
+#[derive(Subcommand)]
+enum SettingAction {
+    /// Print the current value (the default)
+    Get,
+    /// Change the value; run `get` or `props` to see the accepted values
+    Set { value: String },
 }
 
 fn main() -> Result<()> {
+    startup::begin();
+
+    let mut cli = Cli::parse();
+    cli.tui |= cli.dashboard;
+
     // Acquire instance lock — only one instance allowed
-    let _lock = match instance_lock::InstanceLock::acquire() {
-        Ok(lock) => lock,
-        Err(e) => {
+    let lock = if cli.replace {
+        instance_lock::InstanceLock::acquire_replacing()
+    } else {
+        instance_lock::InstanceLock::acquire()
+    };
+    // A plain GUI launch (e.g. from the app menu) while running just raises
+    // the existing window, like --show
+    let activate = cli.show || (!cli.tui && cli.command.is_none());
+    let lock = match lock {
+        Ok(lock) => Some(lock),
+        Err(_) if activate && control::send_expecting_ok(&ControlCommand::Show).is_ok() => {
+            return Ok(());
+        }
+        Err(e) if cli.show || matches!(cli.command, Some(Command::Daemon)) => {
             eprintln!("{}", e);
             std::process::exit(1);
         }
+        // Otherwise attach to the running session below
+        Err(_) => None,
     };
+    // Initialize logging — in TUI and CLI modes, write to a log file to avoid corrupting the output
+    let to_file = cli.tui || (cli.command.is_some() && !matches!(cli.command, Some(Command::Daemon)));
+    let log_file = cli.log_file.clone().or_else(|| {
+        // Clients of a running instance get their own file, so they don't
+        // truncate the log the running instance is writing
+        to_file.then(|| match lock {
+            Some(_) => logging::default_log_file(),
+            None => logging::state_dir().join("mybuds-attached.log"),
+        })
+    });
+    logging::init(log_file)?;
+    crash::install_panic_hook();
 
-    let cli = Cli::parse();
-
-    // Initialize logging — in TUI mode, write to a log file to avoid corrupting the terminal
-    let env_filter = tracing_subscriber::EnvFilter::from_default_env()
-        .add_directive("mybuds=debug".parse().unwrap())
-        .add_directive("bluer=info".parse().unwrap());
+    info!("MyBuds starting");
 
-    if cli.tui {
-        let log_file = std::fs::File::create("/tmp/mybuds.log")?;
-        tracing_subscriber::fmt()
-            .with_env_filter(env_filter)
-            .with_writer(log_file)
-            .with_ansi(false)
-            .init();
-    } else {
-        tracing_subscriber::fmt()
-            .with_env_filter(env_filter)
-            .init();
+    if lock.is_none() {
+        return run_attached(cli);
     }
 
-    info!("MyBuds starting");
-
     // Load config
-    let config = AppConfig::load();
+    let mut config = AppConfig::load();
+    config.read_only |= cli.read_only;
+    if config.read_only {
+        info!("Read-only mode: setting changes won't be sent to the device");
+    }
 
     // Property change channel (UI -> device manager)
-    let (prop_tx, prop_rx) = mpsc::channel::<(String, String, String)>(32);
+    let (prop_tx, prop_rx) = mpsc::channel::<PropertyRequest>(32);
 
     // Shared property store
     let props: PropertyStore = Arc::new(Mutex::new(std::collections::HashMap::new()));
 
-    if cli.tui {
-        run_tui_mode(config, props, prop_tx, prop_rx)
+    if let Some(Command::Daemon) = cli.command {
+        run_daemon(config, props, prop_tx, prop_rx)
+    } else if let Some(command) = cli.command {
+        run_cli_mode(command, config, props, prop_tx, prop_rx)
+    } else if cli.tui {
+        run_tui_mode(config, props, prop_tx, prop_rx, cli.dashboard)
     } else {
         run_gui_mode(config, props, prop_tx, prop_rx)
     }
 }
 
+/// Run a UI or CLI command on top of the instance that holds the lock,
+/// sharing its connection instead of opening a second one.
+fn run_attached(cli: Cli) -> Result<()> {
+    if cli.read_only {
+        eprintln!("--read-only only applies when starting MyBuds; the running instance keeps its setting");
+    }
+    let (props, prop_tx) = match control::attach() {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Another instance of MyBuds is running and could not be reached: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(command) = cli.command {
+        run_cli_command(command, props, prop_tx)
+    } else if cli.dashboard {
+        tui::dashboard::run(props, CancellationToken::new())
+    } else if cli.tui {
+        tui::run(props, prop_tx, CancellationToken::new())
+    } else {
+        // No tray: the running instance owns it, closing the window exits
+        let title_status = AppConfig::load().window_title_status;
+        iced::daemon(MyBudsApp::title, MyBudsApp::update, MyBudsApp::view)
+            .theme(MyBudsApp::theme)
+            .subscription(MyBudsApp::subscription)
+            .run_with(move || {
+                MyBudsApp::new(props.clone(), Some(prop_tx), None, title_status)
+            })?;
+        Ok(())
+    }
+}
+
 fn run_gui_mode(
     config: AppConfig,
     props: PropertyStore,
-    prop_tx: mpsc::Sender<(String, String, String)>,
-    prop_rx: mpsc::Receiver<(String, String, String)>,
+    prop_tx: PropertySender,
+    prop_rx: PropertyReceiver,
 ) -> Result<()> {
     let props_clone = props.clone();
 
     // Shared tray flags for tray <-> iced communication
-    let tray_flags = TrayFlags::new();
+    let mut tray_flags = TrayFlags::new();
+    *tray_flags.title_format.lock().unwrap() = config.tray_title.clone();
+    tray_flags.favorite_anc_modes = config.favorite_anc_modes.clone();
     let tray_flags_clone = tray_flags.clone();
+    control::serve(ControlFlags {
+        show_window: Some(tray_flags.show_window.clone()),
+        shutdown: tray_flags.shutdown.clone(),
+        props: props.clone(),
+        prop_tx: prop_tx.clone(),
+        varlink: config.varlink,
+    });
 
     // Clone prop_tx so the tray can also send property changes (e.g. ANC mode)
     let prop_tx_tray = prop_tx.clone();
 
-    // Spawn Bluetooth manager in background
-    let config_clone = config.clone();
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async move {
-            // Spawn tray
-            let tray_flags_for_loop = tray_flags_clone.clone();
-            let tray_handle = tray::spawn_tray(tray_flags_clone);
+    // Cancelled when the app exits (window, tray or `--replace`) so pending
+    // connects are aborted and the tray goes away
+    let cancel = tray_flags.shutdown.clone();
+    let cancel_bt = cancel.clone();
 
-            if let Err(e) =
-                run_bluetooth_with_tray(config_clone, props_clone.clone(), prop_rx, tray_handle, tray_flags_for_loop, prop_tx_tray)
-                    .await
-            {
-                error!("Bluetooth manager error: {}", e);
-            }
+    // Spawn Bluetooth manager in background. If it panics it is restarted
+    // with a fresh runtime; requests are relayed from the UI's channel so
+    // the UI keeps its sender across restarts.
+    let config_clone = config.clone();
+    let bt_thread = std::thread::spawn(move || {
+        let tray_handle = tray::spawn_tray(tray_flags_clone.clone());
+        let prop_rx = Arc::new(Mutex::new(prop_rx));
+
+        crash::supervise("Bluetooth manager", &cancel_bt.clone(), || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                let (relay_tx, relay_rx) = mpsc::channel::<PropertyRequest>(32);
+                let shared_rx = prop_rx.clone();
+                tokio::spawn(async move {
+                    let mut rx = shared_rx.lock().await;
+                    while let Some(req) = rx.recv().await {
+                        if relay_tx.send(req).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                if let Err(e) = run_bluetooth_with_tray(
+                    config_clone.clone(),
+                    props_clone.clone(),
+                    relay_rx,
+                    tray_handle.clone(),
+                    tray_flags_clone.clone(),
+                    prop_tx_tray.clone(),
+                    cancel_bt.clone(),
+                )
+                .await
+                {
+                    error!("Bluetooth manager error: {}", e);
+                }
+            });
+            // Don't wait on blocking connects that were already abandoned
+            rt.shutdown_background();
         });
+        tray_handle.shutdown();
     });
 
     // Run iced daemon on main thread.
     // Unlike iced::application, the daemon does NOT exit when the last window
     // is closed — it keeps running so we can reopen from the system tray.
-    iced::daemon("MyBuds", MyBudsApp::update, MyBudsApp::view)
+    let title_status = config.window_title_status;
+    iced::daemon(MyBudsApp::title, MyBudsApp::update, MyBudsApp::view)
         .theme(MyBudsApp::theme)
         .subscription(MyBudsApp::subscription)
-        .run_with(move || MyBudsApp::new(props.clone(), Some(prop_tx), Some(tray_flags)))?;
+        .run_with(move || {
+            MyBudsApp::new(props.clone(), Some(prop_tx), Some(tray_flags), title_status)
+        })?;
 
+    shutdown_bluetooth(cancel, bt_thread);
     Ok(())
 }
 
+/// Stop the Bluetooth manager and wait for its thread to finish.
+fn shutdown_bluetooth(cancel: CancellationToken, bt_thread: std::thread::JoinHandle<()>) {
+    info!("Shutting down");
+    cancel.cancel();
+    if bt_thread.join().is_err() {
+        error!("Bluetooth thread panicked");
+    }
+}
+
 fn run_tui_mode(
     config: AppConfig,
     props: PropertyStore,
-    prop_tx: mpsc::Sender<(String, String, String)>,
-    prop_rx: mpsc::Receiver<(String, String, String)>,
+    prop_tx: PropertySender,
+    prop_rx: PropertyReceiver,
+    dashboard: bool,
 ) -> Result<()> {
+    let varlink = config.varlink;
     let props_clone = props.clone();
+    let prop_tx_bt = prop_tx.clone();
+    let cancel = CancellationToken::new();
+    let cancel_bt = cancel.clone();
 
     // Spawn Bluetooth manager in background (no tray for TUI mode)
-    std::thread::spawn(move || {
+    let bt_thread = std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async move {
-            if let Err(e) = run_bluetooth_headless(config, props_clone, prop_rx).await {
+            spawn_integrations(&config, &props_clone, &prop_tx_bt);
+            if let Err(e) = run_bluetooth_headless(config, props_clone, prop_rx, cancel_bt).await {
                 error!("Bluetooth manager error: {}", e);
             }
         });
+        rt.shutdown_background();
+    });
+
+    control::serve(ControlFlags {
+        show_window: None,
+        shutdown: cancel.clone(),
+        props: props.clone(),
+        prop_tx: prop_tx.clone(),
+        varlink,
     });
 
     // Run TUI on main thread
-    tui::run(props, prop_tx)
+    let result = if dashboard {
+        tui::dashboard::run(props, cancel.clone())
+    } else {
+        tui::run(props, prop_tx, cancel.clone())
+    };
+    shutdown_bluetooth(cancel, bt_thread);
+    result
+}
+
+fn run_cli_mode(
+    command: Command,
+    config: AppConfig,
+    props: PropertyStore,
+    prop_tx: PropertySender,
+    prop_rx: PropertyReceiver,
+) -> Result<()> {
+    let props_clone = props.clone();
+    let cancel = CancellationToken::new();
+    let cancel_bt = cancel.clone();
+
+    // Integrations are for long-running sessions; a CLI run only needs the device
+    let bt_thread = std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            if let Err(e) = run_bluetooth_headless(config, props_clone, prop_rx, cancel_bt).await {
+                error!("Bluetooth manager error: {}", e);
+            }
+        });
+        rt.shutdown_background();
+    });
+
+    let result = run_cli_command(command, props, prop_tx);
+    shutdown_bluetooth(cancel, bt_thread);
+    result
+}
+
+/// Run a CLI subcommand against a session (own connection or attached).
+fn run_cli_command(command: Command, props: PropertyStore, prop_tx: PropertySender) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new()?;
+    let result = rt.block_on(async move {
+        match command {
+            Command::Props { watch, set, json } => {
+                cli::props::run(props, prop_tx, set, watch, json).await
+            }
+            Command::Status => cli::status::status(props).await,
+            Command::Battery => cli::status::battery(props).await,
+            Command::Anc { action } => setting(props, prop_tx, &cli::setting::ANC, action).await,
+            Command::Eq { action } => {
+                setting(props, prop_tx, &cli::setting::EQUALIZER, action).await
+            }
+            Command::Snapshot => cli::snapshot::save(props).await,
+            Command::Restore => cli::snapshot::restore(props, prop_tx).await,
+            Command::Stats => cli::stats::run(props).await,
+            Command::AncToggle => cli::anc::toggle(props, prop_tx).await,
+            Command::Watch { json } => cli::watch::run(props, json).await,
+            Command::Daemon => unreachable!("daemon mode is started by main"),
+        }
+    });
+    rt.shutdown_background();
+    result
+}
+
+async fn setting(
+    props: PropertyStore,
+    prop_tx: PropertySender,
+    setting: &cli::setting::Setting,
+    action: Option<SettingAction>,
+) -> Result<()> {
+    match action.unwrap_or(SettingAction::Get) {
+        SettingAction::Get => cli::setting::get(props, setting).await,
+        SettingAction::Set { value } => cli::setting::set(props, prop_tx, setting, &value).await,
+    }
+}
+
+/// Headless mode: own the connection and serve UIs that attach over the
+/// control socket, until asked to quit or signalled.
+fn run_daemon(
+    config: AppConfig,
+    props: PropertyStore,
+    prop_tx: PropertySender,
+    prop_rx: PropertyReceiver,
+) -> Result<()> {
+    let cancel = CancellationToken::new();
+    control::serve(ControlFlags {
+        show_window: None,
+        shutdown: cancel.clone(),
+        props: props.clone(),
+        prop_tx: prop_tx.clone(),
+        varlink: config.varlink,
+    });
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        spawn_integrations(&config, &props, &prop_tx);
+        let bt = tokio::spawn(run_bluetooth_headless(config, props, prop_rx, cancel.clone()));
+
+        let mut sigterm =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+            _ = cancel.cancelled() => {}
+        }
+
+        info!("Shutting down");
+        cancel.cancel();
+        if let Ok(Err(e)) = bt.await {
+            error!("Bluetooth manager error: {}", e);
+        }
+        Ok::<_, anyhow::Error>(())
+    })?;
+    rt.shutdown_background();
+    Ok(())
 }
 
 // Re-export for iced
@@ -149,61 +483,48 @@ use ui::MyBudsApp;
 async fn run_bluetooth_with_tray(
     config: AppConfig,
     props: PropertyStore,
-    prop_rx: mpsc::Receiver<(String, String, String)>,
+    prop_rx: PropertyReceiver,
     tray_handle: ksni::Handle<tray::MyBudsTray>,
     tray_flags: TrayFlags,
-    prop_tx: mpsc::Sender<(String, String, String)>,
+    prop_tx: PropertySender,
+    cancel: CancellationToken,
 ) -> Result<()> {
-    // Find device
-    let (address, device_name) = match find_device(&config).await {
-        Some(dev) => dev,
-        None => {
-            info!("No device found. Waiting for device...");
-            loop {
-                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                if let Some(dev) = find_device(&config).await {
-                    break dev;
-                }
-            }
-        }
-    };
-
-    info!("Using device: {} ({})", device_name, address);
-
-    let profile = profile_for_device(&device_name);
-    info!(
-        "Device profile: {}, transport: {:?}",
-        profile.name, profile.transport
-    );
+    spawn_integrations(&config, &props, &prop_tx);
 
-    let mut bt_manager = bluetooth::BluetoothManager::new(address, profile, props.clone(), prop_rx);
+    let Some(device) = wait_for_device(&config, &props, &cancel).await else {
+        return Ok(());
+    };
 
-    // Update tray with device name
-    let name = device_name.clone();
-    tray_handle.update(move |tray| {
-        tray.device_name = Some(name.clone());
-    });
+    let mut bt_manager = bluetooth_manager(&config, props.clone(), prop_rx, cancel, device);
+    tray::follow_battery(tray_handle.clone(), bt_manager.battery_events());
 
-    // Spawn tray update loop
+    // Spawn tray update loop, naming whichever device is in use
+    let devices = bt_manager.device_events();
     let dm_props = props.clone();
     let tray_handle_clone = tray_handle.clone();
-    let device_name_clone = device_name.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
         loop {
             interval.tick().await;
-            tray::update_tray_from_props(
-                &tray_handle_clone,
-                &dm_props,
-                Some(&device_name_clone),
-            )
-            .await;
+            let device_name = devices.borrow().1.clone();
+            tray::update_tray_from_props(&tray_handle_clone, &dm_props, Some(&device_name)).await;
 
             // Check for pending ANC mode change from tray menu
             let pending = tray_flags.pending_anc_mode.lock().unwrap().take();
             if let Some(mode) = pending {
                 info!("Tray ANC mode change: {}", mode);
-                let _ = prop_tx.send(("anc".to_string(), "mode".to_string(), mode)).await;
+                let _ = prop_tx
+                    .send(PropertyRequest::fire_and_forget("anc", "mode", &mode))
+                    .await;
+            }
+
+            // Check for pending ANC level change (voice boost) from tray menu
+            let pending_level = tray_flags.pending_anc_level.lock().unwrap().take();
+            if let Some(level) = pending_level {
+                info!("Tray ANC level change: {}", level);
+                let _ = prop_tx
+                    .send(PropertyRequest::fire_and_forget("anc", "level", &level))
+                    .await;
             }
 
             // Check for pending Dual Connect toggle from tray menu
@@ -211,10 +532,34 @@ async fn run_bluetooth_with_tray(
             if let Some(enabled) = pending_dc {
                 info!("Tray Dual Connect toggle: {}", enabled);
                 let _ = prop_tx
-                    .send((
-                        "dual_connect".to_string(),
-                        "enabled".to_string(),
-                        enabled.to_string(),
+                    .send(PropertyRequest::fire_and_forget(
+                        "dual_connect",
+                        "enabled",
+                        &enabled.to_string(),
+                    ))
+                    .await;
+            }
+
+            // Check for an AirPods take-over request from tray menu
+            if tray_flags
+                .pending_take_over
+                .swap(false, std::sync::atomic::Ordering::Relaxed)
+            {
+                info!("Tray audio take-over");
+                let _ = prop_tx
+                    .send(PropertyRequest::fire_and_forget("audio_owner", "take_over", "true"))
+                    .await;
+            }
+
+            // Check for a sleep timer started or cancelled from tray menu
+            let pending_sleep = tray_flags.pending_sleep_timer.lock().unwrap().take();
+            if let Some(value) = pending_sleep {
+                info!("Tray sleep timer: {}", value);
+                let _ = prop_tx
+                    .send(PropertyRequest::fire_and_forget(
+                        CONNECTION_GROUP,
+                        SLEEP_TIMER_PROP,
+                        &value,
                     ))
                     .await;
             }
@@ -226,39 +571,183 @@ async fn run_bluetooth_with_tray(
     Ok(())
 }
 
-async fn run_bluetooth_headless(
-    config: AppConfig,
+/// Set up the Bluetooth manager for `device`, with the config's options
+/// and the profiles of devices it may switch to.
+fn bluetooth_manager(
+    config: &AppConfig,
     props: PropertyStore,
-    prop_rx: mpsc::Receiver<(String, String, String)>,
-) -> Result<()> {
-    let (address, device_name) = match find_device(&config).await {
-        Some(dev) => dev,
-        None => {
-            info!("No device found. Waiting for device...");
-            loop {
-                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                if let Some(dev) = find_device(&config).await {
-                    break dev;
-                }
-            }
-        }
-    };
-
+    prop_rx: PropertyReceiver,
+    cancel: CancellationToken,
+    device: (Address, String),
+) -> bluetooth::BluetoothManager {
+    let (address, device_name) = device;
     info!("Using device: {} ({})", device_name, address);
 
-    let profile = profile_for_device(&device_name);
+    let profile = device_profile(config, &device_name, address, None);
     info!(
         "Device profile: {}, transport: {:?}",
         profile.name, profile.transport
     );
 
-    let mut bt_manager = bluetooth::BluetoothManager::new(address, profile, props.clone(), prop_rx);
-    bt_manager.run_with_reconnect().await;
+    let mut bt_manager =
+        bluetooth::BluetoothManager::new(address, device_name, profile, props, prop_rx, cancel);
+    bt_manager.set_refresh_intervals(&config.refresh_intervals);
+    bt_manager.set_read_only(config.read_only);
+    bt_manager.set_device_rules(&config.device_rules);
+    bt_manager.set_watch_case(config.watch_airpods_case);
+    let profiles = config.clone();
+    bt_manager.set_profiles(move |name, address, model| {
+        device_profile(&profiles, name, address, model)
+    });
+    if config.upower_battery {
+        integrations::upower::spawn(bt_manager.device_events(), bt_manager.battery_events());
+    }
+    bt_manager
+}
+
+/// Resolve the device profile, refined by the reported model code if one
+/// is known, adding config-driven handlers.
+fn device_profile(
+    config: &AppConfig,
+    device_name: &str,
+    address: Address,
+    model: Option<&str>,
+) -> DeviceProfile {
+    let rules = DeviceRules::compile(&config.device_rules);
+    let mut profile = rules.profile(device_name, &address.to_string());
+    if let Some(refined) = model.and_then(|code| models::refine(profile.name, code)) {
+        profile = refined;
+    }
+    overrides::apply(&mut profile, device_name, &config.overrides);
+    if let Some(events) = GestureEventHandler::from_config(&config.gesture_events) {
+        profile.handlers.push(Box::new(events));
+    }
+    profile
+}
+
+/// Start the desktop integrations enabled in the config.
+fn spawn_integrations(
+    config: &AppConfig,
+    props: &PropertyStore,
+    prop_tx: &PropertySender,
+) {
+    if config.focus_mode.enabled {
+        info!("Focus mode enabled, watching desktop Do Not Disturb");
+        integrations::dnd::spawn(config.focus_mode.clone(), props.clone(), prop_tx.clone());
+    }
+    if config.kde_connect.enabled {
+        info!("Sharing headphone state over KDE Connect");
+        integrations::kde_connect::spawn(config.kde_connect.clone(), props.clone());
+    }
+    integrations::activity::spawn(props.clone());
+    // Always running: schedules accepted in the UI land in the config later
+    integrations::anc_habits::spawn(props.clone(), prop_tx.clone());
+    integrations::auto_pause::spawn(props.clone());
+    if config.check_firmware {
+        integrations::firmware::spawn(props.clone());
+    }
+    if config.model_db_sync {
+        device::models::database::spawn_sync();
+    }
+    if config.alerts.enabled {
+        info!("Alert tones enabled");
+        integrations::alerts::spawn(config.alerts.clone(), props.clone());
+    }
+    if config.battery_saver.enabled {
+        info!("Battery saver enabled at {}%", config.battery_saver.threshold);
+        integrations::battery_saver::spawn(config.battery_saver.clone(), props.clone(), prop_tx.clone());
+    }
+    let notices = &config.notifications;
+    if notices.low_battery
+        || notices.disconnected
+        || notices.reconnected
+        || notices.case_opened
+        || notices.anc_changed
+    {
+        info!("Device notifications enabled");
+        integrations::device_notices::spawn(notices.clone(), props.clone(), prop_tx.clone());
+    }
+    if !config.assistant_command.is_empty() {
+        info!("Assistant gesture mapped to {:?}", config.assistant_command);
+        integrations::assistant::spawn(config.assistant_command.clone(), props.clone());
+    }
+    if let Some(ref event) = config.mic_mute_event {
+        info!("Gesture event '{}' toggles microphone mute", event);
+        integrations::mic_mute::spawn(event.clone(), props.clone());
+    }
+    if config.auto_reclaim_audio {
+        info!("Audio reclaim enabled, watching local playback");
+        integrations::audio_reclaim::spawn(props.clone(), prop_tx.clone());
+    }
+    if config.single_bud_mode {
+        info!("Single-bud mode enabled");
+        integrations::single_bud::spawn(props.clone(), prop_tx.clone());
+    }
+    for webhook in &config.webhooks {
+        integrations::webhooks::spawn(webhook.clone(), props.clone());
+    }
+    if !config.webhooks.is_empty() {
+        info!("{} webhook(s) configured", config.webhooks.len());
+    }
+}
+
+async fn run_bluetooth_headless(
+    config: AppConfig,
+    props: PropertyStore,
+    prop_rx: PropertyReceiver,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let Some(device) = wait_for_device(&config, &props, &cancel).await else {
+        return Ok(());
+    };
+    bluetooth_manager(&config, props, prop_rx, cancel, device)
+        .run_with_reconnect()
+        .await;
 
     Ok(())
 }
 
+/// Look for a device, retrying every few seconds until one shows up.
+/// Returns None if cancelled while waiting.
+async fn wait_for_device(
+    config: &AppConfig,
+    props: &PropertyStore,
+    cancel: &CancellationToken,
+) -> Option<(Address, String)> {
+    if let Some(dev) = find_device(config).await {
+        return Some(dev);
+    }
+    info!("No device found. Waiting for device...");
+    let hint = sandbox::bluetooth_hint();
+    if let Some(hint) = &hint {
+        warn!("{}", hint);
+    }
+    bluetooth::state::publish(props, ConnectionState::Searching, hint).await;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(5)) => {}
+            _ = cancel.cancelled() => return None,
+        }
+        if let Some(dev) = find_device(config).await {
+            return Some(dev);
+        }
+    }
+}
+
+/// Give up on a BlueZ scan that takes longer than this and retry later.
+const SCAN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
 async fn find_device(config: &AppConfig) -> Option<(Address, String)> {
+    resolve_device(config)
+        .instrument(info_span!("resolve_device"))
+        .await
+}
+
+async fn resolve_device(config: &AppConfig) -> Option<(Address, String)> {
+    if bluetooth::mock::socket_path().is_some() {
+        return Some(bluetooth::mock::device());
+    }
+
     // Try configured device first
     if let (Some(addr_str), Some(name)) = (&config.device_address, &config.device_name) {
         if let Ok(addr) = addr_str.parse::<Address>() {
@@ -266,18 +755,25 @@ async fn find_device(config: &AppConfig) -> Option<(Address, String)> {
         }
     }
 
-    // Scan for paired devices
-    match scanner::list_paired_devices(true).await {
-        Ok(devices) => {
-            if let Some(dev) = devices.first() {
-                Some((dev.address, dev.name.clone()))
-            } else {
-                None
-            }
+    // Then the device from the last session, skipping the BlueZ scan
+    let rules = DeviceRules::compile(&config.device_rules);
+    if let Some(cached) = BondedDevice::load(&rules) {
+        if let Some(addr) = cached.address() {
+            info!("Using cached device {} ({})", cached.name, addr);
+            return Some((addr, cached.name));
         }
-        Err(e) => {
+    }
+
+    // Scan for paired devices
+    match tokio::time::timeout(SCAN_TIMEOUT, scanner::list_paired_devices(Some(&rules))).await {
+        Ok(Ok(devices)) => devices.first().map(|dev| (dev.address, dev.name.clone())),
+        Ok(Err(e)) => {
             error!("Failed to scan devices: {}", e);
             None
         }
+        Err(_) => {
+            warn!("Device scan took longer than {:?}, retrying later", SCAN_TIMEOUT);
+            None
+        }
     }
 }
@@ -1,7 +1,11 @@
-/// Apple Audio Control Protocol (AACP) packet definitions.
-///
-/// Used by AirPods for communication over L2CAP PSM 0x1001.
-/// Reference: LibrePods project (github.com/kavishdevar/librepods)
+//! Apple Audio Control Protocol (AACP) packet definitions.
+//!
+//! Used by AirPods for communication over L2CAP PSM 0x1001.
+//! Reference: LibrePods project (github.com/kavishdevar/librepods)
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{bail, ensure, Result};
 
 use crate::protocol::HuaweiSppPacket;
 
@@ -22,6 +26,7 @@ pub const OP_SET_FEATURE_FLAGS: u8 = 0x4D;
 
 // --- Control Command identifiers (subtypes of opcode 0x09) ---
 
+pub const CC_OWNS_CONNECTION: u8 = 0x06;
 pub const CC_LISTENING_MODE: u8 = 0x0D;
 pub const CC_LISTENING_MODE_CONFIGS: u8 = 0x1A;
 pub const CC_ONE_BUD_ANC: u8 = 0x1B;
@@ -29,6 +34,32 @@ pub const CC_ADAPTIVE_VOLUME: u8 = 0x26;
 pub const CC_CONVERSATION_DETECT: u8 = 0x28;
 pub const CC_AUTO_ANC_STRENGTH: u8 = 0x2E;
 pub const CC_EAR_DETECTION_CONFIG: u8 = 0x0A;
+pub const CC_DOUBLE_CLICK_INTERVAL: u8 = 0x17;
+pub const CC_CLICK_HOLD_INTERVAL: u8 = 0x18;
+pub const CC_CHIME_VOLUME: u8 = 0x1F;
+pub const CC_VOLUME_SWIPE_INTERVAL: u8 = 0x23;
+pub const CC_VOLUME_SWIPE_MODE: u8 = 0x25;
+
+/// Control commands the handlers write. Anything else is refused rather
+/// than sent: a command the buds don't expect can leave the control
+/// channel unresponsive until they reconnect.
+const WRITABLE_CONTROL_COMMANDS: &[u8] = &[
+    CC_OWNS_CONNECTION,
+    CC_EAR_DETECTION_CONFIG,
+    CC_LISTENING_MODE,
+    CC_DOUBLE_CLICK_INTERVAL,
+    CC_CLICK_HOLD_INTERVAL,
+    CC_ONE_BUD_ANC,
+    CC_CHIME_VOLUME,
+    CC_VOLUME_SWIPE_INTERVAL,
+    CC_VOLUME_SWIPE_MODE,
+    CC_ADAPTIVE_VOLUME,
+    CC_CONVERSATION_DETECT,
+    CC_AUTO_ANC_STRENGTH,
+];
+
+/// Length of a control command payload: identifier and 4 value bytes.
+const CONTROL_COMMAND_LEN: usize = 5;
 
 // --- Listening mode values ---
 
@@ -63,6 +94,129 @@ pub const CMD_PREFIX: u8 = 0xAA;
 /// Prefix for control command subtypes: command_id = [0xA9, identifier]
 pub const CMD_CC_PREFIX: u8 = 0xA9;
 
+// --- Framing ---
+
+/// Bytes before the payload: header, opcode and a zero byte.
+const FRAME_PREFIX_LEN: usize = 6;
+
+/// Largest payload we send: L2CAP's default MTU less the prefix.
+pub const MAX_PAYLOAD_LEN: usize = 672 - FRAME_PREFIX_LEN;
+
+/// Payload length of opcodes whose messages have a fixed shape. AAP has
+/// no length field, so other messages run to the next header.
+fn payload_len(opcode: u8, payload: &[u8]) -> Option<usize> {
+    match opcode {
+        // [count] then 5 bytes per component
+        OP_BATTERY_INFO => Some(payload.first().map_or(1, |&n| 1 + 5 * n as usize)),
+        OP_EAR_DETECTION => Some(2),
+        OP_CONTROL_COMMAND => Some(CONTROL_COMMAND_LEN),
+        _ => None,
+    }
+}
+
+fn find_header(data: &[u8]) -> Option<usize> {
+    data.windows(AAP_HEADER.len()).position(|w| w == AAP_HEADER)
+}
+
+/// Oddities seen while framing received data, for diagnostics.
+#[derive(Debug, Default)]
+pub struct FramingStats {
+    /// Messages that shared a datagram with the one before them.
+    pub concatenated: AtomicU64,
+    /// Messages put back together from more than one datagram.
+    pub reassembled: AtomicU64,
+    /// Bytes dropped because they didn't start with a known header.
+    pub skipped_bytes: AtomicU64,
+    /// Incomplete messages dropped when a new one began.
+    pub truncated: AtomicU64,
+}
+
+impl FramingStats {
+    pub const fn new() -> Self {
+        Self {
+            concatenated: AtomicU64::new(0),
+            reassembled: AtomicU64::new(0),
+            skipped_bytes: AtomicU64::new(0),
+            truncated: AtomicU64::new(0),
+        }
+    }
+
+    fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// Splits received datagrams into AAP messages.
+///
+/// The socket is SEQPACKET, so a datagram is normally one message, but
+/// some adapters join several into one or split one across reads.
+/// Messages of a known length that end early are held until the rest
+/// arrives; anything before a header is skipped.
+pub struct AapFramer {
+    pending: Vec<u8>,
+    stats: &'static FramingStats,
+}
+
+impl AapFramer {
+    pub fn new(stats: &'static FramingStats) -> Self {
+        Self {
+            pending: Vec::new(),
+            stats,
+        }
+    }
+
+    /// Feed one received datagram, returning the messages it completes.
+    pub fn push(&mut self, datagram: &[u8]) -> Vec<AapPacket> {
+        let continues = !self.pending.is_empty() && !datagram.starts_with(&AAP_HEADER);
+        if !self.pending.is_empty() && !continues {
+            FramingStats::add(&self.stats.truncated, 1);
+            self.pending.clear();
+        }
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(datagram);
+
+        let mut packets = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let rest = &data[pos..];
+            if !rest.starts_with(&AAP_HEADER) {
+                let skip = find_header(rest).unwrap_or(rest.len());
+                FramingStats::add(&self.stats.skipped_bytes, skip as u64);
+                pos += skip;
+                continue;
+            }
+
+            let len = if rest.len() < FRAME_PREFIX_LEN {
+                None
+            } else {
+                let payload = &rest[FRAME_PREFIX_LEN..];
+                match payload_len(rest[4], payload) {
+                    Some(n) => Some(FRAME_PREFIX_LEN + n).filter(|&len| len <= rest.len()),
+                    None => Some(
+                        find_header(payload).map_or(rest.len(), |i| FRAME_PREFIX_LEN + i),
+                    ),
+                }
+            };
+            let Some(len) = len else {
+                // Wait for the rest of the message
+                self.pending = rest.to_vec();
+                break;
+            };
+
+            if let Some(packet) = AapPacket::from_bytes(&rest[..len]) {
+                if continues && packets.is_empty() {
+                    FramingStats::add(&self.stats.reassembled, 1);
+                } else if !packets.is_empty() {
+                    FramingStats::add(&self.stats.concatenated, 1);
+                }
+                packets.push(packet);
+            }
+            pos += len;
+        }
+        packets
+    }
+}
+
 // --- Packet type ---
 
 /// An AACP packet.
@@ -73,10 +227,41 @@ pub struct AapPacket {
 }
 
 impl AapPacket {
+    /// Build a packet without the checks of [`AapPacket::outgoing`].
+    #[cfg(test)]
     pub fn new(opcode: u8, payload: Vec<u8>) -> Self {
         Self { opcode, payload }
     }
 
+    /// Build a packet to send, refusing ones the buds could choke on:
+    /// oversized payloads, and control commands that aren't in the
+    /// writable list or don't carry exactly 4 value bytes.
+    pub fn outgoing(opcode: u8, payload: Vec<u8>) -> Result<Self> {
+        ensure!(
+            payload.len() <= MAX_PAYLOAD_LEN,
+            "AAP payload too long: {} bytes (max {})",
+            payload.len(),
+            MAX_PAYLOAD_LEN
+        );
+        if opcode == OP_CONTROL_COMMAND {
+            let Some(&identifier) = payload.first() else {
+                bail!("Control command without identifier");
+            };
+            ensure!(
+                WRITABLE_CONTROL_COMMANDS.contains(&identifier),
+                "Control command 0x{:02X} is not writable",
+                identifier
+            );
+            ensure!(
+                payload.len() == CONTROL_COMMAND_LEN,
+                "Control command 0x{:02X} needs 4 value bytes, got {}",
+                identifier,
+                payload.len() - 1
+            );
+        }
+        Ok(Self { opcode, payload })
+    }
+
     /// Serialize to wire bytes: [header][opcode][0x00][payload]
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(6 + self.payload.len());
@@ -158,33 +343,32 @@ impl AapPacket {
                 Vec::new()
             };
             let mut pkt = HuaweiSppPacket::new([CMD_CC_PREFIX, identifier]);
-            pkt.parameters.insert(0, remaining);
+            pkt.set_param(0, remaining);
             pkt
         } else {
             let mut pkt = HuaweiSppPacket::new([CMD_PREFIX, self.opcode]);
-            pkt.parameters.insert(0, self.payload.clone());
+            pkt.set_param(0, self.payload.clone());
             pkt
         }
     }
 
-    /// Convert a HuaweiSppPacket (from a handler) back to raw AAP bytes for transmission.
-    /// Returns None if the packet isn't an AAP packet.
-    pub fn from_handler_packet(pkt: &HuaweiSppPacket) -> Option<Vec<u8>> {
+    /// Convert a HuaweiSppPacket (from a handler) back to raw AAP bytes for
+    /// transmission, validated with [`AapPacket::outgoing`].
+    pub fn from_handler_packet(pkt: &HuaweiSppPacket) -> Result<Vec<u8>> {
         let [prefix, id] = pkt.command_id;
         let data = pkt.find_param(0);
 
-        if prefix == CMD_CC_PREFIX {
+        let aap = if prefix == CMD_CC_PREFIX {
             // Control command: opcode = 0x09, identifier = id
             let mut payload = vec![id];
             payload.extend_from_slice(data);
-            let aap = AapPacket::new(OP_CONTROL_COMMAND, payload);
-            Some(aap.to_bytes())
+            AapPacket::outgoing(OP_CONTROL_COMMAND, payload)?
         } else if prefix == CMD_PREFIX {
-            let aap = AapPacket::new(id, data.to_vec());
-            Some(aap.to_bytes())
+            AapPacket::outgoing(id, data.to_vec())?
         } else {
-            None
-        }
+            bail!("Not an AAP packet: {:02X}{:02X}", prefix, id);
+        };
+        Ok(aap.to_bytes())
     }
 }
 
@@ -245,4 +429,49 @@ mod tests {
         let rn = AapPacket::request_notifications();
         assert_eq!(rn[4], OP_REQUEST_NOTIFICATIONS);
     }
+
+    #[test]
+    fn frames_joined_split_and_stray_data() {
+        static STATS: FramingStats = FramingStats::new();
+        let mut framer = AapFramer::new(&STATS);
+        let ear = AapPacket::new(OP_EAR_DETECTION, vec![EAR_IN, EAR_OUT]).to_bytes();
+        let anc = AapPacket::control_command(CC_LISTENING_MODE, LM_ANC).to_bytes();
+
+        // Two messages in one datagram, after some stray bytes
+        let mut joined = vec![0xDE, 0xAD];
+        joined.extend_from_slice(&ear);
+        joined.extend_from_slice(&anc);
+        let packets = framer.push(&joined);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].payload, vec![CC_LISTENING_MODE, LM_ANC, 0, 0, 0]);
+        assert_eq!(STATS.skipped_bytes.load(Ordering::Relaxed), 2);
+        assert_eq!(STATS.concatenated.load(Ordering::Relaxed), 1);
+
+        // One message over two datagrams
+        assert!(framer.push(&anc[..7]).is_empty());
+        let packets = framer.push(&anc[7..]);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].opcode, OP_CONTROL_COMMAND);
+        assert_eq!(STATS.reassembled.load(Ordering::Relaxed), 1);
+
+        // A new message abandons an incomplete one
+        assert!(framer.push(&anc[..7]).is_empty());
+        assert_eq!(framer.push(&ear).len(), 1);
+        assert_eq!(STATS.truncated.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn refuses_malformed_writes() {
+        let ok = AapPacket::outgoing(OP_CONTROL_COMMAND, vec![CC_LISTENING_MODE, LM_ANC, 0, 0, 0]);
+        assert!(ok.is_ok());
+        // Not something a handler writes
+        assert!(AapPacket::outgoing(OP_CONTROL_COMMAND, vec![0x7F, 1, 0, 0, 0]).is_err());
+        // Missing value bytes
+        assert!(AapPacket::outgoing(OP_CONTROL_COMMAND, vec![CC_LISTENING_MODE, LM_ANC]).is_err());
+        assert!(AapPacket::outgoing(OP_CONTROL_COMMAND, Vec::new()).is_err());
+        assert!(AapPacket::outgoing(OP_DEVICE_INFO, vec![0; MAX_PAYLOAD_LEN + 1]).is_err());
+
+        let huawei = HuaweiSppPacket::new([0x2B, 0x03]);
+        assert!(AapPacket::from_handler_packet(&huawei).is_err());
+    }
 }
@@ -1,5 +1,5 @@
-/// Command IDs for the Huawei SPP protocol.
-/// Format: [service_id, command_id] as 2-byte big-endian.
+//! Command IDs for the Huawei SPP protocol.
+//! Format: [service_id, command_id] as 2-byte big-endian.
 
 // Device info
 pub const CMD_DEVICE_INFO: [u8; 2] = [0x01, 0x07];
@@ -13,6 +13,9 @@ pub const CMD_ANC_READ: [u8; 2] = [0x2B, 0x2A];
 pub const CMD_ANC_WRITE: [u8; 2] = [0x2B, 0x04];
 pub const CMD_ANC_LEGACY_NOTIFY: [u8; 2] = [0x2B, 0x03];
 
+// Wear state, sent in the same notification as legacy ANC changes
+pub const CMD_IN_EAR_NOTIFY: [u8; 2] = [0x2B, 0x03];
+
 // Auto-pause
 pub const CMD_AUTO_PAUSE_READ: [u8; 2] = [0x2B, 0x11];
 pub const CMD_AUTO_PAUSE_WRITE: [u8; 2] = [0x2B, 0x10];
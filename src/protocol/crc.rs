@@ -1,6 +1,7 @@
-use crc::{Crc, CRC_16_XMODEM};
+use crc::{Crc, Table, CRC_16_XMODEM};
 
-const CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+/// Slice-by-16 tables: every packet sent and received is checksummed.
+static CRC: Crc<u16, Table<16>> = Crc::<u16, Table<16>>::new(&CRC_16_XMODEM);
 
 pub fn crc16_xmodem(data: &[u8]) -> [u8; 2] {
     CRC.checksum(data).to_be_bytes()
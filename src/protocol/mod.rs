@@ -3,4 +3,4 @@ pub mod commands;
 pub mod crc;
 pub mod packet;
 
-pub use packet::HuaweiSppPacket;
+pub use packet::{HuaweiSppPacket, SppFramer};
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 
 use anyhow::{bail, ensure, Result};
+use bytes::{Buf, Bytes, BytesMut};
 
 use super::crc::crc16_xmodem;
 
@@ -19,7 +20,34 @@ const MAGIC: u8 = 0x5A;
 #[derive(Debug, Clone)]
 pub struct HuaweiSppPacket {
     pub command_id: [u8; 2],
-    pub parameters: BTreeMap<u8, Vec<u8>>,
+    params: Params,
+}
+
+/// Parameter storage: received packets borrow from the receive buffer,
+/// packets built for sending own theirs.
+#[derive(Debug, Clone)]
+enum Params {
+    /// The TLV bytes of a received packet, validated when it was parsed.
+    /// Repeated types are kept; lookups return the last one.
+    Received(Bytes),
+    /// Parameters of a packet being built, one per type.
+    Owned(BTreeMap<u8, Vec<u8>>),
+}
+
+/// Iterator over TLV parameters in validated bytes.
+struct Tlvs<'a>(&'a [u8]);
+
+impl<'a> Iterator for Tlvs<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [p_type, p_len, rest @ ..] = self.0 else {
+            return None;
+        };
+        let (value, rest) = rest.split_at(*p_len as usize);
+        self.0 = rest;
+        Some((*p_type, value))
+    }
 }
 
 impl HuaweiSppPacket {
@@ -27,7 +55,7 @@ impl HuaweiSppPacket {
     pub fn new(command_id: [u8; 2]) -> Self {
         Self {
             command_id,
-            parameters: BTreeMap::new(),
+            params: Params::Owned(BTreeMap::new()),
         }
     }
 
@@ -35,63 +63,119 @@ impl HuaweiSppPacket {
     pub fn read_request(command_id: [u8; 2], param_types: &[u8]) -> Self {
         let mut pkt = Self::new(command_id);
         for &t in param_types {
-            pkt.parameters.insert(t, Vec::new());
+            pkt.set_param(t, Vec::new());
         }
         pkt
     }
 
     /// Build a write request with typed parameters.
-    pub fn write_request(command_id: [u8; 2], params: &[(u8, Vec<u8>)]) -> Self {
+    pub fn write_request(
+        command_id: [u8; 2],
+        params: impl IntoIterator<Item = (u8, Vec<u8>)>,
+    ) -> Self {
+        // Inserting beats collect(), which sorts into a Vec first
         let mut pkt = Self::new(command_id);
         for (t, v) in params {
-            pkt.parameters.insert(*t, v.clone());
+            pkt.set_param(t, v);
         }
         pkt
     }
 
+    /// Set a parameter, replacing any earlier value of that type. A received
+    /// packet is copied into owned storage first.
+    pub fn set_param(&mut self, param_type: u8, value: Vec<u8>) {
+        if let Params::Received(_) = self.params {
+            let owned = self.params().map(|(t, v)| (t, v.to_vec())).collect();
+            self.params = Params::Owned(owned);
+        }
+        if let Params::Owned(map) = &mut self.params {
+            map.insert(param_type, value);
+        }
+    }
+
     /// Convenience: get a parameter value, returning empty slice if not present.
     pub fn find_param(&self, param_type: u8) -> &[u8] {
-        self.parameters
-            .get(&param_type)
-            .map(|v| v.as_slice())
-            .unwrap_or(&[])
+        match &self.params {
+            Params::Received(tlvs) => Tlvs(tlvs)
+                .filter(|&(t, _)| t == param_type)
+                .last()
+                .map_or(&[], |(_, v)| v),
+            Params::Owned(map) => map.get(&param_type).map_or(&[], |v| v.as_slice()),
+        }
+    }
+
+    /// Whether the packet carries a parameter of this type (possibly empty,
+    /// as in read requests).
+    #[cfg(test)]
+    pub fn has_param(&self, param_type: u8) -> bool {
+        self.params().any(|(t, _)| t == param_type)
+    }
+
+    /// All parameters: in wire order for received packets, by type for
+    /// built ones.
+    pub fn params(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        let (received, owned) = match &self.params {
+            Params::Received(tlvs) => (Some(Tlvs(tlvs)), None),
+            Params::Owned(map) => (None, Some(map.iter().map(|(&t, v)| (t, v.as_slice())))),
+        };
+        received.into_iter().flatten().chain(owned.into_iter().flatten())
+    }
+
+    /// Size of this packet on the wire.
+    pub fn encoded_len(&self) -> usize {
+        // Header (4) + command (2) + TLVs + CRC (2)
+        let params: usize = self.params().map(|(_, v)| 2 + v.len()).sum();
+        4 + 2 + params + 2
     }
 
     /// Serialize this packet to bytes for transmission.
+    // The app sends through `write_to`; tests, mybuds-sim and benches use this
+    #[allow(dead_code)]
     pub fn to_bytes(&self) -> Vec<u8> {
-        // Build body: command_id + TLV parameters
-        let mut body = Vec::new();
-        body.extend_from_slice(&self.command_id);
-        for (&p_type, p_value) in &self.parameters {
-            body.push(p_type);
-            body.push(p_value.len() as u8);
-            body.extend_from_slice(p_value);
-        }
+        let mut out = Vec::new();
+        self.write_to(&mut out);
+        out
+    }
 
-        // Build packet: magic + length(2 BE) + 0x00 + body + CRC16
-        let length = (body.len() + 1) as u16; // +1 for the 0x00 byte
-        let mut result = Vec::with_capacity(4 + body.len() + 2);
-        result.push(MAGIC);
-        result.extend_from_slice(&length.to_be_bytes());
-        result.push(0x00);
-        result.extend_from_slice(&body);
+    /// Append this packet's wire form to `out`, so senders can reuse a buffer.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        let encoded_len = self.encoded_len();
+        out.reserve(encoded_len);
+        let start = out.len();
+
+        // Length covers the 0x00 byte, the command and the params
+        let length = (encoded_len - 5) as u16;
+        out.push(MAGIC);
+        out.extend_from_slice(&length.to_be_bytes());
+        out.push(0x00);
+        out.extend_from_slice(&self.command_id);
+        for (p_type, p_value) in self.params() {
+            out.push(p_type);
+            out.push(p_value.len() as u8);
+            out.extend_from_slice(p_value);
+        }
 
-        let crc = crc16_xmodem(&result);
-        result.extend_from_slice(&crc);
-        result
+        let crc = crc16_xmodem(&out[start..]);
+        out.extend_from_slice(&crc);
     }
 
-    /// Parse a packet from raw bytes.
+    /// Parse a packet from raw bytes (copied; see [`Self::from_shared`]).
+    #[cfg(test)]
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_shared(Bytes::copy_from_slice(data))
+    }
+
+    /// Parse a packet whose parameters borrow from `data` instead of being
+    /// copied out one by one.
+    pub fn from_shared(data: Bytes) -> Result<Self> {
         ensure!(data.len() >= 8, "Packet too short: {} bytes", data.len());
         ensure!(data[0] == MAGIC, "Invalid magic byte: 0x{:02X}", data[0]);
         ensure!(data[3] == 0x00, "Invalid reserved byte: 0x{:02X}", data[3]);
 
         let length = u16::from_be_bytes([data[1], data[2]]) as usize;
-
         let command_id = [data[4], data[5]];
-        let mut pkt = Self::new(command_id);
 
+        // Validate the TLVs once so lookups can't run off the end
         let mut pos = 6;
         let end = length + 3; // length covers from byte[3] onward, so end = 3 + length
         while pos < end && pos + 1 < data.len() {
@@ -106,11 +190,13 @@ impl HuaweiSppPacket {
                     pos
                 );
             }
-            pkt.parameters.insert(p_type, data[pos + 2..p_end].to_vec());
             pos = p_end;
         }
 
-        Ok(pkt)
+        Ok(Self {
+            command_id,
+            params: Params::Received(data.slice(6..pos)),
+        })
     }
 
     /// Parse from bytes with CRC validation.
@@ -139,13 +225,69 @@ impl std::fmt::Display for HuaweiSppPacket {
             "cmd={:02X}{:02X}",
             self.command_id[0], self.command_id[1]
         )?;
-        for (&t, v) in &self.parameters {
+        for (t, v) in self.params() {
             write!(f, " p{}={}", t, hex::encode(v))?;
         }
         Ok(())
     }
 }
 
+/// Largest `length` field accepted from the device.
+const MAX_LENGTH: usize = 1000;
+
+/// Splits a received byte stream into SPP packets.
+///
+/// RFCOMM delivers a byte stream, so packets can arrive in pieces or several
+/// to a read. Bytes are buffered until a whole packet is in; bytes that can't
+/// start a packet (wrong magic, impossible length) are skipped, and reported
+/// as an error so the caller can log the resync.
+#[derive(Debug, Default)]
+pub struct SppFramer {
+    /// Unconsumed bytes. Packets are split off without copying, so their
+    /// parameters point into this buffer.
+    buf: BytesMut,
+}
+
+impl SppFramer {
+    /// Append received bytes.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Next complete packet, `None` once more bytes are needed.
+    pub fn next_packet(&mut self) -> Option<Result<HuaweiSppPacket>> {
+        if self.buf.len() < 4 {
+            return None;
+        }
+
+        if self.buf[0] != MAGIC {
+            let skip = self
+                .buf
+                .iter()
+                .position(|&b| b == MAGIC)
+                .unwrap_or(self.buf.len());
+            self.buf.advance(skip);
+            return Some(Err(anyhow::anyhow!(
+                "Skipped {} bytes without a packet start",
+                skip
+            )));
+        }
+
+        let length = u16::from_be_bytes([self.buf[1], self.buf[2]]) as usize;
+        if !(3..=MAX_LENGTH).contains(&length) {
+            self.buf.advance(1);
+            return Some(Err(anyhow::anyhow!("Invalid packet length: {}", length)));
+        }
+
+        // Header (4) + body after the reserved byte (length - 1) + CRC (2)
+        let total = length + 5;
+        if self.buf.len() < total {
+            return None;
+        }
+        Some(HuaweiSppPacket::from_shared(self.buf.split_to(total).freeze()))
+    }
+}
+
 /// Helper module for hex encoding in Display (avoid extra dependency)
 mod hex {
     pub fn encode(data: &[u8]) -> String {
@@ -160,8 +302,8 @@ mod tests {
     #[test]
     fn test_roundtrip() {
         let mut pkt = HuaweiSppPacket::new([0x01, 0x08]);
-        pkt.parameters.insert(1, vec![0x64]); // battery 100%
-        pkt.parameters.insert(2, vec![0x50, 0x4E, 0x00]); // L:80 R:78 Case:0
+        pkt.set_param(1, vec![0x64]); // battery 100%
+        pkt.set_param(2, vec![0x50, 0x4E, 0x00]); // L:80 R:78 Case:0
 
         let bytes = pkt.to_bytes();
         let parsed = HuaweiSppPacket::from_bytes_checked(&bytes).unwrap();
@@ -174,7 +316,7 @@ mod tests {
     #[test]
     fn test_read_request() {
         let pkt = HuaweiSppPacket::read_request([0x01, 0x08], &[1, 2, 3]);
-        assert_eq!(pkt.parameters.len(), 3);
+        assert_eq!(pkt.params().count(), 3);
         assert!(pkt.find_param(1).is_empty());
         assert!(pkt.find_param(2).is_empty());
         assert!(pkt.find_param(3).is_empty());
@@ -184,7 +326,7 @@ mod tests {
     fn test_write_request() {
         let pkt = HuaweiSppPacket::write_request(
             [0x2B, 0x04],
-            &[(1, vec![0x01, 0xFF])],
+            [(1, vec![0x01, 0xFF])],
         );
         assert_eq!(pkt.command_id, [0x2B, 0x04]);
         assert_eq!(pkt.find_param(1), &[0x01, 0xFF]);
@@ -206,12 +348,72 @@ mod tests {
         assert_eq!(parsed.command_id, [0x01, 0x08]);
     }
 
+    #[test]
+    fn write_to_appends_the_same_bytes() {
+        let pkt = HuaweiSppPacket::write_request([0x2B, 0x04], [(1, vec![0x01, 0xFF]), (3, vec![40])]);
+        let mut buf = vec![0xEE];
+        pkt.write_to(&mut buf);
+        assert_eq!(buf[1..], pkt.to_bytes());
+        assert_eq!(pkt.to_bytes().len(), pkt.encoded_len());
+    }
+
+    #[test]
+    fn received_params_match_built_ones() {
+        let built = HuaweiSppPacket::write_request(
+            [0x01, 0x07],
+            [(15, b"CD-R551".to_vec()), (3, Vec::new()), (7, b"1.0".to_vec())],
+        );
+        let mut received = HuaweiSppPacket::from_bytes_checked(&built.to_bytes()).unwrap();
+        assert_eq!(received.find_param(15), b"CD-R551");
+        assert!(received.has_param(3) && received.find_param(3).is_empty());
+        assert!(!received.has_param(9));
+        assert_eq!(received.to_bytes(), built.to_bytes());
+
+        // Writing to a received packet keeps its other parameters
+        received.set_param(7, b"2.0".to_vec());
+        assert_eq!(received.find_param(7), b"2.0");
+        assert_eq!(received.find_param(15), b"CD-R551");
+    }
+
+    #[test]
+    fn repeated_received_param_reads_the_last() {
+        let bytes = [0x5A, 0x00, 0x09, 0x00, 0x01, 0x08, 0x01, 0x01, 0x10, 0x01, 0x01, 0x20, 0x00, 0x00];
+        let pkt = HuaweiSppPacket::from_bytes(&bytes).unwrap();
+        assert_eq!(pkt.find_param(1), [0x20]);
+        assert_eq!(pkt.params().count(), 2);
+    }
+
     #[test]
     fn test_empty_param_not_found() {
         let pkt = HuaweiSppPacket::new([0x01, 0x08]);
         assert!(pkt.find_param(99).is_empty());
     }
 
+    #[test]
+    fn framer_handles_split_joined_and_garbled_input() {
+        let battery = HuaweiSppPacket::read_request([0x01, 0x08], &[1, 2]).to_bytes();
+        let anc = HuaweiSppPacket::read_request([0x2B, 0x2A], &[1]).to_bytes();
+        let mut stream = vec![0x00, 0x13];
+        stream.extend_from_slice(&battery);
+        stream.extend_from_slice(&anc);
+
+        let mut framer = SppFramer::default();
+        let mut packets = Vec::new();
+        let mut errors = 0;
+        for chunk in stream.chunks(5) {
+            framer.push(chunk);
+            while let Some(result) = framer.next_packet() {
+                match result {
+                    Ok(pkt) => packets.push(pkt.command_id),
+                    Err(_) => errors += 1,
+                }
+            }
+        }
+        assert_eq!(packets, vec![[0x01, 0x08], [0x2B, 0x2A]]);
+        assert_eq!(errors, 1);
+        assert!(framer.next_packet().is_none());
+    }
+
     #[test]
     fn test_crc_validation_fails_on_corrupt() {
         let pkt = HuaweiSppPacket::new([0x01, 0x08]);
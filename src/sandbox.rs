@@ -0,0 +1,113 @@
+//! Running inside a Flatpak sandbox.
+//!
+//! Flatpak gives each instance a private `/tmp` and `$XDG_RUNTIME_DIR`; only
+//! `$XDG_RUNTIME_DIR/app/<app id>` is shared between instances, so the
+//! instance lock and the control sockets live there. The tray registers under
+//! the bus connection's unique name, as the sandbox may not own
+//! `org.kde.StatusNotifierItem-*` names. Config, cache and state already go
+//! to the app's own XDG dirs (`~/.var/app/<app id>`).
+//!
+//! Bluetooth needs raw socket access and BlueZ on the system bus. Neither can
+//! be requested at run time, so when the sandbox lacks them MyBuds names the
+//! `flatpak override` that grants them.
+
+use std::path::Path;
+
+/// Sandbox description Flatpak mounts into every app.
+const INFO_FILE: &str = "/.flatpak-info";
+
+/// `key=value` lines of one section of a keyfile such as `.flatpak-info`.
+fn section<'a>(info: &'a str, name: &str) -> impl Iterator<Item = (&'a str, &'a str)> {
+    let header = format!("[{}]", name);
+    info.lines()
+        .map(str::trim)
+        .skip_while(move |line| *line != header)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+fn list_has(info: &str, group: &str, key: &str, item: &str) -> bool {
+    section(info, group)
+        .filter(|(k, _)| *k == key)
+        .any(|(_, value)| value.split(';').any(|v| v == item))
+}
+
+/// Whether MyBuds runs as a Flatpak.
+pub fn is_flatpak() -> bool {
+    Path::new(INFO_FILE).exists()
+}
+
+/// Flatpak app id, when sandboxed.
+pub fn app_id() -> Option<String> {
+    if !is_flatpak() {
+        return None;
+    }
+    std::env::var("FLATPAK_ID").ok().or_else(|| {
+        let info = std::fs::read_to_string(INFO_FILE).ok()?;
+        let name = section(&info, "Application").find(|(key, _)| *key == "name");
+        name.map(|(_, value)| value.to_string())
+    })
+}
+
+/// `flatpak override` options for the Bluetooth permissions `info` lacks.
+fn missing_bluetooth_permissions(info: &str) -> Vec<&'static str> {
+    let mut missing = Vec::new();
+    if !list_has(info, "Context", "features", "bluetooth") {
+        missing.push("--allow=bluetooth");
+    }
+    let bluez = section(info, "System Bus Policy")
+        .any(|(name, policy)| name == "org.bluez" && matches!(policy, "talk" | "own"));
+    if !bluez && !list_has(info, "Context", "sockets", "system-bus") {
+        missing.push("--system-talk-name=org.bluez");
+    }
+    missing
+}
+
+/// What to run to let the sandbox reach Bluetooth, if it can't.
+pub fn bluetooth_hint() -> Option<String> {
+    let info = std::fs::read_to_string(INFO_FILE).ok()?;
+    let missing = missing_bluetooth_permissions(&info);
+    if missing.is_empty() {
+        return None;
+    }
+    let app = app_id().unwrap_or_else(|| "<app id>".into());
+    Some(format!(
+        "The Flatpak sandbox blocks Bluetooth. Allow it with: flatpak override --user {} {}",
+        missing.join(" "),
+        app
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_missing_bluetooth_permissions() {
+        let granted = "[Application]\n\
+                       name=io.github.joshuapassos.MyBuds\n\
+                       \n\
+                       [Context]\n\
+                       sockets=wayland;pulseaudio;\n\
+                       features=devel;bluetooth;\n\
+                       \n\
+                       [System Bus Policy]\n\
+                       org.bluez=talk\n";
+        assert!(missing_bluetooth_permissions(granted).is_empty());
+
+        let system_bus = "[Context]\nsockets=system-bus;\nfeatures=bluetooth\n";
+        assert!(missing_bluetooth_permissions(system_bus).is_empty());
+
+        let revoked = "[Context]\n\
+                       features=devel;\n\
+                       \n\
+                       [Session Bus Policy]\n\
+                       org.bluez=talk\n";
+        assert_eq!(
+            missing_bluetooth_permissions(revoked),
+            vec!["--allow=bluetooth", "--system-talk-name=org.bluez"]
+        );
+    }
+}
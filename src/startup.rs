@@ -0,0 +1,78 @@
+//! Startup latency tracking.
+//!
+//! Measures time from process start to the first connection and to the
+//! first battery reading, logs them and publishes them to the
+//! `diagnostics` property group for the Device Info page.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::device::handler::{put_properties, PropertyStore};
+
+/// Property group holding app-level diagnostics. Survives disconnects.
+pub const DIAGNOSTICS_GROUP: &str = "diagnostics";
+
+/// Time from launch to first battery reading we aim for.
+pub const STARTUP_BUDGET: Duration = Duration::from_secs(5);
+
+static START: OnceLock<Instant> = OnceLock::new();
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+static FIRST_BATTERY: AtomicBool = AtomicBool::new(false);
+
+/// Start the startup clock. Call first thing in main.
+pub fn begin() {
+    START.get_or_init(Instant::now);
+}
+
+/// Time since `begin()`.
+pub fn elapsed() -> Duration {
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Record the first successful connection. Later calls are no-ops.
+pub async fn mark_connected(props: &PropertyStore) {
+    if CONNECTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let t = elapsed();
+    info!("Startup: connected after {} ms", t.as_millis());
+    record(props, "time_to_connected_ms", t).await;
+}
+
+/// Record the first battery reading. Later calls are no-ops.
+pub async fn mark_first_battery(props: &PropertyStore) {
+    if FIRST_BATTERY.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let t = elapsed();
+    let connected = props
+        .lock()
+        .await
+        .get(DIAGNOSTICS_GROUP)
+        .and_then(|g| g.get("time_to_connected_ms"))
+        .cloned()
+        .unwrap_or_else(|| "?".into());
+    info!(
+        "Startup summary: connected in {} ms, first battery in {} ms",
+        connected,
+        t.as_millis()
+    );
+    if t > STARTUP_BUDGET {
+        warn!(
+            "Startup took {} ms, over the {} ms budget",
+            t.as_millis(),
+            STARTUP_BUDGET.as_millis()
+        );
+    }
+    record(props, "time_to_first_battery_ms", t).await;
+}
+
+async fn record(props: &PropertyStore, key: &str, t: Duration) {
+    let mut values = HashMap::new();
+    values.insert(key.to_string(), t.as_millis().to_string());
+    put_properties(props, DIAGNOSTICS_GROUP, values).await;
+}
@@ -1,18 +1,22 @@
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use ksni::menu::*;
 
+use crate::bluetooth::sleep_timer::MINUTES;
+use crate::bluetooth::state::ConnectionState;
+use crate::integrations::ambient::{self, Sound, SLEEP_TIMERS};
+use crate::labels;
+
 /// Build the tray context menu from device state.
-pub fn build_menu(
-    device_name: Option<&str>,
-    battery: &HashMap<String, String>,
-    anc_mode: Option<&str>,
-    anc_options: &[&str],
-    connected: bool,
-    dual_connect_enabled: bool,
-    dual_connect_available: bool,
-) -> Vec<MenuItem<super::MyBudsTray>> {
+pub fn build_menu(tray: &super::MyBudsTray) -> Vec<MenuItem<super::MyBudsTray>> {
+    let device_name = tray.device_name.as_deref();
+    let battery = &tray.battery;
+    let anc_mode = tray.anc_mode.as_deref();
+    let anc_options: Vec<&str> = tray.anc_options.iter().map(|s| s.as_str()).collect();
+    let connection = tray.connection;
+
     let mut items: Vec<MenuItem<super::MyBudsTray>> = Vec::new();
 
     // Device name header
@@ -27,30 +31,38 @@ pub fn build_menu(
         );
     }
 
-    if connected {
-        // Battery info
-        let mut battery_parts = Vec::new();
-        if let Some(left) = battery.get("left") {
-            battery_parts.push(format!("L: {}%", left));
-        }
-        if let Some(right) = battery.get("right") {
-            battery_parts.push(format!("R: {}%", right));
-        }
-        if let Some(case) = battery.get("case") {
-            if case != "0" {
-                battery_parts.push(format!("Case: {}%", case));
-            }
-        }
-        if battery_parts.is_empty() {
-            if let Some(global) = battery.get("global") {
-                battery_parts.push(format!("Battery: {}%", global));
-            }
-        }
-        if !battery_parts.is_empty() {
+    if connection == ConnectionState::Connected {
+        // Battery: summary line opening a submenu with one entry per component
+        let components = battery_components(battery);
+        if !components.is_empty() {
+            let summary: Vec<String> = components
+                .iter()
+                .map(|c| format!("{}: {}%", c.short, c.level))
+                .collect();
+            let lowest = components.iter().map(|c| c.level).min().unwrap_or(0);
+            let charging = components.iter().any(|c| c.charging);
             items.push(
-                StandardItem {
-                    label: battery_parts.join("  "),
-                    enabled: false,
+                SubMenu {
+                    label: summary.join("  "),
+                    icon_name: battery_icon(lowest, charging),
+                    submenu: components
+                        .iter()
+                        .map(|c| {
+                            StandardItem {
+                                label: format!(
+                                    "{:<6} {} {:>3}%{}",
+                                    c.name,
+                                    level_bar(c.level),
+                                    c.level,
+                                    if c.charging { " \u{26a1}" } else { "" }
+                                ),
+                                icon_name: battery_icon(c.level, c.charging),
+                                enabled: false,
+                                ..Default::default()
+                            }
+                            .into()
+                        })
+                        .collect(),
                     ..Default::default()
                 }
                 .into(),
@@ -69,14 +81,8 @@ pub fn build_menu(
             let options: Vec<RadioItem> = anc_options
                 .iter()
                 .map(|&opt| {
-                    let label = match opt {
-                        "normal" => "Off",
-                        "cancellation" => "Noise Cancelling",
-                        "awareness" => "Awareness",
-                        _ => opt,
-                    };
                     RadioItem {
-                        label: label.to_string(),
+                        label: labels::anc_mode(opt),
                         enabled: true,
                         ..Default::default()
                     }
@@ -96,15 +102,46 @@ pub fn build_menu(
                 }
                 .into(),
             );
+
+            if let Some(next) = tray.next_favorite_anc() {
+                let next = next.to_string();
+                items.push(
+                    StandardItem {
+                        label: format!("Switch to {} (middle click)", labels::anc_mode(&next)),
+                        activate: Box::new(move |tray: &mut super::MyBudsTray| {
+                            *tray.flags.pending_anc_mode.lock().unwrap() = Some(next.clone());
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                );
+            }
+
+            // Voice boost, only offered while awareness is active
+            if let Some(boost) = tray.voice_boost {
+                items.push(
+                    CheckmarkItem {
+                        label: "Voice boost".to_string(),
+                        checked: boost,
+                        enabled: true,
+                        activate: Box::new(move |tray: &mut super::MyBudsTray| {
+                            *tray.flags.pending_anc_level.lock().unwrap() =
+                                Some(if boost { "normal" } else { "voice_boost" }.to_string());
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                );
+            }
             items.push(MenuItem::Separator);
         }
 
         // Dual Connect toggle
-        if dual_connect_available {
+        if tray.dual_connect_available {
             items.push(
                 CheckmarkItem {
                     label: "Dual Connect".to_string(),
-                    checked: dual_connect_enabled,
+                    checked: tray.dual_connect_enabled,
                     enabled: true,
                     activate: Box::new(|tray: &mut super::MyBudsTray| {
                         let new_state = !tray.dual_connect_enabled;
@@ -114,12 +151,39 @@ pub fn build_menu(
                 }
                 .into(),
             );
+            if let Some(ref source) = tray.audio_source {
+                items.push(
+                    StandardItem {
+                        label: format!("Audio: {}", source),
+                        enabled: false,
+                        ..Default::default()
+                    }
+                    .into(),
+                );
+            }
+            items.push(MenuItem::Separator);
+        }
+
+        items.push(sleep_timer_menu(tray));
+        items.push(MenuItem::Separator);
+
+        if tray.audio_elsewhere {
+            items.push(
+                StandardItem {
+                    label: "Use on This Computer".to_string(),
+                    activate: Box::new(|tray: &mut super::MyBudsTray| {
+                        tray.flags.pending_take_over.store(true, Ordering::Relaxed);
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
             items.push(MenuItem::Separator);
         }
     } else {
         items.push(
             StandardItem {
-                label: "Not connected".to_string(),
+                label: connection.label().to_string(),
                 enabled: false,
                 ..Default::default()
             }
@@ -128,6 +192,9 @@ pub fn build_menu(
         items.push(MenuItem::Separator);
     }
 
+    items.push(ambient_menu(tray));
+    items.push(MenuItem::Separator);
+
     // Show window
     items.push(
         StandardItem {
@@ -147,7 +214,7 @@ pub fn build_menu(
         StandardItem {
             label: "Quit".to_string(),
             activate: Box::new(|tray: &mut super::MyBudsTray| {
-                tray.flags.quit_app.store(true, Ordering::Relaxed);
+                tray.flags.shutdown.cancel();
             }),
             ..Default::default()
         }
@@ -156,3 +223,228 @@ pub fn build_menu(
 
     items
 }
+
+/// Sleep timer submenu: durations and extra actions, or Cancel while one
+/// is running.
+fn sleep_timer_menu(tray: &super::MyBudsTray) -> MenuItem<super::MyBudsTray> {
+    let mut submenu: Vec<MenuItem<super::MyBudsTray>> = Vec::new();
+    let label = match tray.sleep_timer_left {
+        Some(left) => {
+            submenu.push(
+                StandardItem {
+                    label: "Cancel".to_string(),
+                    activate: Box::new(|tray: &mut super::MyBudsTray| {
+                        *tray.flags.pending_sleep_timer.lock().unwrap() = Some("off".to_string());
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+            format!("Sleep Timer ({} min left)", left.as_secs().div_ceil(60))
+        }
+        None => {
+            for &minutes in MINUTES {
+                submenu.push(
+                    StandardItem {
+                        label: format!("{} min", minutes),
+                        activate: Box::new(move |tray: &mut super::MyBudsTray| {
+                            *tray.flags.pending_sleep_timer.lock().unwrap() =
+                                Some(tray.sleep_actions.request(minutes));
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                );
+            }
+            submenu.push(MenuItem::Separator);
+            submenu.push(
+                CheckmarkItem {
+                    label: "Also Switch ANC Off".to_string(),
+                    checked: tray.sleep_actions.anc_off,
+                    activate: Box::new(|tray: &mut super::MyBudsTray| {
+                        tray.sleep_actions.anc_off = !tray.sleep_actions.anc_off;
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+            submenu.push(
+                CheckmarkItem {
+                    label: "Also Disconnect".to_string(),
+                    checked: tray.sleep_actions.disconnect,
+                    activate: Box::new(|tray: &mut super::MyBudsTray| {
+                        tray.sleep_actions.disconnect = !tray.sleep_actions.disconnect;
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+            "Sleep Timer".to_string()
+        }
+    };
+    SubMenu {
+        label,
+        submenu,
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Ambient sound submenu: one entry per sound, the sleep timer and Stop.
+fn ambient_menu(tray: &super::MyBudsTray) -> MenuItem<super::MyBudsTray> {
+    let mut submenu: Vec<MenuItem<super::MyBudsTray>> = Sound::ALL
+        .iter()
+        .map(|&sound| {
+            CheckmarkItem {
+                label: sound.label().to_string(),
+                checked: tray.ambient == Some(sound),
+                activate: Box::new(move |tray: &mut super::MyBudsTray| {
+                    let timer = tray.ambient_timer.map(|m| Duration::from_secs(m * 60));
+                    match ambient::start(sound, timer, tray.device_address.as_deref()) {
+                        Ok(()) => tray.ambient = Some(sound),
+                        Err(e) => tracing::warn!("Cannot play {}: {:#}", sound, e),
+                    }
+                }),
+                ..Default::default()
+            }
+            .into()
+        })
+        .collect();
+    submenu.push(MenuItem::Separator);
+
+    let timers: Vec<Option<u64>> = std::iter::once(None)
+        .chain(SLEEP_TIMERS.iter().copied().map(Some))
+        .collect();
+    submenu.push(
+        SubMenu {
+            label: "Sleep Timer".to_string(),
+            submenu: vec![RadioGroup {
+                selected: timers.iter().position(|&t| t == tray.ambient_timer).unwrap_or(0),
+                options: timers
+                    .iter()
+                    .map(|t| RadioItem {
+                        label: t.map_or("Off".to_string(), |m| format!("{} min", m)),
+                        ..Default::default()
+                    })
+                    .collect(),
+                select: Box::new(move |tray: &mut super::MyBudsTray, idx| {
+                    tray.ambient_timer = timers.get(idx).copied().flatten();
+                }),
+            }
+            .into()],
+            ..Default::default()
+        }
+        .into(),
+    );
+    submenu.push(
+        StandardItem {
+            label: "Stop".to_string(),
+            enabled: tray.ambient.is_some(),
+            activate: Box::new(|tray: &mut super::MyBudsTray| {
+                ambient::stop();
+                tray.ambient = None;
+            }),
+            ..Default::default()
+        }
+        .into(),
+    );
+
+    SubMenu {
+        label: "Ambient Sound".to_string(),
+        submenu,
+        ..Default::default()
+    }
+    .into()
+}
+
+/// One battery reading shown in the tray.
+struct BatteryComponent {
+    name: &'static str,
+    short: &'static str,
+    level: u8,
+    charging: bool,
+}
+
+/// Per-component readings (left, right, case), or the global level on
+/// devices that only report one. A case level of 0 means no case reading.
+fn battery_components(battery: &HashMap<String, String>) -> Vec<BatteryComponent> {
+    let any_charging = battery.get("is_charging").is_some_and(|s| s == "true");
+    let read = |key: &str, name, short| {
+        let level = battery.get(key)?.parse::<u8>().ok()?;
+        let charging = match battery.get(&format!("{}_charging", key)) {
+            Some(state) => state == "true",
+            None => any_charging && key == "global",
+        };
+        Some(BatteryComponent {
+            name,
+            short,
+            level,
+            charging,
+        })
+    };
+
+    let mut components: Vec<BatteryComponent> = [
+        read("left", "Left", "L"),
+        read("right", "Right", "R"),
+        read("case", "Case", "Case"),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|c| c.name != "Case" || c.level != 0)
+    .collect();
+    if components.is_empty() {
+        components.extend(read("global", "Battery", "Battery"));
+    }
+    components
+}
+
+/// Eight-cell bar using the Unicode eighth blocks, e.g. "█████▍  ".
+fn level_bar(level: u8) -> String {
+    const PARTIAL: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    let eighths = usize::from(level.min(100)) * 64 / 100;
+    let mut bar = "█".repeat(eighths / 8);
+    if eighths < 64 {
+        bar.push(PARTIAL[eighths % 8]);
+        bar.push_str(&" ".repeat(7 - eighths / 8));
+    }
+    bar
+}
+
+/// Freedesktop battery icon name for a level.
+fn battery_icon(level: u8, charging: bool) -> String {
+    let name = match level {
+        90.. => "battery-full",
+        50.. => "battery-good",
+        20.. => "battery-low",
+        10.. => "battery-caution",
+        _ => "battery-empty",
+    };
+    if charging {
+        format!("{}-charging", name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_entries_per_component() {
+        let battery = HashMap::from([
+            ("left".to_string(), "80".to_string()),
+            ("right".to_string(), "75".to_string()),
+            ("case".to_string(), "0".to_string()),
+            ("left_charging".to_string(), "true".to_string()),
+        ]);
+        let components = battery_components(&battery);
+        assert_eq!(components.len(), 2);
+        assert!(components[0].charging && !components[1].charging);
+
+        assert_eq!(level_bar(100), "████████");
+        assert_eq!(level_bar(50), "████    ");
+        assert_eq!(level_bar(0).chars().count(), 8);
+        assert_eq!(battery_icon(15, true), "battery-caution-charging");
+    }
+}
@@ -4,72 +4,158 @@ pub mod menu;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use tokio_util::sync::CancellationToken;
+
+use crate::bluetooth::sleep_timer::{self, Actions};
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::config::DEFAULT_TRAY_TITLE;
+use crate::device::anc;
+use crate::device::dual_connect::playing_device_name;
 use crate::device::handler::PropertyStore;
+use crate::device::DEVICE_KEY;
+use crate::integrations::ambient::{self, Sound};
+use crate::labels;
+use crate::sandbox;
 
 /// Shared flags for tray <-> iced communication.
 #[derive(Clone)]
 pub struct TrayFlags {
     pub show_window: Arc<AtomicBool>,
-    pub quit_app: Arc<AtomicBool>,
+    /// Cancelled to quit the app: stops the Bluetooth loop, the tray
+    /// service and the window, whichever side asks for it.
+    pub shutdown: CancellationToken,
     /// Pending ANC mode change from tray menu (consumed by bluetooth loop).
     pub pending_anc_mode: Arc<std::sync::Mutex<Option<String>>>,
     /// Pending Dual Connect toggle from tray menu (consumed by bluetooth loop).
     pub pending_dual_connect: Arc<std::sync::Mutex<Option<bool>>>,
+    /// Pending ANC level change from tray menu (consumed by bluetooth loop).
+    pub pending_anc_level: Arc<std::sync::Mutex<Option<String>>>,
+    /// AirPods take-over requested from tray menu (consumed by bluetooth loop).
+    pub pending_take_over: Arc<AtomicBool>,
+    /// Pending `connection.sleep_timer` value from tray menu (consumed by
+    /// bluetooth loop).
+    pub pending_sleep_timer: Arc<std::sync::Mutex<Option<String>>>,
+    /// Title format while connected (see [`render_title`]); the settings
+    /// page can change it while the tray runs.
+    pub title_format: Arc<std::sync::Mutex<String>>,
+    /// Two ANC modes a middle click switches between (`favorite_anc_modes`).
+    pub favorite_anc_modes: Vec<String>,
 }
 
 impl TrayFlags {
     pub fn new() -> Self {
         Self {
             show_window: Arc::new(AtomicBool::new(false)),
-            quit_app: Arc::new(AtomicBool::new(false)),
+            shutdown: CancellationToken::new(),
             pending_anc_mode: Arc::new(std::sync::Mutex::new(None)),
             pending_dual_connect: Arc::new(std::sync::Mutex::new(None)),
+            pending_anc_level: Arc::new(std::sync::Mutex::new(None)),
+            pending_take_over: Arc::new(AtomicBool::new(false)),
+            pending_sleep_timer: Arc::new(std::sync::Mutex::new(None)),
+            title_format: Arc::new(std::sync::Mutex::new(DEFAULT_TRAY_TITLE.to_string())),
+            favorite_anc_modes: Vec::new(),
         }
     }
 }
 
 /// Tray application state.
 pub struct MyBudsTray {
-    pub connected: bool,
+    pub connection: ConnectionState,
     pub device_name: Option<String>,
     pub battery: HashMap<String, String>,
     pub anc_mode: Option<String>,
     pub anc_options: Vec<String>,
+    /// Voice boost state; None unless awareness mode offers it.
+    pub voice_boost: Option<bool>,
     pub dual_connect_enabled: bool,
     pub dual_connect_available: bool,
+    /// Dual Connect device currently holding the audio.
+    pub audio_source: Option<String>,
+    /// AirPods playing for another Apple device.
+    pub audio_elsewhere: bool,
+    /// Bluetooth address of the buds, to route ambient sound to.
+    pub device_address: Option<String>,
+    /// Ambient sound playing in this process.
+    pub ambient: Option<Sound>,
+    /// Sleep timer for ambient sound started from the tray, in minutes.
+    pub ambient_timer: Option<u64>,
+    /// Time left on the running sleep timer.
+    pub sleep_timer_left: Option<Duration>,
+    /// Actions for sleep timers started from the tray.
+    pub sleep_actions: Actions,
     pub flags: TrayFlags,
 }
 
 impl MyBudsTray {
     pub fn new(flags: TrayFlags) -> Self {
         Self {
-            connected: false,
+            connection: ConnectionState::default(),
             device_name: None,
             battery: HashMap::new(),
             anc_mode: None,
             anc_options: Vec::new(),
+            voice_boost: None,
             dual_connect_enabled: false,
             dual_connect_available: false,
+            audio_source: None,
+            audio_elsewhere: false,
+            device_address: None,
+            ambient: None,
+            ambient_timer: None,
+            sleep_timer_left: None,
+            sleep_actions: Actions::default(),
             flags,
         }
     }
 }
 
+impl MyBudsTray {
+    /// Favorite ANC mode to switch to, if both favorites are offered.
+    pub fn next_favorite_anc(&self) -> Option<&str> {
+        let favorites = &self.flags.favorite_anc_modes;
+        if self.connection != ConnectionState::Connected
+            || !favorites.iter().all(|m| self.anc_options.contains(m))
+        {
+            return None;
+        }
+        anc::next_favorite(favorites, self.anc_mode.as_deref())
+    }
+}
+
 impl ksni::Tray for MyBudsTray {
     fn id(&self) -> String {
         "mybuds".into()
     }
 
     fn title(&self) -> String {
-        if let Some(ref name) = self.device_name {
-            if let Some(global) = self.battery.get("global") {
-                format!("{} - {}%", name, global)
-            } else {
-                name.clone()
-            }
+        let Some(ref name) = self.device_name else {
+            return "MyBuds".into();
+        };
+        if self.connection != ConnectionState::Connected {
+            return name.clone();
+        }
+        let format = self.flags.title_format.lock().unwrap().clone();
+        render_title(&format, |key| match key {
+            "name" => Some(name.clone()),
+            "left" | "right" | "case" | "global" => self.battery.get(key).cloned(),
+            "anc" => self.anc_mode.as_deref().map(labels::anc_mode),
+            "audio" => self.audio_source.clone(),
+            _ => None,
+        })
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        let description = if self.connection == ConnectionState::Connected {
+            String::new()
         } else {
-            "MyBuds".into()
+            self.connection.label().to_string()
+        };
+        ksni::ToolTip {
+            title: self.title(),
+            description,
+            ..Default::default()
         }
     }
 
@@ -86,29 +172,51 @@ impl ksni::Tray for MyBudsTray {
         self.flags.show_window.store(true, Ordering::Relaxed);
     }
 
+    fn secondary_activate(&mut self, _x: i32, _y: i32) {
+        if let Some(mode) = self.next_favorite_anc() {
+            *self.flags.pending_anc_mode.lock().unwrap() = Some(mode.to_string());
+        }
+    }
+
     fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
-        let anc_refs: Vec<&str> = self.anc_options.iter().map(|s| s.as_str()).collect();
-        menu::build_menu(
-            self.device_name.as_deref(),
-            &self.battery,
-            self.anc_mode.as_deref(),
-            &anc_refs,
-            self.connected,
-            self.dual_connect_enabled,
-            self.dual_connect_available,
-        )
+        menu::build_menu(self)
     }
 }
 
+/// Expand the `{placeholder}`s in a tray title format. `lookup` resolves
+/// a placeholder; unknown ones and missing values show as "--".
+pub fn render_title(format: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        out.push_str(&lookup(&after[..end]).unwrap_or_else(|| "--".into()));
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
 /// Spawn the tray service. Returns a handle to update tray state.
 pub fn spawn_tray(flags: TrayFlags) -> ksni::Handle<MyBudsTray> {
     let service = ksni::TrayService::new(MyBudsTray::new(flags));
     let handle = service.handle();
-    service.spawn();
+    // A sandbox may not own a well-known name; register the unique one
+    if sandbox::is_flatpak() {
+        service.spawn_without_dbus_name();
+    } else {
+        service.spawn();
+    }
     handle
 }
 
-/// Update tray state from the property store.
+/// Update tray state from the property store. Battery levels aren't read
+/// here; they come from [`follow_battery`] as they change.
 pub async fn update_tray_from_props(
     handle: &ksni::Handle<MyBudsTray>,
     props: &PropertyStore,
@@ -116,7 +224,6 @@ pub async fn update_tray_from_props(
 ) {
     let store = props.lock().await;
 
-    let battery = store.get("battery").cloned().unwrap_or_default();
     let anc_mode = store
         .get("anc")
         .and_then(|m| m.get("mode"))
@@ -126,21 +233,77 @@ pub async fn update_tray_from_props(
         .and_then(|m| m.get("mode_options"))
         .map(|s| s.split(',').map(String::from).collect())
         .unwrap_or_default();
-    let connected = !battery.is_empty();
+    let voice_boost = store.get("anc").and_then(|anc| {
+        let offered = anc.get("mode").is_some_and(|m| m == "awareness")
+            && anc
+                .get("level_options")
+                .is_some_and(|o| o.split(',').any(|l| l == "voice_boost"));
+        offered.then(|| anc.get("level").is_some_and(|l| l == "voice_boost"))
+    });
+    let connection = ConnectionState::from_group(store.get(CONNECTION_GROUP));
+    let device_address = store
+        .get(CONNECTION_GROUP)
+        .and_then(|c| c.get(DEVICE_KEY))
+        .cloned();
+    let ambient = ambient::status().map(|(sound, _)| sound);
+    let sleep_timer_left = sleep_timer::remaining(store.get(CONNECTION_GROUP));
 
     let dual_connect = store.get("dual_connect").cloned().unwrap_or_default();
-    let dual_connect_enabled = dual_connect.get("enabled").map_or(false, |s| s == "true");
+    let dual_connect_enabled = dual_connect.get("enabled").is_some_and(|s| s == "true");
     let dual_connect_available = !dual_connect.is_empty();
+    let audio_source = playing_device_name(&dual_connect);
+    let audio_elsewhere = store
+        .get("audio_owner")
+        .and_then(|o| o.get("owned"))
+        .is_some_and(|o| o == "false");
 
     let name = device_name.map(String::from);
 
     handle.update(move |tray| {
-        tray.connected = connected;
+        tray.connection = connection;
         tray.device_name = name.clone();
-        tray.battery = battery.clone();
         tray.anc_mode = anc_mode.clone();
         tray.anc_options = anc_options.clone();
+        tray.voice_boost = voice_boost;
         tray.dual_connect_enabled = dual_connect_enabled;
         tray.dual_connect_available = dual_connect_available;
+        tray.audio_source = audio_source.clone();
+        tray.audio_elsewhere = audio_elsewhere;
+        tray.device_address = device_address.clone();
+        tray.ambient = ambient;
+        tray.sleep_timer_left = sleep_timer_left;
     });
 }
+
+/// Keep the tray's battery readings in step with the device, updating it
+/// on each battery change rather than on the polling loop.
+pub fn follow_battery(
+    handle: ksni::Handle<MyBudsTray>,
+    mut battery: tokio::sync::watch::Receiver<HashMap<String, String>>,
+) {
+    tokio::spawn(async move {
+        while battery.changed().await.is_ok() {
+            let levels = battery.borrow_and_update().clone();
+            handle.update(move |tray| tray.battery = levels);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_title_placeholders() {
+        let lookup = |key: &str| match key {
+            "name" => Some("FreeBuds".to_string()),
+            "left" => Some("80".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            render_title("{name} L{left}% R{right}%", lookup),
+            "FreeBuds L80% R--%"
+        );
+        assert_eq!(render_title("{name} {oops", lookup), "FreeBuds {oops");
+    }
+}
@@ -0,0 +1,190 @@
+//! Read-only status screen (`--dashboard`).
+//!
+//! Shows battery, ANC, codec and link quality and refreshes on its own. The
+//! only keys it reacts to are the quit keys, so it can sit in a tmux pane or
+//! on a kiosk monitor without stray keypresses changing settings.
+
+use std::collections::HashMap;
+use std::io;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Paragraph};
+use tokio_util::sync::CancellationToken;
+
+use super::pages::home;
+use super::{TerminalGuard, POLL_INTERVAL};
+use crate::bluetooth::link::LINK_GROUP;
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::device::handler::PropertyStore;
+use crate::labels;
+
+type Groups = HashMap<String, HashMap<String, String>>;
+
+fn group(store: &Groups, name: &str) -> HashMap<String, String> {
+    store.get(name).cloned().unwrap_or_default()
+}
+
+/// Rough quality label for an RSSI reading in dBm.
+fn signal_label(rssi: i16) -> &'static str {
+    match rssi {
+        r if r >= -60 => "good",
+        r if r >= -75 => "fair",
+        _ => "weak",
+    }
+}
+
+fn detail_line<'a>(label: &'a str, value: String) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(
+            format!("{:<8}", label),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::raw(value),
+    ])
+}
+
+fn draw(frame: &mut Frame, store: &Groups) {
+    let connection = group(store, CONNECTION_GROUP);
+    let state = ConnectionState::from_group(Some(&connection));
+    let info = group(store, "info");
+    let model = info
+        .get("device_model")
+        .or_else(|| info.get("field_15"))
+        .map(|s| s.as_str())
+        .unwrap_or("FreeBuds");
+
+    let outer = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" MyBuds: {} ", model))
+        .title_bottom(" q:quit ");
+    let area = outer.inner(frame.area());
+    frame.render_widget(outer, frame.area());
+
+    if state != ConnectionState::Connected {
+        let msg = Paragraph::new(state.label())
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center);
+        frame.render_widget(msg, area);
+        return;
+    }
+
+    let battery = group(store, "battery");
+    let gauges = home::battery_gauges(&battery);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(gauges.len().max(1) as u16 + 2), // battery
+            Constraint::Min(0),                                 // details
+        ])
+        .split(area);
+    home::render_battery(frame, chunks[0], &battery, &gauges);
+
+    let anc = group(store, "anc");
+    let anc_text = match (anc.get("mode"), anc.get("level")) {
+        (Some(mode), Some(level)) => format!(
+            "{} ({})",
+            labels::anc_mode(mode),
+            labels::anc_level(level)
+        ),
+        (Some(mode), None) => labels::anc_mode(mode),
+        _ => "n/a".to_string(),
+    };
+
+    let link = group(store, LINK_GROUP);
+    let codec_text = match link.get("codec") {
+        Some(codec) if link.get("streaming").is_some_and(|s| s == "true") => {
+            format!("{} (streaming)", codec)
+        }
+        Some(codec) => codec.clone(),
+        None => "n/a".to_string(),
+    };
+    let signal_text = match link.get("rssi").and_then(|s| s.parse::<i16>().ok()) {
+        Some(rssi) => format!("{} dBm ({})", rssi, signal_label(rssi)),
+        None => "n/a".to_string(),
+    };
+
+    let details = Paragraph::new(vec![
+        detail_line("ANC", anc_text),
+        detail_line("Codec", codec_text),
+        detail_line("Signal", signal_text),
+    ])
+    .block(Block::default().borders(Borders::TOP));
+    frame.render_widget(details, chunks[1]);
+}
+
+/// Run the dashboard until a quit key is pressed or `shutdown` fires.
+pub fn run(props: PropertyStore, shutdown: CancellationToken) -> Result<()> {
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut store = Groups::new();
+
+    while !shutdown.is_cancelled() {
+        if let Ok(current) = props.try_lock() {
+            store = current.clone();
+        }
+        terminal.draw(|f| draw(f, &store))?;
+
+        if !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+        match event::read()? {
+            Event::Key(key) if key.kind == event::KeyEventKind::Press => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                _ => {}
+            },
+            Event::Resize(_, _) => {
+                terminal.autoresize()?;
+                terminal.clear()?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn shows_link_details_when_connected() {
+        let mut store = Groups::new();
+        store.insert(
+            CONNECTION_GROUP.into(),
+            HashMap::from([("state".into(), "connected".into())]),
+        );
+        store.insert(
+            "battery".into(),
+            HashMap::from([("left".into(), "80".into()), ("right".into(), "75".into())]),
+        );
+        store.insert(
+            "anc".into(),
+            HashMap::from([("mode".into(), "awareness".into())]),
+        );
+        store.insert(
+            LINK_GROUP.into(),
+            HashMap::from([
+                ("codec".into(), "AAC".into()),
+                ("rssi".into(), "-58".into()),
+            ]),
+        );
+
+        let mut terminal = Terminal::new(TestBackend::new(50, 14)).unwrap();
+        terminal.draw(|f| draw(f, &store)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(screen.contains("Awareness"));
+        assert!(screen.contains("AAC"));
+        assert!(screen.contains("-58 dBm (good)"));
+    }
+}
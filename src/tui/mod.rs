@@ -1,4 +1,7 @@
+pub mod dashboard;
 pub mod pages;
+#[cfg(test)]
+mod snapshots;
 
 use std::collections::HashMap;
 use std::io;
@@ -9,12 +12,42 @@ use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::{execute};
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Borders, Paragraph, Tabs};
-use tokio::sync::mpsc;
-
+use ratatui::widgets::{Block, Borders, Paragraph, Tabs, Wrap};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+use crate::bluetooth::paired::{LIST_PROP, PAIRED_GROUP, SWITCH_PROP};
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::bluetooth::stats::STATS_GROUP;
+use crate::device::gestures;
 use crate::device::handler::PropertyStore;
+use crate::labels;
+use crate::device::request::{PropertyRequest, PropertySender, SetPropertyError, REPLY_TIMEOUT};
 
 const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a confirmed change stays in the status bar.
+const DONE_DISPLAY_TIME: Duration = Duration::from_secs(2);
+/// How long an error stays in the status bar.
+const ERROR_DISPLAY_TIME: Duration = Duration::from_secs(5);
+/// Smallest terminal the pages are laid out for. Below this only a
+/// "too small" message is drawn.
+const MIN_WIDTH: u16 = 44;
+const MIN_HEIGHT: u16 = 16;
+
+/// A property request waiting for its outcome.
+struct PendingReply {
+    sent: Instant,
+    /// What the change looks like in the status bar.
+    change: String,
+    rx: oneshot::Receiver<Result<(), SetPropertyError>>,
+}
+
+/// Outcome of the last property change, shown in place of the status bar.
+enum Notice {
+    Pending(String),
+    Done(String, Instant),
+    Failed(String, Instant),
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tab {
@@ -24,6 +57,8 @@ pub enum Tab {
     DualConnect,
     DeviceInfo,
     Settings,
+    Statistics,
+    Devices,
 }
 
 impl Tab {
@@ -35,6 +70,8 @@ impl Tab {
             Tab::DualConnect,
             Tab::DeviceInfo,
             Tab::Settings,
+            Tab::Statistics,
+            Tab::Devices,
         ]
     }
 
@@ -46,6 +83,38 @@ impl Tab {
             Tab::DualConnect => "Dual Connect",
             Tab::DeviceInfo => "Device Info",
             Tab::Settings => "Settings",
+            Tab::Statistics => "Statistics",
+            Tab::Devices => "Devices",
+        }
+    }
+
+    /// Label used when the full tab bar doesn't fit.
+    fn short_label(&self) -> &'static str {
+        match self {
+            Tab::Home => "Home",
+            Tab::Sound => "Sound",
+            Tab::Gestures => "Gest",
+            Tab::DualConnect => "Dual",
+            Tab::DeviceInfo => "Info",
+            Tab::Settings => "Set",
+            Tab::Statistics => "Stats",
+            Tab::Devices => "Devs",
+        }
+    }
+
+    /// Tab titles that fit in `width` columns: full labels when possible,
+    /// short ones otherwise.
+    fn titles(width: u16) -> Vec<&'static str> {
+        // Tabs pads each title with a space on both sides and puts a
+        // one-column divider between them
+        let needed = |labels: &[&str]| -> usize {
+            labels.iter().map(|l| l.len() + 2).sum::<usize>() + labels.len() - 1
+        };
+        let full: Vec<&str> = Tab::all().iter().map(|t| t.label()).collect();
+        if needed(&full) <= usize::from(width) {
+            full
+        } else {
+            Tab::all().iter().map(|t| t.short_label()).collect()
         }
     }
 
@@ -106,32 +175,49 @@ impl PageState {
 pub struct TuiApp {
     current_tab: Tab,
     props: PropertyStore,
-    prop_tx: mpsc::Sender<(String, String, String)>,
+    prop_tx: PropertySender,
+    /// Property requests still waiting for an outcome.
+    pending: Vec<PendingReply>,
+    /// Feedback for the last property change.
+    notice: Option<Notice>,
     // Cached snapshots
     battery: HashMap<String, String>,
     anc: HashMap<String, String>,
     info: HashMap<String, String>,
+    firmware: HashMap<String, String>,
+    diagnostics: HashMap<String, String>,
+    stats: HashMap<String, String>,
     sound: HashMap<String, String>,
     actions: HashMap<String, String>,
     config: HashMap<String, String>,
     dual_connect: HashMap<String, String>,
+    connection: HashMap<String, String>,
+    /// Paired devices for the Devices page (see `bluetooth::paired`).
+    paired: HashMap<String, String>,
     connected: bool,
     page_state: PageState,
 }
 
 impl TuiApp {
-    fn new(props: PropertyStore, prop_tx: mpsc::Sender<(String, String, String)>) -> Self {
+    fn new(props: PropertyStore, prop_tx: PropertySender) -> Self {
         Self {
             current_tab: Tab::Home,
             props,
             prop_tx,
+            pending: Vec::new(),
+            notice: None,
             battery: HashMap::new(),
             anc: HashMap::new(),
             info: HashMap::new(),
+            firmware: HashMap::new(),
+            diagnostics: HashMap::new(),
+            stats: HashMap::new(),
             sound: HashMap::new(),
             actions: HashMap::new(),
             config: HashMap::new(),
             dual_connect: HashMap::new(),
+            connection: HashMap::new(),
+            paired: HashMap::new(),
             connected: false,
             page_state: PageState::new(),
         }
@@ -143,29 +229,99 @@ impl TuiApp {
             self.battery = store.get("battery").cloned().unwrap_or_default();
             self.anc = store.get("anc").cloned().unwrap_or_default();
             self.info = store.get("info").cloned().unwrap_or_default();
+            self.firmware = store.get("firmware").cloned().unwrap_or_default();
+            self.diagnostics = store
+                .get(crate::startup::DIAGNOSTICS_GROUP)
+                .cloned()
+                .unwrap_or_default();
+            self.stats = store.get(STATS_GROUP).cloned().unwrap_or_default();
             self.sound = store.get("sound").cloned().unwrap_or_default();
             self.actions = store.get("action").cloned().unwrap_or_default();
             self.config = store.get("config").cloned().unwrap_or_default();
             self.dual_connect = store.get("dual_connect").cloned().unwrap_or_default();
-            self.connected = !self.battery.is_empty();
+            self.connection = store.get(CONNECTION_GROUP).cloned().unwrap_or_default();
+            self.paired = store.get(PAIRED_GROUP).cloned().unwrap_or_default();
+            self.connected = self.connection_state() == ConnectionState::Connected;
         }
     }
 
-    fn send_property(&self, group: &str, prop: &str, value: &str) {
-        let _ = self.prop_tx.try_send((
-            group.to_string(),
-            prop.to_string(),
-            value.to_string(),
-        ));
+    fn connection_state(&self) -> ConnectionState {
+        ConnectionState::from_group(Some(&self.connection))
+    }
+
+    fn send_property(&mut self, group: &str, prop: &str, value: &str) {
+        let change = describe_change(group, prop, value);
+        let (req, rx) = PropertyRequest::new(group, prop, value);
+        match self.prop_tx.try_send(req) {
+            Ok(()) => {
+                self.notice = Some(Notice::Pending(change.clone()));
+                self.pending.push(PendingReply {
+                    sent: Instant::now(),
+                    change,
+                    rx,
+                });
+            }
+            Err(_) => self.report(&change, Err(SetPropertyError::NotConnected)),
+        }
+    }
+
+    /// Collect outcomes of in-flight property requests.
+    fn poll_replies(&mut self) {
+        let mut outcomes = Vec::new();
+        self.pending.retain_mut(|p| match p.rx.try_recv() {
+            Ok(result) => {
+                outcomes.push((p.change.clone(), result));
+                false
+            }
+            Err(oneshot::error::TryRecvError::Empty) if p.sent.elapsed() < REPLY_TIMEOUT => true,
+            Err(_) => {
+                outcomes.push((p.change.clone(), Err(SetPropertyError::NotConnected)));
+                false
+            }
+        });
+        for (change, result) in outcomes {
+            self.report(&change, result);
+        }
+        let expired = match &self.notice {
+            Some(Notice::Done(_, at)) => at.elapsed() >= DONE_DISPLAY_TIME,
+            Some(Notice::Failed(_, at)) => at.elapsed() >= ERROR_DISPLAY_TIME,
+            _ => false,
+        };
+        if expired {
+            self.notice = None;
+        }
+    }
+
+    fn report(&mut self, change: &str, result: Result<(), SetPropertyError>) {
+        self.notice = Some(match result {
+            Ok(()) => Notice::Done(format!("{} \u{2713}", change), Instant::now()),
+            Err(e) => Notice::Failed(
+                format!("Write failed: {}: {}", change, e),
+                Instant::now(),
+            ),
+        });
     }
 
     fn switch_tab(&mut self, tab: Tab) {
         if self.current_tab != tab {
             self.current_tab = tab;
             self.page_state = PageState::new();
+            if tab == Tab::Devices {
+                self.list_devices();
+            }
         }
     }
 
+    /// Ask for the paired devices to be listed again. No reply is waited
+    /// for: the list shows up in the store.
+    fn list_devices(&self) {
+        let _ = self.prop_tx.try_send(PropertyRequest::fire_and_forget(
+            CONNECTION_GROUP,
+            LIST_PROP,
+            "true",
+        ));
+    }
+
     fn next_tab(&mut self) {
         let idx = self.current_tab.index();
         let next = (idx + 1) % Tab::all().len();
@@ -192,6 +348,20 @@ impl TuiApp {
     }
 
     fn draw(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            let msg = Paragraph::new(vec![
+                Line::from("Terminal too small"),
+                Line::from(format!("{}x{}", area.width, area.height)),
+                Line::from(format!("need {}x{}", MIN_WIDTH, MIN_HEIGHT)),
+                Line::from("q:quit"),
+            ])
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+            frame.render_widget(msg, area);
+            return;
+        }
+
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -199,11 +369,10 @@ impl TuiApp {
                 Constraint::Min(0),   // page content
                 Constraint::Length(1), // status bar
             ])
-            .split(frame.area());
+            .split(area);
 
         // Tab bar
-        let titles: Vec<&str> = Tab::all().iter().map(|t| t.label()).collect();
-        let tabs = Tabs::new(titles)
+        let tabs = Tabs::new(Tab::titles(area.width))
             .block(Block::default().borders(Borders::BOTTOM).title("MyBuds"))
             .select(self.current_tab.index())
             .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
@@ -211,8 +380,8 @@ impl TuiApp {
 
         // Page content
         let page_area = chunks[1];
-        if !self.connected && self.current_tab != Tab::Home {
-            // Show disconnected message on all non-Home tabs
+        if !self.connected && !matches!(self.current_tab, Tab::Home | Tab::Statistics | Tab::Devices) {
+            // Show disconnected message on the device tabs
             let msg = Paragraph::new(vec![
                 Line::from(""),
                 Line::from(Span::styled(
@@ -220,7 +389,7 @@ impl TuiApp {
                     Style::default().fg(Color::DarkGray),
                 )),
                 Line::from(Span::styled(
-                    "Waiting for device...",
+                    self.connection_state().label(),
                     Style::default().fg(Color::DarkGray),
                 )),
             ])
@@ -235,7 +404,7 @@ impl TuiApp {
                     &self.battery,
                     &self.anc,
                     &self.info,
-                    self.connected,
+                    &self.connection,
                     &mut self.page_state,
                 ),
                 Tab::Sound => pages::sound::render(
@@ -261,6 +430,8 @@ impl TuiApp {
                     frame,
                     page_area,
                     &self.info,
+                    &self.firmware,
+                    &self.diagnostics,
                 ),
                 Tab::Settings => pages::settings::render(
                     frame,
@@ -268,21 +439,58 @@ impl TuiApp {
                     &self.config,
                     &mut self.page_state,
                 ),
+                Tab::Statistics => pages::statistics::render(frame, page_area, &self.stats),
+                Tab::Devices => pages::devices::render(
+                    frame,
+                    page_area,
+                    &self.paired,
+                    &self.connection,
+                    &mut self.page_state,
+                ),
             };
         }
 
-        // Status bar
-        let status = if self.connected {
+        // Status bar, dropping the key hints that don't fit
+        let (summary, hints): (String, &[&str]) = if self.connected {
             let model = self.info.get("device_model")
                 .or_else(|| self.info.get("field_15"))
                 .map(|s| s.as_str())
                 .unwrap_or("FreeBuds");
-            format!(" Connected: {} | q:quit Tab:switch 1-6:tab j/k:nav Enter:select h/l:cycle", model)
+            (
+                format!(" Connected: {}", model),
+                &["q:quit", "Tab:switch", "1-8:tab", "j/k:nav", "Enter:select", "h/l:cycle", "r:refresh"],
+            )
+        } else if let Some(left) = ConnectionState::retry_in(Some(&self.connection)) {
+            (
+                format!(
+                    " {} Retrying in {}s",
+                    self.connection_state().label(),
+                    left.as_secs()
+                ),
+                &["r:retry now", "q:quit", "Tab:switch"],
+            )
+        } else if self.connection_state() == ConnectionState::Asleep {
+            (
+                format!(" {}", self.connection_state().label()),
+                &["r:reconnect", "q:quit", "Tab:switch"],
+            )
         } else {
-            " Waiting for device... | q:quit Tab:switch".to_string()
+            (
+                format!(" {}", self.connection_state().label()),
+                &["q:quit", "Tab:switch"],
+            )
+        };
+        let status = status_line(&summary, hints, usize::from(area.width));
+        let status_bar = match &self.notice {
+            Some(Notice::Pending(change)) => Line::from(format!(" {} \u{2026}", change))
+                .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
+            Some(Notice::Done(text, _)) => Line::from(format!(" {}", text))
+                .style(Style::default().fg(Color::Black).bg(Color::Green)),
+            Some(Notice::Failed(text, _)) => Line::from(format!(" {}", text))
+                .style(Style::default().fg(Color::White).bg(Color::Red)),
+            None => Line::from(status)
+                .style(Style::default().fg(Color::White).bg(Color::DarkGray)),
         };
-        let status_bar = Line::from(status)
-            .style(Style::default().fg(Color::White).bg(Color::DarkGray));
         frame.render_widget(status_bar, chunks[2]);
     }
 
@@ -299,9 +507,27 @@ impl TuiApp {
             KeyCode::Char('4') => self.switch_tab(Tab::DualConnect),
             KeyCode::Char('5') => self.switch_tab(Tab::DeviceInfo),
             KeyCode::Char('6') => self.switch_tab(Tab::Settings),
+            KeyCode::Char('7') => self.switch_tab(Tab::Statistics),
+            KeyCode::Char('8') => self.switch_tab(Tab::Devices),
             KeyCode::Tab => self.next_tab(),
             KeyCode::BackTab => self.prev_tab(),
 
+            // List the paired devices again
+            KeyCode::Char('r') if self.current_tab == Tab::Devices => self.list_devices(),
+
+            // Skip the reconnect backoff, or re-read battery and ANC
+            KeyCode::Char('r') if !self.connected => {
+                self.send_property(CONNECTION_GROUP, "retry_now", "true");
+            }
+            KeyCode::Char('r') => self.send_property(CONNECTION_GROUP, "refresh", "true"),
+
+            // Mirror the left and right gesture assignments
+            KeyCode::Char('s') if self.current_tab == Tab::Gestures => {
+                for (prop, value) in gestures::swap_sides(&self.actions) {
+                    self.send_property("action", &prop, &value);
+                }
+            }
+
             // Page navigation
             KeyCode::Up | KeyCode::Char('k') => self.page_state.move_up(),
             KeyCode::Down | KeyCode::Char('j') => self.page_state.move_down(),
@@ -332,6 +558,7 @@ impl TuiApp {
             Tab::Sound => pages::sound::on_enter(&self.sound, &self.config, &self.page_state),
             Tab::DualConnect => pages::dual_connect::on_enter(&self.dual_connect, &self.page_state),
             Tab::Settings => pages::settings::on_enter(&self.config, &self.page_state),
+            Tab::Devices => pages::devices::on_enter(&self.paired, &self.page_state),
             _ => Action::None,
         }
     }
@@ -349,46 +576,168 @@ impl TuiApp {
     }
 }
 
+/// Human-readable form of a property change, e.g. "ANC \u{2192} Awareness".
+fn describe_change(group: &str, prop: &str, value: &str) -> String {
+    let name = match (group, prop) {
+        ("anc", "mode") => "ANC".to_string(),
+        ("anc", _) => format!("ANC {}", prop.replace('_', " ")),
+        (CONNECTION_GROUP, "retry_now") => return "Reconnecting".to_string(),
+        (CONNECTION_GROUP, "refresh") => return "Refresh".to_string(),
+        (CONNECTION_GROUP, SWITCH_PROP) => return format!("Switching to {}", value),
+        (_, "enabled") => labels::humanize(group),
+        _ => labels::humanize(prop),
+    };
+    let value = match value {
+        "true" => "On".to_string(),
+        "false" => "Off".to_string(),
+        _ => labels::for_property(group, prop, value).unwrap_or_else(|| labels::humanize(value)),
+    };
+    format!("{} \u{2192} {}", name, value)
+}
+
+/// Join `summary` and as many `hints` as fit in `width` columns.
+fn status_line(summary: &str, hints: &[&str], width: usize) -> String {
+    let mut line = summary.to_string();
+    for (i, hint) in hints.iter().enumerate() {
+        let sep = if i == 0 { " | " } else { " " };
+        if line.chars().count() + sep.len() + hint.len() > width {
+            break;
+        }
+        line.push_str(sep);
+        line.push_str(hint);
+    }
+    line
+}
+
+/// Leave raw mode and the alternate screen so error and panic messages (and
+/// the user's shell) aren't garbled. Does nothing if the TUI isn't active,
+/// so it is safe to call from both the panic hook and [`TerminalGuard`].
+fn restore_terminal() {
+    if terminal::is_raw_mode_enabled().unwrap_or(false) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, crossterm::cursor::Show);
+    }
+}
+
+/// Puts the terminal in raw mode on the alternate screen for as long as it
+/// lives. Dropping it restores the terminal however `run` exits: clean quit,
+/// `?` on an I/O error, or unwinding from a panic.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        crate::crash::on_panic(restore_terminal);
+        terminal::enable_raw_mode()?;
+        // From here on, a failure must still undo raw mode
+        let guard = TerminalGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
 pub fn run(
     props: PropertyStore,
-    prop_tx: mpsc::Sender<(String, String, String)>,
+    prop_tx: PropertySender,
+    shutdown: CancellationToken,
 ) -> Result<()> {
-    // Setup terminal
-    terminal::enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
 
     let mut app = TuiApp::new(props, prop_tx);
     let mut last_poll = Instant::now();
 
     loop {
+        // Another instance asked us to exit (--replace)
+        if shutdown.is_cancelled() {
+            break;
+        }
+
         // Poll properties periodically
         if last_poll.elapsed() >= POLL_INTERVAL {
             app.refresh_props();
             last_poll = Instant::now();
         }
+        app.poll_replies();
 
         // Draw
         terminal.draw(|f| app.draw(f))?;
 
         // Handle events with a timeout so we keep refreshing
         if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == event::KeyEventKind::Press {
-                    if app.handle_key(key.code, key.modifiers) {
-                        break;
-                    }
+            match event::read()? {
+                Event::Key(key)
+                    if key.kind == event::KeyEventKind::Press
+                        && app.handle_key(key.code, key.modifiers) =>
+                {
+                    break;
+                }
+                // Redraw from scratch so nothing from the old size lingers
+                Event::Resize(_, _) => {
+                    terminal.autoresize()?;
+                    terminal.clear()?;
                 }
+                _ => {}
             }
         }
     }
 
-    // Restore terminal
-    terminal::disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    fn screen(width: u16, height: u16) -> String {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let props = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+        let mut app = TuiApp::new(props, tx);
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer.content().iter().map(|c| c.symbol()).collect()
+    }
+
+    #[test]
+    fn layout_adapts_to_terminal_size() {
+        let wide = screen(100, 24);
+        assert!(wide.contains("Dual Connect"));
+        assert!(wide.contains("Tab:switch"));
+
+        let narrow = screen(50, 24);
+        assert!(narrow.contains("Dual"));
+        assert!(!narrow.contains("Dual Connect"));
+
+        assert!(screen(30, 10).contains("Terminal too small"));
+    }
+
+    #[test]
+    fn changes_read_naturally() {
+        assert_eq!(describe_change("anc", "mode", "awareness"), "ANC \u{2192} Awareness");
+        assert_eq!(
+            describe_change("dual_connect", "enabled", "true"),
+            "Dual connect \u{2192} On"
+        );
+        assert_eq!(
+            describe_change("action", "double_tap_left", "tap_action_next"),
+            "Double tap left \u{2192} Next Track"
+        );
+    }
+
+    #[test]
+    fn status_line_drops_hints_that_do_not_fit() {
+        let hints = ["q:quit", "Tab:switch"];
+        assert_eq!(status_line(" Idle", &hints, 80), " Idle | q:quit Tab:switch");
+        assert_eq!(status_line(" Idle", &hints, 16), " Idle | q:quit");
+        assert_eq!(status_line(" Idle", &hints, 5), " Idle");
+    }
+}
@@ -7,6 +7,8 @@ pub fn render(
     frame: &mut Frame,
     area: Rect,
     info: &HashMap<String, String>,
+    firmware: &HashMap<String, String>,
+    diagnostics: &HashMap<String, String>,
 ) {
     let known_fields = [
         ("device_model", "Model"),
@@ -34,7 +36,7 @@ pub fn render(
         .iter()
         .filter(|(k, _)| !known_fields.iter().any(|(kf, _)| *kf == k.as_str()))
         .collect();
-    extra.sort_by(|(a, _), (b, _)| a.cmp(b));
+    extra.sort_by_key(|(k, _)| *k);
 
     for (key, value) in extra {
         rows.push(Row::new(vec![
@@ -47,9 +49,95 @@ pub fn render(
         rows.push(Row::new(vec![Cell::from("No device info available")]));
     }
 
+    firmware_rows(&mut rows, firmware);
+    startup_rows(&mut rows, diagnostics);
+    framing_rows(&mut rows, diagnostics);
+
     let widths = [Constraint::Length(20), Constraint::Min(10)];
     let table = Table::new(rows, widths)
         .block(Block::default().borders(Borders::ALL).title("Device Info"))
         .column_spacing(2);
     frame.render_widget(table, area);
 }
+
+/// Changelog lines shown before truncating.
+const MAX_CHANGELOG_LINES: usize = 8;
+
+fn firmware_rows<'a>(rows: &mut Vec<Row<'a>>, firmware: &'a HashMap<String, String>) {
+    if firmware.is_empty() {
+        return;
+    }
+
+    let label = |s: &'a str| Cell::from(s).style(Style::default().fg(Color::DarkGray));
+    rows.push(Row::new(vec![Cell::from("")]));
+
+    if let Some(err) = firmware.get("error") {
+        rows.push(Row::new(vec![
+            label("Update Check"),
+            Cell::from(format!("failed: {}", err)).style(Style::default().fg(Color::Red)),
+        ]));
+        return;
+    }
+
+    if let Some(latest) = firmware.get("latest_version") {
+        rows.push(Row::new(vec![label("Latest Firmware"), Cell::from(latest.as_str())]));
+    }
+
+    let status = if firmware.get("update_available").is_some_and(|v| v == "true") {
+        Cell::from("Update available (install via Huawei AI Life)")
+            .style(Style::default().fg(Color::Yellow))
+    } else {
+        Cell::from("Up to date").style(Style::default().fg(Color::Green))
+    };
+    rows.push(Row::new(vec![label("Update Status"), status]));
+
+    if let Some(changelog) = firmware.get("changelog") {
+        for (i, line) in changelog.lines().take(MAX_CHANGELOG_LINES).enumerate() {
+            let key = if i == 0 { label("Changelog") } else { Cell::from("") };
+            rows.push(Row::new(vec![key, Cell::from(line)]));
+        }
+    }
+}
+
+/// AirPods messages that arrived joined, split or garbled.
+fn framing_rows<'a>(rows: &mut Vec<Row<'a>>, diagnostics: &'a HashMap<String, String>) {
+    let fields = [
+        ("aap_concatenated", "Joined Messages"),
+        ("aap_reassembled", "Reassembled"),
+        ("aap_truncated", "Truncated"),
+        ("aap_skipped_bytes", "Skipped Bytes"),
+    ];
+    let mut first = true;
+    for (key, label) in fields {
+        if let Some(count) = diagnostics.get(key) {
+            if first {
+                rows.push(Row::new(vec![Cell::from("")]));
+                first = false;
+            }
+            rows.push(Row::new(vec![
+                Cell::from(label).style(Style::default().fg(Color::DarkGray)),
+                Cell::from(count.as_str()),
+            ]));
+        }
+    }
+}
+
+fn startup_rows<'a>(rows: &mut Vec<Row<'a>>, diagnostics: &'a HashMap<String, String>) {
+    let fields = [
+        ("time_to_connected_ms", "Connected After"),
+        ("time_to_first_battery_ms", "First Battery After"),
+    ];
+    let mut first = true;
+    for (key, label) in fields {
+        if let Some(ms) = diagnostics.get(key) {
+            if first {
+                rows.push(Row::new(vec![Cell::from("")]));
+                first = false;
+            }
+            rows.push(Row::new(vec![
+                Cell::from(label).style(Style::default().fg(Color::DarkGray)),
+                Cell::from(format!("{} ms", ms)),
+            ]));
+        }
+    }
+}
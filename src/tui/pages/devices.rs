@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+
+use crate::bluetooth::paired::{self, SWITCH_PROP};
+use crate::bluetooth::state::CONNECTION_GROUP;
+use crate::device::DEVICE_KEY;
+use crate::tui::{Action, PageState};
+
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    paired_group: &HashMap<String, String>,
+    connection: &HashMap<String, String>,
+    state: &mut PageState,
+) {
+    let devices = paired::from_group(Some(paired_group));
+    state.item_count = devices.len();
+    state.clamp();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Devices (Enter to use)");
+    if devices.is_empty() {
+        let message = match paired::error(Some(paired_group)) {
+            Some(error) => error,
+            None if paired_group.is_empty() => "Looking for paired devices...",
+            None => "No supported device is paired",
+        };
+        let msg = Paragraph::new(message)
+            .style(Style::default().fg(Color::DarkGray))
+            .block(block);
+        frame.render_widget(msg, area);
+        return;
+    }
+
+    let in_use = connection.get(DEVICE_KEY);
+    let items: Vec<ListItem> = devices
+        .iter()
+        .enumerate()
+        .map(|(i, dev)| {
+            let marker = if in_use == Some(&dev.address) { "(*)" } else { "( )" };
+            let mut line = format!("{} {} {}", marker, dev.name, dev.address);
+            if dev.connected {
+                line.push_str(" [connected]");
+            }
+            let style = if state.selected == i {
+                Style::default().fg(Color::Cyan).bg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            let details: Vec<String> = dev
+                .kind()
+                .map(String::from)
+                .into_iter()
+                .chain(dev.signal())
+                .collect();
+            let mut lines = vec![Line::from(line)];
+            if !details.is_empty() {
+                lines.push(Line::from(format!("    {}", details.join(" · "))).fg(Color::DarkGray));
+            }
+            ListItem::new(lines).style(style)
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+pub fn on_enter(paired_group: &HashMap<String, String>, state: &PageState) -> Action {
+    match paired::from_group(Some(paired_group)).get(state.selected) {
+        Some(dev) => Action::SetProperty {
+            group: CONNECTION_GROUP.into(),
+            prop: SWITCH_PROP.into(),
+            value: dev.address.clone(),
+        },
+        None => Action::None,
+    }
+}
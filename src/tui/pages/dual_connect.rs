@@ -1,10 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::widgets::{Block, Borders, List, ListItem};
+use serde_json::Value;
 
 use crate::tui::{Action, PageState};
 
+/// A paired device from the `devices` JSON, keyed by MAC.
+struct Device {
+    mac: String,
+    name: String,
+    connected: bool,
+    playing: bool,
+}
+
+/// Parse the device list in a stable (MAC) order so selection indices hold.
+fn parse_devices(dc: &HashMap<String, String>) -> Vec<Device> {
+    let json_str = dc.get("devices").map(|s| s.as_str()).unwrap_or("{}");
+    let Ok(parsed) = serde_json::from_str::<BTreeMap<String, Value>>(json_str) else {
+        return Vec::new();
+    };
+    parsed
+        .into_iter()
+        .map(|(mac, obj)| Device {
+            name: obj
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("Unknown Device")
+                .to_string(),
+            connected: obj.get("connected").and_then(|v| v.as_bool()).unwrap_or(false),
+            playing: obj.get("playing").and_then(|v| v.as_bool()).unwrap_or(false),
+            mac,
+        })
+        .collect()
+}
+
 pub fn render(
     frame: &mut Frame,
     area: Rect,
@@ -21,8 +51,10 @@ pub fn render(
         ])
         .split(area);
 
-    // Toggle item
-    state.item_count = 1;
+    let devices = if enabled { parse_devices(dc) } else { Vec::new() };
+
+    // Toggle item plus one selectable row per device
+    state.item_count = 1 + devices.len();
     state.clamp();
 
     let toggle_text = format!("Dual Connect: {}", if enabled { "ON" } else { "OFF" });
@@ -35,17 +67,36 @@ pub fn render(
         .block(Block::default().borders(Borders::ALL).title("Dual Connect (Enter to toggle)"));
     frame.render_widget(toggle, chunks[0]);
 
-    // Paired devices info
+    // Paired devices with preferred marker
     if enabled {
-        let devices_json = dc.get("devices").cloned().unwrap_or_default();
-        let text = if !devices_json.is_empty() && devices_json != "{}" {
-            format!("Connected Devices:\n{}", devices_json)
+        let preferred = dc.get("preferred_device").map(|s| s.as_str()).unwrap_or("");
+        let items: Vec<ListItem> = if devices.is_empty() {
+            vec![ListItem::new("No devices paired")]
         } else {
-            "No devices paired".into()
+            devices
+                .iter()
+                .enumerate()
+                .map(|(i, dev)| {
+                    let marker = if dev.mac == preferred { "(*)" } else { "( )" };
+                    let mut line = format!("{} {}", marker, dev.name);
+                    if dev.connected {
+                        line.push_str(" [connected]");
+                    }
+                    if dev.playing {
+                        line.push_str(" [playing]");
+                    }
+                    let style = if state.selected == i + 1 {
+                        Style::default().fg(Color::Cyan).bg(Color::DarkGray)
+                    } else {
+                        Style::default()
+                    };
+                    ListItem::new(line).style(style)
+                })
+                .collect()
         };
-        let para = Paragraph::new(text)
-            .block(Block::default().borders(Borders::ALL).title("Devices"));
-        frame.render_widget(para, chunks[1]);
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Devices (Enter to set preferred)"));
+        frame.render_widget(list, chunks[1]);
     }
 }
 
@@ -61,6 +112,12 @@ pub fn on_cycle(dc: &HashMap<String, String>, state: &PageState, _direction: i32
             prop: "enabled".into(),
             value: if enabled { "false" } else { "true" }.into(),
         }
+    } else if let Some(dev) = parse_devices(dc).get(state.selected - 1) {
+        Action::SetProperty {
+            group: "dual_connect".into(),
+            prop: "preferred_device".into(),
+            value: dev.mac.clone(),
+        }
     } else {
         Action::None
     }
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Borders, List, ListItem};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
 
+use crate::device::gestures;
 use crate::tui::{Action, PageState};
+use crate::labels;
 
 struct GestureItem {
     label: String,
@@ -13,7 +15,44 @@ struct GestureItem {
     group: &'static str,
 }
 
+/// Items of buds set once for both sides (FreeClip), in-call actions next
+/// to their tap.
+fn both_buds_items(actions: &HashMap<String, String>) -> Vec<GestureItem> {
+    let mut items = Vec::new();
+    for (prefix, label, in_call, group) in [
+        ("double_tap", "Double Tap", "double_tap_in_call", "gesture_double"),
+        ("triple_tap", "Triple Tap", "triple_tap_in_call", "gesture_triple"),
+    ] {
+        // Without the buds' own list the value is shown but not cycled
+        let opts = parse_options(actions.get(&format!("{}_options", prefix)));
+        if opts.is_empty() && !actions.contains_key(prefix) {
+            continue;
+        }
+        items.push(GestureItem {
+            label: format!("{} (both buds)", label),
+            value: gesture_display(actions.get(prefix)),
+            options: opts,
+            prop_name: prefix,
+            group,
+        });
+        let call_opts = parse_options(actions.get(&format!("{}_options", in_call)));
+        if !call_opts.is_empty() {
+            items.push(GestureItem {
+                label: format!("{} in a Call", label),
+                value: gesture_display(actions.get(in_call)),
+                options: call_opts,
+                prop_name: in_call,
+                group,
+            });
+        }
+    }
+    items
+}
+
 fn build_items(actions: &HashMap<String, String>) -> Vec<GestureItem> {
+    if gestures::both_buds(actions) {
+        return both_buds_items(actions);
+    }
     let mut items = Vec::new();
 
     // Double tap
@@ -121,12 +160,43 @@ pub fn render(
     state.item_count = items.len();
     state.clamp();
 
+    // Hints about conflicting assignments go below the list, and the
+    // gestures they are about get a marker
+    let conflicts = gestures::conflicts(actions);
+    let (list_area, hints_area) = if conflicts.is_empty() {
+        (area, None)
+    } else {
+        // One row per hint, more where a hint wraps, plus the borders
+        let width = usize::from(area.width.saturating_sub(2)).max(1);
+        let rows: usize = conflicts
+            .iter()
+            .map(|c| (c.hint.chars().count() + 2).div_ceil(width))
+            .sum();
+        let height = u16::try_from(rows).unwrap_or(u16::MAX).saturating_add(2);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(height)])
+            .split(area);
+        (chunks[0], Some(chunks[1]))
+    };
+    let flagged = |prop: &str| {
+        conflicts.iter().any(|c| {
+            !c.gesture.is_empty()
+                && prop
+                    .strip_prefix(c.gesture)
+                    .is_some_and(|side| matches!(side, "" | "_left" | "_right"))
+        })
+    };
+
     let list_items: Vec<ListItem> = items
         .iter()
         .enumerate()
         .map(|(i, item)| {
             let is_focused = i == state.selected;
-            let line = format!("{}: {}", item.label, item.value);
+            let mut line = format!("{}: {}", item.label, item.value);
+            if flagged(item.prop_name) {
+                line.push_str(" \u{26a0}");
+            }
             let style = if is_focused {
                 Style::default().fg(Color::Cyan).bg(Color::DarkGray).add_modifier(Modifier::BOLD)
             } else {
@@ -136,9 +206,25 @@ pub fn render(
         })
         .collect();
 
-    let list = List::new(list_items)
-        .block(Block::default().borders(Borders::ALL).title("Gesture Settings (h/l to cycle)"));
-    frame.render_widget(list, area);
+    let title = if gestures::both_buds(actions) {
+        "Gesture Settings (h/l to cycle)"
+    } else {
+        "Gesture Settings (h/l to cycle, s to swap sides)"
+    };
+    let list = List::new(list_items).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(list, list_area);
+
+    if let Some(hints_area) = hints_area {
+        let lines: Vec<Line> = conflicts
+            .iter()
+            .map(|c| Line::from(format!("\u{26a0} {}", c.hint)))
+            .collect();
+        let hints = Paragraph::new(lines)
+            .style(Style::default().fg(Color::Yellow))
+            .wrap(Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL).title("Hints"));
+        frame.render_widget(hints, hints_area);
+    }
 }
 
 pub fn on_cycle(
@@ -186,23 +272,6 @@ fn parse_options(raw: Option<&String>) -> Vec<String> {
 }
 
 fn gesture_display(val: Option<&String>) -> String {
-    val.map(|s| gesture_display_name(s)).unwrap_or_else(|| "—".into())
+    val.map(|s| labels::gesture(s)).unwrap_or_else(|| "—".into())
 }
 
-fn gesture_display_name(name: &str) -> String {
-    match name {
-        "tap_action_off" => "Disabled".into(),
-        "tap_action_pause" => "Play/Pause".into(),
-        "tap_action_next" => "Next Track".into(),
-        "tap_action_prev" => "Previous Track".into(),
-        "tap_action_assistant" => "Voice Assistant".into(),
-        "tap_action_answer" => "Answer Call".into(),
-        "tap_action_switch_anc" => "Switch ANC".into(),
-        "tap_action_change_volume" => "Volume Control".into(),
-        "noise_control_off_on" => "Off / NC".into(),
-        "noise_control_off_on_aw" => "Off / NC / Awareness".into(),
-        "noise_control_on_aw" => "NC / Awareness".into(),
-        "noise_control_off_aw" => "Off / Awareness".into(),
-        other => other.replace('_', " "),
-    }
-}
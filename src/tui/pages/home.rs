@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph};
 
+use crate::bluetooth::state::ConnectionState;
+use crate::labels;
 use crate::tui::{Action, PageState};
 
 pub fn render(
@@ -11,11 +13,12 @@ pub fn render(
     battery: &HashMap<String, String>,
     anc: &HashMap<String, String>,
     info: &HashMap<String, String>,
-    connected: bool,
+    connection: &HashMap<String, String>,
     state: &mut PageState,
 ) {
-    if !connected {
-        let msg = Paragraph::new(vec![
+    let conn_state = ConnectionState::from_group(Some(connection));
+    if conn_state != ConnectionState::Connected {
+        let mut lines = vec![
             Line::from(""),
             Line::from(Span::styled(
                 "MyBuds",
@@ -23,14 +26,23 @@ pub fn render(
             )),
             Line::from(""),
             Line::from(Span::styled(
-                "No device connected",
+                conn_state.label(),
                 Style::default().fg(Color::DarkGray),
             )),
-            Line::from(Span::styled(
+        ];
+        if let Some(err) = connection.get("error") {
+            lines.push(Line::from(Span::styled(
+                err.as_str(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+        if matches!(conn_state, ConnectionState::Searching | ConnectionState::Disconnected) {
+            lines.push(Line::from(Span::styled(
                 "Pair your FreeBuds via Bluetooth settings.",
                 Style::default().fg(Color::DarkGray),
-            )),
-        ])
+            )));
+        }
+        let msg = Paragraph::new(lines)
         .alignment(Alignment::Center)
         .block(Block::default().borders(Borders::ALL).title("Home"));
         frame.render_widget(msg, area);
@@ -38,12 +50,16 @@ pub fn render(
         return;
     }
 
+    // Size the battery box to its gauges so it doesn't squeeze the ANC list
+    // on short terminals
+    let gauges = battery_gauges(battery);
+    let battery_height = gauges.len().max(1) as u16 + 2;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3),  // device header
-            Constraint::Length(8),  // battery section
-            Constraint::Min(4),    // ANC section
+            Constraint::Length(3),              // device header
+            Constraint::Length(battery_height), // battery section
+            Constraint::Min(4),                 // ANC section
         ])
         .split(area);
 
@@ -66,40 +82,54 @@ pub fn render(
     frame.render_widget(header, chunks[0]);
 
     // Battery section
-    render_battery(frame, chunks[1], battery);
+    render_battery(frame, chunks[1], battery, &gauges);
 
     // ANC section
     render_anc(frame, chunks[2], anc, state);
 }
 
-fn render_battery(frame: &mut Frame, area: Rect, battery: &HashMap<String, String>) {
-    let is_charging = battery.get("is_charging").map_or(false, |s| s == "true");
-    let title = if is_charging {
-        "Battery [Charging]"
-    } else {
-        "Battery"
-    };
-
-    let block = Block::default().borders(Borders::ALL).title(title);
-    let inner = block.inner(area);
-    frame.render_widget(block, area);
-
-    let mut gauges: Vec<(&str, Option<u16>)> = Vec::new();
+/// Battery gauges to show, as (label, percent) pairs.
+pub fn battery_gauges(battery: &HashMap<String, String>) -> Vec<(&'static str, Option<u16>)> {
+    let mut gauges = Vec::new();
 
     let left = battery.get("left").and_then(|s| s.parse().ok());
     let right = battery.get("right").and_then(|s| s.parse().ok());
     let case_val = battery.get("case").and_then(|s| s.parse().ok());
     let global = battery.get("global").and_then(|s| s.parse().ok());
 
+    // One bud in use: show only that one rather than an empty gauge
     if left.is_some() || right.is_some() {
-        gauges.push(("Left ", left));
-        gauges.push(("Right", right));
+        if left.is_some() {
+            gauges.push(("Left ", left));
+        }
+        if right.is_some() {
+            gauges.push(("Right", right));
+        }
         if case_val.is_some() {
             gauges.push(("Case ", case_val));
         }
     } else if global.is_some() {
         gauges.push(("Level", global));
     }
+    gauges
+}
+
+pub fn render_battery(
+    frame: &mut Frame,
+    area: Rect,
+    battery: &HashMap<String, String>,
+    gauges: &[(&str, Option<u16>)],
+) {
+    let is_charging = battery.get("is_charging").is_some_and(|s| s == "true");
+    let title = if is_charging {
+        "Battery [Charging]"
+    } else {
+        "Battery"
+    };
+
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
 
     if gauges.is_empty() {
         let p = Paragraph::new("No battery data");
@@ -157,7 +187,7 @@ fn render_anc(
     for opt in &mode_options {
         let is_selected = current_mode.as_ref() == Some(opt);
         let marker = if is_selected { ">" } else { " " };
-        let display = anc_display_name(opt);
+        let display = labels::anc_mode(opt);
         let style = if is_selected {
             Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
         } else {
@@ -169,7 +199,7 @@ fn render_anc(
     for opt in &level_options {
         let is_selected = current_level.as_ref() == Some(opt);
         let marker = if is_selected { ">" } else { " " };
-        let display = format!("Level: {}", anc_display_name(opt));
+        let display = format!("Level: {}", labels::anc_level(opt));
         let style = if is_selected {
             Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
         } else {
@@ -178,6 +208,16 @@ fn render_anc(
         items.push(ListItem::new(format!("{} {}", marker, display)).style(style));
     }
 
+    // Continuous depth, shown read-only after the levels when reported
+    if let Some((depth, max)) = anc_depth(anc) {
+        let filled = (usize::from(depth) * 10) / usize::from(max.max(1));
+        let bar = format!("{}{}", "#".repeat(filled), "-".repeat(10 - filled));
+        items.push(
+            ListItem::new(format!("  Depth: [{}] {}", bar, depth))
+                .style(Style::default().fg(Color::Yellow)),
+        );
+    }
+
     state.item_count = items.len();
     state.clamp();
 
@@ -193,19 +233,6 @@ fn render_anc(
     frame.render_widget(list, area);
 }
 
-fn anc_display_name(name: &str) -> String {
-    match name {
-        "normal" | "off" => "Off".into(),
-        "cancellation" | "noise_cancelling" => "Noise Cancellation".into(),
-        "awareness" | "transparency" => "Awareness".into(),
-        "dynamic" => "Dynamic".into(),
-        "ultra" => "Ultra".into(),
-        "general" => "General".into(),
-        "cozy" | "comfort" => "Cozy".into(),
-        other => other.replace('_', " "),
-    }
-}
-
 pub fn on_enter(anc: &HashMap<String, String>, state: &PageState) -> Action {
     on_cycle(anc, state, 0)
 }
@@ -248,6 +275,16 @@ pub fn on_cycle(anc: &HashMap<String, String>, state: &PageState, direction: i32
     Action::None
 }
 
+/// Current and maximum ANC depth, if the device reports one.
+fn anc_depth(anc: &HashMap<String, String>) -> Option<(u8, u8)> {
+    let depth = anc.get("depth")?.parse().ok()?;
+    let max = anc
+        .get("depth_max")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(100);
+    Some((depth, max))
+}
+
 fn cycle_option(options: &[String], current: Option<&str>, direction: i32) -> Option<String> {
     if options.is_empty() {
         return None;
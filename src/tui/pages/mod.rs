@@ -1,6 +1,8 @@
 pub mod device_info;
+pub mod devices;
 pub mod dual_connect;
 pub mod gestures;
 pub mod home;
 pub mod settings;
 pub mod sound;
+pub mod statistics;
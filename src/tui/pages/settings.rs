@@ -50,6 +50,10 @@ pub fn render(
         Line::from("MyBuds v0.1.0"),
         Line::from("Manages Huawei FreeBuds headphones via SPP protocol"),
         Line::from("Based on the OpenFreebuds project"),
+        Line::from(match crate::logging::log_file() {
+            Some(path) => format!("Log file: {}", path.display()),
+            None => "Logging to the terminal".to_string(),
+        }),
     ])
     .block(Block::default().borders(Borders::ALL));
     frame.render_widget(about, chunks[1]);
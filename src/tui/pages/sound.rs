@@ -4,6 +4,7 @@ use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, List, ListItem};
 
 use crate::tui::{Action, PageState};
+use crate::labels;
 
 struct SoundItem {
     label: String,
@@ -25,7 +26,7 @@ fn build_items(sound: &HashMap<String, String>, config: &HashMap<String, String>
         let current = sound.get("equalizer_preset").cloned().unwrap_or_default();
         items.push(SoundItem {
             label: "EQ Preset".into(),
-            value: eq_display_name(&current),
+            value: labels::eq_preset(&current),
             options: eq_options,
             group: "config_eq",
             prop: "equalizer_preset",
@@ -41,7 +42,7 @@ fn build_items(sound: &HashMap<String, String>, config: &HashMap<String, String>
         let current = sound.get("quality_preference").cloned().unwrap_or_default();
         items.push(SoundItem {
             label: "Sound Quality".into(),
-            value: quality_display_name(&current),
+            value: labels::sound_quality(&current),
             options: quality_options,
             group: "config_sound_quality",
             prop: "quality_preference",
@@ -125,11 +126,7 @@ pub fn on_cycle(
     }
 
     // Cycle through options
-    let current = if item.prop == "equalizer_preset" {
-        sound.get(item.prop)
-    } else {
-        sound.get(item.prop)
-    };
+    let current = sound.get(item.prop);
     let new_val = cycle_option(&item.options, current.map(|s| s.as_str()), direction);
     if let Some(val) = new_val {
         Action::SetProperty {
@@ -157,20 +154,3 @@ fn cycle_option(options: &[String], current: Option<&str>, direction: i32) -> Op
     Some(options[new_idx].clone())
 }
 
-fn eq_display_name(key: &str) -> String {
-    match key {
-        "equalizer_preset_default" => "Default".into(),
-        "equalizer_preset_hardbass" => "Bass Boost".into(),
-        "equalizer_preset_treble" => "Treble Boost".into(),
-        "equalizer_preset_voices" | "equalizer_preset_voice" => "Voice".into(),
-        other => other.replace("equalizer_preset_", "").replace('_', " "),
-    }
-}
-
-fn quality_display_name(key: &str) -> String {
-    match key {
-        "sqp_connectivity" => "Connectivity Priority".into(),
-        "sqp_quality" => "Sound Quality Priority".into(),
-        other => other.to_string(),
-    }
-}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Cell, Row, Table};
+
+use crate::bluetooth::stats;
+use crate::labels;
+
+pub fn render(frame: &mut Frame, area: Rect, group: &HashMap<String, String>) {
+    let label = |s: String| Cell::from(s).style(Style::default().fg(Color::DarkGray));
+
+    let mut rows: Vec<Row> = stats::summary(group)
+        .into_iter()
+        .map(|(name, value)| Row::new(vec![label(name.to_string()), Cell::from(value)]))
+        .collect();
+
+    let shares = stats::anc_distribution(group);
+    if !shares.is_empty() {
+        rows.push(Row::new(vec![Cell::from("")]));
+        for (mode, share) in shares {
+            let secs = group
+                .get(&format!("{}{}", stats::ANC_TIME_PREFIX, mode))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            rows.push(Row::new(vec![
+                label(labels::anc_mode(&mode)),
+                Cell::from(format!(
+                    "{:>3.0}%  {}",
+                    share * 100.0,
+                    stats::format_duration(secs)
+                )),
+            ]));
+        }
+    }
+
+    if group.is_empty() {
+        rows = vec![Row::new(vec![Cell::from("Nothing recorded yet this session")])];
+    }
+
+    let widths = [Constraint::Length(20), Constraint::Min(10)];
+    let table = Table::new(rows, widths)
+        .block(Block::default().borders(Borders::ALL).title("Statistics"))
+        .column_spacing(2);
+    frame.render_widget(table, area);
+}
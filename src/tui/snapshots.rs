@@ -0,0 +1,210 @@
+//! Snapshot tests: every page drawn on a `TestBackend` from fixed stores.
+//!
+//! Each page is rendered for a connected device, a disconnected one and a
+//! device connected but still answering its init reads, and compared with
+//! `testdata/<page>_<state>.txt`. After an intended change in a page,
+//! rewrite the snapshots with `UPDATE_GOLDEN=1 cargo test tui::snapshots`
+//! and review the diff.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+
+use super::{Tab, TuiApp};
+use crate::bluetooth::paired::PAIRED_GROUP;
+use crate::bluetooth::state::CONNECTION_GROUP;
+use crate::bluetooth::stats::STATS_GROUP;
+use crate::device::DEVICE_KEY;
+
+const WIDTH: u16 = 64;
+const HEIGHT: u16 = 22;
+
+type Store = HashMap<String, HashMap<String, String>>;
+
+fn group(values: &[(&str, &str)]) -> HashMap<String, String> {
+    values
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn connected() -> Store {
+    HashMap::from([
+        (
+            CONNECTION_GROUP.into(),
+            group(&[("state", "connected"), (DEVICE_KEY, "AA:BB:CC:DD:EE:FF")]),
+        ),
+        (
+            "info".into(),
+            group(&[
+                ("device_model", "FreeBuds Pro 3"),
+                ("software_ver", "1.0.0.150"),
+                ("serial_number", "ABC123"),
+            ]),
+        ),
+        (
+            "battery".into(),
+            group(&[
+                ("global", "70"),
+                ("left", "80"),
+                ("right", "70"),
+                ("case", "40"),
+                ("is_charging", "false"),
+            ]),
+        ),
+        (
+            "anc".into(),
+            group(&[
+                ("mode", "cancellation"),
+                ("mode_options", "normal,cancellation,awareness"),
+                ("level", "comfort"),
+                ("level_options", "comfort,normal_cancellation,ultra"),
+            ]),
+        ),
+        (
+            "sound".into(),
+            group(&[
+                ("equalizer_preset", "equalizer_preset_hardbass"),
+                (
+                    "equalizer_preset_options",
+                    "equalizer_preset_default,equalizer_preset_hardbass",
+                ),
+                ("quality_preference", "sqp_quality"),
+                ("quality_preference_options", "sqp_connectivity,sqp_quality"),
+            ]),
+        ),
+        (
+            "action".into(),
+            group(&[
+                ("double_tap_left", "tap_action_pause"),
+                ("double_tap_right", "tap_action_next"),
+                (
+                    "double_tap_options",
+                    "tap_action_off,tap_action_pause,tap_action_next",
+                ),
+                ("long_tap_left", "tap_action_switch_anc"),
+                ("long_tap_options", "tap_action_off,tap_action_switch_anc"),
+            ]),
+        ),
+        (
+            "dual_connect".into(),
+            group(&[
+                ("enabled", "true"),
+                (
+                    "devices",
+                    r#"{"11:22:33:44:55:66": {"name": "Laptop", "connected": true, "playing": true},
+                        "77:88:99:AA:BB:CC": {"name": "Phone", "connected": true}}"#,
+                ),
+            ]),
+        ),
+        ("config".into(), group(&[("auto_pause", "true")])),
+        (
+            PAIRED_GROUP.into(),
+            group(&[(
+                "devices",
+                r#"[{"address": "AA:BB:CC:DD:EE:FF", "name": "HUAWEI FreeBuds Pro 3", "connected": true, "rssi": -58, "icon": "audio-headset"},
+                    {"address": "11:22:33:44:55:66", "name": "HUAWEI FreeBuds 5i", "connected": false}]"#,
+            )]),
+        ),
+        (
+            STATS_GROUP.into(),
+            group(&[
+                ("connected_secs", "5400"),
+                ("reconnects", "1"),
+                ("anc_secs:cancellation", "3600"),
+                ("anc_secs:awareness", "1200"),
+            ]),
+        ),
+    ])
+}
+
+fn disconnected() -> Store {
+    HashMap::from([(
+        CONNECTION_GROUP.into(),
+        group(&[("state", "failed"), ("error", "Host is down")]),
+    )])
+}
+
+/// Connected, with only the first answers in.
+fn partial() -> Store {
+    let full = connected();
+    let mut store: Store = ["info", "battery", "anc"]
+        .into_iter()
+        .map(|g| (g.to_string(), full[g].clone()))
+        .collect();
+    store.insert(CONNECTION_GROUP.into(), full[CONNECTION_GROUP].clone());
+    store.get_mut("anc").unwrap().retain(|k, _| k == "mode");
+    store
+}
+
+/// The screen as text, one line per row, trailing blanks trimmed.
+fn render(store: Store, tab: Tab) -> String {
+    let (tx, _rx) = tokio::sync::mpsc::channel(1);
+    let mut app = TuiApp::new(Arc::new(tokio::sync::Mutex::new(store)), tx);
+    app.refresh_props();
+    app.switch_tab(tab);
+    let mut terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap();
+    terminal.draw(|f| app.draw(f)).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    let mut out = String::new();
+    for row in buffer.content().chunks(usize::from(WIDTH)) {
+        let line: String = row.iter().map(|c| c.symbol()).collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn check(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tui/testdata")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        actual == expected,
+        "{} no longer matches {} (UPDATE_GOLDEN=1 to accept)\n\
+         expected:\n{}\nactual:\n{}",
+        name,
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+macro_rules! snapshots {
+    ($($name:ident: $tab:expr),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                for (state, store) in [
+                    ("connected", connected as fn() -> Store),
+                    ("disconnected", disconnected),
+                    ("partial", partial),
+                ] {
+                    let name = format!("{}_{}", stringify!($name), state);
+                    check(&name, &render(store(), $tab));
+                }
+            }
+        )*
+    };
+}
+
+snapshots!(
+    home: Tab::Home,
+    sound: Tab::Sound,
+    gestures: Tab::Gestures,
+    dual_connect: Tab::DualConnect,
+    device_info: Tab::DeviceInfo,
+    settings: Tab::Settings,
+    statistics: Tab::Statistics,
+    devices: Tab::Devices,
+);
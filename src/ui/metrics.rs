@@ -0,0 +1,95 @@
+//! Debug overlay with UI update statistics (toggled with F12).
+//!
+//! Counts updates and view rebuilds per second and how long copying the
+//! property store takes, to keep an eye on what the GUI costs while it
+//! idles in the tray.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use iced::widget::{container, text};
+use iced::{Element, Theme};
+
+use super::Message;
+
+/// Length of the window the per-second rates are computed over.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+pub struct Metrics {
+    pub visible: bool,
+    window_start: Instant,
+    updates: u32,
+    /// Bumped from `view`, which only gets `&self`.
+    views: Cell<u32>,
+    updates_per_sec: f32,
+    views_per_sec: f32,
+    /// Time the last property store copy took.
+    last_copy: Duration,
+    /// Store copies that found nothing new, since startup.
+    unchanged: u64,
+    /// Store copies skipped because the window was in the tray.
+    skipped: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            window_start: Instant::now(),
+            updates: 0,
+            views: Cell::new(0),
+            updates_per_sec: 0.0,
+            views_per_sec: 0.0,
+            last_copy: Duration::ZERO,
+            unchanged: 0,
+            skipped: 0,
+        }
+    }
+
+    pub fn record_update(&mut self) {
+        self.updates += 1;
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= RATE_WINDOW {
+            let secs = elapsed.as_secs_f32();
+            self.updates_per_sec = self.updates as f32 / secs;
+            self.views_per_sec = self.views.get() as f32 / secs;
+            self.updates = 0;
+            self.views.set(0);
+            self.window_start = Instant::now();
+        }
+    }
+
+    pub fn record_view(&self) {
+        self.views.set(self.views.get() + 1);
+    }
+
+    pub fn record_copy(&mut self, took: Duration, changed: bool) {
+        self.last_copy = took;
+        if !changed {
+            self.unchanged += 1;
+        }
+    }
+
+    pub fn record_skip(&mut self) {
+        self.skipped += 1;
+    }
+
+    pub fn overlay(&self) -> Element<'_, Message> {
+        container(
+            text(format!(
+                "{:.1} updates/s  {:.1} views/s  store copy {} \u{b5}s  unchanged {}  skipped {}",
+                self.updates_per_sec,
+                self.views_per_sec,
+                self.last_copy.as_micros(),
+                self.unchanged,
+                self.skipped,
+            ))
+            .size(11)
+            .style(|theme: &Theme| text::Style {
+                color: Some(theme.extended_palette().background.strong.text),
+            }),
+        )
+        .padding([2, 8])
+        .into()
+    }
+}
@@ -1,25 +1,85 @@
+pub mod metrics;
 pub mod pages;
+#[cfg(test)]
+mod testing;
 pub mod theme;
 pub mod widgets;
 
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
-use iced::widget::{button, column, container, horizontal_rule, row, scrollable, text};
-use iced::{Element, Length, Task, Theme};
+use iced::widget::{button, column, container, horizontal_rule, row, scrollable, text, Space};
+use iced::{Alignment, Element, Length, Task, Theme};
 
+use crate::bluetooth::paired::{self, PAIRED_GROUP};
+use crate::bluetooth::state::CONNECTION_GROUP;
+use crate::bluetooth::stats::STATS_GROUP;
+use crate::config::{AncSchedule, AppConfig};
 use crate::device::handler::PropertyStore;
+use crate::device::request::{self, PropertyRequest, PropertySender, SetPropertyError};
+use crate::device::snapshot::SettingsSnapshot;
+use crate::bluetooth::sleep_timer::{Actions, SLEEP_TIMER_PROP};
+use crate::device::{gestures, preset_labels, DEVICE_KEY, PROFILE_KEY};
+use crate::integrations::ambient::{self, Sound};
+use crate::integrations::anc_habits::{self, SUGGESTION_GROUP};
+use crate::labels;
 use crate::tray::TrayFlags;
+use metrics::Metrics;
+use pages::relax::SleepTimer;
+use pages::settings::SleepTimerForm;
+use widgets::battery_indicator::BatteryCards;
+
+/// Hidden in the tray for this long, the battery and ANC shown are likely
+/// stale; they are re-read when the window comes back.
+const STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// Frame interval of the battery card animations (about 30 fps).
+const ANIMATION_FRAME: Duration = Duration::from_millis(33);
+
+/// Compact device status for the window title, e.g. "L82 R79 · ANC".
+/// Bud levels when reported, the single level otherwise; the ANC mode
+/// unless it is off.
+fn title_status(store: &HashMap<String, HashMap<String, String>>) -> Option<String> {
+    let battery = store.get("battery");
+    let level = |key: &str| {
+        battery?
+            .get(key)?
+            .parse::<u8>()
+            .ok()
+            .filter(|&l| l > 0)
+    };
+    let mut parts = Vec::new();
+    let buds: Vec<String> = [("L", "left"), ("R", "right")]
+        .into_iter()
+        .filter_map(|(side, key)| Some(format!("{}{}", side, level(key)?)))
+        .collect();
+    if !buds.is_empty() {
+        parts.push(buds.join(" "));
+    } else if let Some(global) = level("global") {
+        parts.push(format!("{}%", global));
+    }
+    let anc = store.get("anc").and_then(|anc| anc.get("mode"));
+    match anc.map(String::as_str) {
+        None | Some("normal" | "off") => {}
+        Some("cancellation" | "noise_cancelling" | "anc") => parts.push("ANC".to_string()),
+        Some(mode) => parts.push(labels::anc_mode(mode)),
+    }
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
 
 /// Tab pages.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tab {
     Home,
     Sound,
+    Relax,
     Gestures,
     DualConnect,
     DeviceInfo,
     Settings,
+    Statistics,
+    Capabilities,
+    Devices,
 }
 
 impl Tab {
@@ -27,10 +87,14 @@ impl Tab {
         match self {
             Tab::Home => "Home",
             Tab::Sound => "Sound",
+            Tab::Relax => "Relax",
             Tab::Gestures => "Gestures",
             Tab::DualConnect => "Dual Connect",
             Tab::DeviceInfo => "Device Info",
             Tab::Settings => "Settings",
+            Tab::Statistics => "Statistics",
+            Tab::Capabilities => "Capabilities",
+            Tab::Devices => "Devices",
         }
     }
 
@@ -38,10 +102,14 @@ impl Tab {
         &[
             Tab::Home,
             Tab::Sound,
+            Tab::Relax,
             Tab::Gestures,
             Tab::DualConnect,
             Tab::DeviceInfo,
             Tab::Settings,
+            Tab::Statistics,
+            Tab::Capabilities,
+            Tab::Devices,
         ]
     }
 }
@@ -53,19 +121,74 @@ pub enum Message {
     SetAncMode(String),
     SetAncLevel(String),
     SetEqPreset(String),
+    /// Name typed for an unnamed EQ preset (ID, name).
+    PresetLabelChanged(u8, String),
+    SavePresetLabel(u8),
     SetSoundQuality(String),
     SetLowLatency(bool),
     SetAutoPause(bool),
     SetGesture(String, String),
+    /// Mirror the left and right gesture assignments.
+    SwapGestureSides,
     SetDualConnect(bool),
+    SetPreferredDevice(String),
+    UnpairDevice(String),
     // AirPods-specific
+    SetEarDetection(bool),
     SetConversationAwareness(bool),
     SetPersonalizedVolume(bool),
-    /// Property store snapshot received from async task.
-    PropsRefreshed(HashMap<String, HashMap<String, String>>),
+    /// Claim the AirPods back from another Apple device.
+    TakeOverAudio,
+    /// Write one of the AirPods settings (group, prop, value).
+    SetAirPodsControl(&'static str, &'static str, String),
+    ToneVolumeChanged(u8),
+    ToneVolumeReleased,
+    /// Skip the reconnect backoff and try again immediately.
+    RetryNow,
+    /// List the paired devices again for the Devices page.
+    ListDevices,
+    /// Use the paired device with this address from now on.
+    SwitchDevice(String),
+    SleepMinutesSelected(u64),
+    SleepActionsChanged(Actions),
+    StartSleepTimer,
+    CancelSleepTimer,
+    /// Re-read battery and ANC from the device.
+    RefreshNow,
+    /// Save the device's current settings to the snapshot file.
+    SnapshotSettings,
+    /// Replay the snapshot file onto the device.
+    RestoreSettings,
+    /// Outcome of a snapshot or restore, as a status line.
+    SnapshotFinished(String),
+    /// Tray title format edited in the settings page.
+    TrayTitleChanged(String),
+    /// Apply the edited tray title format and write it to the config.
+    SaveTrayTitle,
+    /// Turn the suggestion card into an ANC schedule.
+    AcceptSuggestion,
+    /// Hide the suggestion card for good.
+    DismissSuggestion,
+    RelaxSoundSelected(Sound),
+    RelaxTimerSelected(SleepTimer),
+    RelaxVolumeChanged(u8),
+    /// Start the ambient sound picked on the Relax page.
+    RelaxPlay,
+    RelaxStop,
+    /// Outcome of a property change request.
+    PropertyResult(Result<(), SetPropertyError>),
+    /// Property store snapshot received from async task, and how long
+    /// copying it took.
+    PropsRefreshed(HashMap<String, HashMap<String, String>>, Duration),
+    /// Show or hide the debug metrics overlay (F12).
+    ToggleMetrics,
     /// Window close button was clicked.
     WindowCloseRequested(iced::window::Id),
+    /// The window got keyboard focus.
+    WindowFocused,
     Tick,
+    /// Next frame of the battery card animations.
+    AnimationFrame(Instant),
 }
 
 /// Application state.
@@ -73,9 +196,12 @@ pub struct MyBudsApp {
     current_tab: Tab,
     props: PropertyStore,
     // Cached property snapshots
-    battery: HashMap<String, String>,
+    battery: BatteryCards,
     anc: HashMap<String, String>,
     info: HashMap<String, String>,
+    firmware: HashMap<String, String>,
+    diagnostics: HashMap<String, String>,
+    stats: HashMap<String, String>,
     sound: HashMap<String, String>,
     actions: HashMap<String, String>,
     config: HashMap<String, String>,
@@ -83,14 +209,51 @@ pub struct MyBudsApp {
     // AirPods-specific
     ear_detection: HashMap<String, String>,
     conversation_awareness: HashMap<String, String>,
+    audio_owner: HashMap<String, String>,
     personalized_volume: HashMap<String, String>,
-    connected: bool,
+    /// Settings page AirPods controls, by group.
+    airpods_controls: HashMap<String, HashMap<String, String>>,
+    /// Tone volume while the slider is being dragged; sent on release.
+    tone_volume_drag: Option<u8>,
+    connection: HashMap<String, String>,
+    /// Paired devices for the Devices page (see `bluetooth::paired`).
+    paired: HashMap<String, String>,
+    /// ANC schedule suggestion (see `integrations::anc_habits`).
+    suggestion: HashMap<String, String>,
+    /// Suggestions accepted or dismissed here, hidden until the session
+    /// drops them at its next analysis.
+    handled_suggestions: Vec<String>,
+    /// Ambient sound and sleep timer picked on the Relax page.
+    relax_sound: Sound,
+    relax_timer: SleepTimer,
+    /// Why the ambient sound couldn't start.
+    relax_error: Option<String>,
+    /// Last failed property change, shown until the next success.
+    property_error: Option<String>,
+    /// Result of the last settings snapshot or restore.
+    snapshot_status: Option<String>,
+    /// Tray title format being edited; None without a tray.
+    tray_title: Option<String>,
+    /// Result of the last tray title save.
+    tray_title_status: Option<String>,
+    sleep_form: SleepTimerForm,
+    /// Names being typed for unnamed EQ presets, by preset ID.
+    preset_label_drafts: HashMap<u8, String>,
+    /// Result of the last preset name save.
+    preset_label_status: Option<String>,
     /// Currently open main window
     main_window: iced::window::Id,
+    /// When the window was last sent to the tray.
+    hidden_since: Option<Instant>,
+    /// Last property store snapshot, to skip refreshes that change nothing.
+    last_store: HashMap<String, HashMap<String, String>>,
+    metrics: Metrics,
     /// Channel to send property change requests
-    property_tx: Option<tokio::sync::mpsc::Sender<(String, String, String)>>,
+    property_tx: Option<PropertySender>,
     /// Tray communication flags
     tray_flags: Option<TrayFlags>,
+    /// Show battery and ANC in the window title (`window_title_status`).
+    title_status: bool,
 }
 
 fn window_settings() -> iced::window::Settings {
@@ -110,90 +273,295 @@ fn window_settings() -> iced::window::Settings {
 impl MyBudsApp {
     pub fn new(
         props: PropertyStore,
-        property_tx: Option<tokio::sync::mpsc::Sender<(String, String, String)>>,
+        property_tx: Option<PropertySender>,
         tray_flags: Option<TrayFlags>,
+        title_status: bool,
     ) -> (Self, Task<Message>) {
         // Daemon doesn't open a window — we open one ourselves
         let (id, open_task) = iced::window::open(window_settings());
+        let tray_title = tray_flags
+            .as_ref()
+            .map(|flags| flags.title_format.lock().unwrap().clone());
 
         (
             Self {
                 current_tab: Tab::Home,
                 props,
-                battery: HashMap::new(),
+                battery: BatteryCards::default(),
                 anc: HashMap::new(),
                 info: HashMap::new(),
+                firmware: HashMap::new(),
+                diagnostics: HashMap::new(),
+                stats: HashMap::new(),
                 sound: HashMap::new(),
                 actions: HashMap::new(),
                 config: HashMap::new(),
                 dual_connect: HashMap::new(),
                 ear_detection: HashMap::new(),
                 conversation_awareness: HashMap::new(),
+                audio_owner: HashMap::new(),
                 personalized_volume: HashMap::new(),
-                connected: false,
+                airpods_controls: HashMap::new(),
+                tone_volume_drag: None,
+                connection: HashMap::new(),
+                paired: HashMap::new(),
+                suggestion: HashMap::new(),
+                handled_suggestions: Vec::new(),
+                relax_sound: Sound::Rain,
+                relax_timer: SleepTimer(None),
+                relax_error: None,
+                property_error: None,
+                snapshot_status: None,
+                tray_title,
+                tray_title_status: None,
+                sleep_form: SleepTimerForm::default(),
+                preset_label_drafts: HashMap::new(),
+                preset_label_status: None,
                 main_window: id,
+                hidden_since: None,
+                last_store: HashMap::new(),
+                metrics: Metrics::new(),
                 property_tx,
                 tray_flags,
+                title_status,
             },
             open_task.discard(),
         )
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
+        self.metrics.record_update();
         match message {
             Message::SwitchTab(tab) => {
                 self.current_tab = tab;
+                if tab == Tab::Devices {
+                    return self.update(Message::ListDevices);
+                }
             }
             Message::SetAncMode(mode) => {
-                self.send_property("anc", "mode", &mode);
+                return self.send_property("anc", "mode", &mode);
             }
             Message::SetAncLevel(level) => {
-                self.send_property("anc", "level", &level);
+                return self.send_property("anc", "level", &level);
             }
             Message::SetEqPreset(preset) => {
-                self.send_property("config_eq", "equalizer_preset", &preset);
+                return self.send_property("config_eq", "equalizer_preset", &preset);
             }
             Message::SetSoundQuality(quality) => {
-                self.send_property("config_sound_quality", "quality_preference", &quality);
+                return self.send_property("config_sound_quality", "quality_preference", &quality);
             }
             Message::SetLowLatency(enabled) => {
-                self.send_property("low_latency", "low_latency", if enabled { "true" } else { "false" });
+                return self.send_property("low_latency", "low_latency", if enabled { "true" } else { "false" });
             }
             Message::SetAutoPause(enabled) => {
-                self.send_property("tws_auto_pause", "auto_pause", if enabled { "true" } else { "false" });
+                return self.send_property("tws_auto_pause", "auto_pause", if enabled { "true" } else { "false" });
             }
             Message::SetGesture(prop, value) => {
-                let group = if prop.starts_with("double_tap") {
-                    "gesture_double"
-                } else if prop.starts_with("triple_tap") {
-                    "gesture_triple"
-                } else if prop.starts_with("long_tap") || prop.starts_with("noise_control") {
-                    "gesture_long_split"
-                } else if prop.starts_with("swipe") {
-                    "gesture_swipe"
-                } else {
-                    "action"
-                };
-                self.send_property(group, &prop, &value);
+                // Gesture handlers pick their own properties out of the "action" group
+                return self.send_property("action", &prop, &value);
+            }
+            Message::SwapGestureSides => {
+                let writes = gestures::swap_sides(&self.actions);
+                return Task::batch(
+                    writes
+                        .iter()
+                        .map(|(prop, value)| self.send_property("action", prop, value)),
+                );
             }
             Message::SetDualConnect(enabled) => {
-                self.send_property("dual_connect", "enabled", if enabled { "true" } else { "false" });
+                return self.send_property("dual_connect", "enabled", if enabled { "true" } else { "false" });
+            }
+            Message::SetPreferredDevice(mac) => {
+                return self.send_property("dual_connect", "preferred_device", &mac);
+            }
+            Message::UnpairDevice(mac) => {
+                return self.send_property("dual_connect", &format!("{}:unpair", mac), "true");
+            }
+            Message::SetEarDetection(enabled) => {
+                return self.send_property("ear_detection", "enabled", if enabled { "true" } else { "false" });
             }
             Message::SetConversationAwareness(enabled) => {
-                self.send_property("conversation_awareness", "enabled", if enabled { "true" } else { "false" });
+                return self.send_property("conversation_awareness", "enabled", if enabled { "true" } else { "false" });
             }
             Message::SetPersonalizedVolume(enabled) => {
-                self.send_property("personalized_volume", "enabled", if enabled { "true" } else { "false" });
+                return self.send_property("personalized_volume", "enabled", if enabled { "true" } else { "false" });
+            }
+            Message::TakeOverAudio => {
+                return self.send_property("audio_owner", "take_over", "true");
+            }
+            Message::SetAirPodsControl(group, prop, value) => {
+                return self.send_property(group, prop, &value);
+            }
+            Message::ToneVolumeChanged(level) => {
+                self.tone_volume_drag = Some(level);
+                self.airpods_controls
+                    .entry("tone_volume".into())
+                    .or_default()
+                    .insert("level".into(), level.to_string());
+            }
+            Message::ToneVolumeReleased => {
+                if let Some(level) = self.tone_volume_drag.take() {
+                    return self.send_property("tone_volume", "level", &level.to_string());
+                }
+            }
+            Message::RetryNow => {
+                return self.send_property(CONNECTION_GROUP, "retry_now", "true");
+            }
+            Message::ListDevices => {
+                // No reply wanted: the list shows up in the store, and
+                // nothing answers while no device was found yet
+                if let Some(tx) = &self.property_tx {
+                    let _ = tx.try_send(PropertyRequest::fire_and_forget(
+                        CONNECTION_GROUP,
+                        paired::LIST_PROP,
+                        "true",
+                    ));
+                }
+            }
+            Message::SwitchDevice(address) => {
+                return self.send_property(CONNECTION_GROUP, paired::SWITCH_PROP, &address);
+            }
+            Message::PresetLabelChanged(id, name) => {
+                self.preset_label_drafts.insert(id, name);
+            }
+            Message::SavePresetLabel(id) => {
+                let model = self.connection.get(PROFILE_KEY).cloned();
+                let name = self.preset_label_drafts.remove(&id).unwrap_or_default();
+                if let Some(model) = model {
+                    self.preset_label_status = Some(match preset_labels::save(&model, id, &name) {
+                        Ok(()) => "Saved. If it matches the official app, please share it upstream \
+                                   (see models.toml in the repository)."
+                            .to_string(),
+                        Err(e) => format!("Could not save: {:#}", e),
+                    });
+                }
+            }
+            Message::SleepMinutesSelected(minutes) => self.sleep_form.minutes = minutes,
+            Message::SleepActionsChanged(actions) => self.sleep_form.actions = actions,
+            Message::StartSleepTimer => {
+                let value = self.sleep_form.actions.request(self.sleep_form.minutes);
+                return self.send_property(CONNECTION_GROUP, SLEEP_TIMER_PROP, &value);
+            }
+            Message::CancelSleepTimer => {
+                return self.send_property(CONNECTION_GROUP, SLEEP_TIMER_PROP, "off");
+            }
+            Message::RefreshNow => {
+                return self.send_property(CONNECTION_GROUP, "refresh", "true");
+            }
+            Message::WindowFocused => {
+                return self.refresh_if_stale();
+            }
+            Message::SnapshotSettings => {
+                let props = self.props.clone();
+                return Task::perform(
+                    async move {
+                        let snapshot = SettingsSnapshot::capture(&*props.lock().await);
+                        if snapshot.settings.is_empty() {
+                            return "Nothing to save: the device reported no settings".to_string();
+                        }
+                        match snapshot.save() {
+                            Ok(()) => format!("Saved {} settings", snapshot.settings.len()),
+                            Err(e) => format!("{:#}", e),
+                        }
+                    },
+                    Message::SnapshotFinished,
+                );
+            }
+            Message::RestoreSettings => {
+                let Some(tx) = self.property_tx.clone() else {
+                    return Task::none();
+                };
+                self.snapshot_status = Some("Restoring settings...".into());
+                return Task::perform(
+                    async move {
+                        let snapshot = match SettingsSnapshot::load() {
+                            Ok(s) => s,
+                            Err(e) => return format!("{:#}", e),
+                        };
+                        let report = snapshot.restore(&tx).await;
+                        let mut status = format!(
+                            "Restored {} of {} settings",
+                            report.applied,
+                            snapshot.settings.len()
+                        );
+                        if let Some((prop, reason)) = report.failed.first() {
+                            status.push_str(&format!(" ({}: {})", prop, reason));
+                        }
+                        status
+                    },
+                    Message::SnapshotFinished,
+                );
+            }
+            Message::SnapshotFinished(status) => {
+                self.snapshot_status = Some(status);
+            }
+            Message::TrayTitleChanged(format) => {
+                self.tray_title = Some(format);
+                self.tray_title_status = None;
+            }
+            Message::SaveTrayTitle => {
+                if let (Some(format), Some(flags)) = (&self.tray_title, &self.tray_flags) {
+                    *flags.title_format.lock().unwrap() = format.clone();
+                    self.tray_title_status = Some(match AppConfig::save_tray_title(format) {
+                        Ok(()) => "Saved".to_string(),
+                        Err(e) => format!("{:#}", e),
+                    });
+                }
+            }
+            Message::AcceptSuggestion | Message::DismissSuggestion => {
+                let Some(id) = self.suggestion.get("id").cloned() else {
+                    return Task::none();
+                };
+                let result = if matches!(message, Message::AcceptSuggestion) {
+                    let field = |key: &str| self.suggestion.get(key).cloned().unwrap_or_default();
+                    AppConfig::add_anc_schedule(&AncSchedule {
+                        days: field("days"),
+                        time: field("time"),
+                        mode: field("mode"),
+                    })
+                } else {
+                    anc_habits::dismiss(&id)
+                };
+                match result {
+                    Ok(()) => self.handled_suggestions.push(id),
+                    Err(e) => self.property_error = Some(format!("{:#}", e)),
+                }
+            }
+            Message::RelaxSoundSelected(sound) => {
+                self.relax_sound = sound;
+                // Switch right away when something is already playing
+                if ambient::status().is_some() {
+                    return self.update(Message::RelaxPlay);
+                }
+            }
+            Message::RelaxTimerSelected(timer) => self.relax_timer = timer,
+            Message::RelaxVolumeChanged(volume) => ambient::set_volume(volume),
+            Message::RelaxPlay => {
+                let timer = self.relax_timer.0.map(|m| Duration::from_secs(m * 60));
+                let device = self.connection.get(DEVICE_KEY).map(String::as_str);
+                self.relax_error = ambient::start(self.relax_sound, timer, device)
+                    .err()
+                    .map(|e| format!("{:#}", e));
+            }
+            Message::RelaxStop => ambient::stop(),
+            Message::PropertyResult(result) => {
+                self.property_error = result.err().map(|e| e.to_string());
             }
             Message::WindowCloseRequested(_id) => {
+                // Without a tray (attached to another instance) there is
+                // nothing to reopen the window from
+                if self.tray_flags.is_none() {
+                    return iced::exit();
+                }
                 // Minimize window instead of closing (keeps daemon alive)
                 // Note: This works on GNOME, KDE, Sway but not on Niri (tiling compositors)
+                self.hidden_since = Some(Instant::now());
                 return iced::window::minimize(self.main_window, true);
             }
             Message::Tick => {
                 if let Some(ref flags) = self.tray_flags {
-                    // Check tray quit signal
-                    if flags.quit_app.load(Ordering::Relaxed) {
+                    // Quit from the tray or another instance
+                    if flags.shutdown.is_cancelled() {
                         return iced::exit();
                     }
                     // Check tray show-window signal
@@ -202,39 +570,77 @@ impl MyBudsApp {
                         return Task::batch([
                             iced::window::minimize(self.main_window, false),
                             iced::window::gain_focus(self.main_window),
+                            self.refresh_if_stale(),
                         ]);
                     }
                 }
 
+                // Nothing is on screen while in the tray; the store is
+                // read again when the window comes back
+                if self.hidden_since.is_some() {
+                    self.metrics.record_skip();
+                    return Task::none();
+                }
+
                 // Fetch latest props from the shared store
                 let props = self.props.clone();
                 return Task::perform(
                     async move {
                         let store = props.lock().await;
-                        store.clone()
+                        let start = Instant::now();
+                        let copy = store.clone();
+                        (copy, start.elapsed())
                     },
-                    Message::PropsRefreshed,
+                    |(store, took)| Message::PropsRefreshed(store, took),
                 );
             }
-            Message::PropsRefreshed(store) => {
-                self.battery = store.get("battery").cloned().unwrap_or_default();
+            Message::AnimationFrame(now) => {
+                self.battery.frame(now);
+            }
+            Message::ToggleMetrics => {
+                self.metrics.visible = !self.metrics.visible;
+            }
+            Message::PropsRefreshed(store, took) => {
+                let changed = store != self.last_store;
+                self.metrics.record_copy(took, changed);
+                if !changed {
+                    return Task::none();
+                }
+                self.battery
+                    .update(store.get("battery").unwrap_or(&HashMap::new()));
                 self.anc = store.get("anc").cloned().unwrap_or_default();
                 self.info = store.get("info").cloned().unwrap_or_default();
+                self.firmware = store.get("firmware").cloned().unwrap_or_default();
+                self.diagnostics = store
+                    .get(crate::startup::DIAGNOSTICS_GROUP)
+                    .cloned()
+                    .unwrap_or_default();
+                self.stats = store.get(STATS_GROUP).cloned().unwrap_or_default();
                 self.sound = store.get("sound").cloned().unwrap_or_default();
                 self.actions = store.get("action").cloned().unwrap_or_default();
                 self.config = store.get("config").cloned().unwrap_or_default();
                 self.dual_connect = store.get("dual_connect").cloned().unwrap_or_default();
                 self.ear_detection = store.get("ear_detection").cloned().unwrap_or_default();
                 self.conversation_awareness = store.get("conversation_awareness").cloned().unwrap_or_default();
+                self.audio_owner = store.get("audio_owner").cloned().unwrap_or_default();
                 self.personalized_volume = store.get("personalized_volume").cloned().unwrap_or_default();
-                self.connected = !self.battery.is_empty();
+                self.airpods_controls = pages::settings::AIRPODS_GROUPS
+                    .iter()
+                    .filter_map(|&g| Some((g.to_string(), store.get(g)?.clone())))
+                    .collect();
+                self.connection = store.get(CONNECTION_GROUP).cloned().unwrap_or_default();
+                self.paired = store.get(PAIRED_GROUP).cloned().unwrap_or_default();
+                self.suggestion = store.get(SUGGESTION_GROUP).cloned().unwrap_or_default();
+                self.last_store = store;
             }
         }
         Task::none()
     }
 
     pub fn view(&self, _window_id: iced::window::Id) -> Element<'_, Message> {
-        // Tab bar
+        self.metrics.record_view();
+
+        // Tab bar, wrapping onto a second line when the window is narrow
         let tab_bar = row(
             Tab::all().iter().map(|&tab| {
                 let is_active = tab == self.current_tab;
@@ -250,32 +656,96 @@ impl MyBudsApp {
             }),
         )
         .spacing(4)
-        .padding(8);
+        .padding(8)
+        .wrap();
 
         // Page content
         let page_content: Element<'_, Message> = match self.current_tab {
-            Tab::Home => pages::home::view(
-                &self.battery,
-                &self.anc,
-                &self.info,
-                &self.ear_detection,
-                &self.conversation_awareness,
-                &self.personalized_volume,
-                self.connected,
+            Tab::Home => {
+                let page = pages::home::view(
+                    &self.battery,
+                    &self.anc,
+                    &self.info,
+                    &self.ear_detection,
+                    &self.conversation_awareness,
+                    &self.personalized_volume,
+                    &self.connection,
+                );
+                let suggestion = self
+                    .suggestion
+                    .get("id")
+                    .filter(|id| !self.handled_suggestions.contains(id))
+                    .and(self.suggestion.get("text"));
+                match suggestion {
+                    Some(suggestion) => column![
+                        container(pages::home::suggestion_card(suggestion)).padding([12, 20]),
+                        page,
+                    ]
+                    .into(),
+                    None => page,
+                }
+            }
+            Tab::Sound => pages::sound::view(
+                &self.sound,
+                &self.config,
+                self.connection.get(PROFILE_KEY).map(String::as_str),
+                &self.preset_label_drafts,
+                self.preset_label_status.as_deref(),
+            ),
+            Tab::Relax => pages::relax::view(
+                self.relax_sound,
+                self.relax_timer,
+                self.relax_error.as_deref(),
             ),
-            Tab::Sound => pages::sound::view(&self.sound, &self.config),
             Tab::Gestures => pages::gestures::view(&self.actions),
             Tab::DualConnect => pages::dual_connect::view(&self.dual_connect),
-            Tab::DeviceInfo => pages::device_info::view(&self.info),
-            Tab::Settings => pages::settings::view(&self.config),
+            Tab::DeviceInfo => pages::device_info::view(&self.info, &self.firmware, &self.diagnostics),
+            Tab::Settings => pages::settings::view(
+                &self.config,
+                &self.connection,
+                &self.sleep_form,
+                self.snapshot_status.as_deref(),
+                self.tray_title.as_deref(),
+                self.tray_title_status.as_deref(),
+                &self.airpods_controls,
+            ),
+            Tab::Statistics => pages::statistics::view(&self.stats),
+            Tab::Capabilities => pages::capabilities::view(&self.last_store),
+            Tab::Devices => pages::devices::view(&self.paired, &self.connection),
         };
 
-        let content = column![
-            tab_bar,
-            horizontal_rule(1),
-            scrollable(page_content).height(Length::Fill),
-        ]
-        .spacing(0);
+        let mut content = column![tab_bar, horizontal_rule(1)].spacing(0);
+        if let Some(ref err) = self.property_error {
+            content = content.push(
+                container(
+                    text(format!("Could not apply change: {}", err))
+                        .size(13)
+                        .style(|theme: &Theme| text::Style {
+                            color: Some(theme.palette().danger),
+                        }),
+                )
+                .padding([4, 8]),
+            );
+        }
+        // AirPods taken over by another Apple device
+        if self.audio_owner.get("owned").is_some_and(|o| o == "false") {
+            content = content.push(
+                container(
+                    row![
+                        text("Playing on another device").size(13),
+                        Space::with_width(Length::Fill),
+                        button(text("Use on this computer").size(12))
+                            .on_press(Message::TakeOverAudio),
+                    ]
+                    .align_y(Alignment::Center),
+                )
+                .padding([4, 8]),
+            );
+        }
+        let mut content = content.push(scrollable(page_content).height(Length::Fill));
+        if self.metrics.visible {
+            content = content.push(self.metrics.overlay());
+        }
 
         container(content)
             .width(Length::Fill)
@@ -283,20 +753,263 @@ impl MyBudsApp {
             .into()
     }
 
+    /// Window title, with the battery and ANC status in front when enabled
+    /// and connected. Re-read by iced after every update.
+    pub fn title(&self, _window_id: iced::window::Id) -> String {
+        let connected = self.connection.get("state").is_some_and(|s| s == "connected");
+        let status = (self.title_status && connected)
+            .then(|| title_status(&self.last_store))
+            .flatten();
+        match status {
+            Some(status) => format!("{} - MyBuds", status),
+            None => "MyBuds".to_string(),
+        }
+    }
+
     pub fn theme(&self, _window_id: iced::window::Id) -> Theme {
         theme::app_theme()
     }
 
     pub fn subscription(&self) -> iced::Subscription<Message> {
+        // Frames only while the battery cards are on screen and moving
+        let animation = if self.hidden_since.is_none()
+            && self.current_tab == Tab::Home
+            && self.battery.is_animating()
+        {
+            iced::time::every(ANIMATION_FRAME).map(Message::AnimationFrame)
+        } else {
+            iced::Subscription::none()
+        };
         iced::Subscription::batch([
+            animation,
             iced::time::every(std::time::Duration::from_secs(1)).map(|_| Message::Tick),
             iced::window::close_requests().map(Message::WindowCloseRequested),
+            iced::event::listen_with(|event, _status, _id| match event {
+                iced::Event::Window(iced::window::Event::Focused) => Some(Message::WindowFocused),
+                _ => None,
+            }),
+            iced::keyboard::on_key_press(|key, _modifiers| match key {
+                iced::keyboard::Key::Named(iced::keyboard::key::Named::F12) => {
+                    Some(Message::ToggleMetrics)
+                }
+                _ => None,
+            }),
+        ])
+    }
+
+    /// Re-read battery and ANC if the window was in the tray long enough
+    /// for them to be out of date.
+    fn refresh_if_stale(&mut self) -> Task<Message> {
+        match self.hidden_since.take() {
+            Some(since) if since.elapsed() >= STALE_AFTER => {
+                self.send_property(CONNECTION_GROUP, "refresh", "true")
+            }
+            _ => Task::none(),
+        }
+    }
+
+    fn send_property(&self, group: &str, prop: &str, value: &str) -> Task<Message> {
+        let Some(tx) = self.property_tx.clone() else {
+            return Task::none();
+        };
+        let (group, prop, value) = (group.to_string(), prop.to_string(), value.to_string());
+        Task::perform(
+            async move { request::request(&tx, &group, &prop, &value).await },
+            Message::PropertyResult,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::testing::Screen;
+    use super::*;
+
+    type Store = HashMap<String, HashMap<String, String>>;
+
+    fn group(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// The app after its first property refresh.
+    fn app(store: Store) -> MyBudsApp {
+        let props: PropertyStore = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+        let (mut app, _) = MyBudsApp::new(props, None, None, true);
+        let _ = app.update(Message::PropsRefreshed(store, Duration::ZERO));
+        app
+    }
+
+    fn screen(app: &MyBudsApp) -> Screen<'_> {
+        Screen::new(app.view(app.main_window))
+    }
+
+    fn freebuds() -> Store {
+        HashMap::from([
+            (
+                CONNECTION_GROUP.into(),
+                group(&[("state", "connected"), (PROFILE_KEY, "FreeBuds Pro 3")]),
+            ),
+            ("info".into(), group(&[("device_name", "HUAWEI FreeBuds Pro 3")])),
+            (
+                "battery".into(),
+                group(&[("left", "80"), ("right", "70"), ("case", "40")]),
+            ),
+            (
+                "anc".into(),
+                group(&[
+                    ("mode", "cancellation"),
+                    ("mode_options", "normal,cancellation,awareness"),
+                ]),
+            ),
+            (
+                "action".into(),
+                group(&[
+                    ("double_tap_left", "play_pause"),
+                    ("double_tap_right", "next"),
+                    ("double_tap_options", "off,play_pause,next"),
+                ]),
+            ),
         ])
     }
 
-    fn send_property(&self, group: &str, prop: &str, value: &str) {
-        if let Some(ref tx) = self.property_tx {
-            let _ = tx.try_send((group.to_string(), prop.to_string(), value.to_string()));
+    #[test]
+    fn disconnected_home_explains_what_to_do() {
+        let app = app(HashMap::from([(
+            CONNECTION_GROUP.into(),
+            group(&[("state", "searching")]),
+        )]));
+        let screen = screen(&app);
+        assert!(screen.has("Waiting for device..."));
+        assert!(screen.has("Pair your headphones via Bluetooth settings."));
+        assert!(!screen.has("Noise Control"));
+    }
+
+    #[test]
+    fn home_controls_send_their_messages() {
+        let app = app(freebuds());
+        let mut screen = screen(&app);
+        assert!(screen.has("HUAWEI FreeBuds Pro 3"));
+        assert!(screen.has("Noise Control"));
+        // Sections for capabilities the device lacks stay hidden
+        assert!(!screen.has("Ear Detection"));
+
+        let messages = screen.click("Awareness");
+        assert!(
+            matches!(messages.as_slice(), [Message::SetAncMode(m)] if m == "awareness"),
+            "{:?}",
+            messages
+        );
+        assert!(matches!(
+            screen.click("Refresh").as_slice(),
+            [Message::RefreshNow]
+        ));
+    }
+
+    #[test]
+    fn tabs_show_the_sections_the_device_reports() {
+        let mut app = app(freebuds());
+        let messages = screen(&app).click("Gestures");
+        let [Message::SwitchTab(tab)] = messages.as_slice() else {
+            panic!("unexpected {:?}", messages);
+        };
+        let _ = app.update(Message::SwitchTab(*tab));
+
+        let screen = screen(&app);
+        assert!(screen.has("Gesture Settings"));
+        assert!(screen.has("Double Tap"));
+        assert!(!screen.has("Triple Tap"));
+        assert!(!screen.has("Swipe Gesture"));
+    }
+
+    #[test]
+    fn every_tab_renders_in_every_state() {
+        let disconnected = HashMap::from([(
+            CONNECTION_GROUP.into(),
+            group(&[("state", "reconnecting")]),
+        )]);
+        // Connected, before the device answered anything
+        let partial = HashMap::from([(
+            CONNECTION_GROUP.into(),
+            group(&[("state", "connected")]),
+        )]);
+        for store in [disconnected, partial, freebuds()] {
+            let mut app = app(store);
+            for &tab in Tab::all() {
+                let _ = app.update(Message::SwitchTab(tab));
+                assert!(screen(&app).has(tab.label()), "{:?}", tab);
+            }
         }
     }
+
+    #[test]
+    fn airpods_sections_follow_their_groups() {
+        let mut store = freebuds();
+        store.insert(
+            "ear_detection".into(),
+            group(&[("primary", "in_ear"), ("secondary", "in_case"), ("enabled", "true")]),
+        );
+        store.insert("audio_owner".into(), group(&[("owned", "false")]));
+        let app = app(store);
+        let mut screen = screen(&app);
+        assert!(screen.has("Ear Detection"));
+        assert!(screen.has("L: In Ear"));
+        assert!(screen.has("Automatic Ear Detection"));
+        assert!(matches!(
+            screen.click("Use on this computer").as_slice(),
+            [Message::TakeOverAudio]
+        ));
+    }
+
+    #[test]
+    fn devices_page_switches_to_another_headset() {
+        let mut store = freebuds();
+        store
+            .get_mut(CONNECTION_GROUP)
+            .unwrap()
+            .insert(DEVICE_KEY.into(), "AA:AA:AA:AA:AA:AA".into());
+        store.insert(
+            PAIRED_GROUP.into(),
+            group(&[(
+                "devices",
+                r#"[{"address": "AA:AA:AA:AA:AA:AA", "name": "HUAWEI FreeBuds Pro 3", "connected": true},
+                    {"address": "BB:BB:BB:BB:BB:BB", "name": "HUAWEI FreeBuds 5i", "connected": false}]"#,
+            )]),
+        );
+        let mut app = app(store);
+        let _ = app.update(Message::SwitchTab(Tab::Devices));
+
+        let mut screen = screen(&app);
+        assert!(screen.has("HUAWEI FreeBuds 5i"));
+        assert!(screen.has("In use"));
+        let messages = screen.click("Use");
+        assert!(
+            matches!(messages.as_slice(), [Message::SwitchDevice(a)] if a == "BB:BB:BB:BB:BB:BB"),
+            "{:?}",
+            messages
+        );
+    }
+
+    #[test]
+    fn window_title_shows_battery_and_anc() {
+        let window = iced::window::Id::unique();
+        let title = app(freebuds()).title(window);
+        assert!(title.ends_with(" · ANC - MyBuds"), "{}", title);
+        assert_eq!(app(HashMap::new()).title(window), "MyBuds");
+
+        let store = HashMap::from([
+            ("battery".into(), group(&[("left", "82"), ("right", "79"), ("global", "79")])),
+            ("anc".into(), group(&[("mode", "cancellation")])),
+        ]);
+        assert_eq!(title_status(&store).as_deref(), Some("L82 R79 · ANC"));
+        let neckband = HashMap::from([
+            ("battery".into(), group(&[("global", "55")])),
+            ("anc".into(), group(&[("mode", "normal")])),
+        ]);
+        assert_eq!(title_status(&neckband).as_deref(), Some("55%"));
+    }
 }
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use iced::widget::{column, container, row, text};
+use iced::{Color, Element, Length};
+
+use crate::device::capabilities::{self, Support};
+use crate::device::PROFILE_KEY;
+use crate::ui::Message;
+
+fn color(support: Support) -> Color {
+    match support {
+        Support::Supported => Color::from_rgb(0.2, 0.6, 0.3),
+        Support::Unsupported => Color::from_rgb(0.6, 0.6, 0.6),
+        Support::Unknown => Color::from_rgb(0.8, 0.6, 0.2),
+    }
+}
+
+pub fn view(store: &HashMap<String, HashMap<String, String>>) -> Element<'_, Message> {
+    let mut content = column![text("Capabilities").size(18)].spacing(8);
+
+    let rows = capabilities::matrix(store);
+    if rows.is_empty() {
+        content = content.push(text("Connect the device to see what it supports").size(14));
+        return container(content).padding(20).width(Length::Fill).into();
+    }
+
+    let profile = store
+        .get(crate::bluetooth::state::CONNECTION_GROUP)
+        .and_then(|c| c.get(PROFILE_KEY));
+    if let Some(profile) = profile {
+        content = content.push(
+            text(format!(
+                "Profile: {}. Include this page when reporting a missing feature.",
+                profile
+            ))
+            .size(12),
+        );
+    }
+
+    for row_data in rows {
+        content = content.push(
+            row![
+                text(row_data.feature).size(14).width(Length::Fixed(200.0)),
+                text(row_data.support.label())
+                    .size(14)
+                    .color(color(row_data.support))
+                    .width(Length::Fixed(110.0)),
+                text(row_data.reason)
+                    .size(12)
+                    .color(Color::from_rgb(0.5, 0.5, 0.5)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+        );
+    }
+
+    container(content).padding(20).width(Length::Fill).into()
+}
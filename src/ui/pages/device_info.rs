@@ -3,9 +3,14 @@ use std::collections::HashMap;
 use iced::widget::{column, container, row, text};
 use iced::{Element, Length};
 
+use crate::device::models::database;
 use crate::ui::Message;
 
-pub fn view(info: &HashMap<String, String>) -> Element<'_, Message> {
+pub fn view<'a>(
+    info: &'a HashMap<String, String>,
+    firmware: &'a HashMap<String, String>,
+    diagnostics: &'a HashMap<String, String>,
+) -> Element<'a, Message> {
     let mut content = column![text("Device Info").size(18)].spacing(8);
 
     let fields = [
@@ -20,7 +25,11 @@ pub fn view(info: &HashMap<String, String>) -> Element<'_, Message> {
 
     for (key, label) in &fields {
         if let Some(value) = info.get(*key) {
-            let value = value.clone();
+            let name = (*key == "device_model").then(|| database::model_name(value)).flatten();
+            let value = match name {
+                Some(name) => format!("{} ({})", value, name),
+                None => value.clone(),
+            };
             content = content.push(
                 row![
                     text(format!("{}:", label))
@@ -50,5 +59,107 @@ pub fn view(info: &HashMap<String, String>) -> Element<'_, Message> {
         }
     }
 
+    if let Some(section) = firmware_section(firmware) {
+        content = content.push(section);
+    }
+
+    if let Some(section) = startup_section(diagnostics) {
+        content = content.push(section);
+    }
+
+    if let Some(section) = framing_section(diagnostics) {
+        content = content.push(section);
+    }
+
     container(content).padding(20).width(Length::Fill).into()
 }
+
+/// Result of the firmware update check, if one has run.
+fn firmware_section(firmware: &HashMap<String, String>) -> Option<Element<'_, Message>> {
+    if firmware.is_empty() {
+        return None;
+    }
+
+    let mut section = column![text("Firmware Update").size(16)].spacing(6);
+
+    if let Some(err) = firmware.get("error") {
+        section = section.push(text(format!("Update check failed: {}", err)).size(13));
+        return Some(section.into());
+    }
+
+    if let Some(latest) = firmware.get("latest_version") {
+        section = section.push(
+            row![
+                text("Latest Version:").size(14).width(Length::Fixed(150.0)),
+                text(latest.clone()).size(14),
+            ]
+            .spacing(8),
+        );
+    }
+
+    let status = if firmware.get("update_available").is_some_and(|v| v == "true") {
+        "Update available — install it from the Huawei AI Life app"
+    } else {
+        "Firmware is up to date"
+    };
+    section = section.push(text(status).size(13));
+
+    if let Some(changelog) = firmware.get("changelog") {
+        section = section.push(text(changelog.clone()).size(12));
+    }
+
+    Some(section.into())
+}
+
+/// Startup latency measured this session.
+fn startup_section(diagnostics: &HashMap<String, String>) -> Option<Element<'_, Message>> {
+    let fields = [
+        ("time_to_connected_ms", "Connected After"),
+        ("time_to_first_battery_ms", "First Battery After"),
+    ];
+    if !fields.iter().any(|(key, _)| diagnostics.contains_key(*key)) {
+        return None;
+    }
+
+    let mut section = column![text("Startup").size(16)].spacing(6);
+    for (key, label) in fields {
+        if let Some(ms) = diagnostics.get(key) {
+            section = section.push(
+                row![
+                    text(format!("{}:", label)).size(14).width(Length::Fixed(150.0)),
+                    text(format!("{} ms", ms)).size(14),
+                ]
+                .spacing(8),
+            );
+        }
+    }
+    Some(section.into())
+}
+
+/// AirPods messages that arrived joined, split or garbled (shown only
+/// when something went wrong).
+fn framing_section(diagnostics: &HashMap<String, String>) -> Option<Element<'_, Message>> {
+    let fields = [
+        ("aap_concatenated", "Joined"),
+        ("aap_reassembled", "Reassembled"),
+        ("aap_truncated", "Truncated"),
+        ("aap_skipped_bytes", "Skipped Bytes"),
+    ];
+    if !fields.iter().any(|(key, _)| diagnostics.contains_key(*key)) {
+        return None;
+    }
+
+    let mut section = column![text("Message Framing").size(16)].spacing(6);
+    for (key, label) in fields {
+        if let Some(count) = diagnostics.get(key) {
+            section = section.push(
+                row![
+                    text(format!("{}:", label)).size(14).width(Length::Fixed(150.0)),
+                    text(count.as_str()).size(14),
+                ]
+                .spacing(8),
+            );
+        }
+    }
+    Some(section.into())
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use iced::widget::{button, column, container, row, text, Space};
+use iced::{Alignment, Color, Element, Length, Theme};
+
+use crate::bluetooth::paired;
+use crate::device::DEVICE_KEY;
+use crate::ui::Message;
+
+/// Supported paired devices, with the one in use marked and a button to
+/// switch to each of the others.
+pub fn view<'a>(
+    paired_group: &'a HashMap<String, String>,
+    connection: &'a HashMap<String, String>,
+) -> Element<'a, Message> {
+    let mut content = column![row![
+        text("Devices").size(18),
+        Space::with_width(Length::Fill),
+        button(text("Refresh").size(12))
+            .on_press(Message::ListDevices)
+            .style(button::secondary),
+    ]
+    .align_y(Alignment::Center)]
+    .spacing(8);
+
+    if let Some(error) = paired::error(Some(paired_group)) {
+        content = content.push(text(error).size(13).style(|theme: &Theme| text::Style {
+            color: Some(theme.palette().danger),
+        }));
+    }
+
+    let devices = paired::from_group(Some(paired_group));
+    if devices.is_empty() {
+        let message = if paired_group.is_empty() {
+            "Looking for paired devices..."
+        } else {
+            "No supported device is paired"
+        };
+        content = content.push(text(message).size(14));
+        return container(content).padding(20).width(Length::Fill).into();
+    }
+
+    content = content.push(
+        text("The device picked here is used from now on, also after a restart.")
+            .size(12)
+            .color(Color::from_rgb(0.5, 0.5, 0.5)),
+    );
+
+    let in_use = connection.get(DEVICE_KEY);
+    for device in devices {
+        let status = if device.connected {
+            "Connected"
+        } else {
+            "Not connected"
+        };
+        let mut details = vec![device.address.clone()];
+        details.extend(device.kind().map(String::from));
+        details.push(status.to_string());
+        details.extend(device.signal());
+        let action: Element<'_, Message> = if in_use == Some(&device.address) {
+            text("In use").size(13).into()
+        } else {
+            button(text("Use").size(13))
+                .on_press(Message::SwitchDevice(device.address.clone()))
+                .into()
+        };
+        content = content.push(
+            row![
+                column![
+                    text(device.name).size(14),
+                    text(details.join(" · "))
+                        .size(12)
+                        .color(Color::from_rgb(0.5, 0.5, 0.5)),
+                ]
+                .spacing(2),
+                Space::with_width(Length::Fill),
+                action,
+            ]
+            .spacing(8)
+            .align_y(Alignment::Center),
+        );
+    }
+
+    container(content).padding(20).width(Length::Fill).into()
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::ffi::CStr;
 
-use iced::widget::{column, container, horizontal_rule, row, text, toggler, Space};
+use iced::widget::{button, column, container, horizontal_rule, radio, row, text, toggler, Space};
 use iced::{Border, Element, Length, Theme};
 use serde_json::Value;
 
@@ -23,6 +23,7 @@ fn get_hostname() -> String {
 
 #[derive(Debug)]
 struct Device {
+    mac: String,
     name: String,
     connected: bool,
     playing: bool,
@@ -40,6 +41,7 @@ fn parse_devices(json_str: &str) -> Vec<Device> {
         .into_iter()
         .map(|(mac, obj)| {
             let device = Device {
+                mac: mac.clone(),
                 name: obj
                     .get("name")
                     .and_then(|v| v.as_str())
@@ -74,7 +76,14 @@ fn parse_devices(json_str: &str) -> Vec<Device> {
     devices.into_iter().map(|(_, device)| device).collect()
 }
 
-fn device_card(name: String, connected: bool, playing: bool, auto_connect: bool, is_this_pc: bool) -> Element<'static, Message> {
+fn device_card(
+    device: Device,
+    connected: bool,
+    is_this_pc: bool,
+    index: usize,
+    preferred_index: Option<usize>,
+) -> Element<'static, Message> {
+    let Device { mac, name, playing, auto_connect, .. } = device;
     let status_icon = if connected { "●" } else { "○" };
     let status_color = if connected {
         iced::Color::from_rgb(0.0, 0.8, 0.0) // Green
@@ -130,7 +139,22 @@ fn device_card(name: String, connected: bool, playing: bool, auto_connect: bool,
         details = details.push(text("Auto-connect").size(12).color(iced::Color::from_rgb(0.5, 0.5, 0.5)));
     }
 
-    let card_content = column![name_row, details].spacing(6);
+    let preferred_mac = mac.clone();
+    let preferred = radio("Preferred", index, preferred_index, move |_| {
+        Message::SetPreferredDevice(preferred_mac.clone())
+    })
+    .size(14)
+    .text_size(12);
+
+    // Unpairing makes the buds forget the device
+    let unpair = row![
+        Space::with_width(Length::Fill),
+        button(text("Unpair").size(12))
+            .on_press(Message::UnpairDevice(mac))
+            .style(button::danger),
+    ];
+
+    let card_content = column![name_row, details, preferred, unpair].spacing(6);
 
     container(card_content)
         .padding(12)
@@ -171,7 +195,7 @@ pub fn view(dc: &HashMap<String, String>) -> Element<'_, Message> {
     content = content.push(
         row![
             text("Dual Connect").size(14),
-            toggler(enabled).on_toggle(|v| Message::SetDualConnect(v)),
+            toggler(enabled).on_toggle(Message::SetDualConnect),
         ]
         .spacing(12),
     );
@@ -191,7 +215,9 @@ pub fn view(dc: &HashMap<String, String>) -> Element<'_, Message> {
             );
         } else {
             let hostname = get_hostname();
-            for device in devices {
+            let preferred_mac = dc.get("preferred_device").cloned().unwrap_or_default();
+            let preferred_index = devices.iter().position(|d| d.mac == preferred_mac);
+            for (index, device) in devices.into_iter().enumerate() {
                 let is_this_pc = !hostname.is_empty() &&
                     device.name.to_lowercase().contains(&hostname.to_lowercase());
 
@@ -199,11 +225,11 @@ pub fn view(dc: &HashMap<String, String>) -> Element<'_, Message> {
                 let connected = device.connected || is_this_pc;
 
                 content = content.push(device_card(
-                    device.name,
+                    device,
                     connected,
-                    device.playing,
-                    device.auto_connect,
                     is_this_pc,
+                    index,
+                    preferred_index,
                 ));
             }
         }
@@ -1,12 +1,29 @@
 use std::collections::HashMap;
 
-use iced::widget::{column, container, horizontal_rule, pick_list, row, text};
-use iced::{Element, Length};
+use iced::widget::{
+    button, column, container, horizontal_rule, pick_list, row, text, Column, Space,
+};
+use iced::{Alignment, Color, Element, Length};
 
+use crate::device::gestures::{self, Conflict};
+use crate::labels;
 use crate::ui::Message;
 
 pub fn view(actions: &HashMap<String, String>) -> Element<'_, Message> {
-    let mut content = column![text("Gesture Settings").size(18)].spacing(12);
+    if gestures::both_buds(actions) {
+        return both_buds_view(actions);
+    }
+
+    // Only offered when there is something to mirror
+    let mut swap = button(text("Swap sides").size(12)).style(button::secondary);
+    if !gestures::swap_sides(actions).is_empty() {
+        swap = swap.on_press(Message::SwapGestureSides);
+    }
+    let header = row![text("Gesture Settings").size(18), Space::with_width(Length::Fill), swap]
+        .align_y(Alignment::Center);
+    let mut content = column![header].spacing(12);
+    let conflicts = gestures::conflicts(actions);
+    content = push_hints(content, &conflicts, "");
 
     // Double tap
     let dt_options = parse_options(actions.get("double_tap_options"));
@@ -24,6 +41,7 @@ pub fn view(actions: &HashMap<String, String>) -> Element<'_, Message> {
             dt_options,
             "double_tap_right",
         ));
+        content = push_hints(content, &conflicts, "double_tap");
         content = content.push(horizontal_rule(1));
     }
 
@@ -43,6 +61,7 @@ pub fn view(actions: &HashMap<String, String>) -> Element<'_, Message> {
             tt_options,
             "triple_tap_right",
         ));
+        content = push_hints(content, &conflicts, "triple_tap");
         content = content.push(horizontal_rule(1));
     }
 
@@ -65,6 +84,7 @@ pub fn view(actions: &HashMap<String, String>) -> Element<'_, Message> {
                 "long_tap_right",
             ));
         }
+        content = push_hints(content, &conflicts, "long_tap");
         content = content.push(horizontal_rule(1));
     }
 
@@ -86,6 +106,7 @@ pub fn view(actions: &HashMap<String, String>) -> Element<'_, Message> {
                 "noise_control_right",
             ));
         }
+        content = push_hints(content, &conflicts, "noise_control");
         content = content.push(horizontal_rule(1));
     }
 
@@ -104,14 +125,84 @@ pub fn view(actions: &HashMap<String, String>) -> Element<'_, Message> {
     container(content).padding(20).width(Length::Fill).into()
 }
 
+/// Layout for buds set once for both sides (FreeClip): no sides to pick or
+/// swap, and the in-call action next to the tap it belongs to.
+fn both_buds_view(actions: &HashMap<String, String>) -> Element<'_, Message> {
+    let mut content = column![
+        text("Gesture Settings").size(18),
+        text("The buds fit either ear and swap sides on their own, so each gesture applies to both.")
+            .size(12)
+            .color(Color::from_rgb(0.5, 0.5, 0.5)),
+    ]
+    .spacing(12);
+    let conflicts = gestures::conflicts(actions);
+    content = push_hints(content, &conflicts, "");
+
+    for (prefix, title, in_call) in [
+        ("double_tap", "Double Tap", "double_tap_in_call"),
+        ("triple_tap", "Triple Tap", "triple_tap_in_call"),
+    ] {
+        let options = parse_options(actions.get(&format!("{}_options", prefix)));
+        let current = actions.get(prefix);
+        if options.is_empty() && current.is_none() {
+            continue;
+        }
+        content = content.push(text(title).size(16));
+        if options.is_empty() {
+            // The buds didn't list their actions: show, don't offer
+            let current = current.map(|s| labels::gesture(s)).unwrap_or_default();
+            content = content.push(
+                row![
+                    text("Both buds:").size(14).width(Length::Fixed(80.0)),
+                    text(current).size(14),
+                ]
+                .spacing(8),
+            );
+        } else {
+            content = content.push(gesture_row("Both buds:", current.cloned(), options, prefix));
+        }
+        let call_options = parse_options(actions.get(&format!("{}_options", in_call)));
+        if !call_options.is_empty() {
+            content = content.push(gesture_row(
+                "In a call:",
+                actions.get(in_call).cloned(),
+                call_options,
+                in_call,
+            ));
+        }
+        content = push_hints(content, &conflicts, prefix);
+        content = content.push(horizontal_rule(1));
+    }
+
+    container(content).padding(20).width(Length::Fill).into()
+}
+
+/// Add the hints about `gesture` (see `gestures::conflicts`) below it.
+fn push_hints<'a>(
+    content: Column<'a, Message>,
+    conflicts: &[Conflict],
+    gesture: &str,
+) -> Column<'a, Message> {
+    conflicts
+        .iter()
+        .filter(|c| c.gesture == gesture)
+        .fold(content, |content, c| {
+            content.push(
+                text(format!("\u{26a0} {}", c.hint))
+                    .size(12)
+                    .color(Color::from_rgb(0.8, 0.6, 0.2)),
+            )
+        })
+}
+
 fn gesture_row<'a>(
     label: &'a str,
     current: Option<String>,
     options: Vec<String>,
     prop_name: &'static str,
 ) -> Element<'a, Message> {
-    let display_options: Vec<String> = options.iter().map(|s| gesture_display_name(s)).collect();
-    let current_display = current.as_ref().map(|s| gesture_display_name(s));
+    let display_options: Vec<String> = options.iter().map(|s| labels::gesture(s)).collect();
+    let current_display = current.as_ref().map(|s| labels::gesture(s));
 
     let options_clone = options.clone();
     let display_clone = display_options.clone();
@@ -136,20 +227,3 @@ fn parse_options(raw: Option<&String>) -> Vec<String> {
         .unwrap_or_default()
 }
 
-fn gesture_display_name(name: &str) -> String {
-    match name {
-        "tap_action_off" => "Disabled".into(),
-        "tap_action_pause" => "Play/Pause".into(),
-        "tap_action_next" => "Next Track".into(),
-        "tap_action_prev" => "Previous Track".into(),
-        "tap_action_assistant" => "Voice Assistant".into(),
-        "tap_action_answer" => "Answer Call".into(),
-        "tap_action_switch_anc" => "Switch ANC".into(),
-        "tap_action_change_volume" => "Volume Control".into(),
-        "noise_control_off_on" => "Off / NC".into(),
-        "noise_control_off_on_aw" => "Off / NC / Awareness".into(),
-        "noise_control_on_aw" => "NC / Awareness".into(),
-        "noise_control_off_aw" => "Off / Awareness".into(),
-        other => other.replace('_', " "),
-    }
-}
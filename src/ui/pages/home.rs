@@ -1,37 +1,64 @@
 use std::collections::HashMap;
 
-use iced::widget::{column, container, horizontal_rule, row, text, toggler, Space};
+use iced::widget::{button, column, container, horizontal_rule, row, text, toggler, Space};
 use iced::{Alignment, Element, Length};
 
-use crate::ui::widgets::anc_selector::{anc_level_selector, anc_mode_selector};
-use crate::ui::widgets::battery_indicator::battery_display;
+use crate::bluetooth::state::ConnectionState;
+use crate::integrations::single_bud::SINGLE_BUD_KEY;
+use crate::ui::widgets::anc_selector::{anc_depth_label, anc_level_selector, anc_mode_selector};
+use crate::ui::widgets::battery_indicator::{battery_display, BatteryCards};
 use crate::ui::Message;
 
 pub fn view<'a>(
-    battery: &'a HashMap<String, String>,
+    battery: &'a BatteryCards,
     anc: &'a HashMap<String, String>,
     info: &'a HashMap<String, String>,
     ear_detection: &'a HashMap<String, String>,
     conversation_awareness: &'a HashMap<String, String>,
     personalized_volume: &'a HashMap<String, String>,
-    connected: bool,
+    connection: &'a HashMap<String, String>,
 ) -> Element<'a, Message> {
-    if !connected {
-        return container(
-            column![
-                Space::with_height(40),
-                text("MyBuds").size(24),
-                Space::with_height(8),
-                text("No device connected")
-                    .size(16)
-                    .color(iced::Color::from_rgb(0.5, 0.5, 0.5)),
-                Space::with_height(8),
+    let conn_state = ConnectionState::from_group(Some(connection));
+    if conn_state != ConnectionState::Connected {
+        let mut status = column![
+            Space::with_height(40),
+            text("MyBuds").size(24),
+            Space::with_height(8),
+            text(conn_state.label())
+                .size(16)
+                .color(iced::Color::from_rgb(0.5, 0.5, 0.5)),
+        ]
+        .align_x(Alignment::Center);
+        if let Some(err) = connection.get("error") {
+            status = status.push(
+                text(err.as_str())
+                    .size(12)
+                    .color(iced::Color::from_rgb(0.8, 0.3, 0.3)),
+            );
+        }
+        if let Some(left) = ConnectionState::retry_in(Some(connection)) {
+            status = status
+                .push(Space::with_height(8))
+                .push(
+                    text(format!("Reconnecting in {}s", left.as_secs()))
+                        .size(13)
+                        .color(iced::Color::from_rgb(0.6, 0.6, 0.6)),
+                )
+                .push(button(text("Retry now").size(13)).on_press(Message::RetryNow));
+        }
+        if conn_state == ConnectionState::Asleep {
+            status = status
+                .push(Space::with_height(8))
+                .push(button(text("Reconnect").size(13)).on_press(Message::RetryNow));
+        }
+        if matches!(conn_state, ConnectionState::Searching | ConnectionState::Disconnected) {
+            status = status.push(Space::with_height(8)).push(
                 text("Pair your headphones via Bluetooth settings.")
                     .size(13)
                     .color(iced::Color::from_rgb(0.6, 0.6, 0.6)),
-            ]
-            .align_x(Alignment::Center),
-        )
+            );
+        }
+        return container(status)
         .padding(40)
         .center_x(Length::Fill)
         .into();
@@ -60,20 +87,36 @@ pub fn view<'a>(
         );
     }
 
+    if connection.get("read_only").is_some_and(|v| v == "true") {
+        header = header.push(
+            text("Read-only mode: changes aren't sent to the device")
+                .size(12)
+                .color(iced::Color::from_rgb(0.8, 0.6, 0.2)),
+        );
+    }
+
     let header_section = container(header).center_x(Length::Fill).padding(16);
 
     // Battery
-    let left = battery.get("left").and_then(|s| s.parse().ok());
-    let right = battery.get("right").and_then(|s| s.parse().ok());
-    let case = battery.get("case").and_then(|s| s.parse().ok());
-    let global = battery.get("global").and_then(|s| s.parse().ok());
-    let is_charging = battery.get("is_charging").map_or(false, |s| s == "true");
-
-    let battery_section = column![
-        section_title("Battery"),
-        battery_display(left, right, case, global, is_charging),
+    let mut battery_section = column![
+        row![
+            section_title("Battery"),
+            Space::with_width(Length::Fill),
+            button(text("Refresh").size(12))
+                .on_press(Message::RefreshNow)
+                .style(button::secondary),
+        ]
+        .align_y(Alignment::Center),
+        battery_display(battery),
     ]
     .spacing(8);
+    if let Some(side) = connection.get(SINGLE_BUD_KEY) {
+        battery_section = battery_section.push(
+            text(format!("Single-bud mode: {} bud in use, audio in mono", side))
+                .size(12)
+                .color(iced::Color::from_rgb(0.18, 0.55, 0.85)),
+        );
+    }
 
     // ANC
     let anc_mode = anc.get("mode").cloned();
@@ -102,19 +145,30 @@ pub fn view<'a>(
             .cloned()
             .unwrap_or_else(|| "unknown".into());
 
-        content = content.push(divider());
-        content = content.push(
-            column![
-                section_title("Ear Detection"),
-                row![
-                    text(format!("L: {}", ear_label(&primary))).size(14),
-                    Space::with_width(20),
-                    text(format!("R: {}", ear_label(&secondary))).size(14),
-                ]
-                .spacing(8),
+        let mut section = column![
+            section_title("Ear Detection"),
+            row![
+                text(format!("L: {}", ear_label(&primary))).size(14),
+                Space::with_width(20),
+                text(format!("R: {}", ear_label(&secondary))).size(14),
             ]
             .spacing(8),
-        );
+        ]
+        .spacing(8);
+
+        // Only shown once the device has reported its config
+        if let Some(enabled) = ear_detection.get("enabled") {
+            section = section.push(
+                row![
+                    text("Automatic Ear Detection").size(14),
+                    toggler(enabled == "true").on_toggle(Message::SetEarDetection),
+                ]
+                .spacing(12),
+            );
+        }
+
+        content = content.push(divider());
+        content = content.push(section);
     }
 
     // ANC modes
@@ -123,16 +177,26 @@ pub fn view<'a>(
         content = content.push(anc_mode_selector(
             anc_mode.as_deref(),
             &anc_options,
-            |mode| Message::SetAncMode(mode),
+            Message::SetAncMode,
         ));
 
         if !anc_level_options.is_empty() {
             content = content.push(anc_level_selector(
                 anc_level.as_deref(),
                 &anc_level_options,
-                |level| Message::SetAncLevel(level),
+                Message::SetAncLevel,
             ));
         }
+
+        // Models reporting a continuous depth show it below the levels
+        let anc_depth = anc.get("depth").and_then(|s| s.parse().ok());
+        let anc_depth_max = anc
+            .get("depth_max")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(100);
+        if let Some(depth) = anc_depth {
+            content = content.push(anc_depth_label(depth, anc_depth_max));
+        }
     }
 
     // Conversational Awareness (AirPods)
@@ -146,7 +210,7 @@ pub fn view<'a>(
         content = content.push(
             row![
                 text("Conversational Awareness").size(14),
-                toggler(ca_enabled).on_toggle(|v| Message::SetConversationAwareness(v)),
+                toggler(ca_enabled).on_toggle(Message::SetConversationAwareness),
             ]
             .spacing(12),
         );
@@ -162,7 +226,7 @@ pub fn view<'a>(
         content = content.push(
             row![
                 text("Personalized Volume").size(14),
-                toggler(pv_enabled).on_toggle(|v| Message::SetPersonalizedVolume(v)),
+                toggler(pv_enabled).on_toggle(Message::SetPersonalizedVolume),
             ]
             .spacing(12),
         );
@@ -171,6 +235,29 @@ pub fn view<'a>(
     content.into()
 }
 
+/// Dismissible card offering to turn an ANC habit into a schedule, shown
+/// above the page.
+pub fn suggestion_card(suggestion: &str) -> Element<'_, Message> {
+    container(
+        column![
+            text(suggestion).size(13),
+            row![
+                Space::with_width(Length::Fill),
+                button(text("Dismiss").size(12))
+                    .on_press(Message::DismissSuggestion)
+                    .style(button::secondary),
+                button(text("Create schedule").size(12)).on_press(Message::AcceptSuggestion),
+            ]
+            .spacing(8),
+        ]
+        .spacing(8),
+    )
+    .padding(12)
+    .width(Length::Fill)
+    .style(container::rounded_box)
+    .into()
+}
+
 fn section_title<'a, M: 'a>(title: &'a str) -> Element<'a, M> {
     text(title)
         .size(14)
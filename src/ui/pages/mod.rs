@@ -1,6 +1,10 @@
+pub mod capabilities;
 pub mod device_info;
+pub mod devices;
 pub mod dual_connect;
 pub mod gestures;
 pub mod home;
+pub mod relax;
 pub mod settings;
 pub mod sound;
+pub mod statistics;
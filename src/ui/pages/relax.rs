@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use iced::widget::{button, column, container, pick_list, radio, row, slider, text};
+use iced::{Element, Length};
+
+use crate::integrations::ambient::{self, Sound, SLEEP_TIMERS};
+use crate::ui::Message;
+
+/// Sleep timer choice, in minutes (None = play until stopped).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepTimer(pub Option<u64>);
+
+impl SleepTimer {
+    fn options() -> Vec<SleepTimer> {
+        std::iter::once(SleepTimer(None))
+            .chain(SLEEP_TIMERS.iter().map(|&m| SleepTimer(Some(m))))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for SleepTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(minutes) => write!(f, "{} min", minutes),
+            None => f.write_str("Off"),
+        }
+    }
+}
+
+pub fn view<'a>(sound: Sound, timer: SleepTimer, error: Option<&'a str>) -> Element<'a, Message> {
+    let mut content = column![
+        text("Relax").size(18),
+        text("Ambient sound played to the buds, for focus or falling asleep.").size(13),
+    ]
+    .spacing(12);
+
+    let mut sounds = column![].spacing(6);
+    for option in Sound::ALL {
+        sounds = sounds.push(
+            radio(option.label(), option, Some(sound), Message::RelaxSoundSelected)
+                .size(14)
+                .text_size(14),
+        );
+    }
+    content = content.push(sounds);
+
+    content = content.push(
+        row![
+            text("Sleep Timer").size(14).width(Length::Fixed(120.0)),
+            pick_list(SleepTimer::options(), Some(timer), Message::RelaxTimerSelected),
+        ]
+        .spacing(12)
+        .align_y(iced::Alignment::Center),
+    );
+    content = content.push(
+        row![
+            text("Volume").size(14).width(Length::Fixed(120.0)),
+            slider(0..=100, ambient::volume(), Message::RelaxVolumeChanged)
+                .width(Length::Fixed(160.0)),
+            text(format!("{}%", ambient::volume())).size(13),
+        ]
+        .spacing(12)
+        .align_y(iced::Alignment::Center),
+    );
+
+    let status = ambient::status();
+    let action = match status {
+        Some(_) => button(text("Stop").size(14)).on_press(Message::RelaxStop),
+        None => button(text("Play").size(14)).on_press(Message::RelaxPlay),
+    };
+    content = content.push(action);
+    if let Some((playing, left)) = status {
+        content = content.push(text(playing_line(playing, left)).size(13));
+    }
+    if let Some(error) = error {
+        content = content.push(
+            text(error)
+                .size(13)
+                .color(iced::Color::from_rgb(0.8, 0.2, 0.2)),
+        );
+    }
+
+    container(content).padding(20).width(Length::Fill).into()
+}
+
+fn playing_line(sound: Sound, left: Option<Duration>) -> String {
+    match left {
+        Some(left) => format!(
+            "Playing {}, stops in {} min",
+            sound.label().to_lowercase(),
+            left.as_secs().div_ceil(60)
+        ),
+        None => format!("Playing {}", sound.label().to_lowercase()),
+    }
+}
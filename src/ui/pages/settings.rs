@@ -1,11 +1,201 @@
 use std::collections::HashMap;
 
-use iced::widget::{column, container, row, text, toggler};
+use iced::widget::{
+    button, column, container, pick_list, row, slider, text, text_input, toggler,
+};
 use iced::{Element, Length};
 
+use crate::bluetooth::sleep_timer::{self, Actions, MINUTES};
+use crate::bluetooth::state::ConnectionState;
+use crate::labels;
 use crate::ui::Message;
 
-pub fn view(config: &HashMap<String, String>) -> Element<'_, Message> {
+/// Property groups of the AirPods section.
+pub const AIRPODS_GROUPS: &[&str] = &["press_speed", "press_duration", "volume_swipe", "tone_volume"];
+
+/// Pick list for a `<prop>` / `<prop>_options` pair, showing display names.
+fn choice<'a>(
+    label: &'a str,
+    values: &'a HashMap<String, String>,
+    group: &'static str,
+    prop: &'static str,
+) -> Option<Element<'a, Message>> {
+    let options: Vec<String> = values
+        .get(&format!("{}_options", prop))?
+        .split(',')
+        .map(String::from)
+        .collect();
+    let names: Vec<String> = options.iter().map(|o| labels::humanize(o)).collect();
+    let current = values.get(prop).map(|v| labels::humanize(v));
+    Some(
+        row![
+            text(label).size(14).width(Length::Fixed(200.0)),
+            pick_list(names, current, move |selected: String| {
+                let value = options
+                    .iter()
+                    .find(|o| labels::humanize(o) == selected)
+                    .cloned()
+                    .unwrap_or(selected);
+                Message::SetAirPodsControl(group, prop, value)
+            })
+            .width(Length::Fixed(160.0)),
+        ]
+        .spacing(12)
+        .into(),
+    )
+}
+
+/// Stem and tone settings of AirPods models that report them.
+fn airpods_section<'a>(
+    controls: &'a HashMap<String, HashMap<String, String>>,
+) -> Option<Element<'a, Message>> {
+    if controls.is_empty() {
+        return None;
+    }
+    let mut section = column![text("AirPods").size(16)].spacing(8);
+
+    if let Some(values) = controls.get("press_speed") {
+        section = section.push_maybe(choice("Press speed", values, "press_speed", "speed"));
+    }
+    if let Some(values) = controls.get("press_duration") {
+        section = section.push_maybe(choice(
+            "Press and hold duration",
+            values,
+            "press_duration",
+            "duration",
+        ));
+    }
+    if let Some(values) = controls.get("volume_swipe") {
+        if let Some(enabled) = values.get("enabled") {
+            section = section.push(
+                row![
+                    text("Volume swipe").size(14).width(Length::Fixed(200.0)),
+                    toggler(enabled == "true").on_toggle(|on| {
+                        Message::SetAirPodsControl("volume_swipe", "enabled", on.to_string())
+                    }),
+                ]
+                .spacing(12),
+            );
+        }
+        section = section.push_maybe(choice(
+            "Volume swipe length",
+            values,
+            "volume_swipe",
+            "interval",
+        ));
+    }
+    if let Some(level) = controls
+        .get("tone_volume")
+        .and_then(|v| v.get("level"))
+        .and_then(|l| l.parse::<u8>().ok())
+    {
+        section = section.push(
+            row![
+                text(format!("Tone volume {}%", level))
+                    .size(14)
+                    .width(Length::Fixed(200.0)),
+                slider(15..=100, level, Message::ToneVolumeChanged)
+                    .on_release(Message::ToneVolumeReleased)
+                    .width(Length::Fixed(160.0)),
+            ]
+            .spacing(12),
+        );
+    }
+    Some(section.into())
+}
+
+/// Sleep timer choices not sent yet.
+#[derive(Debug, Clone, Copy)]
+pub struct SleepTimerForm {
+    pub minutes: u64,
+    pub actions: Actions,
+}
+
+impl Default for SleepTimerForm {
+    fn default() -> Self {
+        Self {
+            minutes: 30,
+            actions: Actions::default(),
+        }
+    }
+}
+
+/// Minutes in the duration pick list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Minutes(pub u64);
+
+impl std::fmt::Display for Minutes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} min", self.0)
+    }
+}
+
+/// Start the sleep timer, or show the running one with a way to cancel it.
+fn sleep_timer_section<'a>(
+    connection: &'a HashMap<String, String>,
+    form: &SleepTimerForm,
+) -> Element<'a, Message> {
+    let mut section = column![
+        text("Sleep timer").size(16),
+        text("Pause playback after a while, e.g. when falling asleep").size(12),
+    ]
+    .spacing(6);
+    if let Some(left) = sleep_timer::remaining(Some(connection)) {
+        return section
+            .push(
+                row![
+                    text(format!("Stops in {} min", left.as_secs().div_ceil(60))).size(13),
+                    button(text("Cancel").size(13))
+                        .on_press(Message::CancelSleepTimer)
+                        .style(button::secondary),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            )
+            .into();
+    }
+    let actions = form.actions;
+    section = section
+        .push(
+            row![
+                pick_list(
+                    MINUTES.iter().copied().map(Minutes).collect::<Vec<_>>(),
+                    Some(Minutes(form.minutes)),
+                    |m| Message::SleepMinutesSelected(m.0),
+                ),
+                button(text("Start").size(13)).on_press(Message::StartSleepTimer),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+        )
+        .push(
+            row![
+                text("Also switch ANC off").size(13),
+                toggler(actions.anc_off)
+                    .on_toggle(move |anc_off| Message::SleepActionsChanged(Actions { anc_off, ..actions })),
+            ]
+            .spacing(12),
+        )
+        .push(
+            row![
+                text("Also disconnect the buds").size(13),
+                toggler(actions.disconnect)
+                    .on_toggle(move |disconnect| Message::SleepActionsChanged(Actions { disconnect, ..actions })),
+            ]
+            .spacing(12),
+        );
+    section.into()
+}
+
+pub fn view<'a>(
+    config: &'a HashMap<String, String>,
+    connection: &'a HashMap<String, String>,
+    sleep_form: &SleepTimerForm,
+    snapshot_status: Option<&'a str>,
+    tray_title: Option<&'a str>,
+    tray_title_status: Option<&'a str>,
+    airpods_controls: &'a HashMap<String, HashMap<String, String>>,
+) -> Element<'a, Message> {
     let mut content = column![text("Settings").size(18)].spacing(12);
 
     // Auto-pause
@@ -17,17 +207,68 @@ pub fn view(config: &HashMap<String, String>) -> Element<'_, Message> {
     content = content.push(
         row![
             text("Auto-pause on ear removal").size(14),
-            toggler(auto_pause).on_toggle(|v| Message::SetAutoPause(v)),
+            toggler(auto_pause).on_toggle(Message::SetAutoPause),
         ]
         .spacing(12),
     );
 
+    content = content.push_maybe(airpods_section(airpods_controls));
+
+    if ConnectionState::from_group(Some(connection)) == ConnectionState::Connected {
+        content = content.push(sleep_timer_section(connection, sleep_form));
+    }
+
+    // Settings snapshot
+    let mut backup = column![
+        text("Settings snapshot").size(16),
+        text("Save the current settings before experimenting, restore them afterwards").size(12),
+        row![
+            button(text("Save snapshot").size(13)).on_press(Message::SnapshotSettings),
+            button(text("Restore").size(13))
+                .on_press(Message::RestoreSettings)
+                .style(button::secondary),
+        ]
+        .spacing(8),
+    ]
+    .spacing(6);
+    if let Some(status) = snapshot_status {
+        backup = backup.push(text(status).size(12));
+    }
+    content = content.push(backup);
+
+    // Tray title, only when running with a tray
+    if let Some(format) = tray_title {
+        let mut tray = column![
+            text("Tray title").size(16),
+            text("Placeholders: {name} {left} {right} {case} {global} {anc} {audio}").size(12),
+            row![
+                text_input(crate::config::DEFAULT_TRAY_TITLE, format)
+                    .on_input(Message::TrayTitleChanged)
+                    .on_submit(Message::SaveTrayTitle)
+                    .size(13)
+                    .width(Length::Fixed(300.0)),
+                button(text("Save").size(13)).on_press(Message::SaveTrayTitle),
+            ]
+            .spacing(8),
+        ]
+        .spacing(6);
+        if let Some(status) = tray_title_status {
+            tray = tray.push(text(status).size(12));
+        }
+        content = content.push(tray);
+    }
+
+    let log = match crate::logging::log_file() {
+        Some(path) => format!("Log file: {}", path.display()),
+        None => "Logging to the terminal".to_string(),
+    };
     content = content.push(
         column![
             text("About").size(16),
             text("MyBuds v0.1.0").size(12),
             text("Manages Huawei FreeBuds and Apple AirPods headphones").size(12),
             text("Based on the OpenFreebuds project").size(12),
+            text(log).size(12),
         ]
         .spacing(4),
     );
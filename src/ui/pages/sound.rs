@@ -1,13 +1,66 @@
 use std::collections::HashMap;
 
-use iced::widget::{column, container, horizontal_rule, pick_list, row, text, toggler};
+use iced::widget::{
+    button, column, container, horizontal_rule, pick_list, row, text, text_input, toggler,
+};
 use iced::{Element, Length};
 
+use crate::device::preset_labels;
+use crate::labels;
 use crate::ui::Message;
 
+/// Name fields for the presets the device reports without a known name.
+fn unknown_presets<'a>(
+    options: &[String],
+    model: Option<&'a str>,
+    drafts: &'a HashMap<u8, String>,
+    status: Option<&'a str>,
+) -> Option<Element<'a, Message>> {
+    let model = model?;
+    let ids: Vec<u8> = options
+        .iter()
+        .filter_map(|key| preset_labels::unknown_id(key))
+        .collect();
+    if ids.is_empty() {
+        return None;
+    }
+    let mut section = column![
+        text("Unnamed presets").size(14),
+        text(format!(
+            "{} reports presets MyBuds has no name for. Try them, then name them after the official app.",
+            model
+        ))
+        .size(12),
+    ]
+    .spacing(6);
+    for id in ids {
+        let draft = drafts.get(&id).map(String::as_str).unwrap_or_default();
+        section = section.push(
+            row![
+                text(format!("Preset {}", id)).size(13).width(Length::Fixed(80.0)),
+                text_input("Name", draft)
+                    .on_input(move |name| Message::PresetLabelChanged(id, name))
+                    .on_submit(Message::SavePresetLabel(id))
+                    .size(13)
+                    .width(Length::Fixed(200.0)),
+                button(text("Save").size(13)).on_press(Message::SavePresetLabel(id)),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+        );
+    }
+    if let Some(status) = status {
+        section = section.push(text(status).size(12));
+    }
+    Some(section.into())
+}
+
 pub fn view<'a>(
     sound: &'a HashMap<String, String>,
     config: &'a HashMap<String, String>,
+    model: Option<&'a str>,
+    label_drafts: &'a HashMap<u8, String>,
+    label_status: Option<&'a str>,
 ) -> Element<'a, Message> {
     let mut content = column![text("Sound Settings").size(18)].spacing(12);
 
@@ -19,8 +72,11 @@ pub fn view<'a>(
         .unwrap_or_default();
 
     if !eq_options.is_empty() {
-        let eq_labels: Vec<String> = eq_options.iter().map(|s| eq_display_name(s)).collect();
-        let current_label = current_eq.as_ref().map(|s| eq_display_name(s));
+        let eq_labels: Vec<String> = eq_options
+            .iter()
+            .map(|s| preset_labels::display(model, s))
+            .collect();
+        let current_label = current_eq.as_ref().map(|s| preset_labels::display(model, s));
         let eq_labels_clone = eq_labels.clone();
         let eq_options_clone = eq_options.clone();
 
@@ -38,6 +94,7 @@ pub fn view<'a>(
             ]
             .spacing(4),
         );
+        content = content.push_maybe(unknown_presets(&eq_options, model, label_drafts, label_status));
     }
 
     content = content.push(horizontal_rule(1));
@@ -52,17 +109,9 @@ pub fn view<'a>(
     if !quality_options.is_empty() {
         let quality_labels: Vec<String> = quality_options
             .iter()
-            .map(|s| match s.as_str() {
-                "sqp_connectivity" => "Connectivity Priority".to_string(),
-                "sqp_quality" => "Sound Quality Priority".to_string(),
-                other => other.to_string(),
-            })
+            .map(|s| labels::sound_quality(s))
             .collect();
-        let current_quality_label = quality.as_ref().map(|s| match s.as_str() {
-            "sqp_connectivity" => "Connectivity Priority".to_string(),
-            "sqp_quality" => "Sound Quality Priority".to_string(),
-            other => other.to_string(),
-        });
+        let current_quality_label = quality.as_ref().map(|s| labels::sound_quality(s));
 
         content = content.push(
             column![
@@ -90,7 +139,7 @@ pub fn view<'a>(
     content = content.push(
         row![
             text("Low Latency Mode").size(14),
-            toggler(low_latency).on_toggle(|v| Message::SetLowLatency(v)),
+            toggler(low_latency).on_toggle(Message::SetLowLatency),
         ]
         .spacing(12),
     );
@@ -98,12 +147,3 @@ pub fn view<'a>(
     container(content).padding(20).width(Length::Fill).into()
 }
 
-fn eq_display_name(key: &str) -> String {
-    match key {
-        "equalizer_preset_default" => "Default".to_string(),
-        "equalizer_preset_hardbass" => "Bass Boost".to_string(),
-        "equalizer_preset_treble" => "Treble Boost".to_string(),
-        "equalizer_preset_voices" | "equalizer_preset_voice" => "Voice".to_string(),
-        other => other.replace("equalizer_preset_", "").replace('_', " "),
-    }
-}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use iced::widget::{column, container, progress_bar, row, text};
+use iced::{Element, Length};
+
+use crate::bluetooth::stats;
+use crate::labels;
+use crate::ui::Message;
+
+pub fn view(group: &HashMap<String, String>) -> Element<'_, Message> {
+    let mut content = column![text("Statistics").size(18)].spacing(8);
+
+    if group.is_empty() {
+        content = content.push(text("Nothing recorded yet this session").size(14));
+        return container(content).padding(20).width(Length::Fill).into();
+    }
+
+    for (label, value) in stats::summary(group) {
+        content = content.push(
+            row![
+                text(format!("{}:", label)).size(14).width(Length::Fixed(150.0)),
+                text(value).size(14),
+            ]
+            .spacing(8),
+        );
+    }
+
+    let shares = stats::anc_distribution(group);
+    if !shares.is_empty() {
+        let mut section = column![text("ANC Modes").size(16)].spacing(6);
+        for (mode, share) in shares {
+            let secs = group
+                .get(&format!("{}{}", stats::ANC_TIME_PREFIX, mode))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            section = section.push(
+                row![
+                    text(labels::anc_mode(&mode)).size(14).width(Length::Fixed(150.0)),
+                    progress_bar(0.0..=1.0, share as f32).height(12).width(Length::Fixed(160.0)),
+                    text(format!("{:.0}%  {}", share * 100.0, stats::format_duration(secs))).size(13),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        content = content.push(section);
+    }
+
+    container(content).padding(20).width(Length::Fill).into()
+}
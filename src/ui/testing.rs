@@ -0,0 +1,142 @@
+//! Headless screen for GUI tests.
+//!
+//! Lays a view out with the software renderer, without opening a window,
+//! so tests can read the text on screen and click widgets by their label
+//! to get the messages a user would trigger.
+
+use iced::advanced::graphics::text::Paragraph;
+use iced::advanced::widget::{text, tree, Tree};
+use iced::advanced::{clipboard, layout, mouse, Layout, Shell};
+use iced::{Element, Event, Font, Pixels, Point, Rectangle, Renderer, Size};
+
+use super::Message;
+
+/// Tall enough for every page to fit without scrolling.
+const VIEWPORT: Size = Size::new(480.0, 4000.0);
+
+pub struct Screen<'a> {
+    element: Element<'a, Message>,
+    tree: Tree,
+    node: layout::Node,
+    renderer: Renderer,
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(element: Element<'a, Message>) -> Self {
+        let renderer =
+            Renderer::Secondary(iced_tiny_skia::Renderer::new(Font::default(), Pixels(16.0)));
+        let mut tree = Tree::new(&element);
+        let limits = layout::Limits::new(Size::ZERO, VIEWPORT);
+        let node = element.as_widget().layout(&mut tree, &renderer, &limits);
+        Self {
+            element,
+            tree,
+            node,
+            renderer,
+        }
+    }
+
+    /// Every text on screen with where it is, in layout order.
+    fn texts_with_bounds(&self) -> Vec<(String, Rectangle)> {
+        let mut out = Vec::new();
+        let matched = collect(&self.tree, Layout::new(&self.node), &mut out);
+        assert!(matched, "the widget tree doesn't match its layout");
+        out
+    }
+
+    /// Every text on screen, in layout order.
+    pub fn texts(&self) -> Vec<String> {
+        self.texts_with_bounds()
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect()
+    }
+
+    /// Whether some text on screen reads `label`.
+    pub fn has(&self, label: &str) -> bool {
+        self.texts().iter().any(|t| t == label)
+    }
+
+    /// Click the first widget labelled `label`, returning the messages
+    /// the click produced.
+    ///
+    /// # Panics
+    /// When no text on screen reads `label`.
+    pub fn click(&mut self, label: &str) -> Vec<Message> {
+        let Some((_, bounds)) = self
+            .texts_with_bounds()
+            .into_iter()
+            .find(|(t, _)| t == label)
+        else {
+            panic!("{:?} is not on screen: {:?}", label, self.texts());
+        };
+        let cursor = mouse::Cursor::Available(bounds.center());
+        let mut messages = Vec::new();
+        for event in [
+            mouse::Event::CursorMoved {
+                position: bounds.center(),
+            },
+            mouse::Event::ButtonPressed(mouse::Button::Left),
+            mouse::Event::ButtonReleased(mouse::Button::Left),
+        ] {
+            let mut shell = Shell::new(&mut messages);
+            self.element.as_widget_mut().on_event(
+                &mut self.tree,
+                Event::Mouse(event),
+                Layout::new(&self.node),
+                cursor,
+                &self.renderer,
+                &mut clipboard::Null,
+                &mut shell,
+                &Rectangle::new(Point::ORIGIN, VIEWPORT),
+            );
+        }
+        messages
+    }
+}
+
+fn label(tree: &Tree) -> Option<String> {
+    // Text, and widgets with a label of their own (togglers, radios)
+    if tree.tag != tree::Tag::of::<text::State<Paragraph>>() {
+        return None;
+    }
+    let state = tree.state.downcast_ref::<text::State<Paragraph>>();
+    let lines: Vec<&str> = state
+        .0
+        .raw()
+        .buffer()
+        .lines
+        .iter()
+        .map(|l| l.text())
+        .collect();
+    Some(lines.join("\n")).filter(|t| !t.is_empty())
+}
+
+/// Walk the widget tree along its layout, collecting texts.
+///
+/// A container has no node of its own in the widget tree but adds a level
+/// to the layout, so when the children don't line up the node is tried
+/// against its only layout child. Leaves may lay out parts of their own.
+fn collect(tree: &Tree, layout: Layout<'_>, out: &mut Vec<(String, Rectangle)>) -> bool {
+    if tree.children.is_empty() {
+        out.extend(label(tree).map(|t| (t, layout.bounds())));
+        return true;
+    }
+    let layouts: Vec<Layout<'_>> = layout.children().collect();
+    if layouts.len() == tree.children.len() {
+        let mut found = Vec::new();
+        let matched = tree
+            .children
+            .iter()
+            .zip(layouts.iter())
+            .all(|(child, layout)| collect(child, *layout, &mut found));
+        if matched {
+            out.extend(found);
+            return true;
+        }
+    }
+    match layouts.as_slice() {
+        [content] => collect(tree, *content, out),
+        _ => false,
+    }
+}
@@ -1,25 +1,7 @@
 use iced::widget::{button, column, container, row, text};
 use iced::{Element, Length};
 
-fn anc_mode_label(mode: &str) -> String {
-    match mode {
-        "normal" => "Off".into(),
-        "cancellation" => "Noise Cancelling".into(),
-        "awareness" => "Awareness".into(),
-        other => other.to_string(),
-    }
-}
-
-fn anc_level_label(level: &str) -> String {
-    match level {
-        "comfort" => "Comfort".into(),
-        "normal" => "Normal".into(),
-        "ultra" => "Ultra".into(),
-        "dynamic" => "Dynamic".into(),
-        "voice_boost" => "Voice Boost".into(),
-        other => other.to_string(),
-    }
-}
+use crate::labels;
 
 /// Render ANC mode as styled toggle buttons.
 pub fn anc_mode_selector<'a, M: Clone + 'a>(
@@ -34,7 +16,7 @@ pub fn anc_mode_selector<'a, M: Clone + 'a>(
     let mut buttons: Vec<Element<'a, M>> = Vec::new();
 
     for opt in options.iter() {
-        let label = anc_mode_label(opt);
+        let label = labels::anc_mode(opt);
         let is_active = current_mode == Some(opt.as_str());
         let opt_clone = opt.clone();
 
@@ -76,7 +58,7 @@ pub fn anc_level_selector<'a, M: Clone + 'a>(
     let mut buttons: Vec<Element<'a, M>> = Vec::new();
 
     for opt in options.iter() {
-        let label = anc_level_label(opt);
+        let label = labels::anc_level(opt);
         let is_active = current_level == Some(opt.as_str());
         let opt_clone = opt.clone();
 
@@ -104,3 +86,12 @@ pub fn anc_level_selector<'a, M: Clone + 'a>(
         .spacing(6)
         .into()
 }
+
+/// Render the reported ANC depth. Read-only: the write command isn't mapped.
+pub fn anc_depth_label<'a, M: 'a>(depth: u8, max: u8) -> Element<'a, M> {
+    let percent = u32::from(depth) * 100 / u32::from(max.max(1));
+    text(format!("Depth: {}%", percent))
+        .size(14)
+        .color(iced::Color::from_rgb(0.4, 0.4, 0.4))
+        .into()
+}
@@ -1,6 +1,117 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+
 use iced::widget::{column, container, row, text};
 use iced::{Alignment, Element, Length};
 
+/// Battery slots with a card, in display order.
+const SLOTS: [&str; 4] = ["left", "right", "case", "global"];
+/// How fast a bar closes the gap to a new level (share of the gap per
+/// second, roughly).
+const FILL_RATE: f32 = 6.0;
+/// A bar this close to its level snaps to it and stops animating.
+const FILL_EPSILON: f32 = 0.002;
+/// One breath of the charging pulse.
+const PULSE_PERIOD: Duration = Duration::from_secs(2);
+/// Frame gaps longer than this (a stalled window) count as this long, so
+/// bars glide instead of jumping.
+const MAX_FRAME_GAP: Duration = Duration::from_millis(100);
+
+/// Levels shown on the battery cards and the state of their animations:
+/// bars slide to new levels and charging cards pulse.
+///
+/// Driven by `frame()` only while `is_animating()`, which the app asks for
+/// frames only while its window is on screen.
+#[derive(Debug, Default)]
+pub struct BatteryCards {
+    levels: HashMap<&'static str, u8>,
+    charging: HashMap<&'static str, bool>,
+    /// Bar fill currently drawn, by slot (0.0 to 1.0).
+    fills: HashMap<&'static str, f32>,
+    /// Position in the pulse cycle (0.0 to 1.0).
+    pulse: f32,
+    last_frame: Option<Instant>,
+}
+
+impl BatteryCards {
+    /// Take new levels from a battery group. Bars start from empty the first
+    /// time a slot shows up.
+    pub fn update(&mut self, battery: &HashMap<String, String>) {
+        let level = |key: &str| battery.get(key)?.parse::<u8>().ok();
+        self.levels = SLOTS
+            .into_iter()
+            .filter_map(|slot| Some((slot, level(slot)?.min(100))))
+            .collect();
+        self.charging = SLOTS
+            .into_iter()
+            .map(|slot| {
+                let key = match slot {
+                    "global" => "is_charging".to_string(),
+                    _ => format!("{}_charging", slot),
+                };
+                (slot, battery.get(&key).is_some_and(|v| v == "true"))
+            })
+            .collect();
+        self.fills.retain(|slot, _| self.levels.contains_key(slot));
+    }
+
+    fn level(&self, slot: &str) -> Option<u8> {
+        self.levels.get(slot).copied()
+    }
+
+    fn is_charging(&self, slot: &str) -> bool {
+        self.charging.get(slot).copied().unwrap_or(false)
+    }
+
+    fn target(level: u8) -> f32 {
+        f32::from(level) / 100.0
+    }
+
+    /// Whether a bar is still moving or a card pulses.
+    pub fn is_animating(&self) -> bool {
+        self.levels.iter().any(|(slot, &level)| {
+            let fill = self.fills.get(slot).copied().unwrap_or(0.0);
+            (fill - Self::target(level)).abs() > FILL_EPSILON || self.is_charging(slot)
+        })
+    }
+
+    /// Advance the animations to `now`.
+    pub fn frame(&mut self, now: Instant) {
+        let dt = self
+            .last_frame
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last))
+            .min(MAX_FRAME_GAP)
+            .as_secs_f32();
+        self.last_frame = Some(now);
+
+        let step = (dt * FILL_RATE).min(1.0);
+        for (&slot, &level) in &self.levels {
+            let target = Self::target(level);
+            let fill = self.fills.entry(slot).or_insert(0.0);
+            *fill += (target - *fill) * step;
+            if (target - *fill).abs() <= FILL_EPSILON {
+                *fill = target;
+            }
+        }
+        self.pulse = (self.pulse + dt / PULSE_PERIOD.as_secs_f32()).fract();
+        if !self.is_animating() {
+            // Next animation starts without a gap to catch up on
+            self.last_frame = None;
+        }
+    }
+
+    /// Bar fill for `slot`.
+    fn fill(&self, slot: &str) -> f32 {
+        self.fills.get(slot).copied().unwrap_or(0.0)
+    }
+
+    /// Opacity of a charging bar: a slow swell between 55% and 100%.
+    fn pulse_alpha(&self) -> f32 {
+        0.775 + 0.225 * (self.pulse * TAU).cos()
+    }
+}
+
 /// Color for battery level.
 fn battery_color(percent: u8) -> iced::Color {
     if percent > 60 {
@@ -15,9 +126,18 @@ fn battery_color(percent: u8) -> iced::Color {
 }
 
 /// A single battery card with icon label, percentage, and progress bar.
-fn battery_card<'a, M: 'a>(label: &str, emoji: &str, percent: u8) -> Element<'a, M> {
-    let color = battery_color(percent);
-    let bar_fraction = percent as f32 / 100.0;
+/// `fill` is the share of the bar drawn, `alpha` its opacity.
+fn battery_card<'a, M: 'a>(
+    label: &str,
+    emoji: &str,
+    percent: u8,
+    fill: f32,
+    alpha: f32,
+) -> Element<'a, M> {
+    let color = iced::Color {
+        a: alpha,
+        ..battery_color(percent)
+    };
 
     let percentage_text = text(format!("{}%", percent))
         .size(26);
@@ -28,7 +148,7 @@ fn battery_card<'a, M: 'a>(label: &str, emoji: &str, percent: u8) -> Element<'a,
 
     // Progress bar: colored fill inside a gray track
     let bar_width = 100.0;
-    let fill_width = (bar_fraction * bar_width).max(2.0);
+    let fill_width = (fill * bar_width).max(2.0);
 
     let bar_track = container(
         container(text(""))
@@ -78,33 +198,49 @@ fn battery_card<'a, M: 'a>(label: &str, emoji: &str, percent: u8) -> Element<'a,
         .into()
 }
 
-/// Full battery display with cards for left, right, case (or global).
-pub fn battery_display<'a, M: 'a>(
-    left: Option<u8>,
-    right: Option<u8>,
-    case: Option<u8>,
-    global: Option<u8>,
-    is_charging: bool,
-) -> Element<'a, M> {
+/// Full battery display: a card for each bud and the case that report a
+/// level, or the global level on models without per-bud data (neckbands).
+pub fn battery_display<'a, M: 'a>(battery: &BatteryCards) -> Element<'a, M> {
+    let present = |slot: &str| battery.level(slot).filter(|l| *l > 0);
+    let card = |slot: &str, label: &str, emoji: &str, level: u8| {
+        let alpha = if battery.is_charging(slot) {
+            battery.pulse_alpha()
+        } else {
+            1.0
+        };
+        battery_card(label, emoji, level, battery.fill(slot), alpha)
+    };
     let mut cards: Vec<Element<'a, M>> = Vec::new();
 
-    if let (Some(l), Some(r)) = (left, right) {
-        cards.push(battery_card("Left", "L", l));
-        cards.push(battery_card("Right", "R", r));
-        if let Some(c) = case {
-            if c > 0 {
-                cards.push(battery_card("Case", "C", c));
-            }
+    if let Some(l) = present("left") {
+        cards.push(card("left", "Left", "L", l));
+    }
+    if let Some(r) = present("right") {
+        cards.push(card("right", "Right", "R", r));
+    }
+    if cards.is_empty() {
+        if let Some(g) = battery.level("global") {
+            cards.push(card("global", "Battery", "~", g));
         }
-    } else if let Some(g) = global {
-        cards.push(battery_card("Battery", "~", g));
+    } else if let Some(c) = present("case") {
+        cards.push(card("case", "Case", "C", c));
+    }
+
+    if cards.is_empty() {
+        return container(
+            text("Waiting for battery level")
+                .size(12)
+                .color(iced::Color::from_rgb(0.45, 0.45, 0.45)),
+        )
+        .center_x(Length::Fill)
+        .into();
     }
 
     let battery_row = row(cards).spacing(10);
 
     let mut content = column![battery_row].spacing(6);
 
-    if is_charging {
+    if battery.is_charging("global") {
         content = content.push(
             container(
                 text("Charging...")
@@ -117,3 +253,40 @@ pub fn battery_display<'a, M: 'a>(
 
     content.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn bars_settle_and_charging_keeps_pulsing() {
+        let mut cards = BatteryCards::default();
+        cards.update(&battery(&[("left", "80"), ("right", "60")]));
+        assert!(cards.is_animating());
+
+        let start = Instant::now();
+        for i in 0..=60 {
+            cards.frame(start + Duration::from_millis(33 * i));
+        }
+        assert_eq!(cards.fill("left"), 0.8);
+        assert!(!cards.is_animating());
+
+        cards.update(&battery(&[
+            ("left", "80"),
+            ("right", "60"),
+            ("right_charging", "true"),
+        ]));
+        assert!(cards.is_animating());
+        cards.frame(start + Duration::from_secs(3));
+        cards.frame(start + Duration::from_secs(3) + Duration::from_millis(500));
+        assert!(cards.pulse_alpha() < 1.0);
+        assert_eq!(cards.fill("right"), 0.6);
+    }
+}
@@ -0,0 +1,334 @@
+//! Varlink interface to the running instance.
+//!
+//! An opt-in alternative to the JSON control socket for integrations that
+//! want a typed, introspectable API (`varlink = true` in the config). It
+//! listens on `mybuds.varlink` next to the control socket and implements
+//! [`INTERFACE`] plus `org.varlink.service`, so generic tools work:
+//!
+//! ```text
+//! varlinkctl introspect unix:$XDG_RUNTIME_DIR/mybuds.varlink
+//! varlinkctl call unix:... io.github.joshuapassos.mybuds.GetState '{}'
+//! ```
+//!
+//! Every call goes through [`control::handle`], like the commands of the
+//! control socket. Messages are JSON objects terminated by a NUL byte; a
+//! connection may carry several calls.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use crate::bluetooth::state::{ConnectionState, CONNECTION_GROUP};
+use crate::control::{self, ControlCommand, ControlFlags, ControlReply};
+use crate::device::request::SetPropertyError;
+use crate::instance_lock::InstanceLock;
+
+pub const INTERFACE: &str = "io.github.joshuapassos.mybuds";
+const SERVICE: &str = "org.varlink.service";
+
+/// Interface definition, as returned by `GetInterfaceDescription`.
+const DESCRIPTION: &str = "\
+# Headphone state and control for MyBuds
+interface io.github.joshuapassos.mybuds
+
+type Battery (
+  left: ?int,
+  right: ?int,
+  case: ?int,
+  # Models without per-bud levels
+  global: ?int,
+  charging: bool
+)
+
+type State (
+  # disconnected, searching, connecting, connected, reconnecting, failed or asleep
+  connection: string,
+  # Human-readable connection state
+  label: string,
+  device: ?string,
+  battery: Battery,
+  # Values as in GetProperties, e.g. cancellation or awareness
+  anc_mode: ?string
+)
+
+# Summary of the connected headphones
+method GetState() -> (state: State)
+
+# Every property by group, as `mybuds props` prints them
+method GetProperties() -> (properties: [string][string]string)
+
+# Write a property, e.g. group anc, prop mode, value awareness
+method SetProperty(group: string, prop: string, value: string) -> ()
+
+# Raise the MyBuds window
+method Show() -> ()
+
+# Exit the running instance
+method Quit() -> ()
+
+error NotConnected ()
+error NoHandler (group: string)
+error InvalidValue (prop: string, value: string, expected: string)
+error Rejected (reason: string)
+error NoWindow ()
+";
+
+/// Socket path, next to the control socket.
+pub fn socket_path() -> PathBuf {
+    InstanceLock::runtime_dir().join("mybuds.varlink")
+}
+
+#[derive(Debug, Deserialize)]
+struct Call {
+    method: String,
+    #[serde(default)]
+    parameters: Value,
+    #[serde(default)]
+    oneway: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    parameters: Value,
+}
+
+impl Reply {
+    fn ok(parameters: Value) -> Self {
+        Self {
+            error: None,
+            parameters,
+        }
+    }
+
+    fn error(interface: &str, name: &str, parameters: Value) -> Self {
+        Self {
+            error: Some(format!("{}.{}", interface, name)),
+            parameters,
+        }
+    }
+
+    fn invalid_parameter(parameter: &str) -> Self {
+        Self::error(SERVICE, "InvalidParameter", json!({ "parameter": parameter }))
+    }
+}
+
+/// String parameter `name` of a call.
+fn string_param(parameters: &Value, name: &str) -> Result<String, Reply> {
+    parameters
+        .get(name)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| Reply::invalid_parameter(name))
+}
+
+/// `State` of [`DESCRIPTION`] from the property store.
+fn state(store: &HashMap<String, HashMap<String, String>>) -> Value {
+    let get = |group: &str, key: &str| store.get(group).and_then(|g| g.get(key));
+    let level = |key: &str| get("battery", key).and_then(|v| v.parse::<u8>().ok());
+    let connection = ConnectionState::from_group(store.get(CONNECTION_GROUP));
+    json!({
+        "connection": connection.as_str(),
+        "label": connection.label(),
+        "device": get("info", "device_name").or_else(|| get("info", "device_model")),
+        "battery": {
+            "left": level("left"),
+            "right": level("right"),
+            "case": level("case"),
+            "global": level("global"),
+            "charging": get("battery", "is_charging").is_some_and(|v| v == "true"),
+        },
+        "anc_mode": get("anc", "mode"),
+    })
+}
+
+fn set_error(e: SetPropertyError) -> Reply {
+    match e {
+        SetPropertyError::NotConnected => Reply::error(INTERFACE, "NotConnected", json!({})),
+        SetPropertyError::NoHandler(group) => {
+            Reply::error(INTERFACE, "NoHandler", json!({ "group": group }))
+        }
+        SetPropertyError::InvalidValue {
+            prop,
+            value,
+            expected,
+        } => Reply::error(
+            INTERFACE,
+            "InvalidValue",
+            json!({ "prop": prop, "value": value, "expected": expected }),
+        ),
+        SetPropertyError::Rejected(reason) => {
+            Reply::error(INTERFACE, "Rejected", json!({ "reason": reason }))
+        }
+    }
+}
+
+/// Turn a control reply into the varlink reply of a method without output.
+fn acknowledged(reply: ControlReply) -> Reply {
+    match reply {
+        ControlReply::Ok | ControlReply::Set { result: Ok(()) } => Reply::ok(json!({})),
+        ControlReply::Set { result: Err(e) } => set_error(e),
+        ControlReply::Error { .. } => Reply::error(INTERFACE, "NoWindow", json!({})),
+        ControlReply::Props { .. } => unreachable!("only answers ControlCommand::Props"),
+    }
+}
+
+async fn properties(flags: &ControlFlags) -> HashMap<String, HashMap<String, String>> {
+    match control::handle(ControlCommand::Props, flags).await {
+        ControlReply::Props { store } => store,
+        _ => HashMap::new(),
+    }
+}
+
+async fn dispatch(call: Call, flags: &ControlFlags) -> Reply {
+    let Some((interface, method)) = call.method.rsplit_once('.') else {
+        return Reply::error(SERVICE, "MethodNotFound", json!({ "method": call.method }));
+    };
+    match (interface, method) {
+        (SERVICE, "GetInfo") => Reply::ok(json!({
+            "vendor": "MyBuds",
+            "product": "MyBuds",
+            "version": env!("CARGO_PKG_VERSION"),
+            "url": "https://github.com/joshuapassos/mybuds",
+            "interfaces": [SERVICE, INTERFACE],
+        })),
+        (SERVICE, "GetInterfaceDescription") => match string_param(&call.parameters, "interface") {
+            Ok(name) if name == INTERFACE => Reply::ok(json!({ "description": DESCRIPTION })),
+            Ok(name) => Reply::error(SERVICE, "InterfaceNotFound", json!({ "interface": name })),
+            Err(reply) => reply,
+        },
+        (INTERFACE, "GetState") => Reply::ok(json!({ "state": state(&properties(flags).await) })),
+        (INTERFACE, "GetProperties") => {
+            Reply::ok(json!({ "properties": properties(flags).await }))
+        }
+        (INTERFACE, "SetProperty") => {
+            let param = |name| string_param(&call.parameters, name);
+            let command = match (param("group"), param("prop"), param("value")) {
+                (Ok(group), Ok(prop), Ok(value)) => ControlCommand::Set { group, prop, value },
+                (Err(reply), _, _) | (_, Err(reply), _) | (_, _, Err(reply)) => return reply,
+            };
+            acknowledged(control::handle(command, flags).await)
+        }
+        (INTERFACE, "Show") => acknowledged(control::handle(ControlCommand::Show, flags).await),
+        (INTERFACE, "Quit") => acknowledged(control::handle(ControlCommand::Quit, flags).await),
+        (SERVICE | INTERFACE, _) => {
+            Reply::error(SERVICE, "MethodNotFound", json!({ "method": call.method }))
+        }
+        _ => Reply::error(SERVICE, "InterfaceNotFound", json!({ "interface": interface })),
+    }
+}
+
+/// Answer calls on one connection until the client hangs up.
+pub async fn serve_connection(stream: tokio::net::UnixStream, flags: ControlFlags) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut read = tokio::io::BufReader::new(read);
+    let mut message = Vec::new();
+    loop {
+        message.clear();
+        if read.read_until(0, &mut message).await? == 0 {
+            return Ok(());
+        }
+        if message.last() == Some(&0) {
+            message.pop();
+        }
+        let (reply, oneway) = match serde_json::from_slice::<Call>(&message) {
+            Ok(call) => {
+                let oneway = call.oneway;
+                (dispatch(call, &flags).await, oneway)
+            }
+            // Not a varlink call; the protocol has no way to answer that
+            Err(e) => anyhow::bail!("malformed varlink message: {}", e),
+        };
+        if oneway {
+            continue;
+        }
+        let mut out = serde_json::to_vec(&reply)?;
+        out.push(0);
+        write.write_all(&out).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio_util::sync::CancellationToken;
+
+    fn flags(store: HashMap<String, HashMap<String, String>>) -> ControlFlags {
+        // Nobody listens for writes, as when the manager has gone away
+        let (prop_tx, _) = tokio::sync::mpsc::channel(1);
+        ControlFlags {
+            show_window: None,
+            shutdown: CancellationToken::new(),
+            props: Arc::new(tokio::sync::Mutex::new(store)),
+            prop_tx,
+            varlink: true,
+        }
+    }
+
+    async fn call(flags: &ControlFlags, message: &str) -> Reply {
+        dispatch(serde_json::from_str(message).unwrap(), flags).await
+    }
+
+    #[tokio::test]
+    async fn answers_calls_through_the_control_layer() {
+        let store = HashMap::from([
+            (
+                CONNECTION_GROUP.to_string(),
+                HashMap::from([("state".to_string(), "connected".to_string())]),
+            ),
+            (
+                "battery".to_string(),
+                HashMap::from([
+                    ("left".to_string(), "80".to_string()),
+                    ("is_charging".to_string(), "false".to_string()),
+                ]),
+            ),
+        ]);
+        let flags = flags(store);
+
+        let reply = call(&flags, r#"{"method":"io.github.joshuapassos.mybuds.GetState"}"#).await;
+        assert_eq!(reply.error, None);
+        let state = &reply.parameters["state"];
+        assert_eq!(state["connection"], "connected");
+        assert_eq!(state["battery"]["left"], 80);
+        assert_eq!(state["battery"]["right"], Value::Null);
+        assert_eq!(state["anc_mode"], Value::Null);
+
+        let reply = call(
+            &flags,
+            r#"{"method":"io.github.joshuapassos.mybuds.SetProperty",
+                "parameters":{"group":"anc","prop":"mode","value":"awareness"}}"#,
+        )
+        .await;
+        assert_eq!(reply.error.as_deref(), Some("io.github.joshuapassos.mybuds.NotConnected"));
+
+        let reply = call(&flags, r#"{"method":"io.github.joshuapassos.mybuds.Show"}"#).await;
+        assert_eq!(reply.error.as_deref(), Some("io.github.joshuapassos.mybuds.NoWindow"));
+    }
+
+    #[tokio::test]
+    async fn implements_the_service_interface() {
+        let flags = flags(HashMap::new());
+        let reply = call(&flags, r#"{"method":"org.varlink.service.GetInfo"}"#).await;
+        assert_eq!(reply.parameters["interfaces"][1], INTERFACE);
+
+        let reply = call(
+            &flags,
+            r#"{"method":"org.varlink.service.GetInterfaceDescription",
+                "parameters":{"interface":"io.github.joshuapassos.mybuds"}}"#,
+        )
+        .await;
+        assert_eq!(reply.parameters["description"], DESCRIPTION);
+
+        let reply = call(&flags, r#"{"method":"com.example.Nope.Call"}"#).await;
+        assert_eq!(reply.error.as_deref(), Some("org.varlink.service.InterfaceNotFound"));
+        let reply = call(&flags, r#"{"method":"io.github.joshuapassos.mybuds.Nope"}"#).await;
+        assert_eq!(reply.error.as_deref(), Some("org.varlink.service.MethodNotFound"));
+    }
+}
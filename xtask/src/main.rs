@@ -0,0 +1,455 @@
+//! Development tasks, run as `cargo xtask <task>`.
+//!
+//! `new-handler` scaffolds a Huawei feature handler: a boolean setting read
+//! and written with two new commands, wired everywhere a handler has to be
+//! (see "Adding a New Feature Handler" in CLAUDE.md), with a unit test.
+//! With `--profile`, the profile's golden test is set up too: a recording
+//! stub in `src/device/testdata` and an entry in `golden!`.
+//!
+//! ```text
+//! cargo xtask new-handler bass_boost --read 2BB1 --write 2BB0 --profile freebuds_5i
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+const USAGE: &str = "\
+Usage: cargo xtask new-handler <name> --read <cmd> --write <cmd> [--profile <fn>]
+
+  <name>     handler id, property group and file name, in snake_case
+  --read     read command id in hex as in debug logs, e.g. 2BB1
+  --write    write command id, e.g. 2BB0
+  --profile  profile function in src/device/models/mod.rs to add it to";
+
+const HANDLER_TEMPLATE: &str = r#"//! {title} handler.
+//!
+//! Generated by `cargo xtask new-handler`: a boolean setting read with
+//! `CMD_{CONST}_READ` and written with `CMD_{CONST}_WRITE`, published as
+//! `{name}.enabled`. Adjust the parsing to what the device sends.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::handler::{put_properties, DeviceHandler, PacketSender, PropertyStore};
+use crate::protocol::commands::*;
+use crate::protocol::HuaweiSppPacket;
+
+/// {title} on/off.
+pub struct {Camel}Handler;
+
+#[async_trait]
+impl DeviceHandler for {Camel}Handler {
+    fn handler_id(&self) -> &'static str {
+        "{name}"
+    }
+
+    fn handled_groups(&self) -> &[&'static str] {
+        &["{name}"]
+    }
+
+    fn commands(&self) -> &[CommandId] {
+        &[CMD_{CONST}_READ, CMD_{CONST}_WRITE]
+    }
+
+    async fn on_init(&mut self, sender: &PacketSender, _props: &PropertyStore) -> Result<()> {
+        let pkt = HuaweiSppPacket::read_request(CMD_{CONST}_READ, &[1]);
+        sender.send(pkt).await?;
+        Ok(())
+    }
+
+    async fn on_packet(&mut self, packet: &HuaweiSppPacket, props: &PropertyStore) -> Result<()> {
+        let data = packet.find_param(1);
+        if data.len() == 1 {
+            // A boolean current value lets schema::validate check writes
+            let mut out = HashMap::new();
+            out.insert("enabled".into(), (data[0] == 1).to_string());
+            put_properties(props, "{name}", out).await;
+        }
+        Ok(())
+    }
+
+    async fn set_property(
+        &mut self,
+        sender: &PacketSender,
+        _props: &PropertyStore,
+        _group: &str,
+        _prop: &str,
+        value: &str,
+    ) -> Result<()> {
+        let byte_val = u8::from(value == "true");
+        let pkt = HuaweiSppPacket::write_request(CMD_{CONST}_WRITE, [(1, vec![byte_val])]);
+        sender.send(pkt).await?;
+
+        // Re-read so the store shows what the device accepted
+        let read_pkt = HuaweiSppPacket::read_request(CMD_{CONST}_READ, &[1]);
+        sender.send(read_pkt).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn publishes_the_setting() {
+        let props = PropertyStore::default();
+        let mut handler = {Camel}Handler;
+
+        let pkt = HuaweiSppPacket::write_request(CMD_{CONST}_READ, [(1, vec![1])]);
+        handler.on_packet(&pkt, &props).await.unwrap();
+        assert_eq!(props.lock().await["{name}"]["enabled"], "true");
+    }
+}
+"#;
+
+/// Names derived from the snake_case handler name.
+struct Names {
+    name: String,
+    camel: String,
+    constant: String,
+    title: String,
+}
+
+impl Names {
+    fn new(name: &str) -> Result<Self> {
+        let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid || name.contains("__") || name.ends_with('_') {
+            bail!(
+                "Handler name must be snake_case, e.g. bass_boost (got {:?})",
+                name
+            );
+        }
+        let words: Vec<&str> = name.split('_').collect();
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        };
+        let title = capitalize(&words.join(" "));
+        Ok(Self {
+            name: name.to_string(),
+            camel: words.iter().map(|w| capitalize(w)).collect(),
+            constant: name.to_ascii_uppercase(),
+            title,
+        })
+    }
+
+    fn render(&self, template: &str) -> String {
+        template
+            .replace("{name}", &self.name)
+            .replace("{Camel}", &self.camel)
+            .replace("{CONST}", &self.constant)
+            .replace("{title}", &self.title)
+    }
+}
+
+/// Parse a command id written as in debug logs (`2BB1`).
+fn command_id(hex: &str) -> Result<[u8; 2]> {
+    let value = u16::from_str_radix(hex.trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|_| hex.trim_start_matches("0x").len() == 4)
+        .with_context(|| format!("Command ids are four hex digits, e.g. 2BB1 (got {:?})", hex))?;
+    Ok(value.to_be_bytes())
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))
+}
+
+/// Insert `text` before the first `anchor` found after `after`.
+fn insert_before(source: &mut String, after: &str, anchor: &str, text: &str) -> Result<()> {
+    let start = source
+        .find(after)
+        .with_context(|| format!("{:?} not found", after))?;
+    let at = source[start..]
+        .find(anchor)
+        .map(|i| start + i)
+        .with_context(|| format!("{:?} not found after {:?}", anchor, after))?;
+    source.insert_str(at, text);
+    Ok(())
+}
+
+/// Insert `line` into the run of lines starting with `prefix`, keeping them
+/// sorted.
+fn insert_sorted(source: &mut String, prefix: &str, line: &str) -> Result<()> {
+    let mut offset = 0;
+    let mut at = None;
+    for existing in source.split_inclusive('\n') {
+        if existing.starts_with(prefix) {
+            if existing.trim_end() > line {
+                at = Some(offset);
+                break;
+            }
+            at = Some(offset + existing.len());
+        }
+        offset += existing.len();
+    }
+    let at = at.with_context(|| format!("No {:?} lines found", prefix))?;
+    source.insert_str(at, &format!("{}\n", line));
+    Ok(())
+}
+
+fn edit(path: &Path, f: impl FnOnce(&mut String) -> Result<()>) -> Result<()> {
+    let mut source = read(path)?;
+    f(&mut source).with_context(|| format!("Editing {}", path.display()))?;
+    fs::write(path, source).with_context(|| format!("Writing {}", path.display()))
+}
+
+/// Add `profile` to the `golden!` list unless it is there. Returns whether
+/// it was added.
+fn add_golden_entry(source: &mut String, profile: &str) -> Result<bool> {
+    if source.contains(&format!("\n    {},\n", profile)) {
+        return Ok(false);
+    }
+    insert_before(source, "golden!(\n", ");", &format!("    {},\n", profile))?;
+    Ok(true)
+}
+
+/// Set up the golden test of `profile` for the new handler: a recording to
+/// paste the device's answer into and the `golden!` entry.
+fn add_golden(device: &Path, names: &Names, read_cmd: [u8; 2], profile: &str) -> Result<()> {
+    let log = device.join(format!("testdata/{}.log", profile));
+    let mut recording = if log.exists() {
+        read(&log)?
+    } else {
+        format!(
+            "# {} connecting, for the golden test (src/device/golden.rs)\n\
+             # RX lines from RUST_LOG=mybuds=debug; everything else is ignored.\n",
+            profile
+        )
+    };
+    if !recording.ends_with('\n') {
+        recording.push('\n');
+    }
+    recording.push_str(&format!(
+        "# {}: paste the device's answer to cmd={:02X}{:02X} (its RX line) here\n",
+        names.name, read_cmd[0], read_cmd[1]
+    ));
+    fs::write(&log, recording).with_context(|| format!("Writing {}", log.display()))?;
+    println!("updated  {}", log.display());
+
+    let golden = device.join("golden.rs");
+    let mut added = false;
+    edit(&golden, |source| {
+        added = add_golden_entry(source, profile)?;
+        Ok(())
+    })?;
+    if added {
+        println!(
+            "updated  {} (golden test for {})",
+            golden.display(),
+            profile
+        );
+    }
+    Ok(())
+}
+
+fn new_handler(
+    root: &Path,
+    names: &Names,
+    read_cmd: [u8; 2],
+    write_cmd: [u8; 2],
+    profile: Option<&str>,
+) -> Result<()> {
+    let device = root.join("src/device");
+    let file = device.join(format!("{}.rs", names.name));
+    if file.exists() {
+        bail!("{} already exists", file.display());
+    }
+    let commands = root.join("src/protocol/commands.rs");
+    if read(&commands)?.contains(&format!("CMD_{}_READ", names.constant)) {
+        bail!("CMD_{}_READ is already defined", names.constant);
+    }
+
+    // Fail on an unknown profile before writing anything
+    let profiles = device.join("models/mod.rs");
+    if let Some(profile) = profile {
+        if !read(&profiles)?.contains(&format!("pub fn {}() -> DeviceProfile", profile)) {
+            bail!("No profile function {} in {}", profile, profiles.display());
+        }
+    }
+
+    fs::write(&file, names.render(HANDLER_TEMPLATE))?;
+    println!("created  {}", file.display());
+
+    let hex = |cmd: [u8; 2]| format!("[0x{:02X}, 0x{:02X}]", cmd[0], cmd[1]);
+    edit(&commands, |source| {
+        let block = format!(
+            "// {title}\npub const CMD_{c}_READ: [u8; 2] = {read};\npub const CMD_{c}_WRITE: [u8; 2] = {write};\n\n",
+            title = names.title,
+            c = names.constant,
+            read = hex(read_cmd),
+            write = hex(write_cmd),
+        );
+        insert_before(source, "", "/// Helper type for command IDs", &block)
+    })?;
+    println!("updated  {}", commands.display());
+
+    edit(&device.join("mod.rs"), |source| {
+        insert_sorted(source, "pub mod ", &format!("pub mod {};", names.name))
+    })?;
+    println!("updated  {}", device.join("mod.rs").display());
+
+    let handler_path = format!("crate::device::{}::{}Handler", names.name, names.camel);
+    edit(&device.join("models/overrides.rs"), |source| {
+        insert_sorted(
+            source,
+            "use crate::device::",
+            &format!("use {};", handler_path),
+        )?;
+        let entry = format!(
+            "\n    (\"{n}\", \"{n}\", || Box::new({c}Handler)),",
+            n = names.name,
+            c = names.camel
+        );
+        insert_before(source, "const FEATURES", "\n];", &entry)
+    })?;
+    println!(
+        "updated  {} (`{} = true` in [overrides])",
+        device.join("models/overrides.rs").display(),
+        names.name
+    );
+
+    edit(&device.join("capabilities.rs"), |source| {
+        let entry = format!(
+            "\n    feature(\"{t}\", &[\"{n}\"], &[(\"{n}\", \"enabled\")]),",
+            t = names.title,
+            n = names.name
+        );
+        insert_before(source, "pub const FEATURES", "\n];", &entry)
+    })?;
+    println!("updated  {}", device.join("capabilities.rs").display());
+
+    if let Some(profile) = profile {
+        edit(&profiles, |source| {
+            insert_sorted(
+                source,
+                "use super::",
+                &format!("use super::{}::{}Handler;", names.name, names.camel),
+            )?;
+            let entry = format!("            Box::new({}Handler),\n", names.camel);
+            insert_before(
+                source,
+                &format!("pub fn {}() -> DeviceProfile", profile),
+                "        ],\n",
+                &entry,
+            )
+        })?;
+        println!("updated  {} ({})", profiles.display(), profile);
+        add_golden(&device, names, read_cmd, profile)?;
+    }
+
+    println!("\nNext:");
+    println!(
+        "  - adjust parsing in {} to what the device sends",
+        file.display()
+    );
+    println!("  - add controls in src/ui/pages/ and src/tui/pages/");
+    println!(
+        "  - document the `{}` override key in the README",
+        names.name
+    );
+    if let Some(profile) = profile {
+        println!(
+            "  - paste the device's answer into src/device/testdata/{}.log, then run",
+            profile
+        );
+        println!("    UPDATE_GOLDEN=1 cargo test golden and review the snapshot diff");
+    }
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let Some((task, rest)) = args.split_first() else {
+        bail!("{}", USAGE);
+    };
+    if task != "new-handler" {
+        bail!("Unknown task {:?}\n\n{}", task, USAGE);
+    }
+
+    let mut name = None;
+    let (mut read_cmd, mut write_cmd, mut profile) = (None, None, None);
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || {
+            rest.next()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--read" => read_cmd = Some(command_id(value()?)?),
+            "--write" => write_cmd = Some(command_id(value()?)?),
+            "--profile" => profile = Some(value()?.clone()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if name.is_none() && !arg.starts_with('-') => name = Some(Names::new(arg)?),
+            _ => bail!("Unexpected argument {:?}\n\n{}", arg, USAGE),
+        }
+    }
+    let (Some(names), Some(read_cmd), Some(write_cmd)) = (name, read_cmd, write_cmd) else {
+        bail!("{}", USAGE);
+    };
+
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .context("xtask lives in the repository")?
+        .to_path_buf();
+    new_handler(&root, &names, read_cmd, write_cmd, profile.as_deref())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_names_and_command_ids() {
+        let names = Names::new("bass_boost").unwrap();
+        assert_eq!(names.camel, "BassBoost");
+        assert_eq!(names.constant, "BASS_BOOST");
+        assert_eq!(names.title, "Bass boost");
+        assert!(Names::new("BassBoost").is_err());
+        assert!(Names::new("bass__boost").is_err());
+
+        assert_eq!(command_id("2BB1").unwrap(), [0x2B, 0xB1]);
+        assert!(command_id("2BB").is_err());
+    }
+
+    #[test]
+    fn keeps_mod_lines_sorted() {
+        let mut source = "pub mod anc;\npub mod battery;\nmod golden;\npub mod info;\n".to_string();
+        insert_sorted(&mut source, "pub mod ", "pub mod bass_boost;").unwrap();
+        assert_eq!(
+            source,
+            "pub mod anc;\npub mod bass_boost;\npub mod battery;\nmod golden;\npub mod info;\n"
+        );
+    }
+
+    #[test]
+    fn adds_profiles_to_the_golden_list_once() {
+        let mut source = "macro_rules! golden {}\n\ngolden!(\n    freebuds_pro3,\n);\n".to_string();
+        assert!(add_golden_entry(&mut source, "freebuds_5i").unwrap());
+        assert!(!add_golden_entry(&mut source, "freebuds_pro3").unwrap());
+        assert_eq!(
+            source,
+            "macro_rules! golden {}\n\ngolden!(\n    freebuds_pro3,\n    freebuds_5i,\n);\n"
+        );
+    }
+}